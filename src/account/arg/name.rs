@@ -35,3 +35,16 @@ pub struct AccountNameFlag {
     #[arg(name = "account_name", value_name = "NAME")]
     pub name: Option<String>,
 }
+
+/// The target account name flag parser.
+#[derive(Debug, Default, Parser)]
+pub struct TargetAccountNameFlag {
+    /// Transfer messages to another account.
+    ///
+    /// The target folder then belongs to the given account. Messages
+    /// are read from the source account, then added to the target
+    /// account with their flags.
+    #[arg(long = "to-account")]
+    #[arg(name = "target_account_name", value_name = "NAME")]
+    pub name: Option<String>,
+}
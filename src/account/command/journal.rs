@@ -0,0 +1,68 @@
+use anyhow::Result;
+use clap::Parser;
+use log::info;
+
+use crate::{
+    account::{
+        arg::name::OptionalAccountNameArg,
+        journal::{JournalEntries, SyncJournal},
+    },
+    config::TomlConfig,
+    printer::{PrintTableOpts, Printer},
+    ui::arg::max_width::TableMaxWidthFlag,
+};
+
+/// Show the synchronization journal of an account.
+///
+/// This command lists the hunks applied or failed by the last
+/// synchronizations of the given account. Failed hunks can be
+/// replayed using the --retry-failed flag of the account sync
+/// command.
+#[derive(Debug, Parser)]
+pub struct AccountJournalCommand {
+    #[command(flatten)]
+    pub account: OptionalAccountNameArg,
+
+    /// List only failed hunks that have not been resolved yet.
+    #[arg(long, short)]
+    pub failed: bool,
+
+    /// The maximum number of entries to list.
+    ///
+    /// Only the most recent entries are listed, oldest first. If
+    /// omitted, all entries are listed.
+    #[arg(long, short = 'n', value_name = "NUMBER")]
+    pub limit: Option<usize>,
+
+    #[command(flatten)]
+    pub table: TableMaxWidthFlag,
+}
+
+impl AccountJournalCommand {
+    pub async fn execute(self, printer: &mut impl Printer, config: &TomlConfig) -> Result<()> {
+        info!("executing account journal command");
+
+        let account = self.account.name.as_deref();
+        let (_, account_config) = config.clone().into_account_configs(account, true)?;
+
+        let journal = SyncJournal::new(&account_config)?;
+
+        let entries = if self.failed {
+            let mut failures = journal.failures()?;
+            if let Some(limit) = self.limit {
+                failures.drain(..failures.len().saturating_sub(limit));
+            }
+            failures
+        } else {
+            journal.list(self.limit)?
+        };
+
+        printer.print_table(
+            Box::new(JournalEntries::from(entries)),
+            PrintTableOpts {
+                format: &account_config.get_message_read_format(),
+                max_width: self.table.max_width,
+            },
+        )
+    }
+}
@@ -6,7 +6,10 @@ use crate::{
     account::Accounts,
     config::TomlConfig,
     printer::{PrintTableOpts, Printer},
-    ui::arg::max_width::TableMaxWidthFlag,
+    ui::{
+        arg::max_width::TableMaxWidthFlag,
+        template::{arg::format::TemplateFormatFlag, print_table_or_template},
+    },
 };
 
 /// List all accounts.
@@ -17,6 +20,9 @@ use crate::{
 pub struct AccountListCommand {
     #[command(flatten)]
     pub table: TableMaxWidthFlag,
+
+    #[command(flatten)]
+    pub format: TemplateFormatFlag,
 }
 
 impl AccountListCommand {
@@ -25,8 +31,10 @@ impl AccountListCommand {
 
         let accounts: Accounts = config.accounts.iter().into();
 
-        printer.print_table(
-            Box::new(accounts),
+        print_table_or_template(
+            printer,
+            accounts,
+            self.format.template,
             PrintTableOpts {
                 format: &Default::default(),
                 max_width: self.table.max_width,
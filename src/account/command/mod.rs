@@ -1,5 +1,7 @@
 mod check_up;
 mod configure;
+#[cfg(feature = "account-sync")]
+mod journal;
 mod list;
 #[cfg(feature = "account-sync")]
 mod sync;
@@ -9,11 +11,11 @@ use clap::Subcommand;
 
 use crate::{config::TomlConfig, printer::Printer};
 
-#[cfg(feature = "account-sync")]
-use self::sync::AccountSyncCommand;
 use self::{
     check_up::AccountCheckUpCommand, configure::AccountConfigureCommand, list::AccountListCommand,
 };
+#[cfg(feature = "account-sync")]
+use self::{journal::AccountJournalCommand, sync::AccountSyncCommand};
 
 /// Manage accounts.
 ///
@@ -28,6 +30,9 @@ pub enum AccountSubcommand {
     #[command(alias = "cfg")]
     Configure(AccountConfigureCommand),
 
+    #[cfg(feature = "account-sync")]
+    Journal(AccountJournalCommand),
+
     #[command(alias = "lst")]
     List(AccountListCommand),
 
@@ -42,6 +47,8 @@ impl AccountSubcommand {
         match self {
             Self::CheckUp(cmd) => cmd.execute(printer, config).await,
             Self::Configure(cmd) => cmd.execute(printer, config).await,
+            #[cfg(feature = "account-sync")]
+            Self::Journal(cmd) => cmd.execute(printer, config).await,
             Self::List(cmd) => cmd.execute(printer, config).await,
             #[cfg(feature = "account-sync")]
             Self::Sync(cmd) => cmd.execute(printer, config).await,
@@ -1,10 +1,31 @@
 use crate::{
-    account::{arg::name::OptionalAccountNameArg, config::TomlAccountConfig},
+    account::{
+        arg::name::OptionalAccountNameArg,
+        config::TomlAccountConfig,
+        daemon::{self, DaemonAccount},
+        journal::{FailedFolders, JournalEntry, ReplayHunk, SyncJournal},
+        partial::{
+            cache::PartialCache,
+            config::{MessageSize, PartialSyncConfig, SyncWindow},
+            PartialSync,
+        },
+        sync::{
+            record_status, write_stream_item, PatchReport, SyncEventReport, SyncEventsFmt,
+            SyncLock, SyncReport, SyncStreamItem, SyncTimings,
+        },
+    },
     backend::{Backend, BackendContextBuilder, BackendKind},
+    cache::IdMapper,
     config::TomlConfig,
-    printer::Printer,
+    contact::{config::ContactConfig, AddressBook},
+    envelope::Envelope,
+    index::SearchIndex,
+    output::OutputFmt,
+    printer::{Printer, StdoutPrinter},
+    rule::{self, config::RulesConfig},
 };
-use anyhow::Result;
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Local};
 use clap::{ArgAction, Parser};
 #[cfg(feature = "imap")]
 use email::imap::ImapContextBuilder;
@@ -14,20 +35,31 @@ use email::maildir::MaildirContextBuilder;
 #[cfg(feature = "notmuch")]
 use email::notmuch::NotmuchContextBuilder;
 use email::{
-    account::{config::AccountConfig, sync::AccountSyncBuilder},
-    backend::BackendBuilder,
+    account::{
+        config::AccountConfig,
+        sync::{AccountSyncBuilder, AccountSyncReport},
+    },
+    backend::{feature::BackendFeatureSource, BackendBuilder},
+    envelope::list::ListEnvelopesOptions,
     folder::sync::config::FolderSyncStrategy,
     sync::SyncEvent,
 };
 use indicatif::{MultiProgress, ProgressBar, ProgressFinish, ProgressStyle};
-use log::info;
-use once_cell::sync::Lazy;
+use log::{debug, info, warn};
+use once_cell::sync::{Lazy, OnceCell};
 use std::{
-    collections::{BTreeSet, HashMap},
+    collections::{BTreeSet, HashMap, HashSet},
+    fs,
     ops::Deref,
+    path::{Path, PathBuf},
     sync::{Arc, Mutex},
+    time::Instant,
 };
 
+/// The number of envelopes listed at once when updating the address
+/// book.
+const CONTACTS_PAGE_SIZE: usize = 100;
+
 static MAIN_PROGRESS_STYLE: Lazy<ProgressStyle> = Lazy::new(|| {
     ProgressStyle::with_template(" {spinner:.dim} {msg:.dim}\n {wide_bar:.cyan/blue} \n").unwrap()
 });
@@ -87,6 +119,81 @@ pub struct AccountSyncCommand {
     #[arg(long, short = 'A')]
     #[arg(conflicts_with = "include_folder", conflicts_with = "exclude_folder")]
     pub all_folders: bool,
+
+    /// Synchronize only emails received within the given window.
+    ///
+    /// The window is a number followed by a unit: d (days), w
+    /// (weeks), m (months) or y (years), for example 90d. Local
+    /// emails falling out of the window are evicted. This overrides
+    /// the partial-sync.since option of your TOML configuration file.
+    #[arg(long, value_name = "WINDOW")]
+    pub since: Option<SyncWindow>,
+
+    /// Synchronize only the headers of emails bigger than the given
+    /// size.
+    ///
+    /// The size is a number followed by a unit: B, KB, MB or GB, for
+    /// example 10MB. Bodies are fetched on demand when reading
+    /// emails. This overrides the partial-sync.max-message-size
+    /// option of your TOML configuration file.
+    #[arg(long, value_name = "SIZE")]
+    pub max_message_size: Option<MessageSize>,
+
+    /// Do not update the local search index after the
+    /// synchronization.
+    ///
+    /// The search index is used by the envelope search command. It is
+    /// updated incrementally, but the first update needs to parse all
+    /// the synchronized messages, which can take time.
+    #[arg(long)]
+    pub skip_index: bool,
+
+    /// Stream synchronization events while synchronizing.
+    ///
+    /// Each event is printed to stdout as soon as it happens, on its
+    /// own line, followed by the final report. Useful to display live
+    /// progress in dashboards or status bars.
+    #[arg(long, value_name = "FORMAT", value_enum)]
+    pub events: Option<SyncEventsFmt>,
+
+    /// Keep running, synchronizing at regular interval.
+    ///
+    /// Without account name, all accounts with synchronization
+    /// enabled are synchronized. The interval and the upstream
+    /// watching can be customized per account with the daemon
+    /// configuration. Failed synchronizations are retried with an
+    /// exponential backoff. In machine-readable output modes, a
+    /// report is printed after every synchronization, on its own
+    /// line.
+    #[arg(long, conflicts_with = "dry_run")]
+    pub daemon: bool,
+
+    /// Replay only the hunks that failed to apply.
+    ///
+    /// Failed hunks are recorded by the synchronization journal, see
+    /// the account journal command. Hunks that only update the
+    /// synchronization cache cannot be replayed: their folders are
+    /// synchronized again instead.
+    #[arg(long, conflicts_with_all = ["include_folder", "exclude_folder", "all_folders"])]
+    #[arg(conflicts_with = "daemon")]
+    pub retry_failed: bool,
+
+    /// Rebuild the synchronization cache from scratch.
+    ///
+    /// The local Maildir is moved aside, then all folders and emails
+    /// are synchronized again. Use it when the synchronization cache
+    /// is corrupted. Local changes that have not been synchronized
+    /// yet are lost. The previous Maildir is removed once the
+    /// synchronization fully succeeds, otherwise it is kept so that
+    /// nothing is lost.
+    ///
+    /// The journal is cleared, and the search index is updated after
+    /// the synchronization unless --skip-index is given. The address
+    /// book and the id aliases are kept: aliases of the previous
+    /// Maildir become stale and can be removed with the cache clean
+    /// command.
+    #[arg(long, conflicts_with_all = ["dry_run", "daemon", "retry_failed"])]
+    pub reset_cache: bool,
 }
 
 impl AccountSyncCommand {
@@ -106,149 +213,873 @@ impl AccountSyncCommand {
             None
         };
 
+        let mut opts = SyncOpts {
+            strategy,
+            dry_run: self.dry_run,
+            skip_index: self.skip_index,
+            events: self.events.is_some(),
+            since: self.since,
+            max_message_size: self.max_message_size,
+        };
+
+        if self.daemon {
+            return run_daemon(printer, config, self.account.name, opts).await;
+        }
+
         let account = self.account.name.as_deref();
         let (toml_account_config, account_config) =
             config.clone().into_account_configs(account, true)?;
-        let account_name = account_config.name.as_str();
-
-        let backend_builder =
-            AccountSyncBackendBuilder::new(toml_account_config, account_config.clone()).await?;
-        let sync_builder = AccountSyncBuilder::new(backend_builder.into())?
-            .with_dry_run(self.dry_run)
-            .with_some_folders_filter(strategy);
-
-        if self.dry_run {
-            let report = sync_builder.sync().await?;
-            let mut hunks_count = report.folder.patch.len();
-
-            if !report.folder.patch.is_empty() {
-                printer.print_log("Folders patch:")?;
-                for (hunk, _) in report.folder.patch {
-                    printer.print_log(format!(" - {hunk}"))?;
-                }
-                printer.print_log("")?;
+        let account_name = account_config.name.clone();
+
+        let _lock = SyncLock::acquire(&account_name)?;
+
+        if self.retry_failed {
+            let journal = SyncJournal::new(&account_config)?;
+            let failures = journal.failures()?;
+
+            if failures.is_empty() {
+                return printer.print(format!(
+                    "Account {account_name} does not have any failed hunk to retry"
+                ));
             }
 
-            if !report.email.patch.is_empty() {
-                printer.print_log("Envelopes patch:")?;
-                for (hunk, _) in report.email.patch {
-                    hunks_count += 1;
-                    printer.print_log(format!(" - {hunk}"))?;
+            let replay = replay_failures(
+                printer,
+                &journal,
+                failures,
+                &toml_account_config,
+                account_config.clone(),
+            )
+            .await?;
+
+            // hunks that cannot be replayed are retried by
+            // synchronizing their folders again
+            if !replay.unreplayable.is_empty() {
+                if !replay.unreplayable.all {
+                    opts.strategy = Some(FolderSyncStrategy::Include(replay.unreplayable.folders));
                 }
-                printer.print_log("")?;
+
+                sync_account(printer, toml_account_config, account_config, &opts).await?;
+            }
+
+            if replay.failed > 0 {
+                bail!(
+                    "cannot replay {} failed hunk(s), see the account journal",
+                    replay.failed
+                );
+            }
+
+            return printer.print(format!(
+                "Successfully replayed {} failed hunk(s) of account {account_name}!",
+                replay.replayed
+            ));
+        }
+
+        let backup_dir = if self.reset_cache {
+            reset_cache(printer, &account_config, &opts)?
+        } else {
+            None
+        };
+
+        let res = sync_account(printer, toml_account_config, account_config, &opts).await;
+
+        if let Some(backup_dir) = backup_dir {
+            remove_backup(printer, &backup_dir, &res, &opts)?;
+        }
+
+        res.map(|_| ())
+    }
+}
+
+/// Represents the outcome of the replay of failed hunks.
+#[derive(Debug, Default)]
+struct ReplayOutcome {
+    replayed: usize,
+    failed: usize,
+    /// The folders of the failed hunks that cannot be replayed.
+    unreplayable: FailedFolders,
+}
+
+/// Replay the given failed hunks, and record the outcome in the
+/// journal.
+async fn replay_failures(
+    printer: &mut impl Printer,
+    journal: &SyncJournal,
+    failures: Vec<JournalEntry>,
+    toml_account_config: &Arc<TomlAccountConfig>,
+    account_config: Arc<AccountConfig>,
+) -> Result<ReplayOutcome> {
+    let mut outcome = ReplayOutcome::default();
+    let (replayable, unreplayable): (Vec<_>, Vec<_>) = failures
+        .into_iter()
+        .partition(|failure| failure.replay.is_some());
+
+    for failure in unreplayable {
+        match failure.folder {
+            Some(folder) => {
+                outcome.unreplayable.folders.insert(folder);
             }
+            None => outcome.unreplayable.all = true,
+        }
+    }
+
+    if replayable.is_empty() {
+        return Ok(outcome);
+    }
+
+    // features of the local backend must all target the local
+    // Maildir, whatever the backend overrides of the account are
+    let local_toml_account_config = Arc::new(TomlAccountConfig {
+        backend: Some(BackendKind::MaildirForSync),
+        folder: None,
+        envelope: None,
+        flag: None,
+        message: None,
+        ..(**toml_account_config).clone()
+    });
+
+    let local = build_replay_backend(local_toml_account_config, account_config.clone()).await?;
+    let remote = build_replay_backend(toml_account_config.clone(), account_config).await?;
+
+    printer.print_log(format!("Replaying {} failed hunk(s)…", replayable.len()))?;
+
+    for failure in replayable {
+        let res = match &failure.replay {
+            Some(hunk) => hunk.replay(&local, &remote).await,
+            None => continue,
+        };
+
+        match &res {
+            Ok(()) => outcome.replayed += 1,
+            Err(err) => {
+                outcome.failed += 1;
+                printer.print_log(format!("Cannot replay {}: {err}", failure.hunk))?;
+            }
+        }
+
+        journal.record_replay(&failure, &res)?;
+    }
+
+    Ok(outcome)
+}
+
+/// Build a backend able to replay hunks.
+async fn build_replay_backend(
+    toml_account_config: Arc<TomlAccountConfig>,
+    account_config: Arc<AccountConfig>,
+) -> Result<Backend> {
+    let kinds = [
+        toml_account_config.peek_messages_kind(),
+        toml_account_config.add_message_kind(),
+        toml_account_config.set_flags_kind(),
+        toml_account_config.delete_messages_kind(),
+    ];
+
+    Backend::new(
+        toml_account_config.clone(),
+        account_config,
+        kinds.into_iter().flatten(),
+        |builder| {
+            builder.set_peek_messages(BackendFeatureSource::Context);
+            builder.set_add_message(BackendFeatureSource::Context);
+            builder.set_set_flags(BackendFeatureSource::Context);
+            builder.set_delete_messages(BackendFeatureSource::Context);
+        },
+    )
+    .await
+}
+
+/// Synchronize the given account, printing the outcome according
+/// to the output format of the printer.
+///
+/// Returns the number of hunks that failed to apply.
+async fn sync_account(
+    printer: &mut impl Printer,
+    toml_account_config: Arc<TomlAccountConfig>,
+    account_config: Arc<AccountConfig>,
+    opts: &SyncOpts,
+) -> Result<usize> {
+    let account_name = account_config.name.clone();
+
+    if printer.is_machine_readable() || opts.events {
+        let res = sync_with_report(printer, toml_account_config, account_config, opts).await;
+
+        if !opts.dry_run {
+            let failed_hunks = res.as_ref().map(SyncReport::failed_hunks);
+            record_status(&account_name, &res, failed_hunks.unwrap_or_default(), None);
+        }
+
+        let report = res?;
+        let failed_hunks = report.failed_hunks();
+
+        if opts.events {
+            write_stream_item(&SyncStreamItem::Report(report))?;
+        } else {
+            printer.print(report)?;
+        }
+
+        Ok(failed_hunks)
+    } else if opts.dry_run {
+        sync_dry_run(printer, toml_account_config, account_config, opts).await?;
+        Ok(0)
+    } else {
+        let res = sync_with_progress(printer, toml_account_config, account_config, opts).await;
+        let failed_hunks = res.as_ref().map(SyncReport::failed_hunks);
+        record_status(&account_name, &res, failed_hunks.unwrap_or_default(), None);
+        let failed_hunks = res?.failed_hunks();
+
+        printer.print(format!("Account {account_name} successfully synchronized!"))?;
+
+        Ok(failed_hunks)
+    }
+}
+
+/// Represents the options of a synchronization.
+#[derive(Clone, Debug)]
+struct SyncOpts {
+    strategy: Option<FolderSyncStrategy>,
+    dry_run: bool,
+    skip_index: bool,
+    events: bool,
+    since: Option<SyncWindow>,
+    max_message_size: Option<MessageSize>,
+}
+
+impl SyncOpts {
+    /// Check if the given folder is synchronized, according to the
+    /// folders filter of the options.
+    fn is_synced(&self, folder: &str) -> bool {
+        match &self.strategy {
+            None | Some(FolderSyncStrategy::All) => true,
+            Some(FolderSyncStrategy::Include(folders)) => folders.contains(folder),
+            Some(FolderSyncStrategy::Exclude(folders)) => !folders.contains(folder),
+        }
+    }
+}
+
+/// Record the given synchronization report in the journal of the
+/// given account. Errors are only logged.
+fn record_journal(account_config: &AccountConfig, report: &SyncReport, opts: &SyncOpts) {
+    let res = SyncJournal::new(account_config)
+        .and_then(|journal| journal.record(report, |folder| opts.is_synced(folder)));
+
+    if let Err(err) = res {
+        warn!("cannot record sync journal: {err}");
+        debug!("{err:?}");
+    }
+}
+
+/// Move the synchronization directory of the given account aside, then
+/// clear the synchronization journal.
+///
+/// Returns the path of the moved directory, if any.
+fn reset_cache(
+    printer: &mut impl Printer,
+    account_config: &AccountConfig,
+    opts: &SyncOpts,
+) -> Result<Option<PathBuf>> {
+    let sync_dir = account_config.get_sync_dir()?;
 
-            printer.print(format!(
-                "Estimated patch length for account {account_name} to be synchronized: {hunks_count}"
+    if !sync_dir.exists() {
+        SyncJournal::new(account_config)?.clear()?;
+        return Ok(None);
+    }
+
+    let mut backup_name = sync_dir.file_name().unwrap_or_default().to_owned();
+    backup_name.push(format!(".bak-{}", Local::now().format("%Y%m%d%H%M%S")));
+    let backup_dir = sync_dir.with_file_name(backup_name);
+
+    fs::rename(&sync_dir, &backup_dir)
+        .with_context(|| format!("cannot move sync directory {sync_dir:?} to {backup_dir:?}"))?;
+
+    info!("sync directory moved to {backup_dir:?}");
+
+    SyncJournal::new(account_config)?.clear()?;
+
+    // logs must not pollute the events stream
+    if !opts.events {
+        printer.print_log(format!("Sync directory moved to {backup_dir:?}"))?;
+    }
+
+    Ok(Some(backup_dir))
+}
+
+/// Remove the synchronization directory moved aside by the cache
+/// reset, unless the synchronization failed or some hunks failed to
+/// apply.
+fn remove_backup(
+    printer: &mut impl Printer,
+    backup_dir: &Path,
+    res: &Result<usize>,
+    opts: &SyncOpts,
+) -> Result<()> {
+    if !matches!(res, Ok(0)) {
+        warn!("synchronization failed, previous sync directory kept at {backup_dir:?}");
+
+        if !opts.events {
+            printer.print_log(format!(
+                "Synchronization failed, the previous sync directory is kept at {backup_dir:?}"
             ))?;
-        } else if printer.is_json() {
-            sync_builder.sync().await?;
-            printer.print(format!("Account {account_name} successfully synchronized!"))?;
+        }
+
+        return Ok(());
+    }
+
+    fs::remove_dir_all(backup_dir)
+        .with_context(|| format!("cannot remove previous sync directory {backup_dir:?}"))
+}
+
+/// Build the account synchronization builder.
+async fn build_sync(
+    toml_account_config: Arc<TomlAccountConfig>,
+    account_config: Arc<AccountConfig>,
+    opts: &SyncOpts,
+) -> Result<AccountSyncBuilder<BackendContextBuilder>> {
+    let partial_sync = toml_account_config
+        .partial_sync
+        .clone()
+        .unwrap_or_default()
+        .with_overrides(opts.since, opts.max_message_size);
+
+    let backend_builder =
+        AccountSyncBackendBuilder::new(toml_account_config, account_config, partial_sync).await?;
+
+    Ok(AccountSyncBuilder::new(backend_builder.into())?
+        .with_dry_run(opts.dry_run)
+        .with_some_folders_filter(opts.strategy.clone()))
+}
+
+/// List envelopes of the rules folder before the synchronization, so
+/// that rules only apply to new ones.
+///
+/// If the folder cannot be listed, for example because it has never
+/// been synchronized, rules are skipped for this synchronization:
+/// every message would otherwise be considered as new.
+async fn list_rules_ids<'a>(
+    toml_account_config: &'a TomlAccountConfig,
+    account_config: Arc<AccountConfig>,
+    opts: &SyncOpts,
+) -> Result<Option<(&'a RulesConfig, Backend, HashSet<String>)>> {
+    match &toml_account_config.rules {
+        Some(config) if config.is_apply_on_sync_enabled() && !opts.dry_run => {
+            let backend = rule::build_sync_backend(toml_account_config, account_config).await?;
+            let folder = config.get_sync_folder();
+            match rule::list_ids(&backend, folder).await {
+                Ok(ids) => Ok(Some((config, backend, ids))),
+                Err(err) => {
+                    warn!("cannot list envelopes of folder {folder}, skipping rules: {err}");
+                    debug!("{err:?}");
+                    Ok(None)
+                }
+            }
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Synchronize the given account, then build a report of the
+/// synchronization.
+///
+/// Events are streamed to stdout if enabled.
+async fn sync_with_report(
+    printer: &mut impl Printer,
+    toml_account_config: Arc<TomlAccountConfig>,
+    account_config: Arc<AccountConfig>,
+    opts: &SyncOpts,
+) -> Result<SyncReport> {
+    let account_name = account_config.name.clone();
+    let rules = list_rules_ids(&toml_account_config, account_config.clone(), opts).await?;
+    let sync_builder =
+        build_sync(toml_account_config.clone(), account_config.clone(), opts).await?;
+
+    let started_at = Local::now();
+    let start = Instant::now();
+    let timings = Arc::new(Mutex::new(SyncTimings::default()));
+    let events = opts.events;
+
+    let handler_account = account_name.clone();
+    let handler_timings = timings.clone();
+    let report = sync_builder
+        .with_handler(move |evt| {
+            let elapsed = start.elapsed();
+            handler_timings.lock().unwrap().record(&evt, elapsed);
+
+            if events {
+                let evt = SyncEventReport::new(&handler_account, &evt, elapsed);
+                if let Err(err) = write_stream_item(&SyncStreamItem::Event(evt)) {
+                    warn!("cannot write sync event: {err}");
+                    debug!("{err:?}");
+                }
+            }
+
+            async { Ok(()) }
+        })
+        .sync()
+        .await?;
+
+    let mut sync_report = build_report(&account_name, &report, opts, &started_at);
+
+    record_journal(&account_config, &sync_report, opts);
+
+    let mut timings = timings.lock().unwrap().clone();
+
+    if !opts.dry_run {
+        // logs of post-synchronization steps must not pollute the
+        // events stream
+        let mut quiet_printer = StdoutPrinter::from(OutputFmt::Ndjson);
+        let post_timings = if events {
+            post_sync(
+                &mut quiet_printer,
+                rules,
+                &toml_account_config,
+                account_config,
+                opts.skip_index,
+            )
+            .await?
         } else {
-            let multi = MultiProgress::new();
-            let sub_progresses = Mutex::new(HashMap::new());
-            let main_progress = multi.add(
-                ProgressBar::new(100)
-                    .with_style(MAIN_PROGRESS_STYLE.clone())
-                    .with_message("Listing folders…"),
-            );
-
-            main_progress.tick();
-
-            let report = sync_builder
-                .with_handler(move |evt| {
-                    match evt {
-                        SyncEvent::ListedAllFolders => {
-                            main_progress.set_message("Synchronizing folders…");
-                        }
-                        SyncEvent::ProcessedAllFolderHunks => {
-                            main_progress.set_message("Listing envelopes…");
-                        }
-                        SyncEvent::GeneratedEmailPatch(patches) => {
-                            let patches_len = patches.values().flatten().count();
-                            main_progress.set_length(patches_len as u64);
-                            main_progress.set_position(0);
-                            main_progress.set_message("Synchronizing emails…");
-
-                            let mut envelopes_progresses = sub_progresses.lock().unwrap();
-                            for (folder, patch) in patches {
-                                let progress = ProgressBar::new(patch.len() as u64)
-                                    .with_style(SUB_PROGRESS_STYLE.clone())
-                                    .with_prefix(folder.clone())
-                                    .with_finish(ProgressFinish::AndClear);
-                                let progress = multi.add(progress);
-                                envelopes_progresses.insert(folder, progress.clone());
-                            }
-                        }
-                        SyncEvent::ProcessedEmailHunk(hunk) => {
-                            main_progress.inc(1);
-                            let mut progresses = sub_progresses.lock().unwrap();
-                            if let Some(progress) = progresses.get_mut(hunk.folder()) {
-                                progress.inc(1);
-                                if progress.position() == (progress.length().unwrap() - 1) {
-                                    progress.set_style(SUB_PROGRESS_DONE_STYLE.clone())
-                                } else {
-                                    progress.set_message(format!("{hunk}…"));
-                                }
-                            }
-                        }
-                        SyncEvent::ProcessedAllEmailHunks => {
-                            let mut progresses = sub_progresses.lock().unwrap();
-                            for progress in progresses.values() {
-                                progress.finish_and_clear()
-                            }
-                            progresses.clear();
-
-                            main_progress.set_length(100);
-                            main_progress.set_position(100);
-                            main_progress.set_message("Expunging folders…");
-                        }
-                        SyncEvent::ExpungedAllFolders => {
-                            main_progress.finish_and_clear();
-                        }
-                        _ => {
-                            main_progress.tick();
-                        }
-                    };
+            post_sync(
+                printer,
+                rules,
+                &toml_account_config,
+                account_config,
+                opts.skip_index,
+            )
+            .await?
+        };
+
+        timings.rules_ms = post_timings.rules_ms;
+        timings.index_ms = post_timings.index_ms;
+        timings.contacts_ms = post_timings.contacts_ms;
+    }
+
+    sync_report.timings = timings;
+    sync_report.set_duration(start.elapsed());
 
-                    async { Ok(()) }
-                })
-                .sync()
-                .await?;
+    Ok(sync_report)
+}
 
-            let folders_patch_err = report
-                .folder
+/// Build the report of the given synchronization.
+fn build_report(
+    account_name: &str,
+    report: &AccountSyncReport,
+    opts: &SyncOpts,
+    started_at: &DateTime<Local>,
+) -> SyncReport {
+    let mut sync_report = SyncReport::new(account_name, opts.dry_run, started_at.to_rfc3339());
+    sync_report.folders = PatchReport::new(
+        report
+            .folder
+            .patch
+            .iter()
+            .map(|(hunk, err)| (None, hunk, err.as_ref())),
+        opts.dry_run,
+    );
+    sync_report.set_emails(
+        PatchReport::new(
+            report
+                .email
                 .patch
                 .iter()
-                .filter_map(|(hunk, err)| err.as_ref().map(|err| (hunk, err)))
-                .collect::<Vec<_>>();
-            if !folders_patch_err.is_empty() {
-                printer.print_log("")?;
-                printer.print_log("Errors occurred while applying the folders patch:")?;
-                folders_patch_err
-                    .iter()
-                    .try_for_each(|(hunk, err)| printer.print_log(format!(" - {hunk}: {err}")))?;
-            }
-
-            let envelopes_patch_err = report
+                .map(|(hunk, err)| (Some(hunk.folder()), hunk, err.as_ref())),
+            opts.dry_run,
+        )
+        .with_replays(
+            report
                 .email
                 .patch
                 .iter()
-                .filter_map(|(hunk, err)| err.as_ref().map(|err| (hunk, err)))
-                .collect::<Vec<_>>();
-            if !envelopes_patch_err.is_empty() {
-                printer.print_log("")?;
-                printer.print_log("Errors occurred while applying the envelopes patch:")?;
-                for (hunk, err) in envelopes_patch_err {
-                    printer.print_log(format!(" - {hunk}: {err}"))?;
+                .map(|(hunk, _)| ReplayHunk::from_hunk(hunk)),
+        ),
+    );
+    sync_report
+}
+
+/// Print the changes the synchronization of the given account would
+/// apply, without applying them.
+async fn sync_dry_run(
+    printer: &mut impl Printer,
+    toml_account_config: Arc<TomlAccountConfig>,
+    account_config: Arc<AccountConfig>,
+    opts: &SyncOpts,
+) -> Result<()> {
+    let account_name = account_config.name.clone();
+    let report = build_sync(toml_account_config, account_config, opts)
+        .await?
+        .sync()
+        .await?;
+    let mut hunks_count = report.folder.patch.len();
+
+    if !report.folder.patch.is_empty() {
+        printer.print_log("Folders patch:")?;
+        for (hunk, _) in report.folder.patch {
+            printer.print_log(format!(" - {hunk}"))?;
+        }
+        printer.print_log("")?;
+    }
+
+    if !report.email.patch.is_empty() {
+        printer.print_log("Envelopes patch:")?;
+        for (hunk, _) in report.email.patch {
+            hunks_count += 1;
+            printer.print_log(format!(" - {hunk}"))?;
+        }
+        printer.print_log("")?;
+    }
+
+    printer.print(format!(
+        "Estimated patch length for account {account_name} to be synchronized: {hunks_count}"
+    ))
+}
+
+/// Synchronize the given account, displaying progress bars.
+async fn sync_with_progress(
+    printer: &mut impl Printer,
+    toml_account_config: Arc<TomlAccountConfig>,
+    account_config: Arc<AccountConfig>,
+    opts: &SyncOpts,
+) -> Result<SyncReport> {
+    let started_at = Local::now();
+    let rules = list_rules_ids(&toml_account_config, account_config.clone(), opts).await?;
+    let sync_builder =
+        build_sync(toml_account_config.clone(), account_config.clone(), opts).await?;
+
+    let multi = MultiProgress::new();
+    let sub_progresses = Mutex::new(HashMap::new());
+    let main_progress = multi.add(
+        ProgressBar::new(100)
+            .with_style(MAIN_PROGRESS_STYLE.clone())
+            .with_message("Listing folders…"),
+    );
+
+    main_progress.tick();
+
+    let report = sync_builder
+        .with_handler(move |evt| {
+            match evt {
+                SyncEvent::ListedAllFolders => {
+                    main_progress.set_message("Synchronizing folders…");
                 }
-            }
+                SyncEvent::ProcessedAllFolderHunks => {
+                    main_progress.set_message("Listing envelopes…");
+                }
+                SyncEvent::GeneratedEmailPatch(patches) => {
+                    let patches_len = patches.values().flatten().count();
+                    main_progress.set_length(patches_len as u64);
+                    main_progress.set_position(0);
+                    main_progress.set_message("Synchronizing emails…");
 
-            printer.print(format!("Account {account_name} successfully synchronized!"))?;
+                    let mut envelopes_progresses = sub_progresses.lock().unwrap();
+                    for (folder, patch) in patches {
+                        let progress = ProgressBar::new(patch.len() as u64)
+                            .with_style(SUB_PROGRESS_STYLE.clone())
+                            .with_prefix(folder.clone())
+                            .with_finish(ProgressFinish::AndClear);
+                        let progress = multi.add(progress);
+                        envelopes_progresses.insert(folder, progress.clone());
+                    }
+                }
+                SyncEvent::ProcessedEmailHunk(hunk) => {
+                    main_progress.inc(1);
+                    let mut progresses = sub_progresses.lock().unwrap();
+                    if let Some(progress) = progresses.get_mut(hunk.folder()) {
+                        progress.inc(1);
+                        if progress.position() == (progress.length().unwrap() - 1) {
+                            progress.set_style(SUB_PROGRESS_DONE_STYLE.clone())
+                        } else {
+                            progress.set_message(format!("{hunk}…"));
+                        }
+                    }
+                }
+                SyncEvent::ProcessedAllEmailHunks => {
+                    let mut progresses = sub_progresses.lock().unwrap();
+                    for progress in progresses.values() {
+                        progress.finish_and_clear()
+                    }
+                    progresses.clear();
+
+                    main_progress.set_length(100);
+                    main_progress.set_position(100);
+                    main_progress.set_message("Expunging folders…");
+                }
+                SyncEvent::ExpungedAllFolders => {
+                    main_progress.finish_and_clear();
+                }
+                _ => {
+                    main_progress.tick();
+                }
+            };
+
+            async { Ok(()) }
+        })
+        .sync()
+        .await?;
+
+    let folders_patch_err = report
+        .folder
+        .patch
+        .iter()
+        .filter_map(|(hunk, err)| err.as_ref().map(|err| (hunk, err)))
+        .collect::<Vec<_>>();
+    if !folders_patch_err.is_empty() {
+        printer.print_log("")?;
+        printer.print_log("Errors occurred while applying the folders patch:")?;
+        folders_patch_err
+            .iter()
+            .try_for_each(|(hunk, err)| printer.print_log(format!(" - {hunk}: {err}")))?;
+    }
+
+    let envelopes_patch_err = report
+        .email
+        .patch
+        .iter()
+        .filter_map(|(hunk, err)| err.as_ref().map(|err| (hunk, err)))
+        .collect::<Vec<_>>();
+    if !envelopes_patch_err.is_empty() {
+        printer.print_log("")?;
+        printer.print_log("Errors occurred while applying the envelopes patch:")?;
+        for (hunk, err) in &envelopes_patch_err {
+            printer.print_log(format!(" - {hunk}: {err}"))?;
         }
+    }
+
+    let sync_report = build_report(&account_config.name, &report, opts, &started_at);
+
+    record_journal(&account_config, &sync_report, opts);
 
-        Ok(())
+    post_sync(
+        printer,
+        rules,
+        &toml_account_config,
+        account_config,
+        opts.skip_index,
+    )
+    .await?;
+
+    Ok(sync_report)
+}
+
+/// Synchronize accounts periodically, until the process is killed.
+///
+/// Without account name, all accounts with synchronization enabled
+/// are synchronized.
+async fn run_daemon(
+    printer: &mut impl Printer,
+    config: &TomlConfig,
+    account_name: Option<String>,
+    opts: SyncOpts,
+) -> Result<()> {
+    let names = match account_name {
+        Some(name) => vec![name],
+        None => config
+            .accounts
+            .iter()
+            .filter(|(_, config)| {
+                let sync = config.sync.as_ref();
+                sync.and_then(|config| config.enable).unwrap_or_default()
+            })
+            .map(|(name, _)| name.clone())
+            .collect(),
+    };
+
+    if names.is_empty() {
+        bail!("cannot find any account with synchronization enabled");
     }
+
+    let mut accounts = Vec::new();
+    for name in names {
+        let (toml_account_config, account_config) =
+            config.clone().into_account_configs(Some(&name), true)?;
+        accounts.push(DaemonAccount::new(toml_account_config, account_config));
+    }
+
+    let stream = opts.events || printer.is_machine_readable();
+
+    daemon::run(printer, accounts, stream, move |account| {
+        let toml_account_config = account.toml_account_config.clone();
+        let account_config = account.account_config.clone();
+        let opts = opts.clone();
+
+        Box::pin(async move {
+            let mut printer = StdoutPrinter::from(OutputFmt::Ndjson);
+            sync_with_report(&mut printer, toml_account_config, account_config, &opts).await
+        })
+    })
+    .await
+}
+
+/// Run the steps following a synchronization: rules, search index
+/// and address book updates.
+///
+/// Errors are only logged, so that they do not fail a synchronization
+/// that is already applied. Returns the duration of each step that
+/// ran.
+async fn post_sync(
+    printer: &mut impl Printer,
+    rules: Option<(&RulesConfig, Backend, HashSet<String>)>,
+    toml_account_config: &TomlAccountConfig,
+    account_config: Arc<AccountConfig>,
+    skip_index: bool,
+) -> Result<SyncTimings> {
+    let mut timings = SyncTimings::default();
+
+    // the synchronization is already applied, so the following steps
+    // are only run on a best-effort basis
+
+    if let Some((config, backend, ids)) = rules {
+        let start = Instant::now();
+        if let Err(err) = apply_rules(printer, config, &backend, ids).await {
+            warn!("cannot apply rules: {err}");
+            debug!("{err:?}");
+        }
+        timings.rules_ms = Some(start.elapsed().as_millis() as u64);
+    }
+
+    if !skip_index {
+        let start = Instant::now();
+        let res = update_search_index(printer, toml_account_config, account_config.clone()).await;
+        if let Err(err) = res {
+            warn!("cannot update search index: {err}");
+            debug!("{err:?}");
+        }
+        timings.index_ms = Some(start.elapsed().as_millis() as u64);
+    }
+
+    let collect_contacts = toml_account_config
+        .contact
+        .as_ref()
+        .map(ContactConfig::is_collect_enabled)
+        .unwrap_or(false);
+
+    if collect_contacts {
+        let start = Instant::now();
+        if let Err(err) = update_contacts(printer, toml_account_config, account_config).await {
+            warn!("cannot update contacts: {err}");
+            debug!("{err:?}");
+        }
+        timings.contacts_ms = Some(start.elapsed().as_millis() as u64);
+    }
+
+    Ok(timings)
+}
+
+/// Apply rules to envelopes of the rules folder that appeared during
+/// the synchronization.
+async fn apply_rules(
+    printer: &mut impl Printer,
+    config: &RulesConfig,
+    backend: &Backend,
+    known_ids: HashSet<String>,
+) -> Result<()> {
+    let folder = config.get_sync_folder();
+
+    printer.print_log("")?;
+    printer.print_log(format!("Applying rules to new envelopes of {folder}…"))?;
+
+    let ids = rule::list_ids(backend, folder).await?;
+    let new_ids = ids.difference(&known_ids).cloned().collect();
+    let matches = rule::apply(backend, &config.rules, folder, Some(&new_ids), false).await?;
+
+    printer.print_log(format!("Rules applied: {} match(es)", matches.len()))?;
+
+    Ok(())
+}
+
+/// Update the local search index from the freshly synchronized
+/// Maildir.
+async fn update_search_index(
+    printer: &mut impl Printer,
+    toml_account_config: &TomlAccountConfig,
+    account_config: Arc<AccountConfig>,
+) -> Result<()> {
+    printer.print_log("")?;
+    printer.print_log("Updating search index…")?;
+
+    let report = SearchIndex::new(&account_config)?
+        .update_from_sync_dir(toml_account_config, account_config)
+        .await?;
+
+    printer.print_log(format!(
+        "Search index updated: {} added, {} updated, {} removed",
+        report.added, report.updated, report.removed
+    ))?;
+
+    if !report.errors.is_empty() {
+        printer.print_log("Errors occurred while updating the search index:")?;
+        for (folder, id, err) in report.errors {
+            printer.print_log(format!(" - {folder}/{id}: {err}"))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Collect contacts from the envelopes of the freshly synchronized
+/// Maildir.
+///
+/// Envelopes are listed page by page, most recent first, until a page
+/// contains no new message.
+async fn update_contacts(
+    printer: &mut impl Printer,
+    toml_account_config: &TomlAccountConfig,
+    account_config: Arc<AccountConfig>,
+) -> Result<()> {
+    printer.print_log("")?;
+    printer.print_log("Updating address book…")?;
+
+    let toml_account_config = Arc::new(TomlAccountConfig {
+        backend: Some(BackendKind::MaildirForSync),
+        folder: None,
+        envelope: None,
+        flag: None,
+        message: None,
+        ..toml_account_config.clone()
+    });
+
+    let backend = Backend::new(
+        toml_account_config,
+        account_config.clone(),
+        [&BackendKind::MaildirForSync],
+        |builder| {
+            builder.set_list_folders(BackendFeatureSource::Context);
+            builder.set_list_envelopes(BackendFeatureSource::Context);
+        },
+    )
+    .await?;
+
+    let book = AddressBook::new(&account_config)?;
+    let mut recorded = 0;
+
+    for folder in backend.list_folders().await?.iter() {
+        for page in 0.. {
+            let opts = ListEnvelopesOptions {
+                page,
+                page_size: CONTACTS_PAGE_SIZE,
+                query: None,
+            };
+
+            let backend_envelopes = backend.backend.list_envelopes(&folder.name, opts).await?;
+            let envelopes = backend_envelopes
+                .iter()
+                .map(|envelope| Envelope::from_backend(&account_config, &IdMapper::Dummy, envelope))
+                .collect::<Result<Vec<_>>>()?;
+
+            let count =
+                book.record_envelopes(backend_envelopes.iter().zip(&envelopes).map(|(b, e)| {
+                    let mailboxes = [&e.from, &e.to].into_iter().chain(&e.cc).collect();
+                    (b.message_id.as_str(), b.date.timestamp(), mailboxes)
+                }))?;
+
+            recorded += count;
+
+            if count == 0 || envelopes.len() < CONTACTS_PAGE_SIZE {
+                break;
+            }
+        }
+    }
+
+    printer.print_log(format!("Address book updated: {recorded} new message(s)"))?;
+
+    Ok(())
 }
 
 pub struct AccountSyncBackendBuilder {
@@ -260,6 +1091,7 @@ impl AccountSyncBackendBuilder {
     pub async fn new(
         toml_account_config: Arc<TomlAccountConfig>,
         account_config: Arc<AccountConfig>,
+        partial_sync: PartialSyncConfig,
     ) -> Result<Self> {
         #[allow(unused)]
         let used_backends = toml_account_config.get_used_backends();
@@ -326,6 +1158,17 @@ impl AccountSyncBackendBuilder {
 
             #[cfg(feature = "sendmail")]
             sendmail: None,
+
+            partial_sync: if partial_sync.is_enabled() {
+                Some(Arc::new(PartialSync {
+                    config: partial_sync,
+                    cache: PartialCache::new(&account_config)?,
+                }))
+            } else {
+                None
+            },
+
+            partial_cache: None,
         };
 
         let backend_builder = BackendBuilder::new(account_config.clone(), backend_ctx_builder);
@@ -340,6 +1183,7 @@ impl AccountSyncBackendBuilder {
         Ok(Backend {
             toml_account_config: self.toml_account_config,
             backend: self.builder.build().await?,
+            address_book: OnceCell::new(),
         })
     }
 }
@@ -21,9 +21,13 @@ use email::template::config::TemplateConfig;
 use serde::{Deserialize, Serialize};
 use std::{collections::HashSet, path::PathBuf};
 
+#[cfg(feature = "account-sync")]
+use crate::account::{daemon::config::SyncDaemonConfig, partial::config::PartialSyncConfig};
 use crate::{
-    backend::BackendKind, envelope::config::EnvelopeConfig, flag::config::FlagConfig,
-    folder::config::FolderConfig, message::config::MessageConfig,
+    backend::BackendKind, contact::config::ContactConfig, envelope::config::EnvelopeConfig,
+    flag::config::FlagConfig, folder::config::FolderConfig,
+    message::attachment::config::AttachmentConfig, message::config::MessageConfig,
+    rule::config::RulesConfig,
 };
 
 /// Represents all existing kind of account config.
@@ -40,6 +44,10 @@ pub struct TomlAccountConfig {
 
     #[cfg(feature = "account-sync")]
     pub sync: Option<SyncConfig>,
+    #[cfg(feature = "account-sync")]
+    pub daemon: Option<SyncDaemonConfig>,
+    #[cfg(feature = "account-sync")]
+    pub partial_sync: Option<PartialSyncConfig>,
     #[cfg(feature = "pgp")]
     pub pgp: Option<PgpConfig>,
 
@@ -47,7 +55,10 @@ pub struct TomlAccountConfig {
     pub envelope: Option<EnvelopeConfig>,
     pub flag: Option<FlagConfig>,
     pub message: Option<MessageConfig>,
+    pub attachment: Option<AttachmentConfig>,
     pub template: Option<TemplateConfig>,
+    pub rules: Option<RulesConfig>,
+    pub contact: Option<ContactConfig>,
 
     #[cfg(feature = "imap")]
     pub imap: Option<ImapConfig>,
@@ -0,0 +1,41 @@
+use email::folder::INBOX;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// The default interval between two synchronizations.
+const DEFAULT_INTERVAL: u64 = 5 * 60;
+
+/// The synchronization daemon configuration of an account.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct SyncDaemonConfig {
+    /// The interval between two synchronizations, in seconds.
+    ///
+    /// Defaults to 5 minutes.
+    pub interval: Option<u64>,
+
+    /// Synchronize as soon as the backend reports changes in the
+    /// watched folder.
+    ///
+    /// Defaults to true.
+    pub watch: Option<bool>,
+
+    /// The folder watched for upstream changes.
+    ///
+    /// Defaults to the inbox.
+    pub watch_folder: Option<String>,
+}
+
+impl SyncDaemonConfig {
+    pub fn get_interval(&self) -> Duration {
+        Duration::from_secs(self.interval.unwrap_or(DEFAULT_INTERVAL).max(1))
+    }
+
+    pub fn is_watch_enabled(&self) -> bool {
+        self.watch.unwrap_or(true)
+    }
+
+    pub fn get_watch_folder(&self) -> &str {
+        self.watch_folder.as_deref().unwrap_or(INBOX)
+    }
+}
@@ -0,0 +1,321 @@
+//! Module dedicated to the synchronization daemon.
+//!
+//! The daemon synchronizes accounts at regular interval, and as soon
+//! as the backend reports changes in the watched folder. Failed
+//! synchronizations are retried with an exponential backoff, and
+//! synchronizations of the same account never overlap thanks to the
+//! synchronization lock.
+
+pub mod config;
+
+use anyhow::Result;
+use chrono::Local;
+use email::account::config::AccountConfig;
+use email::{backend::feature::BackendFeatureSource, envelope::watch::config::WatchFn};
+use log::{debug, info, warn};
+use std::{
+    collections::HashSet,
+    future::Future,
+    iter,
+    pin::Pin,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::{
+    sync::mpsc::{self, UnboundedSender},
+    task::JoinHandle,
+};
+
+use crate::{
+    account::{
+        config::TomlAccountConfig,
+        sync::{record_status, write_stream_item, SyncLock, SyncReport, SyncStreamItem},
+    },
+    backend::Backend,
+    printer::Printer,
+};
+
+use self::config::SyncDaemonConfig;
+
+/// The delay before retrying a failed synchronization for the first
+/// time. The delay doubles after each consecutive failure.
+const RETRY_DELAY: Duration = Duration::from_secs(30);
+
+/// The maximum delay before retrying a failed synchronization.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60 * 60);
+
+/// The future synchronizing an account, returned by the
+/// synchronization function given to the daemon.
+pub type SyncFuture = Pin<Box<dyn Future<Output = Result<SyncReport>>>>;
+
+/// Watcher detecting upstream changes using the watch feature of the
+/// account backend.
+///
+/// The name of the account is sent to the daemon every time the
+/// backend reports a change in the watched folder.
+struct Watcher {
+    toml_account_config: Arc<TomlAccountConfig>,
+    account_config: Arc<AccountConfig>,
+    folder: String,
+}
+
+impl Watcher {
+    /// Watch the folder forever, building the backend again with an
+    /// exponential backoff when it cannot be built or when watching
+    /// fails.
+    async fn run(self, changes: UnboundedSender<String>) {
+        let name = self.account_config.name.clone();
+        let mut failures = 0;
+
+        loop {
+            let res = match self.build_backend(changes.clone()).await {
+                Ok(backend) => {
+                    failures = 0;
+                    backend.watch_envelopes(&self.folder).await
+                }
+                Err(err) => Err(err),
+            };
+
+            if let Err(err) = res {
+                warn!(
+                    "cannot watch folder {} of account {name}: {err}",
+                    self.folder
+                );
+                debug!("{err:?}");
+            }
+
+            failures += 1;
+            tokio::time::sleep(retry_delay(failures)).await;
+        }
+    }
+
+    /// Build the backend watching the folder, with a hook notifying
+    /// the daemon of any change. Hooks of the account configuration
+    /// are kept.
+    async fn build_backend(&self, changes: UnboundedSender<String>) -> Result<Backend> {
+        let name = self.account_config.name.clone();
+        let mut account_config = (*self.account_config).clone();
+
+        let envelope_config = account_config.envelope.get_or_insert_with(Default::default);
+        let watch_config = envelope_config.watch.get_or_insert_with(Default::default);
+        let hook = watch_config.any.get_or_insert_with(Default::default);
+
+        hook.callback = Some(WatchFn::new(move |_| {
+            let _ = changes.send(name.clone());
+            async { Ok(()) }
+        }));
+
+        let toml_account_config = self.toml_account_config.clone();
+        let watch_envelopes_kind = toml_account_config.watch_envelopes_kind();
+
+        Backend::new(
+            toml_account_config.clone(),
+            Arc::new(account_config),
+            watch_envelopes_kind,
+            |builder| builder.set_watch_envelopes(BackendFeatureSource::Context),
+        )
+        .await
+    }
+}
+
+/// Represents an account synchronized by the daemon.
+pub struct DaemonAccount {
+    pub toml_account_config: Arc<TomlAccountConfig>,
+    pub account_config: Arc<AccountConfig>,
+    config: SyncDaemonConfig,
+    failures: u32,
+    next_sync: Instant,
+}
+
+impl DaemonAccount {
+    pub fn new(
+        toml_account_config: Arc<TomlAccountConfig>,
+        account_config: Arc<AccountConfig>,
+    ) -> Self {
+        let config = toml_account_config.daemon.clone().unwrap_or_default();
+
+        Self {
+            toml_account_config,
+            account_config,
+            config,
+            failures: 0,
+            next_sync: Instant::now(),
+        }
+    }
+
+    fn name(&self) -> &str {
+        &self.account_config.name
+    }
+
+    fn watcher(&self) -> Option<Watcher> {
+        if !self.config.is_watch_enabled() {
+            return None;
+        }
+
+        Some(Watcher {
+            toml_account_config: self.toml_account_config.clone(),
+            account_config: self.account_config.clone(),
+            folder: self.config.get_watch_folder().to_owned(),
+        })
+    }
+
+    /// Synchronize the account, then schedule the next
+    /// synchronization.
+    async fn sync(
+        &mut self,
+        printer: &mut impl Printer,
+        stream: bool,
+        sync: &impl Fn(&DaemonAccount) -> SyncFuture,
+    ) -> Result<()> {
+        let name = self.name().to_owned();
+
+        let lock = match SyncLock::acquire(&name) {
+            Ok(lock) => lock,
+            Err(err) => {
+                info!("skipping synchronization of account {name}: {err}");
+                self.next_sync = Instant::now() + RETRY_DELAY;
+                return Ok(());
+            }
+        };
+
+        printer.print_log(format!("Synchronizing account {name}…"))?;
+
+        let res = sync(self).await;
+        drop(lock);
+
+        let delay = match &res {
+            Ok(_) => {
+                self.failures = 0;
+                self.config.get_interval()
+            }
+            Err(_) => {
+                self.failures += 1;
+                retry_delay(self.failures)
+            }
+        };
+
+        self.next_sync = Instant::now() + delay;
+
+        let next_sync_at = chrono::Duration::from_std(delay)
+            .ok()
+            .map(|delay| (Local::now() + delay).to_rfc3339());
+        let failed_hunks = res.as_ref().map(SyncReport::failed_hunks);
+        record_status(&name, &res, failed_hunks.unwrap_or_default(), next_sync_at);
+
+        match res {
+            Ok(report) if stream => write_stream_item(&SyncStreamItem::Report(report))?,
+            Ok(report) => printer.print_log(report)?,
+            Err(err) => {
+                warn!("cannot synchronize account {name}: {err}");
+                debug!("{err:?}");
+                printer.print_log(format!(
+                    "Synchronization of account {name} failed, retrying in {}s: {err}",
+                    delay.as_secs()
+                ))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Run the synchronization daemon for the given accounts, using the
+/// given function to synchronize them.
+///
+/// Watchers run in the background, so that an account failing to
+/// connect does not prevent the others from being synchronized. When
+/// streaming is enabled, a report is written to stdout after every
+/// successful synchronization. This function never returns, unless
+/// an error occurs.
+pub async fn run(
+    printer: &mut impl Printer,
+    mut accounts: Vec<DaemonAccount>,
+    stream: bool,
+    sync: impl Fn(&DaemonAccount) -> SyncFuture,
+) -> Result<()> {
+    let (tx, mut changes) = mpsc::unbounded_channel();
+
+    let watchers: Vec<_> = accounts
+        .iter()
+        .filter_map(DaemonAccount::watcher)
+        .map(|watcher| tokio::spawn(watcher.run(tx.clone())))
+        .collect();
+
+    // aborts watchers when the daemon stops
+    let _watchers = AbortOnDrop(watchers);
+
+    loop {
+        let due = accounts
+            .iter_mut()
+            .find(|account| account.next_sync <= Instant::now());
+
+        if let Some(account) = due {
+            account.sync(printer, stream, &sync).await?;
+
+            // changes made by the synchronization itself should not
+            // trigger a new synchronization
+            let name = account.name().to_owned();
+            let pending: HashSet<String> = iter::from_fn(|| changes.try_recv().ok())
+                .filter(|changed| *changed != name)
+                .collect();
+            schedule_now(&mut accounts, &pending);
+            continue;
+        }
+
+        let next_sync = accounts.iter().map(|account| account.next_sync).min();
+        let delay = next_sync
+            .map(|next_sync| next_sync.saturating_duration_since(Instant::now()))
+            .unwrap_or(Duration::MAX);
+
+        tokio::select! {
+            _ = tokio::time::sleep(delay) => (),
+            Some(name) = changes.recv() => {
+                debug!("changes detected upstream for account {name}");
+                schedule_now(&mut accounts, &HashSet::from_iter([name]));
+            }
+        }
+    }
+}
+
+/// Schedule an immediate synchronization of the given accounts.
+fn schedule_now(accounts: &mut [DaemonAccount], names: &HashSet<String>) {
+    for account in accounts.iter_mut() {
+        if names.contains(account.name()) {
+            account.next_sync = Instant::now();
+        }
+    }
+}
+
+/// Abort the given tasks when dropped.
+struct AbortOnDrop(Vec<JoinHandle<()>>);
+
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        for task in &self.0 {
+            task.abort();
+        }
+    }
+}
+
+/// Compute the delay before retrying a synchronization, given the
+/// number of consecutive failures.
+fn retry_delay(failures: u32) -> Duration {
+    let factor = 2u32.saturating_pow(failures.saturating_sub(1));
+    RETRY_DELAY.saturating_mul(factor).min(MAX_RETRY_DELAY)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::retry_delay;
+
+    #[test]
+    fn retry_delays() {
+        assert_eq!(retry_delay(1), Duration::from_secs(30));
+        assert_eq!(retry_delay(2), Duration::from_secs(60));
+        assert_eq!(retry_delay(4), Duration::from_secs(240));
+        assert_eq!(retry_delay(10), Duration::from_secs(3600));
+        assert_eq!(retry_delay(100), Duration::from_secs(3600));
+    }
+}
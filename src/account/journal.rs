@@ -0,0 +1,557 @@
+//! Module dedicated to the synchronization journal.
+//!
+//! The journal records every hunk applied or failed by the
+//! synchronizations of an account. Failed hunks are also indexed
+//! until they are resolved, either by replaying them or by a later
+//! synchronization of their folder.
+
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Local};
+use email::{
+    account::config::AccountConfig,
+    email::sync::hunk::EmailSyncHunk,
+    envelope::Id,
+    flag::{set::SetFlags, Flag, Flags},
+    message::{add::AddMessage, delete::DeleteMessages, peek::PeekMessages},
+    sync::SyncDestination,
+};
+use log::debug;
+use serde::{Deserialize, Serialize};
+use sled::{Config, Db, Tree};
+use std::{collections::BTreeSet, ops::Deref};
+
+use crate::{
+    account::{
+        self,
+        sync::{HunkReport, HunkStatus, SyncReport},
+    },
+    backend::Backend,
+    printer::{PrintTable, PrintTableOpts, WriteColor},
+    ui::{Cell, Row, Table},
+};
+
+/// The maximum number of entries kept by the journal. Oldest entries
+/// are removed first.
+const MAX_ENTRIES: usize = 10_000;
+
+/// Represents the kind of a journal entry.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum JournalEntryKind {
+    Folder,
+    Email,
+}
+
+/// Represents a side of the synchronization.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SyncSide {
+    /// The local Maildir.
+    Local,
+    /// The backend of the account.
+    Remote,
+}
+
+impl SyncSide {
+    fn backend<'a>(&self, local: &'a Backend, remote: &'a Backend) -> &'a Backend {
+        match self {
+            Self::Local => local,
+            Self::Remote => remote,
+        }
+    }
+}
+
+impl From<&SyncDestination> for SyncSide {
+    fn from(dest: &SyncDestination) -> Self {
+        match dest {
+            SyncDestination::Left => Self::Local,
+            SyncDestination::Right => Self::Remote,
+        }
+    }
+}
+
+/// Represents an email hunk that can be replayed.
+///
+/// Only hunks changing messages are replayable. Hunks only updating
+/// the synchronization cache are resolved by the next
+/// synchronization of their folder.
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case", tag = "type")]
+pub enum ReplayHunk {
+    /// Copy a message from a side to the other.
+    Copy {
+        folder: String,
+        id: String,
+        flags: Vec<String>,
+        source: SyncSide,
+        target: SyncSide,
+    },
+    /// Replace the flags of a message.
+    SetFlags {
+        folder: String,
+        id: String,
+        flags: Vec<String>,
+        target: SyncSide,
+    },
+    /// Delete a message.
+    Delete {
+        folder: String,
+        id: String,
+        target: SyncSide,
+    },
+}
+
+impl ReplayHunk {
+    pub fn from_hunk(hunk: &EmailSyncHunk) -> Option<Self> {
+        let flags = |flags: &Flags| flags.iter().map(ToString::to_string).collect();
+
+        match hunk {
+            EmailSyncHunk::CopyThenCache(folder, envelope, source, target, _) => Some(Self::Copy {
+                folder: folder.clone(),
+                id: envelope.id.clone(),
+                flags: flags(&envelope.flags),
+                source: source.into(),
+                target: target.into(),
+            }),
+            EmailSyncHunk::UpdateFlags(folder, envelope, target) => Some(Self::SetFlags {
+                folder: folder.clone(),
+                id: envelope.id.clone(),
+                flags: flags(&envelope.flags),
+                target: target.into(),
+            }),
+            EmailSyncHunk::Delete(folder, id, target) => Some(Self::Delete {
+                folder: folder.clone(),
+                id: id.clone(),
+                target: target.into(),
+            }),
+            _ => None,
+        }
+    }
+
+    /// Apply the hunk using the given local and remote backends.
+    pub async fn replay(&self, local: &Backend, remote: &Backend) -> Result<()> {
+        let flags =
+            |flags: &[String]| Flags::from_iter(flags.iter().map(|f| Flag::from(f.as_str())));
+
+        match self {
+            Self::Copy {
+                folder,
+                id,
+                flags: copy_flags,
+                source,
+                target,
+            } => {
+                let msgs = source
+                    .backend(local, remote)
+                    .backend
+                    .peek_messages(folder, &Id::single(id))
+                    .await?;
+                let msg = msgs
+                    .first()
+                    .ok_or_else(|| anyhow!("cannot find message {id} in folder {folder}"))?;
+                target
+                    .backend(local, remote)
+                    .backend
+                    .add_message_with_flags(folder, msg.raw()?, &flags(copy_flags))
+                    .await?;
+            }
+            Self::SetFlags {
+                folder,
+                id,
+                flags: new_flags,
+                target,
+            } => {
+                target
+                    .backend(local, remote)
+                    .backend
+                    .set_flags(folder, &Id::single(id), &flags(new_flags))
+                    .await?;
+            }
+            Self::Delete { folder, id, target } => {
+                target
+                    .backend(local, remote)
+                    .backend
+                    .delete_messages(folder, &Id::single(id))
+                    .await?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Represents a hunk recorded by the journal.
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+pub struct JournalEntry {
+    /// The date of the synchronization that processed the hunk.
+    pub synced_at: String,
+    pub kind: JournalEntryKind,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub folder: Option<String>,
+    pub hunk: String,
+    /// Whether the hunk failed to apply.
+    pub failed: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// How to replay the hunk, if it can be replayed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub replay: Option<ReplayHunk>,
+}
+
+impl JournalEntry {
+    fn new(synced_at: &str, kind: JournalEntryKind, hunk: &HunkReport) -> Self {
+        Self {
+            synced_at: synced_at.to_owned(),
+            kind,
+            folder: hunk.folder.clone(),
+            hunk: hunk.hunk.clone(),
+            failed: hunk.status == HunkStatus::Failed,
+            error: hunk.error.clone(),
+            replay: hunk.replay.clone(),
+        }
+    }
+
+    /// The key identifying the hunk among failures.
+    fn failure_key(&self) -> String {
+        let folder = self.folder.as_deref().unwrap_or_default();
+        format!("{:?}\0{folder}\0{}", self.kind, self.hunk)
+    }
+}
+
+impl Table for JournalEntry {
+    fn head() -> Row {
+        Row::new()
+            .cell(Cell::new("DATE").bold().underline().white())
+            .cell(Cell::new("FOLDER").bold().underline().white())
+            .cell(Cell::new("HUNK").shrinkable().bold().underline().white())
+            .cell(Cell::new("STATUS").bold().underline().white())
+    }
+
+    fn row(&self) -> Row {
+        let date = DateTime::parse_from_rfc3339(&self.synced_at)
+            .map(|date| date.with_timezone(&Local).format("%F %T").to_string())
+            .unwrap_or_else(|_| self.synced_at.clone());
+        let folder = self.folder.as_deref().unwrap_or_default();
+
+        let status = match &self.error {
+            Some(err) => Cell::new(format!("failed: {err}")).red(),
+            None if self.failed => Cell::new("failed").red(),
+            None => Cell::new("applied").green(),
+        };
+
+        Row::new()
+            .cell(Cell::new(date).white())
+            .cell(Cell::new(folder).blue())
+            .cell(Cell::new(&self.hunk).shrinkable().white())
+            .cell(status)
+    }
+}
+
+/// Represents the list of printable journal entries.
+#[derive(Debug, Default, Serialize)]
+pub struct JournalEntries(Vec<JournalEntry>);
+
+impl From<Vec<JournalEntry>> for JournalEntries {
+    fn from(entries: Vec<JournalEntry>) -> Self {
+        Self(entries)
+    }
+}
+
+impl Deref for JournalEntries {
+    type Target = Vec<JournalEntry>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl PrintTable for JournalEntries {
+    fn print_table(&self, writer: &mut dyn WriteColor, opts: PrintTableOpts) -> Result<()> {
+        writeln!(writer)?;
+        Table::print(writer, self, opts)?;
+        writeln!(writer)?;
+        Ok(())
+    }
+}
+
+/// Represents the folders containing failed hunks.
+#[derive(Debug, Default, Eq, PartialEq)]
+pub struct FailedFolders {
+    /// Whether some folder hunks failed, in which case all folders
+    /// need to be synchronized again.
+    pub all: bool,
+    pub folders: BTreeSet<String>,
+}
+
+impl FailedFolders {
+    pub fn is_empty(&self) -> bool {
+        !self.all && self.folders.is_empty()
+    }
+}
+
+/// The synchronization journal of an account.
+pub struct SyncJournal {
+    /// The journal entries, by id.
+    db: Db,
+    /// The unresolved failed entries, by failure key.
+    failures: Tree,
+}
+
+impl SyncJournal {
+    pub fn new(account_config: &AccountConfig) -> Result<Self> {
+        let db_path = account::data_dir(".sync-journal", &account_config.name)?;
+
+        let db = Config::new()
+            .path(&db_path)
+            .open()
+            .with_context(|| format!("cannot open sync journal database at {db_path:?}"))?;
+
+        Self::from_db(db)
+    }
+
+    fn from_db(db: Db) -> Result<Self> {
+        let failures = db
+            .open_tree("failures")
+            .context("cannot open sync journal failures")?;
+        Ok(Self { db, failures })
+    }
+
+    /// Record the hunks of the given synchronization report.
+    ///
+    /// Failures of the synchronized folders that did not fail again
+    /// are considered resolved: either their hunk has been applied,
+    /// or the synchronization does not need it anymore.
+    pub fn record(&self, report: &SyncReport, is_synced: impl Fn(&str) -> bool) -> Result<()> {
+        if report.dry_run {
+            return Ok(());
+        }
+
+        for failure in self.failures()? {
+            let resolved = match &failure.folder {
+                Some(folder) => is_synced(folder),
+                None => true,
+            };
+
+            if resolved {
+                self.failures.remove(failure.failure_key())?;
+            }
+        }
+
+        let hunks = report
+            .folders
+            .hunks
+            .iter()
+            .map(|hunk| (JournalEntryKind::Folder, hunk))
+            .chain(
+                report
+                    .emails
+                    .hunks
+                    .iter()
+                    .map(|hunk| (JournalEntryKind::Email, hunk)),
+            );
+
+        for (kind, hunk) in hunks {
+            self.insert(&JournalEntry::new(&report.started_at, kind, hunk))?;
+        }
+
+        self.prune()?;
+        self.db.flush()?;
+
+        Ok(())
+    }
+
+    /// Record the outcome of the replay of the given failed entry.
+    ///
+    /// The failure is resolved if the replay succeeded, otherwise its
+    /// error is updated.
+    pub fn record_replay(&self, failure: &JournalEntry, res: &Result<()>) -> Result<()> {
+        let entry = JournalEntry {
+            synced_at: Local::now().to_rfc3339(),
+            failed: res.is_err(),
+            error: res.as_ref().err().map(ToString::to_string),
+            ..failure.clone()
+        };
+
+        if !entry.failed {
+            self.failures.remove(entry.failure_key())?;
+        }
+
+        self.insert(&entry)?;
+        self.prune()?;
+        self.db.flush()?;
+
+        Ok(())
+    }
+
+    fn insert(&self, entry: &JournalEntry) -> Result<()> {
+        let value = serde_json::to_vec(entry).context("cannot serialize journal entry")?;
+        let id = self
+            .db
+            .generate_id()
+            .context("cannot generate journal id")?;
+
+        self.db.insert(id.to_be_bytes(), value.as_slice())?;
+
+        if entry.failed {
+            self.failures.insert(entry.failure_key(), value)?;
+        }
+
+        Ok(())
+    }
+
+    /// List the most recent entries, oldest first.
+    pub fn list(&self, limit: Option<usize>) -> Result<Vec<JournalEntry>> {
+        let mut entries = self
+            .db
+            .iter()
+            .values()
+            .rev()
+            .take(limit.unwrap_or(usize::MAX))
+            .map(|entry| Ok(serde_json::from_slice(&entry?)?))
+            .collect::<Result<Vec<JournalEntry>>>()?;
+
+        entries.reverse();
+        Ok(entries)
+    }
+
+    /// List the unresolved failed entries, oldest first.
+    pub fn failures(&self) -> Result<Vec<JournalEntry>> {
+        let mut failures = self
+            .failures
+            .iter()
+            .values()
+            .map(|entry| Ok(serde_json::from_slice(&entry?)?))
+            .collect::<Result<Vec<JournalEntry>>>()?;
+
+        failures.sort_by(|a, b| a.synced_at.cmp(&b.synced_at));
+        Ok(failures)
+    }
+
+    /// Get the folders containing unresolved failed hunks.
+    pub fn failed_folders(&self) -> Result<FailedFolders> {
+        let mut failed = FailedFolders::default();
+
+        for failure in self.failures()? {
+            match failure.folder {
+                Some(folder) => {
+                    failed.folders.insert(folder);
+                }
+                None => failed.all = true,
+            }
+        }
+
+        Ok(failed)
+    }
+
+    /// Remove all entries of the journal.
+    pub fn clear(&self) -> Result<()> {
+        self.db.clear()?;
+        self.failures.clear()?;
+        self.db.flush()?;
+        Ok(())
+    }
+
+    fn prune(&self) -> Result<()> {
+        let excess = self.db.len().saturating_sub(MAX_ENTRIES);
+
+        if excess > 0 {
+            debug!("removing {excess} old sync journal entries");
+            let keys = self.db.iter().keys().take(excess);
+            for key in keys.collect::<Result<Vec<_>, _>>()? {
+                self.db.remove(key)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::account::sync::PatchReport;
+    use crate::cache::temporary_db;
+
+    fn journal() -> SyncJournal {
+        SyncJournal::from_db(temporary_db()).unwrap()
+    }
+
+    fn report(hunks: &[(&str, &str, Option<&str>)]) -> SyncReport {
+        let mut report = SyncReport::new("example", false, "2024-01-01T00:00:00+00:00");
+        report.set_emails(PatchReport::new(
+            hunks
+                .iter()
+                .map(|(folder, hunk, err)| (Some(*folder), hunk, err.as_ref())),
+            false,
+        ));
+        report
+    }
+
+    #[test]
+    fn record_failures() {
+        let journal = journal();
+
+        journal
+            .record(
+                &report(&[
+                    ("INBOX", "add envelope 1", None),
+                    ("INBOX", "add envelope 2", Some("cannot add")),
+                    ("Sent", "add envelope 3", Some("cannot add")),
+                ]),
+                |_| true,
+            )
+            .unwrap();
+
+        assert_eq!(journal.list(None).unwrap().len(), 3);
+        assert_eq!(journal.list(Some(1)).unwrap()[0].hunk, "add envelope 3");
+        assert_eq!(
+            journal.failed_folders().unwrap(),
+            FailedFolders {
+                all: false,
+                folders: BTreeSet::from_iter(["INBOX".into(), "Sent".into()]),
+            }
+        );
+
+        // retrying INBOX only resolves its failure
+        journal
+            .record(&report(&[("INBOX", "add envelope 2", None)]), |folder| {
+                folder == "INBOX"
+            })
+            .unwrap();
+
+        let failures = journal.failures().unwrap();
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].folder.as_deref(), Some("Sent"));
+
+        journal.clear().unwrap();
+        assert!(journal.list(None).unwrap().is_empty());
+        assert!(journal.failed_folders().unwrap().is_empty());
+    }
+
+    #[test]
+    fn record_replays() {
+        let journal = journal();
+
+        journal
+            .record(
+                &report(&[("INBOX", "add envelope 1", Some("cannot add"))]),
+                |_| true,
+            )
+            .unwrap();
+
+        let failure = journal.failures().unwrap().remove(0);
+
+        journal
+            .record_replay(&failure, &Err(anyhow!("still failing")))
+            .unwrap();
+
+        let failures = journal.failures().unwrap();
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].error.as_deref(), Some("still failing"));
+
+        journal.record_replay(&failure, &Ok(())).unwrap();
+
+        assert!(journal.failures().unwrap().is_empty());
+        assert_eq!(journal.list(None).unwrap().len(), 3);
+    }
+}
@@ -1,11 +1,21 @@
 pub mod arg;
 pub mod command;
 pub mod config;
+#[cfg(feature = "account-sync")]
+pub mod daemon;
+#[cfg(feature = "account-sync")]
+pub mod journal;
+#[cfg(feature = "account-sync")]
+pub mod partial;
+#[cfg(feature = "account-sync")]
+pub mod sync;
 pub(crate) mod wizard;
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
+#[cfg(feature = "account-sync")]
+use log::warn;
 use serde::Serialize;
-use std::{collections::hash_map::Iter, fmt, ops::Deref};
+use std::{collections::hash_map::Iter, fmt, ops::Deref, path::PathBuf};
 
 use crate::{
     printer::{PrintTable, PrintTableOpts, WriteColor},
@@ -13,6 +23,24 @@ use crate::{
 };
 
 use self::config::TomlAccountConfig;
+#[cfg(feature = "account-sync")]
+use self::sync::SyncStatus;
+
+/// Get the path of a local store of the given account.
+///
+/// Local stores live in the XDG data directory, under the given
+/// store name, and are named after the MD5 digest of the account
+/// name.
+pub fn data_dir(store: &str, account_name: &str) -> Result<PathBuf> {
+    let digest = md5::compute(account_name);
+    let path = dirs::data_dir()
+        .ok_or(anyhow!("cannot get XDG data directory"))?
+        .join("himalaya")
+        .join(store)
+        .join(format!("{digest:x}"));
+
+    Ok(path)
+}
 
 /// Represents the printable account.
 #[derive(Debug, Default, PartialEq, Eq, Serialize)]
@@ -23,6 +51,10 @@ pub struct Account {
     pub backend: String,
     /// Represents the default state of the account.
     pub default: bool,
+    /// Represents the synchronization status of the account.
+    #[cfg(feature = "account-sync")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sync: Option<SyncStatus>,
 }
 
 impl Account {
@@ -31,6 +63,8 @@ impl Account {
             name: name.into(),
             backend: backend.into(),
             default,
+            #[cfg(feature = "account-sync")]
+            sync: None,
         }
     }
 }
@@ -43,18 +77,34 @@ impl fmt::Display for Account {
 
 impl Table for Account {
     fn head() -> Row {
-        Row::new()
+        let row = Row::new()
             .cell(Cell::new("NAME").shrinkable().bold().underline().white())
             .cell(Cell::new("BACKENDS").bold().underline().white())
-            .cell(Cell::new("DEFAULT").bold().underline().white())
+            .cell(Cell::new("DEFAULT").bold().underline().white());
+
+        #[cfg(feature = "account-sync")]
+        let row = row.cell(Cell::new("LAST SYNC").bold().underline().white());
+
+        row
     }
 
     fn row(&self) -> Row {
         let default = if self.default { "yes" } else { "" };
-        Row::new()
+        let row = Row::new()
             .cell(Cell::new(&self.name).shrinkable().green())
             .cell(Cell::new(&self.backend).blue())
-            .cell(Cell::new(default).white())
+            .cell(Cell::new(default).white());
+
+        #[cfg(feature = "account-sync")]
+        let row = match &self.sync {
+            Some(status) if status.last_error.is_some() => {
+                row.cell(Cell::new(status.summary()).red())
+            }
+            Some(status) => row.cell(Cell::new(status.summary()).white()),
+            None => row.cell(Cell::new("").white()),
+        };
+
+        row
     }
 }
 
@@ -123,7 +173,24 @@ impl From<Iter<'_, String, TomlAccountConfig>> for Accounts {
                     backends.push_str("sendmail");
                 }
 
-                Account::new(name, &backends, account.default.unwrap_or_default())
+                #[allow(unused_mut)]
+                let mut printable_account =
+                    Account::new(name, &backends, account.default.unwrap_or_default());
+
+                #[cfg(feature = "account-sync")]
+                if account
+                    .sync
+                    .as_ref()
+                    .and_then(|c| c.enable)
+                    .unwrap_or_default()
+                {
+                    printable_account.sync = SyncStatus::read(name).unwrap_or_else(|err| {
+                        warn!("cannot read sync status of account {name}: {err}");
+                        None
+                    });
+                }
+
+                printable_account
             })
             .collect();
 
@@ -0,0 +1,265 @@
+use anyhow::{Context, Result};
+use email::account::config::AccountConfig;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashSet,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{account, backend::BackendKind};
+
+/// Represents a message synchronized without its body.
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct PartialEntry {
+    /// The backend the complete message can be fetched from.
+    pub backend: BackendKind,
+    /// The remote folder of the message.
+    pub folder: String,
+    /// The remote id of the message.
+    pub id: String,
+    /// The size of the complete message, in bytes.
+    pub size: usize,
+}
+
+/// The cache of messages synchronized without their body.
+///
+/// Entries are keyed by the MD5 digest of the stub stored in the
+/// local Maildir, so the partial state of a message never depends on
+/// its headers. Each entry is stored in its own file rather than in a
+/// sled database, so that the cache can be read while a
+/// synchronization is running.
+///
+/// Entries are also indexed by remote folder and id, in the `remote`
+/// directory: each folder has its own directory, containing one file
+/// per remote id whose content is the digest of the stub.
+pub struct PartialCache {
+    dir: PathBuf,
+}
+
+impl PartialCache {
+    pub fn new(account_config: &AccountConfig) -> Result<Self> {
+        let dir = account::data_dir(".partial-sync", &account_config.name)?;
+        let cache = Self { dir };
+        cache.build_remote_index()?;
+        Ok(cache)
+    }
+
+    fn path(&self, digest: &str) -> PathBuf {
+        self.dir.join(format!("{digest}.json"))
+    }
+
+    fn remote_dir(&self, folder: &str) -> PathBuf {
+        self.dir
+            .join("remote")
+            .join(format!("{:x}", md5::compute(folder)))
+    }
+
+    fn remote_path(&self, folder: &str, id: &str) -> PathBuf {
+        self.remote_dir(folder)
+            .join(format!("{:x}", md5::compute(id)))
+    }
+
+    /// Record the given stub.
+    pub fn insert(&self, stub: &[u8], entry: &PartialEntry) -> Result<()> {
+        let digest = format!("{:x}", md5::compute(stub));
+        let path = self.path(&digest);
+
+        fs::create_dir_all(&self.dir)
+            .with_context(|| format!("cannot create partial sync cache {:?}", self.dir))?;
+        let contents = serde_json::to_vec(entry)?;
+        fs::write(&path, contents)
+            .with_context(|| format!("cannot write partial sync entry {path:?}"))?;
+
+        self.insert_remote(&entry.folder, &entry.id, &digest)
+    }
+
+    fn insert_remote(&self, folder: &str, id: &str, digest: &str) -> Result<()> {
+        let dir = self.remote_dir(folder);
+        fs::create_dir_all(&dir)
+            .with_context(|| format!("cannot create partial sync index {dir:?}"))?;
+
+        let path = self.remote_path(folder, id);
+        fs::write(&path, digest)
+            .with_context(|| format!("cannot write partial sync index entry {path:?}"))
+    }
+
+    /// Get the entry of the given raw message, if it is a stub.
+    pub fn get(&self, raw: &[u8]) -> Result<Option<PartialEntry>> {
+        let path = self.path(&format!("{:x}", md5::compute(raw)));
+
+        match fs::read(&path) {
+            Ok(contents) => {
+                let entry = serde_json::from_slice(&contents)
+                    .with_context(|| format!("cannot parse partial sync entry {path:?}"))?;
+                Ok(Some(entry))
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => {
+                Err(err).with_context(|| format!("cannot read partial sync entry {path:?}"))
+            }
+        }
+    }
+
+    /// Return true if the given remote message is recorded as a
+    /// stub.
+    pub fn contains_remote(&self, folder: &str, id: &str) -> Result<bool> {
+        let path = self.remote_path(folder, id);
+        path.try_exists()
+            .with_context(|| format!("cannot read partial sync index entry {path:?}"))
+    }
+
+    /// Remove the entries of the given remote folder whose id is not
+    /// part of the given ids, since their stub is evicted from the
+    /// local Maildir by the synchronization.
+    ///
+    /// Returns the number of removed entries.
+    pub fn retain_remote(&self, folder: &str, ids: &HashSet<String>) -> Result<usize> {
+        let dir = self.remote_dir(folder);
+        let entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(0),
+            Err(err) => {
+                return Err(err).with_context(|| format!("cannot read partial sync index {dir:?}"))
+            }
+        };
+
+        let kept: HashSet<String> = ids
+            .iter()
+            .map(|id| format!("{:x}", md5::compute(id)))
+            .collect();
+        let mut count = 0;
+
+        for entry in entries {
+            let path = entry?.path();
+            let is_kept = path
+                .file_name()
+                .is_some_and(|name| kept.contains(&*name.to_string_lossy()));
+
+            if is_kept {
+                continue;
+            }
+
+            let digest = fs::read_to_string(&path)
+                .with_context(|| format!("cannot read partial sync index entry {path:?}"))?;
+            remove_file(&self.path(digest.trim()))?;
+            remove_file(&path)?;
+            count += 1;
+        }
+
+        Ok(count)
+    }
+
+    /// Index the entries recorded before the index existed.
+    fn build_remote_index(&self) -> Result<()> {
+        let remote_dir = self.dir.join("remote");
+        if remote_dir.exists() {
+            return Ok(());
+        }
+
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(err) => {
+                return Err(err)
+                    .with_context(|| format!("cannot read partial sync cache {:?}", self.dir))
+            }
+        };
+
+        for entry in entries {
+            let path = entry?.path();
+            let digest = match path.file_stem() {
+                Some(stem) if path.extension().is_some_and(|ext| ext == "json") => {
+                    stem.to_string_lossy().into_owned()
+                }
+                _ => continue,
+            };
+
+            let contents = fs::read(&path)
+                .with_context(|| format!("cannot read partial sync entry {path:?}"))?;
+            let entry: PartialEntry = serde_json::from_slice(&contents)
+                .with_context(|| format!("cannot parse partial sync entry {path:?}"))?;
+            self.insert_remote(&entry.folder, &entry.id, &digest)?;
+        }
+
+        fs::create_dir_all(&remote_dir)
+            .with_context(|| format!("cannot create partial sync index {remote_dir:?}"))
+    }
+}
+
+fn remove_file(path: &Path) -> Result<()> {
+    match fs::remove_file(path) {
+        Ok(()) => Ok(()),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(err) => Err(err).with_context(|| format!("cannot remove partial sync file {path:?}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, env, fs, process};
+
+    use super::{PartialCache, PartialEntry};
+    use crate::backend::BackendKind;
+
+    #[test]
+    fn record_stubs() {
+        let dir = env::temp_dir().join(format!("himalaya-partial-{}", process::id()));
+        let cache = PartialCache { dir: dir.clone() };
+
+        let entry = PartialEntry {
+            backend: BackendKind::None,
+            folder: "INBOX".into(),
+            id: "42".into(),
+            size: 1024,
+        };
+
+        assert_eq!(cache.get(b"stub").unwrap(), None);
+        assert!(!cache.contains_remote("INBOX", "42").unwrap());
+
+        cache.insert(b"stub", &entry).unwrap();
+
+        assert_eq!(cache.get(b"stub").unwrap(), Some(entry));
+        assert_eq!(cache.get(b"other stub").unwrap(), None);
+        assert!(cache.contains_remote("INBOX", "42").unwrap());
+        assert!(!cache.contains_remote("Archives", "42").unwrap());
+
+        // the stub is kept while its id is listed
+        let ids = HashSet::from_iter(["42".to_owned()]);
+        assert_eq!(cache.retain_remote("INBOX", &ids).unwrap(), 0);
+        assert!(cache.contains_remote("INBOX", "42").unwrap());
+
+        assert_eq!(cache.retain_remote("INBOX", &HashSet::new()).unwrap(), 1);
+        assert_eq!(cache.get(b"stub").unwrap(), None);
+        assert!(!cache.contains_remote("INBOX", "42").unwrap());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn index_existing_entries() {
+        let dir = env::temp_dir().join(format!("himalaya-partial-index-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let entry = PartialEntry {
+            backend: BackendKind::None,
+            folder: "INBOX".into(),
+            id: "42".into(),
+            size: 1024,
+        };
+        let digest = format!("{:x}", md5::compute(b"stub"));
+        fs::write(
+            dir.join(format!("{digest}.json")),
+            serde_json::to_vec(&entry).unwrap(),
+        )
+        .unwrap();
+
+        let cache = PartialCache { dir: dir.clone() };
+        cache.build_remote_index().unwrap();
+
+        assert!(cache.contains_remote("INBOX", "42").unwrap());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
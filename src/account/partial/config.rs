@@ -0,0 +1,278 @@
+use anyhow::{anyhow, bail, Error, Result};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, fmt, str::FromStr};
+
+/// The partial synchronization configuration of an account.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct PartialSyncConfig {
+    /// Synchronize only emails received within the given window,
+    /// for example `90d`, `12w`, `6m` or `1y`.
+    ///
+    /// Local emails falling out of the window are evicted.
+    pub since: Option<SyncWindow>,
+
+    /// Synchronize only the headers of emails bigger than the given
+    /// size, for example `512KB` or `10MB`.
+    ///
+    /// Bodies are fetched on demand when reading emails.
+    pub max_message_size: Option<MessageSize>,
+
+    /// Override the options above for specific folders.
+    #[serde(default)]
+    pub folders: HashMap<String, PartialSyncFolderConfig>,
+}
+
+/// The partial synchronization configuration of a folder.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct PartialSyncFolderConfig {
+    pub since: Option<SyncWindow>,
+    pub max_message_size: Option<MessageSize>,
+}
+
+impl PartialSyncConfig {
+    /// Return true if at least one option is set.
+    pub fn is_enabled(&self) -> bool {
+        self.since.is_some()
+            || self.max_message_size.is_some()
+            || self
+                .folders
+                .values()
+                .any(|c| c.since.is_some() || c.max_message_size.is_some())
+    }
+
+    /// Override the options of all folders with the given ones, if
+    /// any. Used by the options of the synchronization command.
+    pub fn with_overrides(
+        mut self,
+        since: Option<SyncWindow>,
+        max_message_size: Option<MessageSize>,
+    ) -> Self {
+        if since.is_some() {
+            self.since = since;
+            self.folders.values_mut().for_each(|c| c.since = None);
+        }
+
+        if max_message_size.is_some() {
+            self.max_message_size = max_message_size;
+            self.folders
+                .values_mut()
+                .for_each(|c| c.max_message_size = None);
+        }
+
+        self
+    }
+
+    /// Get the options of the given folder, falling back to the
+    /// account ones.
+    pub fn folder(&self, folder: &str) -> PartialSyncFolderConfig {
+        let config = self.folders.get(folder);
+
+        PartialSyncFolderConfig {
+            since: config.and_then(|c| c.since).or(self.since),
+            max_message_size: config
+                .and_then(|c| c.max_message_size)
+                .or(self.max_message_size),
+        }
+    }
+}
+
+/// Represents a synchronization window, in days.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct SyncWindow(u32);
+
+impl SyncWindow {
+    /// Get the date before which emails fall out of the window.
+    ///
+    /// Windows reaching before the earliest representable date keep
+    /// all emails.
+    pub fn cutoff(&self, now: DateTime<Utc>) -> DateTime<Utc> {
+        now.checked_sub_signed(Duration::days(self.0 as i64))
+            .unwrap_or(DateTime::<Utc>::MIN_UTC)
+    }
+}
+
+impl FromStr for SyncWindow {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        let (count, unit) = s.split_at(s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len()));
+
+        let count: u32 = count
+            .parse()
+            .map_err(|_| anyhow!("invalid sync window {s:?}, expected for example 90d"))?;
+
+        let days = match unit.trim().to_lowercase().as_str() {
+            "" | "d" => 1,
+            "w" => 7,
+            "m" => 30,
+            "y" => 365,
+            unit => bail!("invalid sync window unit {unit:?}, expected one of d, w, m or y"),
+        };
+
+        let days = count
+            .checked_mul(days)
+            .ok_or(anyhow!("invalid sync window {s:?}: too large"))?;
+
+        Ok(Self(days))
+    }
+}
+
+impl TryFrom<String> for SyncWindow {
+    type Error = Error;
+
+    fn try_from(s: String) -> Result<Self> {
+        s.parse()
+    }
+}
+
+impl From<SyncWindow> for String {
+    fn from(window: SyncWindow) -> Self {
+        window.to_string()
+    }
+}
+
+impl fmt::Display for SyncWindow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}d", self.0)
+    }
+}
+
+/// Represents a message size, in bytes.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct MessageSize(usize);
+
+impl MessageSize {
+    pub fn bytes(&self) -> usize {
+        self.0
+    }
+}
+
+impl FromStr for MessageSize {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        let (size, unit) = s.split_at(
+            s.find(|c: char| !c.is_ascii_digit() && c != '.')
+                .unwrap_or(s.len()),
+        );
+
+        let size: f64 = size
+            .parse()
+            .map_err(|_| anyhow!("invalid message size {s:?}, expected for example 10MB"))?;
+
+        let factor = match unit.trim().to_lowercase().as_str() {
+            "" | "b" => 1,
+            "k" | "kb" | "kib" => 1024,
+            "m" | "mb" | "mib" => 1024 * 1024,
+            "g" | "gb" | "gib" => 1024 * 1024 * 1024,
+            unit => bail!("invalid message size unit {unit:?}, expected one of B, KB, MB or GB"),
+        };
+
+        Ok(Self((size * factor as f64) as usize))
+    }
+}
+
+impl TryFrom<String> for MessageSize {
+    type Error = Error;
+
+    fn try_from(s: String) -> Result<Self> {
+        s.parse()
+    }
+}
+
+impl From<MessageSize> for String {
+    fn from(size: MessageSize) -> Self {
+        size.to_string()
+    }
+}
+
+impl fmt::Display for MessageSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const UNITS: [(&str, usize); 3] = [("GB", 1 << 30), ("MB", 1 << 20), ("KB", 1 << 10)];
+
+        for (unit, factor) in UNITS {
+            if self.0 >= factor && self.0 % factor == 0 {
+                return write!(f, "{}{unit}", self.0 / factor);
+            }
+        }
+
+        write!(f, "{}B", self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, TimeZone, Utc};
+
+    use super::{MessageSize, PartialSyncConfig, SyncWindow};
+
+    #[test]
+    fn parse_sync_windows() {
+        assert_eq!("90d".parse::<SyncWindow>().unwrap(), SyncWindow(90));
+        assert_eq!("90".parse::<SyncWindow>().unwrap(), SyncWindow(90));
+        assert_eq!("2w".parse::<SyncWindow>().unwrap(), SyncWindow(14));
+        assert_eq!("6M".parse::<SyncWindow>().unwrap(), SyncWindow(180));
+        assert_eq!("1y".parse::<SyncWindow>().unwrap(), SyncWindow(365));
+        assert!("d".parse::<SyncWindow>().is_err());
+        assert!("3h".parse::<SyncWindow>().is_err());
+        assert!("4294967295y".parse::<SyncWindow>().is_err());
+    }
+
+    #[test]
+    fn sync_window_cutoffs() {
+        let now = Utc.with_ymd_and_hms(2024, 3, 10, 12, 0, 0).unwrap();
+
+        assert_eq!(
+            SyncWindow(9).cutoff(now),
+            Utc.with_ymd_and_hms(2024, 3, 1, 12, 0, 0).unwrap()
+        );
+        assert_eq!(SyncWindow(u32::MAX).cutoff(now), DateTime::<Utc>::MIN_UTC);
+    }
+
+    #[test]
+    fn parse_message_sizes() {
+        assert_eq!("512".parse::<MessageSize>().unwrap(), MessageSize(512));
+        assert_eq!("2KB".parse::<MessageSize>().unwrap(), MessageSize(2048));
+        assert_eq!(
+            "10MB".parse::<MessageSize>().unwrap(),
+            MessageSize(10 * 1024 * 1024)
+        );
+        assert_eq!(
+            "1.5 mib".parse::<MessageSize>().unwrap(),
+            MessageSize(1536 * 1024)
+        );
+        assert_eq!(MessageSize(10 * 1024 * 1024).to_string(), "10MB");
+        assert_eq!(MessageSize(1536 * 1024).to_string(), "1536KB");
+        assert!("MB".parse::<MessageSize>().is_err());
+        assert!("10TB".parse::<MessageSize>().is_err());
+    }
+
+    #[test]
+    fn folder_options() {
+        let config: PartialSyncConfig = toml::from_str(
+            r#"
+            since = "90d"
+            max-message-size = "10MB"
+            folders.Archives.since = "1y"
+            "#,
+        )
+        .unwrap();
+
+        let archives = config.folder("Archives");
+        assert_eq!(archives.since.unwrap().to_string(), "365d");
+        assert_eq!(archives.max_message_size.unwrap().to_string(), "10MB");
+        assert_eq!(config.folder("INBOX").since.unwrap().to_string(), "90d");
+
+        let config = config.with_overrides(Some("7d".parse().unwrap()), None);
+        assert_eq!(config.folder("Archives").since.unwrap().to_string(), "7d");
+        assert!(config.is_enabled());
+        assert!(!PartialSyncConfig::default().is_enabled());
+    }
+}
@@ -0,0 +1,577 @@
+//! Module dedicated to the partial synchronization.
+//!
+//! The partial synchronization limits the emails kept in the local
+//! Maildir of an account. The envelopes of the remote backend falling
+//! out of the synchronization window are hidden from the
+//! synchronization, which then evicts them from the local Maildir the
+//! same way it would remove emails deleted upstream. Emails bigger
+//! than the maximum message size are synchronized without their body,
+//! which is fetched on demand when reading them. Such emails cannot be
+//! moved or deleted locally, since the synchronization would lose
+//! their body upstream.
+
+pub mod cache;
+pub mod config;
+
+use anyhow::{anyhow, bail, Context, Result};
+use async_trait::async_trait;
+use chrono::Utc;
+use email::{
+    account::config::AccountConfig,
+    backend::{feature::BackendFeature, feature::BackendFeatureSource},
+    envelope::{
+        list::{ListEnvelopes, ListEnvelopesOptions},
+        Envelopes, Id, SingleId,
+    },
+    flag::Flags,
+    message::{
+        add::AddMessage, delete::DeleteMessages, get::GetMessages, peek::PeekMessages,
+        r#move::MoveMessages, Messages,
+    },
+};
+use log::debug;
+use std::{collections::HashMap, sync::Arc};
+
+use crate::{
+    account::config::TomlAccountConfig,
+    backend::{Backend, BackendContext, BackendKind},
+    error::ErrorKind,
+};
+
+use self::{
+    cache::{PartialCache, PartialEntry},
+    config::PartialSyncConfig,
+};
+
+/// The header added to emails synchronized without their body. Its
+/// value is the size of the original email, in bytes.
+///
+/// The header is only informative: senders can set it too, so the
+/// partial state of emails is tracked by the [`PartialCache`].
+pub const PARTIAL_HEADER: &str = "X-Himalaya-Partial";
+
+/// The partial synchronization of the remote backend of an account.
+pub struct PartialSync {
+    pub config: PartialSyncConfig,
+    pub cache: PartialCache,
+}
+
+/// Wrap the given list envelopes feature, so that envelopes falling
+/// out of the synchronization window are hidden.
+pub fn list_envelopes(
+    f: BackendFeature<BackendContext, dyn ListEnvelopes>,
+    partial: Arc<PartialSync>,
+) -> Option<BackendFeature<BackendContext, dyn ListEnvelopes>> {
+    Some(Arc::new(move |ctx| {
+        let inner = f(ctx)?;
+        let partial = partial.clone();
+        Some(Box::new(PartialListEnvelopes { inner, partial }))
+    }))
+}
+
+/// Wrap the given peek messages feature, so that messages bigger
+/// than the maximum message size are stripped of their body.
+pub fn peek_messages(
+    f: BackendFeature<BackendContext, dyn PeekMessages>,
+    partial: Arc<PartialSync>,
+    backend: BackendKind,
+) -> Option<BackendFeature<BackendContext, dyn PeekMessages>> {
+    Some(Arc::new(move |ctx| {
+        let inner = f(ctx)?;
+        let partial = partial.clone();
+        let backend = backend.clone();
+        Some(Box::new(PartialPeekMessages {
+            inner,
+            partial,
+            backend,
+        }))
+    }))
+}
+
+/// Wrap the given get messages feature, so that messages bigger than
+/// the maximum message size are stripped of their body.
+pub fn get_messages(
+    f: BackendFeature<BackendContext, dyn GetMessages>,
+    partial: Arc<PartialSync>,
+    backend: BackendKind,
+) -> Option<BackendFeature<BackendContext, dyn GetMessages>> {
+    Some(Arc::new(move |ctx| {
+        let inner = f(ctx)?;
+        let partial = partial.clone();
+        let backend = backend.clone();
+        Some(Box::new(PartialGetMessages {
+            inner,
+            partial,
+            backend,
+        }))
+    }))
+}
+
+/// Wrap the given add message feature, so that messages stripped of
+/// their body are never added back upstream.
+pub fn add_message(
+    f: BackendFeature<BackendContext, dyn AddMessage>,
+    partial: Arc<PartialSync>,
+) -> Option<BackendFeature<BackendContext, dyn AddMessage>> {
+    Some(Arc::new(move |ctx| {
+        let inner = f(ctx)?;
+        let partial = partial.clone();
+        Some(Box::new(PartialAddMessage { inner, partial }))
+    }))
+}
+
+/// Wrap the given delete messages feature, so that messages stripped
+/// of their body locally are never deleted upstream.
+///
+/// Moving a partial message between local folders leads to an add
+/// hunk and a delete hunk: the add is refused by [`add_message`], so
+/// the delete must be refused as well.
+pub fn delete_messages(
+    f: BackendFeature<BackendContext, dyn DeleteMessages>,
+    partial: Arc<PartialSync>,
+) -> Option<BackendFeature<BackendContext, dyn DeleteMessages>> {
+    Some(Arc::new(move |ctx| {
+        let inner = f(ctx)?;
+        let partial = partial.clone();
+        Some(Box::new(PartialDeleteMessages { inner, partial }))
+    }))
+}
+
+/// Wrap the given move messages feature of the local Maildir, so
+/// that messages stripped of their body cannot be moved.
+pub fn move_local_messages(
+    f: BackendFeature<BackendContext, dyn MoveMessages>,
+    peek: BackendFeature<BackendContext, dyn PeekMessages>,
+    cache: Arc<PartialCache>,
+) -> Option<BackendFeature<BackendContext, dyn MoveMessages>> {
+    Some(Arc::new(move |ctx| {
+        let inner = f(ctx)?;
+        let peek = peek(ctx)?;
+        let cache = cache.clone();
+        Some(Box::new(LocalMoveMessages { inner, peek, cache }))
+    }))
+}
+
+/// Wrap the given delete messages feature of the local Maildir, so
+/// that messages stripped of their body cannot be deleted.
+pub fn delete_local_messages(
+    f: BackendFeature<BackendContext, dyn DeleteMessages>,
+    peek: BackendFeature<BackendContext, dyn PeekMessages>,
+    cache: Arc<PartialCache>,
+) -> Option<BackendFeature<BackendContext, dyn DeleteMessages>> {
+    Some(Arc::new(move |ctx| {
+        let inner = f(ctx)?;
+        let peek = peek(ctx)?;
+        let cache = cache.clone();
+        Some(Box::new(LocalDeleteMessages { inner, peek, cache }))
+    }))
+}
+
+struct PartialListEnvelopes {
+    inner: Box<dyn ListEnvelopes>,
+    partial: Arc<PartialSync>,
+}
+
+#[async_trait]
+impl ListEnvelopes for PartialListEnvelopes {
+    async fn list_envelopes(&self, folder: &str, opts: ListEnvelopesOptions) -> Result<Envelopes> {
+        // envelopes missing from a complete listing have their stub
+        // evicted from the local Maildir by the synchronization
+        let is_complete = opts.page_size == 0 && opts.query.is_none();
+        let envelopes = self.inner.list_envelopes(folder, opts).await?;
+
+        let envelopes = match self.partial.config.folder(folder).since {
+            Some(since) => {
+                let cutoff = since.cutoff(Utc::now()).timestamp();
+                let count = envelopes.len();
+                let envelopes: Envelopes = envelopes
+                    .iter()
+                    .filter(|envelope| envelope.date.timestamp() >= cutoff)
+                    .cloned()
+                    .collect();
+
+                debug!(
+                    "{} envelope(s) of folder {folder} out of the sync window of {since}",
+                    count - envelopes.len()
+                );
+
+                envelopes
+            }
+            None => envelopes,
+        };
+
+        if is_complete {
+            let ids = envelopes
+                .iter()
+                .map(|envelope| envelope.id.clone())
+                .collect();
+            let count = self.partial.cache.retain_remote(folder, &ids)?;
+            debug!("{count} partial entries of evicted stubs of folder {folder} removed");
+        }
+
+        Ok(envelopes)
+    }
+}
+
+struct PartialPeekMessages {
+    inner: Box<dyn PeekMessages>,
+    partial: Arc<PartialSync>,
+    backend: BackendKind,
+}
+
+#[async_trait]
+impl PeekMessages for PartialPeekMessages {
+    async fn peek_messages(&self, folder: &str, id: &Id) -> Result<Messages> {
+        let msgs = self.inner.peek_messages(folder, id).await?;
+        truncate_messages(&self.partial, &self.backend, folder, id, msgs)
+    }
+}
+
+struct PartialGetMessages {
+    inner: Box<dyn GetMessages>,
+    partial: Arc<PartialSync>,
+    backend: BackendKind,
+}
+
+#[async_trait]
+impl GetMessages for PartialGetMessages {
+    async fn get_messages(&self, folder: &str, id: &Id) -> Result<Messages> {
+        let msgs = self.inner.get_messages(folder, id).await?;
+        truncate_messages(&self.partial, &self.backend, folder, id, msgs)
+    }
+}
+
+struct PartialAddMessage {
+    inner: Box<dyn AddMessage>,
+    partial: Arc<PartialSync>,
+}
+
+#[async_trait]
+impl AddMessage for PartialAddMessage {
+    async fn add_message_with_flags(
+        &self,
+        folder: &str,
+        msg: &[u8],
+        flags: &Flags,
+    ) -> Result<SingleId> {
+        if self.partial.cache.get(msg)?.is_some() {
+            bail!(
+                "cannot add partially synchronized message to folder {folder}: its body is missing"
+            );
+        }
+
+        self.inner.add_message_with_flags(folder, msg, flags).await
+    }
+}
+
+struct PartialDeleteMessages {
+    inner: Box<dyn DeleteMessages>,
+    partial: Arc<PartialSync>,
+}
+
+#[async_trait]
+impl DeleteMessages for PartialDeleteMessages {
+    async fn delete_messages(&self, folder: &str, id: &Id) -> Result<()> {
+        for id in id.iter() {
+            if self
+                .partial
+                .cache
+                .contains_remote(folder, &id.to_string())?
+            {
+                bail!(
+                    "cannot delete message {id} from folder {folder}: it is only partially synchronized"
+                );
+            }
+        }
+
+        self.inner.delete_messages(folder, id).await
+    }
+}
+
+struct LocalMoveMessages {
+    inner: Box<dyn MoveMessages>,
+    peek: Box<dyn PeekMessages>,
+    cache: Arc<PartialCache>,
+}
+
+#[async_trait]
+impl MoveMessages for LocalMoveMessages {
+    async fn move_messages(&self, from_folder: &str, to_folder: &str, id: &Id) -> Result<()> {
+        ensure_complete(&*self.peek, &self.cache, from_folder, id, "move").await?;
+        self.inner.move_messages(from_folder, to_folder, id).await
+    }
+}
+
+struct LocalDeleteMessages {
+    inner: Box<dyn DeleteMessages>,
+    peek: Box<dyn PeekMessages>,
+    cache: Arc<PartialCache>,
+}
+
+#[async_trait]
+impl DeleteMessages for LocalDeleteMessages {
+    async fn delete_messages(&self, folder: &str, id: &Id) -> Result<()> {
+        ensure_complete(&*self.peek, &self.cache, folder, id, "delete").await?;
+        self.inner.delete_messages(folder, id).await
+    }
+}
+
+/// Ensure that none of the given local messages has been stripped of
+/// its body by the partial synchronization.
+async fn ensure_complete(
+    peek: &dyn PeekMessages,
+    cache: &PartialCache,
+    folder: &str,
+    id: &Id,
+    action: &str,
+) -> Result<()> {
+    for msg in peek.peek_messages(folder, id).await?.to_vec() {
+        if cache.get(msg.raw()?)?.is_some() {
+            bail!(
+                "cannot {action} partially synchronized message from folder {folder}: its body only exists upstream, use --disable-cache to {action} it"
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Strip the body of the given messages bigger than the maximum
+/// message size of the given folder, and record them in the partial
+/// cache. Messages peeked from multiple ids are left untouched.
+fn truncate_messages(
+    partial: &PartialSync,
+    backend: &BackendKind,
+    folder: &str,
+    id: &Id,
+    msgs: Messages,
+) -> Result<Messages> {
+    let max_size = match partial.config.folder(folder).max_message_size {
+        Some(max_size) => max_size,
+        None => return Ok(msgs),
+    };
+
+    // backends do not necessarily return messages in the order of
+    // the requested ids, so only single messages can be matched
+    // with their id
+    let id = match id.iter().collect::<Vec<_>>().as_slice() {
+        [id] => id.to_string(),
+        _ => {
+            debug!("skipping truncation of messages peeked from multiple ids");
+            return Ok(msgs);
+        }
+    };
+
+    let mut truncated = false;
+    let mut raws = Vec::new();
+
+    for msg in msgs.to_vec() {
+        let raw = msg.raw()?;
+        match truncate(raw, max_size.bytes()) {
+            Some(stub) => {
+                let entry = PartialEntry {
+                    backend: backend.clone(),
+                    folder: folder.to_owned(),
+                    id: id.clone(),
+                    size: raw.len(),
+                };
+                partial.cache.insert(&stub, &entry)?;
+                truncated = true;
+                raws.push(stub);
+            }
+            None => raws.push(raw.to_vec()),
+        }
+    }
+
+    if truncated {
+        Ok(Messages::from(raws))
+    } else {
+        Ok(msgs)
+    }
+}
+
+/// Find the end of the headers of the given raw message.
+///
+/// Returns the position of the blank line separating headers from
+/// the body, and the line ending used by the message.
+fn find_headers_end(raw: &[u8]) -> (usize, &'static str) {
+    let crlf = raw.windows(4).position(|w| w == b"\r\n\r\n");
+    let lf = raw.windows(2).position(|w| w == b"\n\n");
+
+    match (crlf, lf) {
+        (Some(crlf), Some(lf)) if lf < crlf => (lf, "\n"),
+        (Some(crlf), _) => (crlf, "\r\n"),
+        (None, Some(lf)) => (lf, "\n"),
+        (None, None) => (raw.len(), "\r\n"),
+    }
+}
+
+/// Strip the body of the given raw message if it is bigger than the
+/// given size.
+///
+/// The body is replaced by a short notice, and the message is marked
+/// with the partial header.
+pub fn truncate(raw: &[u8], max_size: usize) -> Option<Vec<u8>> {
+    if raw.len() <= max_size {
+        return None;
+    }
+
+    let (end, eol) = find_headers_end(raw);
+    let headers = String::from_utf8_lossy(&raw[..end]);
+
+    let mut truncated = String::new();
+    let mut skip = false;
+
+    for line in headers.split(eol) {
+        if !line.starts_with([' ', '\t']) {
+            let name = line.split(':').next().unwrap_or_default().trim();
+            // the body is replaced by plain text, and the partial
+            // header set by the sender, if any, is replaced
+            skip = name.eq_ignore_ascii_case("content-type")
+                || name.eq_ignore_ascii_case("content-transfer-encoding")
+                || name.eq_ignore_ascii_case(PARTIAL_HEADER);
+        }
+
+        if !skip {
+            truncated.push_str(line);
+            truncated.push_str(eol);
+        }
+    }
+
+    truncated.push_str(&format!("{PARTIAL_HEADER}: {}{eol}", raw.len()));
+    truncated.push_str(&format!("Content-Type: text/plain; charset=utf-8{eol}"));
+    truncated.push_str(eol);
+    truncated.push_str(&format!(
+        "The body of this message ({} bytes) exceeds the maximum message size of the synchronization.{eol}",
+        raw.len()
+    ));
+    truncated.push_str(&format!(
+        "It is fetched on demand when reading the message.{eol}"
+    ));
+
+    Some(truncated.into_bytes())
+}
+
+/// Replace the given local messages stripped of their body by their
+/// complete version, fetched from the remote backend they have been
+/// synchronized from.
+pub async fn fetch_bodies(
+    toml_account_config: &TomlAccountConfig,
+    account_config: Arc<AccountConfig>,
+    msgs: Messages,
+) -> Result<Messages> {
+    let cache = PartialCache::new(&account_config)?;
+
+    let mut raws = Vec::new();
+    for msg in msgs.to_vec() {
+        let raw = msg.raw()?.to_vec();
+        let entry = cache.get(&raw)?;
+        raws.push((raw, entry));
+    }
+
+    if raws.iter().all(|(_, entry)| entry.is_none()) {
+        return Ok(msgs);
+    }
+
+    let mut backends: HashMap<BackendKind, Backend> = HashMap::new();
+    let mut fetched = Vec::with_capacity(raws.len());
+
+    for (raw, entry) in raws {
+        let entry = match entry {
+            Some(entry) => entry,
+            None => {
+                fetched.push(raw);
+                continue;
+            }
+        };
+
+        debug!(
+            "fetching body of message {} from folder {}",
+            entry.id, entry.folder
+        );
+
+        if !backends.contains_key(&entry.backend) {
+            let backend =
+                build_remote_backend(toml_account_config, account_config.clone(), &entry.backend)
+                    .await?;
+            backends.insert(entry.backend.clone(), backend);
+        }
+
+        let msgs = backends[&entry.backend]
+            .backend
+            .peek_messages(&entry.folder, &Id::single(&entry.id))
+            .await
+            .with_context(|| {
+                format!(
+                    "cannot fetch body of message {} from folder {}",
+                    entry.id, entry.folder
+                )
+            })?;
+        let msg = msgs.first().ok_or_else(|| {
+            ErrorKind::NotFound.attach(anyhow!(
+                "cannot find message {} upstream in folder {}",
+                entry.id,
+                entry.folder
+            ))
+        })?;
+
+        fetched.push(msg.raw()?.to_vec());
+    }
+
+    Ok(Messages::from(fetched))
+}
+
+/// Build the backend partial messages have been synchronized from.
+async fn build_remote_backend(
+    toml_account_config: &TomlAccountConfig,
+    account_config: Arc<AccountConfig>,
+    backend: &BackendKind,
+) -> Result<Backend> {
+    let toml_account_config = Arc::new(TomlAccountConfig {
+        backend: Some(backend.clone()),
+        message: None,
+        ..toml_account_config.clone()
+    });
+
+    Backend::new(
+        toml_account_config,
+        account_config,
+        Some(backend),
+        |builder| builder.set_peek_messages(BackendFeatureSource::Context),
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::truncate;
+
+    #[test]
+    fn truncate_messages() {
+        let raw = concat!(
+            "From: alice@localhost\r\n",
+            "Subject: big\r\n",
+            "X-Himalaya-Partial: 1\r\n",
+            "Content-Type: multipart/mixed;\r\n",
+            "  boundary=\"boundary\"\r\n",
+            "\r\n",
+            "--boundary\r\n",
+            "big body\r\n",
+            "--boundary--\r\n",
+        );
+
+        assert_eq!(truncate(raw.as_bytes(), raw.len()), None);
+
+        let truncated = truncate(raw.as_bytes(), 16).unwrap();
+        let truncated = String::from_utf8(truncated).unwrap();
+
+        assert!(truncated.starts_with(concat!(
+            "From: alice@localhost\r\n",
+            "Subject: big\r\n",
+            "X-Himalaya-Partial: 153\r\n",
+            "Content-Type: text/plain; charset=utf-8\r\n",
+            "\r\n",
+        )));
+        assert!(!truncated.contains("boundary"));
+    }
+}
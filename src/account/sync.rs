@@ -0,0 +1,562 @@
+//! Module dedicated to machine-readable synchronization reports.
+//!
+//! In machine-readable output modes, the account synchronization
+//! prints a [`SyncReport`] describing every hunk of the folders and
+//! emails patches. Synchronization events can also be streamed as
+//! newline-delimited JSON while the synchronization runs, followed by
+//! the final report:
+//!
+//! ```json
+//! {"type":"event","account":"example","event":"processed-email-hunk","folder":"INBOX","hunk":"…","message":"…","elapsed_ms":42}
+//! {"type":"report","account":"example","dry_run":false,…}
+//! ```
+//!
+//! This module also contains the synchronization lock, preventing two
+//! synchronizations of the same account to run at once, and the
+//! synchronization status, displayed by the account list.
+
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Local};
+use clap::ValueEnum;
+use email::sync::SyncEvent;
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use sled::{Config, Db};
+use std::{
+    collections::BTreeMap,
+    fmt::Display,
+    fs,
+    io::{self, Write},
+    path::PathBuf,
+    time::Duration,
+};
+
+use crate::{
+    account::{self, journal::ReplayHunk},
+    printer::{Print, WriteColor},
+};
+
+/// The format of the synchronization events stream.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, ValueEnum)]
+pub enum SyncEventsFmt {
+    /// Stream events as newline-delimited JSON.
+    Ndjson,
+}
+
+/// Represents the status of a patch hunk.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum HunkStatus {
+    /// The hunk would be applied, only used by dry runs.
+    Planned,
+    Applied,
+    Failed,
+}
+
+/// Represents a hunk of a synchronization patch.
+#[derive(Clone, Debug, Serialize)]
+pub struct HunkReport {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub folder: Option<String>,
+    pub hunk: String,
+    pub status: HunkStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// How to replay the hunk, recorded by the journal.
+    #[serde(skip)]
+    pub replay: Option<ReplayHunk>,
+}
+
+/// Represents a synchronization patch, either of folders or of
+/// emails.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct PatchReport {
+    pub total: usize,
+    pub applied: usize,
+    pub failed: usize,
+    pub hunks: Vec<HunkReport>,
+}
+
+impl PatchReport {
+    /// Build a patch report from hunks and their optional error.
+    pub fn new<'a, H, E>(
+        hunks: impl IntoIterator<Item = (Option<&'a str>, &'a H, Option<&'a E>)>,
+        dry_run: bool,
+    ) -> Self
+    where
+        H: Display + 'a,
+        E: Display + 'a,
+    {
+        let mut report = Self::default();
+
+        for (folder, hunk, err) in hunks {
+            let status = match err {
+                _ if dry_run => HunkStatus::Planned,
+                Some(_) => HunkStatus::Failed,
+                None => HunkStatus::Applied,
+            };
+
+            report.total += 1;
+            match status {
+                HunkStatus::Applied => report.applied += 1,
+                HunkStatus::Failed => report.failed += 1,
+                HunkStatus::Planned => (),
+            }
+
+            report.hunks.push(HunkReport {
+                folder: folder.map(ToOwned::to_owned),
+                hunk: hunk.to_string(),
+                status,
+                error: err.map(ToString::to_string),
+                replay: None,
+            });
+        }
+
+        report
+    }
+
+    /// Attach to each hunk the way to replay it, if any.
+    pub fn with_replays(mut self, replays: impl IntoIterator<Item = Option<ReplayHunk>>) -> Self {
+        for (hunk, replay) in self.hunks.iter_mut().zip(replays) {
+            hunk.replay = replay;
+        }
+        self
+    }
+}
+
+/// Represents the emails patch counts of a folder.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize)]
+pub struct FolderReport {
+    pub total: usize,
+    pub applied: usize,
+    pub failed: usize,
+}
+
+/// Represents the durations of the synchronization steps, in
+/// milliseconds.
+///
+/// Steps that did not run are omitted.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct SyncTimings {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub folders_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub emails_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expunge_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rules_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub index_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub contacts_ms: Option<u64>,
+}
+
+impl SyncTimings {
+    /// Record the duration of the synchronization step ended by the
+    /// given event, `elapsed` being the time elapsed since the
+    /// beginning of the synchronization.
+    pub fn record(&mut self, evt: &SyncEvent, elapsed: Duration) {
+        let elapsed = millis(elapsed);
+        let folders = self.folders_ms.unwrap_or_default();
+        let emails = self.emails_ms.unwrap_or_default();
+
+        match evt {
+            SyncEvent::ProcessedAllFolderHunks => {
+                self.folders_ms = Some(elapsed);
+            }
+            SyncEvent::ProcessedAllEmailHunks => {
+                self.emails_ms = Some(elapsed.saturating_sub(folders));
+            }
+            SyncEvent::ExpungedAllFolders => {
+                self.expunge_ms = Some(elapsed.saturating_sub(folders + emails));
+            }
+            _ => (),
+        }
+    }
+}
+
+/// Represents the report of an account synchronization.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct SyncReport {
+    pub account: String,
+    pub dry_run: bool,
+    pub started_at: String,
+    pub duration_ms: u64,
+    pub timings: SyncTimings,
+    pub folders: PatchReport,
+    pub emails: PatchReport,
+    /// The emails patch counts, by folder.
+    pub per_folder: BTreeMap<String, FolderReport>,
+}
+
+impl SyncReport {
+    pub fn new(account: impl ToString, dry_run: bool, started_at: impl ToString) -> Self {
+        Self {
+            account: account.to_string(),
+            dry_run,
+            started_at: started_at.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Set the emails patch, and update the counts by folder
+    /// accordingly.
+    pub fn set_emails(&mut self, emails: PatchReport) {
+        self.per_folder.clear();
+
+        for hunk in &emails.hunks {
+            let folder = hunk.folder.clone().unwrap_or_default();
+            let counts = self.per_folder.entry(folder).or_default();
+            counts.total += 1;
+            match hunk.status {
+                HunkStatus::Applied => counts.applied += 1,
+                HunkStatus::Failed => counts.failed += 1,
+                HunkStatus::Planned => (),
+            }
+        }
+
+        self.emails = emails;
+    }
+
+    pub fn set_duration(&mut self, duration: Duration) {
+        self.duration_ms = millis(duration);
+    }
+
+    /// Count the hunks that failed to apply.
+    pub fn failed_hunks(&self) -> usize {
+        self.folders.failed + self.emails.failed
+    }
+}
+
+impl Print for SyncReport {
+    fn print(&self, writer: &mut dyn WriteColor) -> Result<()> {
+        writeln!(
+            writer,
+            "Account {}: {} folder hunk(s), {} email hunk(s), {} failure(s) in {}ms",
+            self.account,
+            self.folders.total,
+            self.emails.total,
+            self.failed_hunks(),
+            self.duration_ms,
+        )
+        .context("cannot write sync report to writer")?;
+        Ok(writer.reset()?)
+    }
+}
+
+/// Represents a line of the synchronization events stream.
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum SyncStreamItem {
+    Event(SyncEventReport),
+    Report(SyncReport),
+}
+
+/// Represents a synchronization event.
+#[derive(Debug, Serialize)]
+pub struct SyncEventReport {
+    pub account: String,
+    pub event: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub folder: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hunk: Option<String>,
+    /// The number of hunks of a generated patch.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total: Option<usize>,
+    pub message: String,
+    pub elapsed_ms: u64,
+}
+
+impl SyncEventReport {
+    pub fn new(account: impl ToString, evt: &SyncEvent, elapsed: Duration) -> Self {
+        let mut report = Self {
+            account: account.to_string(),
+            event: "progress",
+            folder: None,
+            hunk: None,
+            total: None,
+            message: evt.to_string(),
+            elapsed_ms: millis(elapsed),
+        };
+
+        match evt {
+            SyncEvent::ListedAllFolders => {
+                report.event = "listed-all-folders";
+            }
+            SyncEvent::ProcessedAllFolderHunks => {
+                report.event = "processed-all-folder-hunks";
+            }
+            SyncEvent::GeneratedEmailPatch(patches) => {
+                report.event = "generated-email-patch";
+                report.total = Some(patches.values().flatten().count());
+            }
+            SyncEvent::ProcessedEmailHunk(hunk) => {
+                report.event = "processed-email-hunk";
+                report.folder = Some(hunk.folder().to_owned());
+                report.hunk = Some(hunk.to_string());
+            }
+            SyncEvent::ProcessedAllEmailHunks => {
+                report.event = "processed-all-email-hunks";
+            }
+            SyncEvent::ExpungedAllFolders => {
+                report.event = "expunged-all-folders";
+            }
+            _ => (),
+        }
+
+        report
+    }
+}
+
+/// Write the given item of the events stream to the standard output,
+/// on its own line.
+///
+/// The output is flushed after each item, so that consumers can
+/// display progress live.
+pub fn write_stream_item(item: &SyncStreamItem) -> Result<()> {
+    let mut stdout = io::stdout().lock();
+    serde_json::to_writer(&mut stdout, item).context("cannot write sync event")?;
+    writeln!(stdout).context("cannot write sync event")?;
+    stdout.flush().context("cannot flush sync event")
+}
+
+/// Represents the synchronization lock of an account.
+///
+/// The lock relies on the exclusive file lock taken by sled when
+/// opening a database, which is released by the system when the
+/// process exits, even abnormally. The lock is released when dropped.
+#[derive(Debug)]
+pub struct SyncLock {
+    _db: Db,
+}
+
+impl SyncLock {
+    /// Acquire the synchronization lock of the given account.
+    ///
+    /// Fails if another synchronization of the same account is
+    /// running, whatever the process running it.
+    pub fn acquire(account_name: &str) -> Result<Self> {
+        let path = account::data_dir(".sync-lock", account_name)?;
+
+        let db = Config::new().path(&path).open().map_err(|err| {
+            anyhow!(err).context(format!(
+                "cannot lock synchronization of account {account_name}, \
+                 another synchronization may be running"
+            ))
+        })?;
+
+        Ok(Self { _db: db })
+    }
+}
+
+/// Represents the synchronization status of an account.
+///
+/// The status is saved as a JSON file after every synchronization,
+/// so that it can be read while a synchronization is running.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Deserialize, Serialize)]
+pub struct SyncStatus {
+    /// The date of the last synchronization, successful or not.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_sync_at: Option<String>,
+    /// The date of the last successful synchronization.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_success_at: Option<String>,
+    /// The error of the last synchronization, if it failed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+    /// The number of hunks that failed to apply during the last
+    /// synchronization.
+    #[serde(default)]
+    pub failed_hunks: usize,
+    /// The number of consecutive failed synchronizations.
+    #[serde(default)]
+    pub failures: u32,
+    /// The date of the next synchronization, only set by the daemon.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_sync_at: Option<String>,
+}
+
+impl SyncStatus {
+    /// Read the synchronization status of the given account.
+    ///
+    /// Returns `None` if the account has never been synchronized.
+    pub fn read(account_name: &str) -> Result<Option<Self>> {
+        let path = sync_status_path(account_name)?;
+
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let content =
+            fs::read(&path).with_context(|| format!("cannot read sync status at {path:?}"))?;
+        let status = serde_json::from_slice(&content)
+            .with_context(|| format!("cannot parse sync status at {path:?}"))?;
+
+        Ok(Some(status))
+    }
+
+    /// Write the synchronization status of the given account.
+    ///
+    /// The status is written to a temporary file first, then renamed,
+    /// so that readers never see a partially written status.
+    pub fn write(&self, account_name: &str) -> Result<()> {
+        let path = sync_status_path(account_name)?;
+        let tmp_path = path.with_extension("json.tmp");
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)
+                .with_context(|| format!("cannot create sync status directory at {dir:?}"))?;
+        }
+
+        let content = serde_json::to_vec(self).context("cannot serialize sync status")?;
+        fs::write(&tmp_path, content)
+            .with_context(|| format!("cannot write sync status at {tmp_path:?}"))?;
+        fs::rename(&tmp_path, &path)
+            .with_context(|| format!("cannot write sync status at {path:?}"))?;
+
+        Ok(())
+    }
+
+    /// Update the status with the result of a synchronization.
+    pub fn record<T>(&mut self, res: &Result<T>, failed_hunks: usize) {
+        let now = Local::now().to_rfc3339();
+
+        self.last_sync_at = Some(now.clone());
+        self.failed_hunks = failed_hunks;
+
+        match res {
+            Ok(_) => {
+                self.last_success_at = Some(now);
+                self.last_error = None;
+                self.failures = 0;
+            }
+            Err(err) => {
+                self.last_error = Some(format!("{err:#}"));
+                self.failures += 1;
+            }
+        }
+    }
+
+    /// Build a short, human-readable summary of the status.
+    pub fn summary(&self) -> String {
+        let date = |date: &str| {
+            DateTime::parse_from_rfc3339(date)
+                .map(|date| date.with_timezone(&Local).format("%F %R").to_string())
+                .unwrap_or_else(|_| date.to_owned())
+        };
+
+        match (&self.last_error, &self.last_success_at) {
+            (Some(_), _) => format!("failing ({})", self.failures),
+            (None, Some(at)) if self.failed_hunks > 0 => {
+                format!("{} ({} failed)", date(at), self.failed_hunks)
+            }
+            (None, Some(at)) => date(at),
+            (None, None) => String::new(),
+        }
+    }
+}
+
+/// Update the synchronization status of the given account with the
+/// result of a synchronization. Errors are only logged.
+pub fn record_status<T>(
+    account_name: &str,
+    res: &Result<T>,
+    failed_hunks: usize,
+    next_sync_at: Option<String>,
+) {
+    let res = SyncStatus::read(account_name).and_then(|status| {
+        let mut status = status.unwrap_or_default();
+        status.record(res, failed_hunks);
+        status.next_sync_at = next_sync_at;
+        status.write(account_name)
+    });
+
+    if let Err(err) = res {
+        warn!("cannot save sync status of account {account_name}: {err}");
+        debug!("{err:?}");
+    }
+}
+
+fn sync_status_path(account_name: &str) -> Result<PathBuf> {
+    Ok(account::data_dir(".sync-status", account_name)?.with_extension("json"))
+}
+
+fn millis(duration: Duration) -> u64 {
+    duration.as_millis().try_into().unwrap_or(u64::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn patch_reports() {
+        let hunks = [
+            (Some("INBOX"), "cache envelope 1", None),
+            (Some("INBOX"), "copy envelope 2", Some("cannot copy")),
+            (Some("Sent"), "cache envelope 3", None),
+        ];
+
+        let emails = PatchReport::new(
+            hunks
+                .iter()
+                .map(|(folder, hunk, err)| (*folder, hunk, err.as_ref())),
+            false,
+        );
+
+        assert_eq!(emails.total, 3);
+        assert_eq!(emails.applied, 2);
+        assert_eq!(emails.failed, 1);
+        assert_eq!(emails.hunks[1].error.as_deref(), Some("cannot copy"));
+
+        let mut report = SyncReport::new("example", false, "2024-01-01T00:00:00+00:00");
+        report.set_emails(emails);
+
+        assert_eq!(
+            report.per_folder.get("INBOX"),
+            Some(&FolderReport {
+                total: 2,
+                applied: 1,
+                failed: 1,
+            })
+        );
+
+        let item = serde_json::to_value(SyncStreamItem::Report(report)).unwrap();
+        assert_eq!(item["type"], "report");
+        assert_eq!(item["per_folder"]["Sent"]["applied"], 1);
+    }
+
+    #[test]
+    fn sync_status() {
+        let mut status = SyncStatus::default();
+
+        status.record(&Err::<(), _>(anyhow!("cannot connect")), 0);
+        status.record(&Err::<(), _>(anyhow!("cannot connect")), 0);
+        assert_eq!(status.failures, 2);
+        assert_eq!(status.last_success_at, None);
+        assert_eq!(status.summary(), "failing (2)");
+
+        status.record(&Ok(()), 1);
+        assert_eq!(status.failures, 0);
+        assert_eq!(status.last_error, None);
+        assert!(status.last_success_at.is_some());
+        assert!(status.summary().ends_with("(1 failed)"));
+    }
+
+    #[test]
+    fn dry_run_patch_reports() {
+        let hunks = [(None, "create folder INBOX", None::<&str>)];
+        let folders = PatchReport::new(
+            hunks
+                .iter()
+                .map(|(folder, hunk, err)| (*folder, hunk, err.as_ref())),
+            true,
+        );
+
+        assert_eq!(folders.total, 1);
+        assert_eq!(folders.applied, 0);
+        assert_eq!(folders.hunks[0].status, HunkStatus::Planned);
+    }
+}
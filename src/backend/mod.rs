@@ -3,7 +3,13 @@ pub(crate) mod wizard;
 
 use anyhow::Result;
 use async_trait::async_trait;
-use std::{ops::Deref, sync::Arc};
+use log::{debug, warn};
+use once_cell::sync::OnceCell;
+use std::{
+    collections::{HashMap, HashSet},
+    ops::Deref,
+    sync::Arc,
+};
 
 #[cfg(feature = "imap")]
 use email::imap::{ImapContextBuilder, ImapContextSync};
@@ -40,7 +46,21 @@ use email::{
 };
 use serde::{Deserialize, Serialize};
 
-use crate::{account::config::TomlAccountConfig, cache::IdMapper, envelope::Envelopes};
+#[cfg(feature = "account-sync")]
+use crate::account::partial::{self, cache::PartialCache, PartialSync};
+#[cfg(feature = "imap")]
+use crate::error::ErrorKind;
+#[cfg(any(feature = "imap", feature = "smtp"))]
+use crate::error::ErrorKindExt;
+use crate::{
+    account::config::TomlAccountConfig,
+    cache::IdMapper,
+    contact::{config::ContactConfig, AddressBook},
+    envelope::{
+        column::{EnvelopeColumnKind, EnvelopeColumns},
+        thread, Envelope, Envelopes, ThreadedEnvelopes,
+    },
+};
 
 #[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "kebab-case")]
@@ -105,6 +125,16 @@ pub struct BackendContextBuilder {
 
     #[cfg(feature = "sendmail")]
     pub sendmail: Option<SendmailContextBuilder>,
+
+    /// The partial synchronization, only set when building the
+    /// remote backend of a synchronization.
+    #[cfg(feature = "account-sync")]
+    pub partial_sync: Option<Arc<PartialSync>>,
+
+    /// The partial synchronization cache, used to prevent partial
+    /// messages of the local Maildir from being moved or deleted.
+    #[cfg(feature = "account-sync")]
+    pub partial_cache: Option<Arc<PartialCache>>,
 }
 
 impl BackendContextBuilder {
@@ -130,7 +160,7 @@ impl BackendContextBuilder {
                             .with_prebuilt_credentials()
                     });
                 match builder {
-                    Some(builder) => Some(builder.await?),
+                    Some(builder) => Some(builder.await.with_kind(ErrorKind::Auth)?),
                     None => None,
                 }
             },
@@ -182,8 +212,26 @@ impl BackendContextBuilder {
                 .map(|sendmail_config| {
                     SendmailContextBuilder::new(account_config.clone(), sendmail_config)
                 }),
+
+            #[cfg(feature = "account-sync")]
+            partial_sync: None,
+
+            #[cfg(feature = "account-sync")]
+            partial_cache: if kinds.contains(&&BackendKind::MaildirForSync) {
+                Some(Arc::new(PartialCache::new(&account_config)?))
+            } else {
+                None
+            },
         })
     }
+
+    /// Get the peek messages feature of the local Maildir, used to
+    /// check whether messages have been partially synchronized.
+    #[cfg(feature = "account-sync")]
+    fn partial_peek_messages(&self) -> Option<BackendFeature<BackendContext, dyn PeekMessages>> {
+        let f = self.maildir_for_sync.as_ref()?.peek_messages()?;
+        Some(Arc::new(move |ctx| f(ctx.maildir_for_sync.as_ref()?)))
+    }
 }
 
 #[async_trait]
@@ -293,7 +341,7 @@ impl email::backend::context::BackendContextBuilder for BackendContextBuilder {
     }
 
     fn list_envelopes(&self) -> Option<BackendFeature<Self::Context, dyn ListEnvelopes>> {
-        match self.toml_account_config.list_envelopes_kind() {
+        let feature = match self.toml_account_config.list_envelopes_kind() {
             #[cfg(feature = "imap")]
             Some(BackendKind::Imap) => self.list_envelopes_with_some(&self.imap),
             #[cfg(feature = "maildir")]
@@ -306,7 +354,14 @@ impl email::backend::context::BackendContextBuilder for BackendContextBuilder {
             #[cfg(feature = "notmuch")]
             Some(BackendKind::Notmuch) => self.list_envelopes_with_some(&self.notmuch),
             _ => None,
+        };
+
+        #[cfg(feature = "account-sync")]
+        if let Some(partial) = &self.partial_sync {
+            return partial::list_envelopes(feature?, partial.clone());
         }
+
+        feature
     }
 
     fn watch_envelopes(&self) -> Option<BackendFeature<Self::Context, dyn WatchEnvelopes>> {
@@ -378,7 +433,7 @@ impl email::backend::context::BackendContextBuilder for BackendContextBuilder {
     }
 
     fn add_message(&self) -> Option<BackendFeature<Self::Context, dyn AddMessage>> {
-        match self.toml_account_config.add_message_kind() {
+        let feature = match self.toml_account_config.add_message_kind() {
             #[cfg(feature = "imap")]
             Some(BackendKind::Imap) => self.add_message_with_some(&self.imap),
             #[cfg(feature = "maildir")]
@@ -391,7 +446,14 @@ impl email::backend::context::BackendContextBuilder for BackendContextBuilder {
             #[cfg(feature = "notmuch")]
             Some(BackendKind::Notmuch) => self.add_message_with_some(&self.notmuch),
             _ => None,
+        };
+
+        #[cfg(feature = "account-sync")]
+        if let Some(partial) = &self.partial_sync {
+            return partial::add_message(feature?, partial.clone());
         }
+
+        feature
     }
 
     fn send_message(&self) -> Option<BackendFeature<Self::Context, dyn SendMessage>> {
@@ -405,7 +467,7 @@ impl email::backend::context::BackendContextBuilder for BackendContextBuilder {
     }
 
     fn peek_messages(&self) -> Option<BackendFeature<Self::Context, dyn PeekMessages>> {
-        match self.toml_account_config.peek_messages_kind() {
+        let feature = match self.toml_account_config.peek_messages_kind() {
             #[cfg(feature = "imap")]
             Some(BackendKind::Imap) => self.peek_messages_with_some(&self.imap),
             #[cfg(feature = "maildir")]
@@ -418,11 +480,19 @@ impl email::backend::context::BackendContextBuilder for BackendContextBuilder {
             #[cfg(feature = "notmuch")]
             Some(BackendKind::Notmuch) => self.peek_messages_with_some(&self.notmuch),
             _ => None,
+        };
+
+        #[cfg(feature = "account-sync")]
+        if let Some(partial) = &self.partial_sync {
+            let kind = self.toml_account_config.peek_messages_kind()?.clone();
+            return partial::peek_messages(feature?, partial.clone(), kind);
         }
+
+        feature
     }
 
     fn get_messages(&self) -> Option<BackendFeature<Self::Context, dyn GetMessages>> {
-        match self.toml_account_config.get_messages_kind() {
+        let feature = match self.toml_account_config.get_messages_kind() {
             #[cfg(feature = "imap")]
             Some(BackendKind::Imap) => self.get_messages_with_some(&self.imap),
             #[cfg(feature = "maildir")]
@@ -435,7 +505,15 @@ impl email::backend::context::BackendContextBuilder for BackendContextBuilder {
             #[cfg(feature = "notmuch")]
             Some(BackendKind::Notmuch) => self.get_messages_with_some(&self.notmuch),
             _ => None,
+        };
+
+        #[cfg(feature = "account-sync")]
+        if let Some(partial) = &self.partial_sync {
+            let kind = self.toml_account_config.get_messages_kind()?.clone();
+            return partial::get_messages(feature?, partial.clone(), kind);
         }
+
+        feature
     }
 
     fn copy_messages(&self) -> Option<BackendFeature<Self::Context, dyn CopyMessages>> {
@@ -464,7 +542,16 @@ impl email::backend::context::BackendContextBuilder for BackendContextBuilder {
             #[cfg(feature = "account-sync")]
             Some(BackendKind::MaildirForSync) => {
                 let f = self.maildir_for_sync.as_ref()?.move_messages()?;
-                Some(Arc::new(move |ctx| f(ctx.maildir_for_sync.as_ref()?)))
+                let f: BackendFeature<Self::Context, dyn MoveMessages> =
+                    Arc::new(move |ctx: &BackendContext| f(ctx.maildir_for_sync.as_ref()?));
+
+                match &self.partial_cache {
+                    Some(cache) => {
+                        let peek = self.partial_peek_messages()?;
+                        partial::move_local_messages(f, peek, cache.clone())
+                    }
+                    None => Some(f),
+                }
             }
             #[cfg(feature = "notmuch")]
             Some(BackendKind::Notmuch) => self.move_messages_with_some(&self.notmuch),
@@ -473,7 +560,7 @@ impl email::backend::context::BackendContextBuilder for BackendContextBuilder {
     }
 
     fn delete_messages(&self) -> Option<BackendFeature<Self::Context, dyn DeleteMessages>> {
-        match self.toml_account_config.delete_messages_kind() {
+        let feature = match self.toml_account_config.delete_messages_kind() {
             #[cfg(feature = "imap")]
             Some(BackendKind::Imap) => self.delete_messages_with_some(&self.imap),
             #[cfg(feature = "maildir")]
@@ -481,12 +568,28 @@ impl email::backend::context::BackendContextBuilder for BackendContextBuilder {
             #[cfg(feature = "account-sync")]
             Some(BackendKind::MaildirForSync) => {
                 let f = self.maildir_for_sync.as_ref()?.delete_messages()?;
-                Some(Arc::new(move |ctx| f(ctx.maildir_for_sync.as_ref()?)))
+                let f: BackendFeature<Self::Context, dyn DeleteMessages> =
+                    Arc::new(move |ctx: &BackendContext| f(ctx.maildir_for_sync.as_ref()?));
+
+                match &self.partial_cache {
+                    Some(cache) => {
+                        let peek = self.partial_peek_messages()?;
+                        partial::delete_local_messages(f, peek, cache.clone())
+                    }
+                    None => Some(f),
+                }
             }
             #[cfg(feature = "notmuch")]
             Some(BackendKind::Notmuch) => self.delete_messages_with_some(&self.notmuch),
             _ => None,
+        };
+
+        #[cfg(feature = "account-sync")]
+        if let Some(partial) = &self.partial_sync {
+            return partial::delete_messages(feature?, partial.clone());
         }
+
+        feature
     }
 
     async fn build(self) -> Result<Self::Context> {
@@ -494,7 +597,7 @@ impl email::backend::context::BackendContextBuilder for BackendContextBuilder {
 
         #[cfg(feature = "imap")]
         if let Some(imap) = self.imap {
-            ctx.imap = Some(imap.build().await?);
+            ctx.imap = Some(imap.build().await.with_remote_kind()?);
         }
 
         #[cfg(feature = "maildir")]
@@ -514,7 +617,7 @@ impl email::backend::context::BackendContextBuilder for BackendContextBuilder {
 
         #[cfg(feature = "smtp")]
         if let Some(smtp) = self.smtp {
-            ctx.smtp = Some(smtp.build().await?);
+            ctx.smtp = Some(smtp.build().await.with_remote_kind()?);
         }
 
         #[cfg(feature = "sendmail")]
@@ -582,9 +685,30 @@ impl AsRef<Option<SendmailContextSync>> for BackendContext {
     }
 }
 
+/// Return true if the given backend kind of the given account maps
+/// backend ids to aliases.
+#[allow(unused_variables)]
+pub fn uses_id_mapper(
+    toml_account_config: &TomlAccountConfig,
+    backend_kind: Option<&BackendKind>,
+) -> bool {
+    match backend_kind {
+        #[cfg(feature = "maildir")]
+        Some(BackendKind::Maildir) => toml_account_config.maildir.is_some(),
+        #[cfg(feature = "account-sync")]
+        Some(BackendKind::MaildirForSync) => true,
+        #[cfg(feature = "notmuch")]
+        Some(BackendKind::Notmuch) => toml_account_config.notmuch.is_some(),
+        _ => false,
+    }
+}
+
 pub struct Backend {
     pub toml_account_config: Arc<TomlAccountConfig>,
     pub backend: email::backend::Backend<BackendContext>,
+    /// The address book, opened by the first listing collecting
+    /// contacts.
+    pub address_book: OnceCell<Option<AddressBook>>,
 }
 
 impl Backend {
@@ -610,6 +734,7 @@ impl Backend {
         Ok(Self {
             toml_account_config: toml_account_config.clone(),
             backend: backend_builder.build().await?,
+            address_book: OnceCell::new(),
         })
     }
 
@@ -618,44 +743,245 @@ impl Backend {
         folder: &str,
         backend_kind: Option<&BackendKind>,
     ) -> Result<IdMapper> {
-        #[allow(unused_mut)]
-        let mut id_mapper = IdMapper::Dummy;
+        if uses_id_mapper(&self.toml_account_config, backend_kind) {
+            IdMapper::new(&self.backend.account_config, folder)
+        } else {
+            Ok(IdMapper::Dummy)
+        }
+    }
 
-        match backend_kind {
-            #[cfg(feature = "maildir")]
-            Some(BackendKind::Maildir) => {
-                if let Some(_) = &self.toml_account_config.maildir {
-                    id_mapper = IdMapper::new(&self.backend.account_config, folder)?;
+    pub async fn list_envelopes(
+        &self,
+        folder: &str,
+        opts: ListEnvelopesOptions,
+    ) -> Result<Envelopes> {
+        let backend_kind = self.toml_account_config.list_envelopes_kind();
+        let id_mapper = self.build_id_mapper(folder, backend_kind)?;
+        let envelopes = self.backend.list_envelopes(folder, opts).await?;
+        let envelopes = Envelopes::from_backend(&self.account_config, &id_mapper, envelopes)?;
+        Ok(envelopes)
+    }
+
+    /// List envelopes of the given folder, filled with the data
+    /// needed by the given table columns.
+    pub async fn list_envelopes_with_columns(
+        &self,
+        folder: &str,
+        opts: ListEnvelopesOptions,
+        columns: EnvelopeColumns,
+    ) -> Result<Envelopes> {
+        let mut envelopes = Vec::new();
+
+        self.list_envelopes_with_columns_by_chunk(folder, opts, &columns, usize::MAX, |chunk| {
+            envelopes.extend(chunk);
+            Ok(())
+        })
+        .await?;
+
+        Ok(Envelopes::from(envelopes).with_columns(columns))
+    }
+
+    /// List envelopes of the given folder, filled with the data
+    /// needed by the given table columns, and pass them to the given
+    /// function by chunks of the given size.
+    ///
+    /// Messages are peeked chunk by chunk, so that the first chunks
+    /// can be used before the messages of the next ones are read.
+    pub async fn list_envelopes_with_columns_by_chunk(
+        &self,
+        folder: &str,
+        opts: ListEnvelopesOptions,
+        columns: &EnvelopeColumns,
+        chunk_size: usize,
+        mut f: impl FnMut(Vec<Envelope>) -> Result<()>,
+    ) -> Result<()> {
+        let backend_kind = self.toml_account_config.list_envelopes_kind();
+        let id_mapper = self.build_id_mapper(folder, backend_kind)?;
+        let backend_envelopes = self.backend.list_envelopes(folder, opts).await?;
+
+        for backend_envelopes in backend_envelopes.chunks(chunk_size.max(1)) {
+            let mut envelopes = backend_envelopes
+                .iter()
+                .map(|envelope| Envelope::from_backend(&self.account_config, &id_mapper, envelope))
+                .collect::<Result<Vec<_>>>()?;
+            self.fill_envelopes(folder, backend_envelopes, &mut envelopes, columns)
+                .await?;
+            self.collect_contacts(backend_envelopes, &envelopes);
+            f(envelopes)?;
+        }
+
+        Ok(())
+    }
+
+    /// Record senders and recipients of the given envelopes in the
+    /// address book, if the collection of contacts is enabled.
+    fn collect_contacts(
+        &self,
+        backend_envelopes: &[email::envelope::Envelope],
+        envelopes: &[Envelope],
+    ) {
+        if let Some(book) = self.address_book() {
+            let res =
+                book.record_envelopes(backend_envelopes.iter().zip(envelopes).map(|(b, e)| {
+                    let mailboxes = [&e.from, &e.to].into_iter().chain(&e.cc).collect();
+                    (b.message_id.as_str(), b.date.timestamp(), mailboxes)
+                }));
+
+            if let Err(err) = res {
+                warn!("cannot collect contacts: {err}");
+                debug!("{err:?}");
+            }
+        }
+    }
+
+    /// Get the address book, opening it on first use.
+    ///
+    /// Returns [`None`] if the collection of contacts is disabled or
+    /// if the address book cannot be opened.
+    fn address_book(&self) -> Option<&AddressBook> {
+        self.address_book
+            .get_or_init(|| {
+                let enabled = self
+                    .toml_account_config
+                    .contact
+                    .as_ref()
+                    .map(ContactConfig::is_collect_enabled)
+                    .unwrap_or(false);
+
+                if !enabled {
+                    return None;
                 }
+
+                match AddressBook::new(&self.backend.account_config) {
+                    Ok(book) => Some(book),
+                    Err(err) => {
+                        warn!("cannot open address book: {err}");
+                        debug!("{err:?}");
+                        None
+                    }
+                }
+            })
+            .as_ref()
+    }
+
+    /// Fill envelopes with the data needed by the given columns.
+    ///
+    /// When columns need data that envelopes do not carry, messages
+    /// of all envelopes are peeked at once.
+    async fn fill_envelopes(
+        &self,
+        folder: &str,
+        backend_envelopes: &[email::envelope::Envelope],
+        envelopes: &mut [Envelope],
+        columns: &EnvelopeColumns,
+    ) -> Result<()> {
+        if columns.has(&EnvelopeColumnKind::Folder) {
+            for envelope in envelopes.iter_mut() {
+                envelope.folder = Some(folder.to_owned());
             }
+        }
 
-            #[cfg(feature = "account-sync")]
-            Some(BackendKind::MaildirForSync) => {
-                id_mapper = IdMapper::new(&self.backend.account_config, folder)?;
+        if !columns.needs_message() || backend_envelopes.is_empty() {
+            return Ok(());
+        }
+
+        let ids = Id::multiple(
+            backend_envelopes
+                .iter()
+                .map(|e| e.id.clone())
+                .collect::<Vec<_>>(),
+        );
+        let msgs = self.backend.peek_messages(folder, &ids).await?;
+
+        // backends do not necessarily return peeked messages in the
+        // order of the requested ids, so they are matched to their
+        // envelope using their Message-ID
+        let mut raws = HashMap::new();
+        for msg in msgs.iter() {
+            if let Some(id) = msg.parsed()?.message_id() {
+                raws.insert(thread::normalize_message_id(id), msg.raw()?);
             }
+        }
 
-            #[cfg(feature = "notmuch")]
-            Some(BackendKind::Notmuch) => {
-                if let Some(_) = &self.toml_account_config.notmuch {
-                    id_mapper = IdMapper::new(&self.backend.account_config, folder)?;
-                }
+        for (backend_envelope, envelope) in backend_envelopes.iter().zip(envelopes) {
+            let id = thread::normalize_message_id(&backend_envelope.message_id);
+            if let Some(raw) = raws.get(&id) {
+                columns.fill(envelope, raw);
             }
-            _ => (),
+        }
+
+        Ok(())
+    }
+
+    /// Remove id mappings of the given folder that do not point to
+    /// an existing envelope anymore.
+    ///
+    /// Returns the number of removed mappings.
+    pub async fn clean_id_mapper(&self, folder: &str) -> Result<usize> {
+        let backend_kind = self.toml_account_config.list_envelopes_kind();
+        let id_mapper = self.build_id_mapper(folder, backend_kind)?;
+
+        if let IdMapper::Dummy = id_mapper {
+            return Ok(0);
+        }
+
+        let opts = ListEnvelopesOptions {
+            page: 0,
+            page_size: 0,
+            query: None,
         };
+        let envelopes = self.backend.list_envelopes(folder, opts).await?;
+        let ids = envelopes
+            .iter()
+            .map(|e| e.id.clone())
+            .collect::<HashSet<_>>();
 
-        Ok(id_mapper)
+        id_mapper.retain_ids(&ids)
     }
 
-    pub async fn list_envelopes(
+    /// List envelopes of the given folder, grouped by threads.
+    ///
+    /// Messages of the listed envelopes are peeked in order to read
+    /// their Message-ID, In-Reply-To and References headers.
+    pub async fn thread_envelopes(
         &self,
         folder: &str,
         opts: ListEnvelopesOptions,
-    ) -> Result<Envelopes> {
+    ) -> Result<ThreadedEnvelopes> {
         let backend_kind = self.toml_account_config.list_envelopes_kind();
         let id_mapper = self.build_id_mapper(folder, backend_kind)?;
         let envelopes = self.backend.list_envelopes(folder, opts).await?;
-        let envelopes = Envelopes::from_backend(&self.account_config, &id_mapper, envelopes)?;
-        Ok(envelopes)
+
+        let mut parents = HashMap::new();
+
+        if !envelopes.is_empty() {
+            let ids = Id::multiple(envelopes.iter().map(|e| e.id.clone()).collect::<Vec<_>>());
+            let msgs = self.backend.peek_messages(folder, &ids).await?;
+
+            for msg in msgs.to_vec() {
+                let msg = msg.parsed()?;
+
+                if let Some(id) = msg.message_id() {
+                    let parent_ids = thread::parent_message_ids(
+                        msg.in_reply_to().as_text_list().unwrap_or_default(),
+                        msg.references().as_text_list().unwrap_or_default(),
+                    );
+                    parents.insert(thread::normalize_message_id(id), parent_ids);
+                }
+            }
+        }
+
+        let items = envelopes
+            .iter()
+            .map(|envelope| {
+                let id = thread::normalize_message_id(&envelope.message_id);
+                let parent_ids = parents.remove(&id).unwrap_or_default();
+                let envelope = Envelope::from_backend(&self.account_config, &id_mapper, envelope)?;
+                Ok((id, parent_ids, envelope))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(thread::build_threads(items).into())
     }
 
     pub async fn add_flags(&self, folder: &str, ids: &[usize], flags: &Flags) -> Result<()> {
@@ -708,18 +1034,66 @@ impl Backend {
         Ok(id)
     }
 
+    /// Add the given message with the given flags, then create the
+    /// alias of its id.
+    pub async fn add_message_with_flags_and_alias(
+        &self,
+        folder: &str,
+        email: &[u8],
+        flags: &Flags,
+    ) -> Result<SingleId> {
+        let backend_kind = self.toml_account_config.add_message_kind();
+        let id_mapper = self.build_id_mapper(folder, backend_kind)?;
+        let id = self
+            .backend
+            .add_message_with_flags(folder, email, flags)
+            .await?;
+        id_mapper.create_alias(&*id)?;
+        Ok(id)
+    }
+
+    pub async fn get_envelope(&self, folder: &str, id: usize) -> Result<email::envelope::Envelope> {
+        let backend_kind = self.toml_account_config.get_envelope_kind();
+        let id_mapper = self.build_id_mapper(folder, backend_kind)?;
+        let id = SingleId::from(id_mapper.get_id(id)?);
+        self.backend.get_envelope(folder, &id).await
+    }
+
     pub async fn peek_messages(&self, folder: &str, ids: &[usize]) -> Result<Messages> {
         let backend_kind = self.toml_account_config.get_messages_kind();
         let id_mapper = self.build_id_mapper(folder, backend_kind)?;
         let ids = Id::multiple(id_mapper.get_ids(ids)?);
-        self.backend.peek_messages(folder, &ids).await
+        let msgs = self.backend.peek_messages(folder, &ids).await?;
+
+        #[cfg(feature = "account-sync")]
+        if let Some(BackendKind::MaildirForSync) = backend_kind {
+            return self.fetch_partial_bodies(msgs).await;
+        }
+
+        Ok(msgs)
     }
 
     pub async fn get_messages(&self, folder: &str, ids: &[usize]) -> Result<Messages> {
         let backend_kind = self.toml_account_config.get_messages_kind();
         let id_mapper = self.build_id_mapper(folder, backend_kind)?;
         let ids = Id::multiple(id_mapper.get_ids(ids)?);
-        self.backend.get_messages(folder, &ids).await
+        let msgs = self.backend.get_messages(folder, &ids).await?;
+
+        #[cfg(feature = "account-sync")]
+        if let Some(BackendKind::MaildirForSync) = backend_kind {
+            return self.fetch_partial_bodies(msgs).await;
+        }
+
+        Ok(msgs)
+    }
+
+    /// Replace the messages of the local Maildir stripped of their
+    /// body by the partial synchronization with their complete
+    /// version.
+    #[cfg(feature = "account-sync")]
+    pub async fn fetch_partial_bodies(&self, msgs: Messages) -> Result<Messages> {
+        let account_config = self.backend.account_config.clone();
+        partial::fetch_bodies(&self.toml_account_config, account_config, msgs).await
     }
 
     pub async fn copy_messages(
@@ -0,0 +1,57 @@
+use anyhow::Result;
+use clap::Parser;
+use email::backend::feature::BackendFeatureSource;
+use log::info;
+
+#[cfg(feature = "account-sync")]
+use crate::cache::arg::disable::CacheDisableFlag;
+use crate::{
+    account::arg::name::AccountNameFlag, backend::Backend, config::TomlConfig,
+    folder::arg::name::FolderNameOptionalFlag, printer::Printer,
+};
+
+/// Remove stale id mappings of a folder.
+///
+/// This command lists all envelopes of the given folder, then removes
+/// the mappings of ids that do not exist anymore.
+#[derive(Debug, Parser)]
+pub struct CacheCleanCommand {
+    #[command(flatten)]
+    pub folder: FolderNameOptionalFlag,
+
+    #[cfg(feature = "account-sync")]
+    #[command(flatten)]
+    pub cache: CacheDisableFlag,
+
+    #[command(flatten)]
+    pub account: AccountNameFlag,
+}
+
+impl CacheCleanCommand {
+    pub async fn execute(self, printer: &mut impl Printer, config: &TomlConfig) -> Result<()> {
+        info!("executing clean cache command");
+
+        let folder = &self.folder.name;
+        let (toml_account_config, account_config) = config.clone().into_account_configs(
+            self.account.name.as_deref(),
+            #[cfg(feature = "account-sync")]
+            self.cache.disable,
+        )?;
+
+        let list_envelopes_kind = toml_account_config.list_envelopes_kind();
+
+        let backend = Backend::new(
+            toml_account_config.clone(),
+            account_config,
+            list_envelopes_kind,
+            |builder| builder.set_list_envelopes(BackendFeatureSource::Context),
+        )
+        .await?;
+
+        let count = backend.clean_id_mapper(folder).await?;
+
+        printer.print(format!(
+            "Successfully removed {count} stale id mapping(s) from folder {folder}!"
+        ))
+    }
+}
@@ -0,0 +1,65 @@
+use anyhow::{bail, Result};
+use clap::Parser;
+use log::info;
+
+#[cfg(feature = "account-sync")]
+use crate::cache::arg::disable::CacheDisableFlag;
+use crate::{
+    account::arg::name::AccountNameFlag,
+    backend,
+    cache::IdMapper,
+    config::TomlConfig,
+    folder::arg::name::FolderNameOptionalFlag,
+    printer::{PrintTableOpts, Printer},
+    ui::arg::max_width::TableMaxWidthFlag,
+};
+
+/// List id mappings of a folder.
+///
+/// This command allows you to inspect the aliases that have been
+/// associated to the backend ids of the given folder.
+#[derive(Debug, Parser)]
+pub struct CacheListCommand {
+    #[command(flatten)]
+    pub folder: FolderNameOptionalFlag,
+
+    #[command(flatten)]
+    pub table: TableMaxWidthFlag,
+
+    #[cfg(feature = "account-sync")]
+    #[command(flatten)]
+    pub cache: CacheDisableFlag,
+
+    #[command(flatten)]
+    pub account: AccountNameFlag,
+}
+
+impl CacheListCommand {
+    pub async fn execute(self, printer: &mut impl Printer, config: &TomlConfig) -> Result<()> {
+        info!("executing list cache command");
+
+        let folder = &self.folder.name;
+        let (toml_account_config, account_config) = config.clone().into_account_configs(
+            self.account.name.as_deref(),
+            #[cfg(feature = "account-sync")]
+            self.cache.disable,
+        )?;
+
+        let backend_kind = toml_account_config.list_envelopes_kind();
+        if !backend::uses_id_mapper(&toml_account_config, backend_kind) {
+            let account = &account_config.name;
+            bail!("cannot list id mappings: the backend of account {account} does not use id mappings");
+        }
+
+        let id_mapper = IdMapper::new(&account_config, folder)?;
+        let mappings = id_mapper.mappings()?;
+
+        printer.print_table(
+            Box::new(mappings),
+            PrintTableOpts {
+                format: &account_config.get_message_read_format(),
+                max_width: self.table.max_width,
+            },
+        )
+    }
+}
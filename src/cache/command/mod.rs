@@ -0,0 +1,38 @@
+mod clean;
+mod list;
+mod rebuild;
+
+use anyhow::Result;
+use clap::Subcommand;
+
+use crate::{config::TomlConfig, printer::Printer};
+
+use self::{clean::CacheCleanCommand, list::CacheListCommand, rebuild::CacheRebuildCommand};
+
+/// Manage the id mapping cache.
+///
+/// Some backends (like Maildir or Notmuch) use long ids that are not
+/// convenient to type, so Himalaya maps them to short numeric
+/// aliases. This subcommand allows you to inspect and maintain those
+/// mappings.
+#[derive(Debug, Subcommand)]
+pub enum CacheSubcommand {
+    #[command(alias = "lst")]
+    List(CacheListCommand),
+
+    #[command()]
+    Rebuild(CacheRebuildCommand),
+
+    #[command(alias = "gc")]
+    Clean(CacheCleanCommand),
+}
+
+impl CacheSubcommand {
+    pub async fn execute(self, printer: &mut impl Printer, config: &TomlConfig) -> Result<()> {
+        match self {
+            Self::List(cmd) => cmd.execute(printer, config).await,
+            Self::Rebuild(cmd) => cmd.execute(printer, config).await,
+            Self::Clean(cmd) => cmd.execute(printer, config).await,
+        }
+    }
+}
@@ -0,0 +1,54 @@
+use anyhow::{bail, Result};
+use clap::Parser;
+use log::info;
+
+#[cfg(feature = "account-sync")]
+use crate::cache::arg::disable::CacheDisableFlag;
+use crate::{
+    account::arg::name::AccountNameFlag, backend, cache::IdMapper, config::TomlConfig,
+    folder::arg::name::FolderNameOptionalFlag, printer::Printer,
+};
+
+/// Rebuild the id mapping index of a folder.
+///
+/// This command rebuilds the alias to id index from the id to alias
+/// entries. It can be used to repair the index if aliases cannot be
+/// resolved anymore.
+#[derive(Debug, Parser)]
+pub struct CacheRebuildCommand {
+    #[command(flatten)]
+    pub folder: FolderNameOptionalFlag,
+
+    #[cfg(feature = "account-sync")]
+    #[command(flatten)]
+    pub cache: CacheDisableFlag,
+
+    #[command(flatten)]
+    pub account: AccountNameFlag,
+}
+
+impl CacheRebuildCommand {
+    pub async fn execute(self, printer: &mut impl Printer, config: &TomlConfig) -> Result<()> {
+        info!("executing rebuild cache command");
+
+        let folder = &self.folder.name;
+        let (toml_account_config, account_config) = config.clone().into_account_configs(
+            self.account.name.as_deref(),
+            #[cfg(feature = "account-sync")]
+            self.cache.disable,
+        )?;
+
+        let backend_kind = toml_account_config.list_envelopes_kind();
+        if !backend::uses_id_mapper(&toml_account_config, backend_kind) {
+            let account = &account_config.name;
+            bail!("cannot rebuild id mappings: the backend of account {account} does not use id mappings");
+        }
+
+        let id_mapper = IdMapper::new(&account_config, folder)?;
+        let count = id_mapper.rebuild()?;
+
+        printer.print(format!(
+            "Successfully rebuilt {count} id mapping(s) of folder {folder}!"
+        ))
+    }
+}
@@ -1,16 +1,38 @@
 pub mod arg;
+pub mod command;
 
 use anyhow::{anyhow, Context, Result};
 use dirs::data_dir;
 use email::account::config::AccountConfig;
 use log::debug;
-use sled::{Config, Db};
-use std::collections::HashSet;
+use serde::Serialize;
+use sled::{
+    transaction::{ConflictableTransactionError, Transactional},
+    Config, Db, Tree,
+};
+use std::{collections::HashSet, ops};
+
+use crate::{
+    printer::{PrintTable, PrintTableOpts, WriteColor},
+    ui::{Cell, Row, Table},
+};
+
+/// The current version of the id mapper database layout.
+///
+/// Version 1 only contained the id → alias entries in the default
+/// tree. Version 2 added the alias → id entries in the `aliases`
+/// tree.
+const ID_MAPPER_VERSION: &[u8] = b"2";
 
 #[derive(Debug)]
 pub enum IdMapper {
     Dummy,
-    Mapper(Db),
+    Mapper {
+        /// The id → alias entries.
+        ids: Db,
+        /// The alias → id entries.
+        aliases: Tree,
+    },
 }
 
 impl IdMapper {
@@ -28,7 +50,28 @@ impl IdMapper {
             .open()
             .with_context(|| format!("cannot open id mapper database at {db_path:?}"))?;
 
-        Ok(Self::Mapper(conn))
+        Self::from_db(conn)
+    }
+
+    fn from_db(conn: Db) -> Result<Self> {
+        let aliases = conn
+            .open_tree("aliases")
+            .context("cannot open id mapper aliases")?;
+        let meta = conn
+            .open_tree("meta")
+            .context("cannot open id mapper metadata")?;
+
+        let id_mapper = Self::Mapper { ids: conn, aliases };
+
+        let version = meta.get("version")?;
+        if version.as_deref() != Some(ID_MAPPER_VERSION) {
+            debug!("migrating id mapper database to version 2…");
+            let count = id_mapper.rebuild()?;
+            meta.insert("version", ID_MAPPER_VERSION)?;
+            debug!("migrated {count} id mapper entries");
+        }
+
+        Ok(id_mapper)
     }
 
     pub fn create_alias<I>(&self, id: I) -> Result<String>
@@ -38,16 +81,23 @@ impl IdMapper {
         let id = id.as_ref();
         match self {
             Self::Dummy => Ok(id.to_owned()),
-            Self::Mapper(conn) => {
+            Self::Mapper { ids, aliases } => {
                 debug!("creating alias for id {id}…");
 
-                let alias = conn
+                let alias = ids
                     .generate_id()
                     .with_context(|| format!("cannot create alias for id {id}"))?
                     .to_string();
                 debug!("created alias {alias} for id {id}");
 
-                conn.insert(&id, alias.as_bytes())
+                // both entries are inserted at once, so that a crash
+                // cannot leave a mapping in only one direction
+                (&**ids, aliases)
+                    .transaction(|(ids, aliases)| {
+                        ids.insert(id, alias.as_bytes())?;
+                        aliases.insert(alias.as_bytes(), id)?;
+                        Ok::<_, ConflictableTransactionError<sled::Error>>(())
+                    })
                     .with_context(|| format!("cannot insert alias {alias} for id {id}"))?;
 
                 Ok(alias)
@@ -62,10 +112,10 @@ impl IdMapper {
         let id = id.as_ref();
         match self {
             Self::Dummy => Ok(id.to_owned()),
-            Self::Mapper(conn) => {
+            Self::Mapper { ids, .. } => {
                 debug!("getting alias for id {id}…");
 
-                let alias = conn
+                let alias = ids
                     .get(id)
                     .with_context(|| format!("cannot get alias for id {id}"))?;
 
@@ -94,20 +144,13 @@ impl IdMapper {
 
         match self {
             Self::Dummy => Ok(alias.to_string()),
-            Self::Mapper(conn) => {
+            Self::Mapper { aliases, .. } => {
                 debug!("getting id from alias {alias}…");
 
-                let id = conn
-                    .iter()
-                    .flat_map(|entry| entry)
-                    .find_map(|(entry_id, entry_alias)| {
-                        if entry_alias.as_ref() == alias.as_bytes() {
-                            let entry_id = String::from_utf8_lossy(entry_id.as_ref());
-                            Some(entry_id.to_string())
-                        } else {
-                            None
-                        }
-                    })
+                let id = aliases
+                    .get(&alias)
+                    .with_context(|| format!("cannot get id from alias {alias}"))?
+                    .map(|id| String::from_utf8_lossy(id.as_ref()).to_string())
                     .ok_or_else(|| anyhow!("cannot get id from alias {alias}"))?;
                 debug!("found id {id} from alias {alias}");
 
@@ -121,24 +164,192 @@ impl IdMapper {
 
         match self {
             Self::Dummy => Ok(aliases),
-            Self::Mapper(conn) => {
-                let aliases: HashSet<&str> = aliases.iter().map(|alias| alias.as_str()).collect();
-                let ids: Vec<String> = conn
+            Self::Mapper { .. } => {
+                let mut ids = Vec::with_capacity(aliases.len());
+
+                for alias in aliases {
+                    match self.get_id(&alias) {
+                        Ok(id) => ids.push(id),
+                        Err(err) => debug!("skipping alias {alias}: {err}"),
+                    }
+                }
+
+                Ok(ids)
+            }
+        }
+    }
+
+    /// Return all the id mappings, sorted by alias.
+    pub fn mappings(&self) -> Result<IdMappings> {
+        match self {
+            Self::Dummy => Ok(IdMappings::default()),
+            Self::Mapper { aliases, .. } => {
+                let mut mappings = aliases
                     .iter()
-                    .flat_map(|entry| entry)
-                    .filter_map(|(entry_id, entry_alias)| {
-                        let alias = String::from_utf8_lossy(entry_alias.as_ref());
-                        if aliases.contains(alias.as_ref()) {
-                            let entry_id = String::from_utf8_lossy(entry_id.as_ref());
-                            Some(entry_id.to_string())
-                        } else {
-                            None
-                        }
+                    .map(|entry| {
+                        let (alias, id) = entry?;
+                        Ok(IdMapping {
+                            alias: String::from_utf8_lossy(&alias).to_string(),
+                            id: String::from_utf8_lossy(&id).to_string(),
+                        })
                     })
-                    .collect();
+                    .collect::<Result<Vec<_>>>()?;
 
-                Ok(ids)
+                mappings.sort_by_key(|mapping| mapping.alias.parse::<u64>().unwrap_or(u64::MAX));
+
+                Ok(IdMappings(mappings))
+            }
+        }
+    }
+
+    /// Rebuild the alias → id entries from the id → alias entries.
+    ///
+    /// Returns the number of rebuilt entries.
+    pub fn rebuild(&self) -> Result<usize> {
+        match self {
+            Self::Dummy => Ok(0),
+            Self::Mapper { ids, aliases } => {
+                aliases.clear().context("cannot clear id mapper aliases")?;
+
+                let mut count = 0;
+                for entry in ids.iter() {
+                    let (id, alias) = entry?;
+                    aliases.insert(alias, id)?;
+                    count += 1;
+                }
+
+                ids.flush()?;
+
+                Ok(count)
+            }
+        }
+    }
+
+    /// Remove the mappings of ids that are not part of the given
+    /// existing ids.
+    ///
+    /// Returns the number of removed mappings.
+    pub fn retain_ids(&self, existing_ids: &HashSet<String>) -> Result<usize> {
+        match self {
+            Self::Dummy => Ok(0),
+            Self::Mapper { ids, aliases } => {
+                let mut count = 0;
+
+                for entry in ids.iter() {
+                    let (id, alias) = entry?;
+                    let id_str = String::from_utf8_lossy(&id);
+
+                    if !existing_ids.contains(id_str.as_ref()) {
+                        debug!("removing mapping {alias:?} for id {id_str}");
+                        ids.remove(&id)?;
+                        aliases.remove(&alias)?;
+                        count += 1;
+                    }
+                }
+
+                ids.flush()?;
+
+                Ok(count)
             }
         }
     }
 }
+
+/// Represents a mapping between a backend id and its alias.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize)]
+pub struct IdMapping {
+    pub alias: String,
+    pub id: String,
+}
+
+impl Table for IdMapping {
+    fn head() -> Row {
+        Row::new()
+            .cell(Cell::new("ALIAS").bold().underline().white())
+            .cell(Cell::new("ID").shrinkable().bold().underline().white())
+    }
+
+    fn row(&self) -> Row {
+        Row::new()
+            .cell(Cell::new(&self.alias).red())
+            .cell(Cell::new(&self.id).shrinkable().blue())
+    }
+}
+
+/// Represents the list of id mappings.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct IdMappings(Vec<IdMapping>);
+
+impl ops::Deref for IdMappings {
+    type Target = Vec<IdMapping>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl PrintTable for IdMappings {
+    fn print_table(&self, writer: &mut dyn WriteColor, opts: PrintTableOpts) -> Result<()> {
+        writeln!(writer)?;
+        Table::print(writer, self, opts)?;
+        writeln!(writer)?;
+        Ok(())
+    }
+}
+
+/// Open a temporary database, removed once dropped.
+///
+/// Used by the tests of the modules storing data in sled databases.
+#[cfg(test)]
+pub(crate) fn temporary_db() -> Db {
+    Config::new().temporary(true).open().unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn id_mapper() -> IdMapper {
+        IdMapper::from_db(temporary_db()).unwrap()
+    }
+
+    #[test]
+    fn aliases_roundtrip() {
+        let id_mapper = id_mapper();
+
+        let a = id_mapper.get_or_create_alias("id-a").unwrap();
+        let b = id_mapper.get_or_create_alias("id-b").unwrap();
+
+        assert_eq!(id_mapper.get_or_create_alias("id-a").unwrap(), a);
+        assert_eq!(id_mapper.get_id(&a).unwrap(), "id-a");
+        assert_eq!(id_mapper.get_ids([&b, &a]).unwrap(), vec!["id-b", "id-a"]);
+        assert!(id_mapper.get_id("unknown").is_err());
+    }
+
+    #[test]
+    fn migrate_version_1() {
+        let db = temporary_db();
+        db.insert("id-a", "1").unwrap();
+        db.insert("id-b", "2").unwrap();
+
+        let id_mapper = IdMapper::from_db(db).unwrap();
+
+        assert_eq!(id_mapper.get_id(1).unwrap(), "id-a");
+        assert_eq!(id_mapper.get_id(2).unwrap(), "id-b");
+    }
+
+    #[test]
+    fn retain_existing_ids() {
+        let id_mapper = id_mapper();
+
+        let a = id_mapper.get_or_create_alias("id-a").unwrap();
+        let b = id_mapper.get_or_create_alias("id-b").unwrap();
+
+        let existing = HashSet::from_iter([String::from("id-b")]);
+        assert_eq!(id_mapper.retain_ids(&existing).unwrap(), 1);
+
+        assert!(id_mapper.get_id(&a).is_err());
+        assert_eq!(id_mapper.get_id(&b).unwrap(), "id-b");
+        assert_eq!(id_mapper.mappings().unwrap().len(), 1);
+    }
+}
@@ -4,9 +4,12 @@ use std::path::PathBuf;
 
 use crate::{
     account::command::AccountSubcommand,
-    completion::command::CompletionGenerateCommand,
+    cache::command::CacheSubcommand,
+    completion::command::{CompletionCompleteCommand, CompletionGenerateCommand},
     config::{self, TomlConfig},
+    contact::command::ContactSubcommand,
     envelope::command::EnvelopeSubcommand,
+    error,
     flag::command::FlagSubcommand,
     folder::command::FolderSubcommand,
     manual::command::ManualGenerateCommand,
@@ -14,13 +17,19 @@ use crate::{
         attachment::command::AttachmentSubcommand, command::MessageSubcommand,
         template::command::TemplateSubcommand,
     },
+    outbox::command::OutboxSubcommand,
     output::{ColorFmt, OutputFmt},
     printer::Printer,
+    rule::command::RuleSubcommand,
+    schedule::command::ScheduleSubcommand,
+    server::command::ServerCommand,
+    tui::command::TuiCommand,
 };
 
 #[derive(Parser, Debug)]
 #[command(name = "himalaya", author, version, about)]
 #[command(propagate_version = true, infer_subcommands = true)]
+#[command(after_long_help = error::EXIT_CODES_HELP)]
 pub struct Cli {
     #[command(subcommand)]
     pub command: Option<HimalayaCommand>,
@@ -44,10 +53,20 @@ pub struct Cli {
     ///
     /// The possible values are:
     ///
-    ///  - json: output will be in a form of a JSON-compatible object
+    ///  - json: output will be in a form of a JSON-compatible object,
+    ///    errors included
     ///
     ///  - plain: output will be in a form of either a plain text or
     ///    table, depending on the command
+    ///
+    ///  - csv: tables will be in a form of comma-separated values,
+    ///    with a header row
+    ///
+    ///  - tsv: tables will be in a form of tab-separated values,
+    ///    with a header row
+    ///
+    ///  - ndjson: tables will be in a form of newline-delimited JSON,
+    ///    one record per line
     #[arg(long, short, global = true)]
     #[arg(value_name = "FORMAT", value_enum, default_value_t = Default::default())]
     pub output: OutputFmt,
@@ -108,6 +127,31 @@ pub enum HimalayaCommand {
     #[command(alias = "templates", alias = "tpls", alias = "tpl")]
     Template(TemplateSubcommand),
 
+    #[command(subcommand)]
+    Outbox(OutboxSubcommand),
+
+    #[command(subcommand)]
+    #[command(alias = "contacts")]
+    Contact(ContactSubcommand),
+
+    #[command(subcommand)]
+    #[command(alias = "rules")]
+    Rule(RuleSubcommand),
+
+    #[command(subcommand)]
+    #[command(alias = "schedules")]
+    Schedule(ScheduleSubcommand),
+
+    #[command(subcommand)]
+    #[command(alias = "caches")]
+    Cache(CacheSubcommand),
+
+    #[command(alias = "ui")]
+    Tui(TuiCommand),
+
+    #[command()]
+    Server(ServerCommand),
+
     #[command(arg_required_else_help = true)]
     #[command(alias = "manuals", alias = "mans")]
     Manual(ManualGenerateCommand),
@@ -115,6 +159,9 @@ pub enum HimalayaCommand {
     #[command(arg_required_else_help = true)]
     #[command(alias = "completions")]
     Completion(CompletionGenerateCommand),
+
+    #[command(name = "__complete", hide = true)]
+    Complete(CompletionCompleteCommand),
 }
 
 impl HimalayaCommand {
@@ -148,8 +195,34 @@ impl HimalayaCommand {
                 let config = TomlConfig::from_paths_or_default(config_paths).await?;
                 cmd.execute(printer, &config).await
             }
+            Self::Outbox(cmd) => {
+                let config = TomlConfig::from_paths_or_default(config_paths).await?;
+                cmd.execute(printer, &config).await
+            }
+            Self::Contact(cmd) => {
+                let config = TomlConfig::from_paths_or_default(config_paths).await?;
+                cmd.execute(printer, &config).await
+            }
+            Self::Rule(cmd) => {
+                let config = TomlConfig::from_paths_or_default(config_paths).await?;
+                cmd.execute(printer, &config).await
+            }
+            Self::Schedule(cmd) => {
+                let config = TomlConfig::from_paths_or_default(config_paths).await?;
+                cmd.execute(printer, &config).await
+            }
+            Self::Cache(cmd) => {
+                let config = TomlConfig::from_paths_or_default(config_paths).await?;
+                cmd.execute(printer, &config).await
+            }
+            Self::Tui(cmd) => {
+                let config = TomlConfig::from_paths_or_default(config_paths).await?;
+                cmd.execute(printer, &config).await
+            }
+            Self::Server(cmd) => cmd.execute(config_paths).await,
             Self::Manual(cmd) => cmd.execute(printer).await,
             Self::Completion(cmd) => cmd.execute().await,
+            Self::Complete(cmd) => cmd.execute().await,
         }
     }
 }
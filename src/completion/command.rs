@@ -4,7 +4,10 @@ use clap_complete::Shell;
 use log::info;
 use std::io;
 
-use crate::cli::Cli;
+use crate::{
+    cli::Cli,
+    completion::dynamic::{self, DynamicShell},
+};
 
 /// Print completion script for a shell to stdout.
 ///
@@ -16,6 +19,17 @@ pub struct CompletionGenerateCommand {
     /// Shell for which completion script should be generated for.
     #[arg(value_parser = value_parser!(Shell))]
     pub shell: Shell,
+
+    /// Generate a dynamic completion script.
+    ///
+    /// Dynamic completion calls back into himalaya in order to
+    /// complete account names, folder names and aliases, and recent
+    /// envelope ids with their subject. Folders and envelopes are
+    /// only completed from local backends, including the
+    /// synchronization cache, so that completion never asks for
+    /// credentials. Only bash, zsh and fish are supported.
+    #[arg(long, short)]
+    pub dynamic: bool,
 }
 
 impl CompletionGenerateCommand {
@@ -24,7 +38,40 @@ impl CompletionGenerateCommand {
 
         let mut cmd = Cli::command();
         let name = cmd.get_name().to_string();
-        clap_complete::generate(self.shell, &mut cmd, name, &mut io::stdout());
+
+        if self.dynamic {
+            DynamicShell::try_from(self.shell)?.write_registration(&name, &mut io::stdout())?;
+        } else {
+            clap_complete::generate(self.shell, &mut cmd, name, &mut io::stdout());
+        }
+
+        Ok(())
+    }
+}
+
+/// Print completion candidates of a command line to stdout.
+///
+/// This command is called back by dynamic completion scripts, it is
+/// not meant to be used directly.
+#[derive(Debug, Parser)]
+pub struct CompletionCompleteCommand {
+    /// Shell for which candidates should be printed for.
+    #[arg(value_enum)]
+    pub shell: DynamicShell,
+
+    /// The words of the command line, without the binary name. The
+    /// last word is the one being completed.
+    #[arg(last = true, allow_hyphen_values = true)]
+    pub words: Vec<String>,
+}
+
+impl CompletionCompleteCommand {
+    pub async fn execute(self) -> Result<()> {
+        info!("executing complete completion command");
+
+        let candidates = dynamic::complete(Cli::command(), &self.words).await;
+        self.shell
+            .write_candidates(&candidates, &mut io::stdout())?;
 
         Ok(())
     }
@@ -0,0 +1,630 @@
+//! Module dedicated to the dynamic shell completion.
+//!
+//! Static completion scripts generated by clap cannot complete
+//! values only known at runtime. Dynamic completion scripts call
+//! back into himalaya instead, with the words of the command line
+//! being completed. The command line is walked against the clap
+//! command tree in order to know what the word under the cursor
+//! expects, then configured accounts, folders or envelopes are
+//! suggested when relevant.
+//!
+//! Folders and envelopes are only listed from local backends (the
+//! synchronization cache, Maildir or Notmuch): completion runs
+//! behind the shell prompt, where asking for a password or running
+//! an OAuth flow would hang it.
+
+use anyhow::{bail, Result};
+use clap::{Arg, Command, ValueEnum};
+use clap_complete::Shell;
+use email::{
+    backend::feature::BackendFeatureSource, envelope::list::ListEnvelopesOptions, folder::INBOX,
+};
+use log::debug;
+use std::{collections::BTreeMap, io::Write, path::PathBuf};
+
+use crate::{
+    backend::{Backend, BackendKind},
+    config::{self, TomlConfig},
+};
+
+/// The number of envelopes suggested when completing envelope ids.
+const ENVELOPES_PAGE_SIZE: usize = 20;
+
+/// The name of the hidden command called back by dynamic completion
+/// scripts.
+pub const COMPLETE_COMMAND: &str = "__complete";
+
+/// The shells supported by the dynamic completion.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, ValueEnum)]
+pub enum DynamicShell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+impl TryFrom<Shell> for DynamicShell {
+    type Error = anyhow::Error;
+
+    fn try_from(shell: Shell) -> Result<Self> {
+        match shell {
+            Shell::Bash => Ok(Self::Bash),
+            Shell::Zsh => Ok(Self::Zsh),
+            Shell::Fish => Ok(Self::Fish),
+            shell => bail!("dynamic completion is not supported for shell {shell}"),
+        }
+    }
+}
+
+impl DynamicShell {
+    /// Write the script registering the dynamic completion of the
+    /// given binary.
+    pub fn write_registration(&self, bin: &str, buf: &mut dyn Write) -> Result<()> {
+        let func = format!("_{}", bin.replace('-', "_"));
+
+        match self {
+            // bash splits words on COMP_WORDBREAKS, which contains =
+            // and : by default: words are split on blanks only, using
+            // bash-completion when available, then candidates are
+            // trimmed down to the part of the word bash replaces
+            Self::Bash => writeln!(
+                buf,
+                r#"{func}() {{
+    local IFS=$'\n' cur words cword
+
+    if declare -F _get_comp_words_by_ref >/dev/null; then
+        _get_comp_words_by_ref -n =: cur words cword
+    else
+        IFS=$' \t\n' read -ra words <<<"${{COMP_LINE:0:COMP_POINT}}"
+        if [[ ${{COMP_LINE:COMP_POINT-1:1}} == [[:space:]] ]]; then
+            words+=("")
+        fi
+        cword=$((${{#words[@]}} - 1))
+        cur=${{words[cword]}}
+    fi
+
+    COMPREPLY=($("{bin}" {COMPLETE_COMMAND} bash -- "${{words[@]:1:cword}}" 2>/dev/null))
+
+    local prefix=${{cur%"${{COMP_WORDS[COMP_CWORD]}}"}}
+    if [[ -n $prefix ]]; then
+        COMPREPLY=("${{COMPREPLY[@]#"$prefix"}}")
+    fi
+}}
+
+complete -o default -o nosort -F {func} {bin}"#
+            )?,
+            Self::Zsh => writeln!(
+                buf,
+                r#"#compdef {bin}
+
+{func}() {{
+    local -a candidates
+    candidates=(${{(f)"$("{bin}" {COMPLETE_COMMAND} zsh -- "${{(@)words[2,$CURRENT]}}" 2>/dev/null)"}})
+
+    if (( ${{#candidates}} )); then
+        _describe '{bin}' candidates
+    else
+        _files
+    fi
+}}
+
+compdef {func} {bin}"#
+            )?,
+            Self::Fish => writeln!(
+                buf,
+                r#"complete -c {bin} -a '("{bin}" {COMPLETE_COMMAND} fish -- (commandline -opc)[2..-1] (commandline -ct) 2>/dev/null)'"#
+            )?,
+        };
+
+        Ok(())
+    }
+
+    /// Write the given candidates, one per line, in the format
+    /// expected by the completion script of the shell.
+    pub fn write_candidates(&self, candidates: &[Candidate], buf: &mut dyn Write) -> Result<()> {
+        for candidate in candidates {
+            // zsh uses colons to separate values from their help
+            let value = match self {
+                Self::Zsh => candidate.value.replace(':', "\\:"),
+                _ => candidate.value.clone(),
+            };
+
+            match (self, &candidate.help) {
+                (Self::Zsh, Some(help)) => writeln!(buf, "{value}:{help}")?,
+                (Self::Fish, Some(help)) => writeln!(buf, "{value}\t{help}")?,
+                _ => writeln!(buf, "{value}")?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Represents a completion candidate.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Candidate {
+    pub value: String,
+    pub help: Option<String>,
+}
+
+impl Candidate {
+    fn new(value: impl ToString, help: Option<impl ToString>) -> Self {
+        Self {
+            value: value.to_string(),
+            help: help
+                .map(|help| help.to_string())
+                .and_then(|help| help.lines().next().map(ToOwned::to_owned))
+                .filter(|help| !help.is_empty()),
+        }
+    }
+}
+
+/// Represents what the word under the cursor should be completed
+/// with.
+#[derive(Debug)]
+enum Target<'a> {
+    /// The flags of the current command.
+    Flags,
+    /// The subcommands of the current command, followed by the values
+    /// of the given positional argument, if any.
+    Subcommands(Option<&'a Arg>),
+    /// The values of the given option, prefixed by the given string
+    /// (used by --option=value words).
+    Values(&'a Arg, String),
+}
+
+/// Represents the kind of value expected by an argument.
+#[derive(Debug, Eq, PartialEq)]
+enum ValueKind {
+    Account,
+    Folder,
+    Envelope,
+    /// Values known statically by clap.
+    Static,
+}
+
+impl From<&Arg> for ValueKind {
+    fn from(arg: &Arg) -> Self {
+        match arg.get_id().as_str() {
+            "account_name" | "target_account_name" => Self::Account,
+            "folder_name" | "source_folder_name" | "target_folder_name" => Self::Folder,
+            "envelope_id" | "envelope_ids" | "ids_and_flags" => Self::Envelope,
+            _ => Self::Static,
+        }
+    }
+}
+
+/// Represents the command line being completed.
+#[derive(Debug)]
+struct CommandLine<'a> {
+    cmd: &'a Command,
+    target: Target<'a>,
+    /// The beginning of the word under the cursor.
+    prefix: String,
+    account: Option<String>,
+    folder: Option<String>,
+    config_paths: Vec<PathBuf>,
+}
+
+impl<'a> CommandLine<'a> {
+    /// Walk the given words against the given command tree.
+    ///
+    /// The last word is the one under the cursor.
+    fn parse(cmd: &'a Command, words: &[String]) -> Self {
+        let (current, words) = match words.split_last() {
+            Some((current, words)) => (current.as_str(), words),
+            None => ("", words),
+        };
+
+        let mut line = Self {
+            cmd,
+            target: Target::Flags,
+            prefix: String::new(),
+            account: None,
+            folder: None,
+            config_paths: Vec::new(),
+        };
+
+        let mut pending: Option<&Arg> = None;
+        let mut pos = 1;
+        let mut escaped = false;
+
+        for word in words {
+            if let Some(arg) = pending.take() {
+                line.record(arg, word);
+            } else if escaped {
+                line.advance_positional(&mut pos, word);
+            } else if word == "--" {
+                escaped = true;
+            } else if let Some(long) = word.strip_prefix("--") {
+                let (name, value) = match long.split_once('=') {
+                    Some((name, value)) => (name, Some(value)),
+                    None => (long, None),
+                };
+                let arg = line
+                    .cmd
+                    .get_arguments()
+                    .find(|a| a.get_long() == Some(name));
+                match (arg, value) {
+                    (Some(arg), Some(value)) => line.record(arg, value),
+                    (Some(arg), None) if takes_value(arg) => pending = Some(arg),
+                    _ => (),
+                }
+            } else if let Some(short) = word.strip_prefix('-').filter(|s| !s.is_empty()) {
+                let mut chars = short.chars();
+                let c = chars.next();
+                let arg = line.cmd.get_arguments().find(|a| a.get_short() == c);
+                match arg {
+                    Some(arg) if takes_value(arg) && chars.as_str().is_empty() => {
+                        pending = Some(arg)
+                    }
+                    Some(arg) if takes_value(arg) => line.record(arg, chars.as_str()),
+                    _ => (),
+                }
+            } else if let Some(subcmd) = line.cmd.find_subcommand(word) {
+                line.cmd = subcmd;
+                pos = 1;
+            } else {
+                line.advance_positional(&mut pos, word);
+            }
+        }
+
+        line.target = if let Some(arg) = pending {
+            line.prefix = current.to_owned();
+            Target::Values(arg, String::new())
+        } else if !escaped && current.starts_with('-') {
+            let option = current
+                .strip_prefix("--")
+                .and_then(|long| long.split_once('='))
+                .and_then(|(name, value)| {
+                    let arg = line
+                        .cmd
+                        .get_arguments()
+                        .find(|a| a.get_long() == Some(name))?;
+                    Some((arg, name, value))
+                });
+
+            match option {
+                Some((arg, name, value)) => {
+                    line.prefix = value.to_owned();
+                    Target::Values(arg, format!("--{name}="))
+                }
+                None => {
+                    line.prefix = current.to_owned();
+                    Target::Flags
+                }
+            }
+        } else {
+            line.prefix = current.to_owned();
+            Target::Subcommands(positional(line.cmd, pos))
+        };
+
+        line
+    }
+
+    /// Record the value of the given argument, when it gives context
+    /// to the completion.
+    fn record(&mut self, arg: &Arg, value: &str) {
+        match arg.get_id().as_str() {
+            "account_name" => self.account = Some(value.to_owned()),
+            "folder_name" | "source_folder_name" => self.folder = Some(value.to_owned()),
+            "config_paths" => match config::path_parser(value) {
+                Ok(path) => self.config_paths.push(path),
+                Err(err) => debug!("cannot parse config path {value}: {err}"),
+            },
+            _ => (),
+        }
+    }
+
+    /// Record the value of the positional argument at the given
+    /// position, then move to the next position unless the argument
+    /// accepts multiple values.
+    fn advance_positional(&mut self, pos: &mut usize, value: &str) {
+        if let Some(arg) = positional(self.cmd, *pos) {
+            self.record(arg, value);
+
+            if arg.get_index() == Some(*pos) {
+                *pos += 1;
+            }
+        }
+    }
+
+    /// Collect the candidates of the word under the cursor.
+    async fn candidates(&self) -> Vec<Candidate> {
+        let mut candidates = match &self.target {
+            Target::Flags => flags(self.cmd),
+            Target::Subcommands(arg) => {
+                let mut candidates = subcommands(self.cmd);
+                if let Some(arg) = arg {
+                    candidates.extend(self.values(arg).await);
+                }
+                candidates
+            }
+            Target::Values(arg, prefix) => self
+                .values(arg)
+                .await
+                .into_iter()
+                .map(|mut candidate| {
+                    candidate.value = format!("{prefix}{}", candidate.value);
+                    candidate
+                })
+                .collect(),
+        };
+
+        let prefix = match &self.target {
+            Target::Values(_, prefix) => format!("{prefix}{}", self.prefix),
+            _ => self.prefix.clone(),
+        };
+
+        candidates.retain(|candidate| candidate.value.starts_with(&prefix));
+        candidates
+    }
+
+    /// Collect the values of the given argument.
+    ///
+    /// Errors are logged, since completion should never fail.
+    async fn values(&self, arg: &Arg) -> Vec<Candidate> {
+        let kind = ValueKind::from(arg);
+
+        let res = match kind {
+            ValueKind::Account => self.accounts(),
+            ValueKind::Folder => self.folders().await,
+            ValueKind::Envelope => self.envelopes().await,
+            ValueKind::Static => Ok(possible_values(arg)),
+        };
+
+        match res {
+            Ok(candidates) => candidates,
+            Err(err) => {
+                debug!("cannot complete {kind:?} values: {err}");
+                debug!("{err:?}");
+                Vec::new()
+            }
+        }
+    }
+
+    fn config(&self) -> Result<TomlConfig> {
+        TomlConfig::from_existing_paths(&self.config_paths)
+    }
+
+    fn accounts(&self) -> Result<Vec<Candidate>> {
+        let config = self.config()?;
+        let accounts = BTreeMap::from_iter(config.accounts);
+
+        Ok(accounts
+            .into_iter()
+            .map(|(name, config)| Candidate::new(name, Some(config.email)))
+            .collect())
+    }
+
+    async fn folders(&self) -> Result<Vec<Candidate>> {
+        let config = self.config()?;
+        let (toml_account_config, account_config) = config.into_account_configs(
+            self.account.as_deref(),
+            #[cfg(feature = "account-sync")]
+            false,
+        )?;
+
+        let aliases = toml_account_config
+            .folder
+            .as_ref()
+            .and_then(|folder| folder.alias.clone())
+            .unwrap_or_default();
+        let mut candidates: Vec<Candidate> = BTreeMap::from_iter(aliases)
+            .into_iter()
+            .map(|(alias, folder)| Candidate::new(alias, Some(folder)))
+            .collect();
+
+        if !is_local(toml_account_config.list_folders_kind()) {
+            debug!("skipping folders of remote backend");
+            return Ok(candidates);
+        }
+
+        let backend = Backend::new(
+            toml_account_config.clone(),
+            account_config,
+            toml_account_config.list_folders_kind(),
+            |builder| builder.set_list_folders(BackendFeatureSource::Context),
+        )
+        .await?;
+
+        for folder in backend.list_folders().await?.iter() {
+            let desc = Some(&folder.desc).filter(|desc| !desc.is_empty());
+            candidates.push(Candidate::new(&folder.name, desc));
+        }
+
+        Ok(candidates)
+    }
+
+    async fn envelopes(&self) -> Result<Vec<Candidate>> {
+        // qualified ids (account:id) complete envelopes of their
+        // account
+        let (account, qualifier) = match self.prefix.rsplit_once(':') {
+            Some((account, _)) => (Some(account), format!("{account}:")),
+            None => (self.account.as_deref(), String::new()),
+        };
+
+        let config = self.config()?;
+        let (toml_account_config, account_config) = config.into_account_configs(
+            account,
+            #[cfg(feature = "account-sync")]
+            false,
+        )?;
+
+        if !is_local(toml_account_config.list_envelopes_kind()) {
+            debug!("skipping envelopes of remote backend");
+            return Ok(Vec::new());
+        }
+
+        let folder = self.folder.as_deref().unwrap_or(INBOX);
+
+        let backend = Backend::new(
+            toml_account_config.clone(),
+            account_config,
+            toml_account_config.list_envelopes_kind(),
+            |builder| builder.set_list_envelopes(BackendFeatureSource::Context),
+        )
+        .await?;
+
+        let opts = ListEnvelopesOptions {
+            page: 0,
+            page_size: ENVELOPES_PAGE_SIZE,
+            query: None,
+        };
+
+        let envelopes = backend.list_envelopes(folder, opts).await?;
+
+        Ok(envelopes
+            .iter()
+            .map(|envelope| {
+                let id = format!("{qualifier}{}", envelope.id);
+                Candidate::new(id, Some(&envelope.subject))
+            })
+            .collect())
+    }
+}
+
+/// Return true if the given backend is local, and can therefore be
+/// used without authenticating.
+fn is_local(kind: Option<&BackendKind>) -> bool {
+    match kind {
+        #[cfg(feature = "maildir")]
+        Some(BackendKind::Maildir) => true,
+        #[cfg(feature = "account-sync")]
+        Some(BackendKind::MaildirForSync) => true,
+        #[cfg(feature = "notmuch")]
+        Some(BackendKind::Notmuch) => true,
+        _ => false,
+    }
+}
+
+/// Return true if the given argument expects a value.
+fn takes_value(arg: &Arg) -> bool {
+    arg.get_action().takes_values()
+}
+
+/// Find the positional argument at the given position. The last
+/// positional argument takes all remaining positions if it accepts
+/// multiple values.
+fn positional(cmd: &Command, pos: usize) -> Option<&Arg> {
+    let positionals: Vec<&Arg> = cmd
+        .get_positionals()
+        .filter(|arg| !arg.is_hide_set())
+        .collect();
+
+    positionals
+        .iter()
+        .find(|arg| arg.get_index() == Some(pos))
+        .or_else(|| {
+            positionals
+                .last()
+                .filter(|arg| arg.get_index() < Some(pos))
+                .filter(|arg| {
+                    arg.get_num_args()
+                        .map(|range| range.max_values() > 1)
+                        .unwrap_or_default()
+                })
+        })
+        .copied()
+}
+
+fn flags(cmd: &Command) -> Vec<Candidate> {
+    cmd.get_arguments()
+        .filter(|arg| !arg.is_hide_set())
+        .filter_map(|arg| {
+            let long = arg.get_long()?;
+            Some(Candidate::new(format!("--{long}"), arg.get_help()))
+        })
+        .collect()
+}
+
+fn subcommands(cmd: &Command) -> Vec<Candidate> {
+    cmd.get_subcommands()
+        .filter(|cmd| !cmd.is_hide_set())
+        .map(|cmd| Candidate::new(cmd.get_name(), cmd.get_about()))
+        .collect()
+}
+
+fn possible_values(arg: &Arg) -> Vec<Candidate> {
+    arg.get_possible_values()
+        .into_iter()
+        .filter(|value| !value.is_hide_set())
+        .map(|value| Candidate::new(value.get_name(), value.get_help()))
+        .collect()
+}
+
+/// Complete the given words of a command line against the given
+/// command, the last word being the one under the cursor.
+pub async fn complete(mut cmd: Command, words: &[String]) -> Vec<Candidate> {
+    cmd.build();
+    CommandLine::parse(&cmd, words).candidates().await
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::CommandFactory;
+
+    use super::{complete, flags, CommandLine, DynamicShell, Target, ValueKind};
+    use crate::cli::Cli;
+
+    fn words(words: &[&str]) -> Vec<String> {
+        words.iter().map(ToString::to_string).collect()
+    }
+
+    fn values(candidates: Vec<super::Candidate>) -> Vec<String> {
+        candidates.into_iter().map(|c| c.value).collect()
+    }
+
+    #[test]
+    fn parse_command_lines() {
+        let mut cmd = Cli::command();
+        cmd.build();
+
+        let line = CommandLine::parse(&cmd, &words(&["message", "read", "-a", "work", ""]));
+        assert_eq!(line.cmd.get_name(), "read");
+        assert_eq!(line.account.as_deref(), Some("work"));
+        match line.target {
+            Target::Subcommands(Some(arg)) => assert_eq!(ValueKind::from(arg), ValueKind::Envelope),
+            target => panic!("unexpected target {target:?}"),
+        }
+
+        let line = CommandLine::parse(&cmd, &words(&["message", "read", "--folder", "Arc"]));
+        assert_eq!(line.prefix, "Arc");
+        match line.target {
+            Target::Values(arg, _) => assert_eq!(ValueKind::from(arg), ValueKind::Folder),
+            target => panic!("unexpected target {target:?}"),
+        }
+
+        let line = CommandLine::parse(&cmd, &words(&["envelope", "list", "--folder=Sent", "--p"]));
+        assert_eq!(line.folder.as_deref(), Some("Sent"));
+        assert!(matches!(line.target, Target::Flags));
+        assert!(values(flags(line.cmd)).contains(&"--page".to_owned()));
+
+        let line = CommandLine::parse(&cmd, &words(&["account", "sync", "example", ""]));
+        assert_eq!(line.account.as_deref(), Some("example"));
+    }
+
+    #[test]
+    fn bash_registration_joins_word_breaks() {
+        let mut buf = Vec::new();
+        DynamicShell::Bash
+            .write_registration("himalaya", &mut buf)
+            .unwrap();
+        let script = String::from_utf8(buf).unwrap();
+
+        assert!(script.contains("_get_comp_words_by_ref -n =: cur words cword"));
+        assert!(script.contains(r#"COMPREPLY=("${COMPREPLY[@]#"$prefix"}")"#));
+        assert!(script.ends_with("complete -o default -o nosort -F _himalaya himalaya\n"));
+    }
+
+    #[tokio::test]
+    async fn complete_static_values() {
+        let candidates = complete(Cli::command(), &words(&["acc"])).await;
+        assert_eq!(values(candidates), ["account"]);
+
+        let candidates = complete(Cli::command(), &words(&["--output", "n"])).await;
+        assert_eq!(values(candidates), ["ndjson"]);
+
+        let candidates = complete(Cli::command(), &words(&["--output=j"])).await;
+        assert_eq!(values(candidates), ["--output=json"]);
+    }
+}
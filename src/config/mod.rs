@@ -15,7 +15,11 @@ use toml::{self, Value};
 
 #[cfg(feature = "account-sync")]
 use crate::backend::BackendKind;
-use crate::{account::config::TomlAccountConfig, wizard_prompt, wizard_warn};
+use crate::{
+    account::config::TomlAccountConfig,
+    error::{ErrorKind, ErrorKindExt},
+    wizard_prompt, wizard_warn,
+};
 
 /// Represents the user config file.
 #[derive(Clone, Debug, Default, Eq, PartialEq, Deserialize, Serialize)]
@@ -35,6 +39,10 @@ impl TomlConfig {
     /// Returns an error if a configuration file cannot be read or if
     /// a content cannot be parsed.
     fn from_paths(paths: &[PathBuf]) -> Result<Self> {
+        Self::parse_paths(paths).with_kind(ErrorKind::Config)
+    }
+
+    fn parse_paths(paths: &[PathBuf]) -> Result<Self> {
         match paths.len() {
             0 => {
                 // should never happen
@@ -130,6 +138,21 @@ impl TomlConfig {
         }
     }
 
+    /// Read and parse the TOML configuration at the given paths, or
+    /// at the first valid default path if no path is given.
+    ///
+    /// Unlike [`TomlConfig::from_paths_or_default`], the wizard never
+    /// starts: an error is returned if no configuration can be found.
+    pub fn from_existing_paths(paths: &[PathBuf]) -> Result<Self> {
+        match paths.len() {
+            0 => match Self::first_valid_default_path() {
+                Some(path) => Self::from_paths(&[path]),
+                None => bail!("cannot find configuration file"),
+            },
+            _ => Self::from_paths(paths),
+        }
+    }
+
     /// Get the default configuration path.
     ///
     /// Returns an error if the XDG configuration directory cannot be
@@ -181,7 +204,8 @@ impl TomlConfig {
                 .get(name)
                 .map(|account| (name.to_owned(), account.clone()))
                 .ok_or_else(|| anyhow!("cannot find account {name}")),
-        }?;
+        }
+        .with_kind(ErrorKind::NotFound)?;
 
         #[cfg(feature = "imap")]
         if let Some(imap_config) = toml_account_config.imap.as_mut() {
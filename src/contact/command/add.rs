@@ -0,0 +1,47 @@
+use anyhow::Result;
+use clap::Parser;
+use log::info;
+
+use crate::{
+    account::arg::name::AccountNameFlag,
+    config::TomlConfig,
+    contact::{AddressBook, Contact},
+    printer::Printer,
+};
+
+/// Add a contact to the address book.
+///
+/// If the contact already exists, its name is updated.
+#[derive(Debug, Parser)]
+pub struct ContactAddCommand {
+    /// The email address of the contact.
+    #[arg(value_name = "ADDRESS")]
+    pub addr: String,
+
+    /// The name of the contact.
+    #[arg(long, short, value_name = "NAME")]
+    pub name: Option<String>,
+
+    #[command(flatten)]
+    pub account: AccountNameFlag,
+}
+
+impl ContactAddCommand {
+    pub async fn execute(self, printer: &mut impl Printer, config: &TomlConfig) -> Result<()> {
+        info!("executing add contact command");
+
+        let (_, account_config) = config.clone().into_account_configs(
+            self.account.name.as_deref(),
+            #[cfg(feature = "account-sync")]
+            false,
+        )?;
+
+        AddressBook::new(&account_config)?.add(Contact {
+            addr: self.addr,
+            name: self.name,
+            ..Default::default()
+        })?;
+
+        printer.print("Contact successfully added to the address book!")
+    }
+}
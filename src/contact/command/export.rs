@@ -0,0 +1,63 @@
+use anyhow::{Context, Result};
+use clap::Parser;
+use log::info;
+use std::{
+    fs,
+    io::{self, Write},
+    path::PathBuf,
+};
+
+use crate::{
+    account::arg::name::AccountNameFlag,
+    config::TomlConfig,
+    contact::{vcard, AddressBook},
+    printer::Printer,
+};
+
+/// Export contacts to a vCard file.
+///
+/// Contacts are written to the standard output, unless a file is
+/// given.
+#[derive(Debug, Parser)]
+pub struct ContactExportCommand {
+    /// Write the vCards to the given file instead of the standard
+    /// output.
+    #[arg(long, short, value_name = "PATH")]
+    pub file: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub account: AccountNameFlag,
+}
+
+impl ContactExportCommand {
+    pub async fn execute(self, printer: &mut impl Printer, config: &TomlConfig) -> Result<()> {
+        info!("executing export contacts command");
+
+        let (_, account_config) = config.clone().into_account_configs(
+            self.account.name.as_deref(),
+            #[cfg(feature = "account-sync")]
+            false,
+        )?;
+
+        let contacts = AddressBook::new(&account_config)?.list()?;
+        let content = vcard::write(&contacts);
+
+        match self.file {
+            Some(path) => {
+                fs::write(&path, content)
+                    .with_context(|| format!("cannot write vCard file at {path:?}"))?;
+                printer.print(format!(
+                    "{} contact(s) successfully exported to {path:?}!",
+                    contacts.len()
+                ))
+            }
+            None => {
+                let mut stdout = io::stdout().lock();
+                stdout
+                    .write_all(content.as_bytes())
+                    .and_then(|()| stdout.flush())
+                    .context("cannot write vCards to stdout")
+            }
+        }
+    }
+}
@@ -0,0 +1,52 @@
+use anyhow::{Context, Result};
+use clap::Parser;
+use log::info;
+use std::{fs, path::PathBuf};
+
+use crate::{
+    account::arg::name::AccountNameFlag,
+    config::TomlConfig,
+    contact::{vcard, AddressBook},
+    printer::Printer,
+};
+
+/// Import contacts from a vCard file.
+///
+/// Every email address found in the file is added to the address
+/// book, with the name of its vCard. Existing contacts get their name
+/// updated.
+#[derive(Debug, Parser)]
+pub struct ContactImportCommand {
+    /// The path of the vCard file to import.
+    #[arg(value_name = "PATH")]
+    pub path: PathBuf,
+
+    #[command(flatten)]
+    pub account: AccountNameFlag,
+}
+
+impl ContactImportCommand {
+    pub async fn execute(self, printer: &mut impl Printer, config: &TomlConfig) -> Result<()> {
+        info!("executing import contacts command");
+
+        let (_, account_config) = config.clone().into_account_configs(
+            self.account.name.as_deref(),
+            #[cfg(feature = "account-sync")]
+            false,
+        )?;
+
+        let path = &self.path;
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("cannot read vCard file at {path:?}"))?;
+
+        let book = AddressBook::new(&account_config)?;
+        let contacts = vcard::parse(&content);
+        let count = contacts.len();
+
+        for contact in contacts {
+            book.add(contact)?;
+        }
+
+        printer.print(format!("{count} contact(s) successfully imported!"))
+    }
+}
@@ -0,0 +1,54 @@
+use anyhow::Result;
+use clap::Parser;
+use log::info;
+
+use crate::{
+    account::arg::name::AccountNameFlag,
+    config::TomlConfig,
+    contact::{AddressBook, Contacts},
+    printer::{PrintTableOpts, Printer},
+    ui::arg::max_width::TableMaxWidthFlag,
+};
+
+/// List contacts of the address book.
+///
+/// Contacts are ranked by frequency and recency: contacts you often
+/// and recently exchanged messages with come first.
+#[derive(Debug, Parser)]
+pub struct ContactListCommand {
+    /// The maximum number of contacts to list.
+    #[arg(long, short = 'n', value_name = "NUMBER")]
+    pub limit: Option<usize>,
+
+    #[command(flatten)]
+    pub table: TableMaxWidthFlag,
+
+    #[command(flatten)]
+    pub account: AccountNameFlag,
+}
+
+impl ContactListCommand {
+    pub async fn execute(self, printer: &mut impl Printer, config: &TomlConfig) -> Result<()> {
+        info!("executing list contacts command");
+
+        let (_, account_config) = config.clone().into_account_configs(
+            self.account.name.as_deref(),
+            #[cfg(feature = "account-sync")]
+            false,
+        )?;
+
+        let mut contacts = AddressBook::new(&account_config)?.list()?.to_vec();
+
+        if let Some(limit) = self.limit {
+            contacts.truncate(limit);
+        }
+
+        printer.print_table(
+            Box::new(Contacts::from(contacts)),
+            PrintTableOpts {
+                format: &account_config.get_message_read_format(),
+                max_width: self.table.max_width,
+            },
+        )
+    }
+}
@@ -0,0 +1,60 @@
+mod add;
+mod export;
+mod import;
+mod list;
+mod remove;
+mod search;
+
+use anyhow::Result;
+use clap::Subcommand;
+
+use crate::{config::TomlConfig, printer::Printer};
+
+use self::{
+    add::ContactAddCommand, export::ContactExportCommand, import::ContactImportCommand,
+    list::ContactListCommand, remove::ContactRemoveCommand, search::ContactSearchCommand,
+};
+
+/// Manage the address book.
+///
+/// The address book contains contacts collected from the senders and
+/// recipients of listed and synchronized envelopes, ranked by
+/// frequency and recency. Contacts are used to expand short names
+/// found in the To, Cc and Bcc headers of edited messages.
+#[derive(Debug, Subcommand)]
+pub enum ContactSubcommand {
+    #[command(alias = "lst")]
+    List(ContactListCommand),
+
+    #[command(arg_required_else_help = true)]
+    #[command(alias = "find")]
+    Search(ContactSearchCommand),
+
+    #[command(arg_required_else_help = true)]
+    #[command(alias = "create", alias = "new")]
+    Add(ContactAddCommand),
+
+    #[command(arg_required_else_help = true)]
+    #[command(alias = "delete", alias = "rm")]
+    Remove(ContactRemoveCommand),
+
+    #[command(arg_required_else_help = true)]
+    Import(ContactImportCommand),
+
+    #[command()]
+    Export(ContactExportCommand),
+}
+
+impl ContactSubcommand {
+    #[allow(unused)]
+    pub async fn execute(self, printer: &mut impl Printer, config: &TomlConfig) -> Result<()> {
+        match self {
+            Self::List(cmd) => cmd.execute(printer, config).await,
+            Self::Search(cmd) => cmd.execute(printer, config).await,
+            Self::Add(cmd) => cmd.execute(printer, config).await,
+            Self::Remove(cmd) => cmd.execute(printer, config).await,
+            Self::Import(cmd) => cmd.execute(printer, config).await,
+            Self::Export(cmd) => cmd.execute(printer, config).await,
+        }
+    }
+}
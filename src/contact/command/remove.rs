@@ -0,0 +1,43 @@
+use anyhow::{anyhow, Result};
+use clap::Parser;
+use log::info;
+
+use crate::{
+    account::arg::name::AccountNameFlag, config::TomlConfig, contact::AddressBook,
+    error::ErrorKind, printer::Printer,
+};
+
+/// Remove contacts from the address book.
+///
+/// Removed contacts can be collected again from new messages.
+#[derive(Debug, Parser)]
+pub struct ContactRemoveCommand {
+    /// The email addresses of the contacts to remove.
+    #[arg(value_name = "ADDRESS", required = true)]
+    pub addrs: Vec<String>,
+
+    #[command(flatten)]
+    pub account: AccountNameFlag,
+}
+
+impl ContactRemoveCommand {
+    pub async fn execute(self, printer: &mut impl Printer, config: &TomlConfig) -> Result<()> {
+        info!("executing remove contacts command");
+
+        let (_, account_config) = config.clone().into_account_configs(
+            self.account.name.as_deref(),
+            #[cfg(feature = "account-sync")]
+            false,
+        )?;
+
+        let book = AddressBook::new(&account_config)?;
+
+        for addr in &self.addrs {
+            if !book.remove(addr)? {
+                return Err(ErrorKind::NotFound.attach(anyhow!("cannot find contact {addr}")));
+            }
+        }
+
+        printer.print("Contact(s) successfully removed from the address book!")
+    }
+}
@@ -0,0 +1,50 @@
+use anyhow::Result;
+use clap::Parser;
+use log::info;
+
+use crate::{
+    account::arg::name::AccountNameFlag,
+    config::TomlConfig,
+    contact::AddressBook,
+    printer::{PrintTableOpts, Printer},
+    ui::arg::max_width::TableMaxWidthFlag,
+};
+
+/// Search contacts of the address book.
+///
+/// This command lists contacts whose address or name contains the
+/// given query, case-insensitively, best ranked first.
+#[derive(Debug, Parser)]
+pub struct ContactSearchCommand {
+    /// The search query.
+    #[arg(value_name = "QUERY", required = true, trailing_var_arg = true)]
+    pub query: Vec<String>,
+
+    #[command(flatten)]
+    pub table: TableMaxWidthFlag,
+
+    #[command(flatten)]
+    pub account: AccountNameFlag,
+}
+
+impl ContactSearchCommand {
+    pub async fn execute(self, printer: &mut impl Printer, config: &TomlConfig) -> Result<()> {
+        info!("executing search contacts command");
+
+        let (_, account_config) = config.clone().into_account_configs(
+            self.account.name.as_deref(),
+            #[cfg(feature = "account-sync")]
+            false,
+        )?;
+
+        let contacts = AddressBook::new(&account_config)?.search(&self.query.join(" "))?;
+
+        printer.print_table(
+            Box::new(contacts),
+            PrintTableOpts {
+                format: &account_config.get_message_read_format(),
+                max_width: self.table.max_width,
+            },
+        )
+    }
+}
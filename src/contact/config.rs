@@ -0,0 +1,30 @@
+use serde::{Deserialize, Serialize};
+
+/// The address book configuration of an account.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ContactConfig {
+    /// Collect contacts from the senders and recipients of listed
+    /// and synchronized envelopes.
+    ///
+    /// Defaults to false, since collecting contacts writes to the
+    /// address book every time envelopes are listed.
+    pub collect: Option<bool>,
+
+    /// Expand short names found in the To, Cc and Bcc headers of
+    /// edited messages using the address book. Only names matching
+    /// a single contact are expanded.
+    ///
+    /// Defaults to true.
+    pub expand: Option<bool>,
+}
+
+impl ContactConfig {
+    pub fn is_collect_enabled(&self) -> bool {
+        self.collect.unwrap_or(false)
+    }
+
+    pub fn is_expand_enabled(&self) -> bool {
+        self.expand.unwrap_or(true)
+    }
+}
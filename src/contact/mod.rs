@@ -0,0 +1,586 @@
+//! Module dedicated to the address book.
+//!
+//! The address book is a local store of contacts, populated from the
+//! senders and recipients of listed and synchronized envelopes. Every
+//! message is counted once, thanks to its Message-ID. Contacts are
+//! ranked by frecency: the number of messages they appear in, decayed
+//! by the age of the last one.
+
+pub mod command;
+pub mod config;
+pub mod vcard;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local};
+use email::account::config::AccountConfig;
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use sled::{Config, Db, Tree};
+use std::ops;
+
+use crate::{
+    account,
+    envelope::{thread, Mailbox},
+    printer::{PrintTable, PrintTableOpts, WriteColor},
+    ui::{Cell, Row, Table},
+};
+
+/// The number of days after which the score of a contact is halved.
+const SCORE_HALF_LIFE_DAYS: f64 = 30.0;
+
+/// Represents a contact of the address book.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Contact {
+    pub addr: String,
+    pub name: Option<String>,
+    /// The number of messages the contact appeared in.
+    pub count: u64,
+    /// The date of the last message the contact appeared in, as a
+    /// UNIX timestamp.
+    pub last_seen: i64,
+}
+
+impl Contact {
+    /// Compute the frecency score of the contact at the given date.
+    pub fn score(&self, now: i64) -> f64 {
+        let age_days = (now - self.last_seen).max(0) as f64 / 86400.0;
+        self.count.max(1) as f64 * 0.5f64.powf(age_days / SCORE_HALF_LIFE_DAYS)
+    }
+
+    /// Return true if the given query matches the address or the
+    /// name of the contact, case-insensitively.
+    pub fn matches(&self, query: &str) -> bool {
+        let query = query.to_lowercase();
+        self.addr.to_lowercase().contains(&query)
+            || self
+                .name
+                .as_ref()
+                .map(|name| name.to_lowercase().contains(&query))
+                .unwrap_or_default()
+    }
+
+    /// Format the contact as a mailbox, like `Name <addr>`.
+    pub fn to_mailbox_string(&self) -> String {
+        match &self.name {
+            Some(name) if name.contains(|c: char| ",;:<>@\"()[]\\.".contains(c)) => {
+                let name = name.replace('\\', "\\\\").replace('"', "\\\"");
+                format!("\"{name}\" <{}>", self.addr)
+            }
+            Some(name) => format!("{name} <{}>", self.addr),
+            None => self.addr.clone(),
+        }
+    }
+}
+
+impl Table for Contact {
+    fn head() -> Row {
+        Row::new()
+            .cell(Cell::new("ADDRESS").bold().underline().white())
+            .cell(Cell::new("NAME").shrinkable().bold().underline().white())
+            .cell(Cell::new("COUNT").bold().underline().white())
+            .cell(Cell::new("LAST SEEN").bold().underline().white())
+    }
+
+    fn row(&self) -> Row {
+        let last_seen = DateTime::from_timestamp(self.last_seen, 0)
+            .filter(|_| self.last_seen > 0)
+            .map(|date| date.with_timezone(&Local).format("%Y-%m-%d").to_string())
+            .unwrap_or_default();
+
+        Row::new()
+            .cell(Cell::new(&self.addr).blue())
+            .cell(
+                Cell::new(self.name.as_deref().unwrap_or_default())
+                    .shrinkable()
+                    .green(),
+            )
+            .cell(Cell::new(self.count.to_string()).white())
+            .cell(Cell::new(last_seen).yellow())
+    }
+}
+
+/// Represents a list of contacts.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Contacts(Vec<Contact>);
+
+impl From<Vec<Contact>> for Contacts {
+    fn from(contacts: Vec<Contact>) -> Self {
+        Self(contacts)
+    }
+}
+
+impl ops::Deref for Contacts {
+    type Target = Vec<Contact>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl PrintTable for Contacts {
+    fn print_table(&self, writer: &mut dyn WriteColor, opts: PrintTableOpts) -> Result<()> {
+        writeln!(writer)?;
+        Table::print(writer, self, opts)?;
+        writeln!(writer)?;
+        Ok(())
+    }
+}
+
+/// The address book of an account.
+///
+/// Contacts are stored in the default tree, by lowercased address.
+/// The Message-IDs of already counted messages are stored in the
+/// `seen` tree.
+pub struct AddressBook {
+    db: Db,
+    seen: Tree,
+    /// The address of the account, never added to the address book.
+    own_addr: String,
+}
+
+impl AddressBook {
+    pub fn new(account_config: &AccountConfig) -> Result<Self> {
+        let db_path = account::data_dir(".contacts", &account_config.name)?;
+
+        let db = Config::new()
+            .path(&db_path)
+            .open()
+            .with_context(|| format!("cannot open address book at {db_path:?}"))?;
+
+        Self::from_db(db, &account_config.email)
+    }
+
+    fn from_db(db: Db, own_addr: &str) -> Result<Self> {
+        let seen = db
+            .open_tree("seen")
+            .context("cannot open address book seen messages")?;
+        Ok(Self {
+            db,
+            seen,
+            own_addr: own_addr.to_lowercase(),
+        })
+    }
+
+    fn key(addr: &str) -> String {
+        addr.trim().to_lowercase()
+    }
+
+    /// Get the contact matching the given address.
+    pub fn get(&self, addr: &str) -> Result<Option<Contact>> {
+        match self.db.get(Self::key(addr))? {
+            Some(contact) => Ok(Some(serde_json::from_slice(&contact)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn save(&self, contact: &Contact) -> Result<()> {
+        let key = Self::key(&contact.addr);
+        self.db.insert(key, serde_json::to_vec(contact)?)?;
+        Ok(())
+    }
+
+    /// Add the given contact to the address book.
+    ///
+    /// If the contact already exists, its name is replaced by the
+    /// given one and its statistics are kept.
+    pub fn add(&self, mut contact: Contact) -> Result<()> {
+        contact.addr = contact.addr.trim().to_owned();
+
+        if let Some(existing) = self.get(&contact.addr)? {
+            contact.name = contact.name.or(existing.name);
+            contact.count = contact.count.max(existing.count);
+            contact.last_seen = contact.last_seen.max(existing.last_seen);
+        }
+
+        if contact.last_seen == 0 {
+            contact.last_seen = Local::now().timestamp();
+        }
+
+        self.save(&contact)?;
+        self.db.flush()?;
+        Ok(())
+    }
+
+    /// Remove the contact matching the given address.
+    ///
+    /// Returns false if the contact was not found.
+    pub fn remove(&self, addr: &str) -> Result<bool> {
+        let found = self.db.remove(Self::key(addr))?.is_some();
+        self.db.flush()?;
+        Ok(found)
+    }
+
+    /// List all contacts, best ranked first.
+    pub fn list(&self) -> Result<Contacts> {
+        self.search("")
+    }
+
+    /// List contacts matching the given query, best ranked first.
+    pub fn search(&self, query: &str) -> Result<Contacts> {
+        let mut contacts = self
+            .db
+            .iter()
+            .values()
+            .map(|contact| Ok(serde_json::from_slice::<Contact>(&contact?)?))
+            .filter(|contact| match contact {
+                Ok(contact) => contact.matches(query),
+                Err(_) => true,
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let now = Local::now().timestamp();
+        contacts.sort_by(|a, b| b.score(now).total_cmp(&a.score(now)));
+
+        Ok(Contacts(contacts))
+    }
+
+    /// Record the given mailboxes, found in the message matching the
+    /// given Message-ID and date.
+    ///
+    /// Returns false if the message was already recorded.
+    pub fn record<'a>(
+        &self,
+        message_id: &str,
+        timestamp: i64,
+        mailboxes: impl IntoIterator<Item = &'a Mailbox>,
+    ) -> Result<bool> {
+        let message_id = thread::normalize_message_id(message_id);
+
+        if message_id.is_empty() || self.seen.contains_key(&message_id)? {
+            return Ok(false);
+        }
+
+        let mut addrs = Vec::new();
+
+        for mailbox in mailboxes {
+            let key = Self::key(&mailbox.addr);
+            // counts contacts once per message
+            if key.is_empty() || key == self.own_addr || addrs.contains(&key) {
+                continue;
+            }
+
+            let mut contact = self.get(&key)?.unwrap_or_else(|| Contact {
+                addr: mailbox.addr.trim().to_owned(),
+                ..Default::default()
+            });
+
+            contact.count += 1;
+
+            if timestamp >= contact.last_seen {
+                contact.last_seen = timestamp;
+                // keeps the most recent name
+                if let Some(name) = mailbox.name.as_ref().filter(|name| !name.trim().is_empty()) {
+                    contact.name = Some(name.trim().to_owned());
+                }
+            } else if contact.name.is_none() {
+                contact.name = mailbox.name.clone().filter(|name| !name.trim().is_empty());
+            }
+
+            self.save(&contact)?;
+            addrs.push(key);
+        }
+
+        self.seen.insert(message_id, &[] as &[u8])?;
+
+        Ok(true)
+    }
+
+    pub fn flush(&self) -> Result<()> {
+        self.db.flush()?;
+        Ok(())
+    }
+
+    /// Record the senders and recipients of the given envelopes,
+    /// then flush the address book.
+    ///
+    /// Returns the number of messages that were not recorded yet.
+    pub fn record_envelopes<'a>(
+        &self,
+        envelopes: impl IntoIterator<Item = (&'a str, i64, Vec<&'a Mailbox>)>,
+    ) -> Result<usize> {
+        let mut recorded = 0;
+
+        for (message_id, timestamp, mailboxes) in envelopes {
+            if self.record(message_id, timestamp, mailboxes)? {
+                recorded += 1;
+            }
+        }
+
+        self.flush()?;
+        Ok(recorded)
+    }
+
+    /// Find the contact matching the given short name.
+    ///
+    /// The contact whose name matches exactly is returned, otherwise
+    /// the only contact matching the query. Returns [`None`] if no
+    /// contact or several contacts match.
+    pub fn find(&self, query: &str) -> Result<Option<Contact>> {
+        let contacts = self.search(query)?;
+
+        let mut exact = contacts.iter().filter(|contact| {
+            contact
+                .name
+                .as_ref()
+                .map(|name| name.eq_ignore_ascii_case(query))
+                .unwrap_or_default()
+        });
+
+        let contact = match (exact.next(), exact.next()) {
+            (Some(contact), None) => Some(contact),
+            (Some(_), Some(_)) => None,
+            (None, _) if contacts.len() == 1 => contacts.first(),
+            (None, _) => None,
+        };
+
+        Ok(contact.cloned())
+    }
+}
+
+/// Names of the headers whose short names get expanded.
+const ADDRESS_HEADERS: [&str; 3] = ["to", "cc", "bcc"];
+
+/// Expand short names found in the To, Cc and Bcc headers of the
+/// given template using the address book.
+///
+/// Values containing an `@` are kept as they are, as well as short
+/// names matching no contact or several contacts. Returns the
+/// expanded template together with the expanded short names.
+pub fn expand_template(book: &AddressBook, tpl: &str) -> Result<(String, Vec<(String, Contact)>)> {
+    // headers end at the first empty line
+    let mut headers_len = 0;
+    for line in tpl.split_inclusive('\n') {
+        if line.trim_end_matches(['\r', '\n']).is_empty() {
+            break;
+        }
+        headers_len += line.len();
+    }
+    let (headers, body) = tpl.split_at(headers_len);
+
+    let mut lines: Vec<String> = Vec::new();
+
+    for line in headers.lines() {
+        let is_continuation = line.starts_with([' ', '\t']);
+        match lines.last_mut() {
+            Some(last) if is_continuation => {
+                last.push('\n');
+                last.push_str(line);
+            }
+            _ => lines.push(line.to_owned()),
+        }
+    }
+
+    let mut expanded = Vec::new();
+    let mut expansions = Vec::new();
+
+    for header in lines {
+        let (name, value) = match header.split_once(':') {
+            Some((name, value))
+                if ADDRESS_HEADERS.contains(&name.trim().to_lowercase().as_str()) =>
+            {
+                (name, value)
+            }
+            _ => {
+                expanded.push(header);
+                continue;
+            }
+        };
+
+        let mut addrs = Vec::new();
+
+        for addr in split_addresses(value) {
+            let addr = addr.trim();
+            if addr.is_empty() {
+                continue;
+            }
+
+            if addr.contains('@') {
+                addrs.push(addr.to_owned());
+                continue;
+            }
+
+            match book.find(addr)? {
+                Some(contact) => {
+                    debug!("expanding {addr} into {}", contact.addr);
+                    addrs.push(contact.to_mailbox_string());
+                    expansions.push((addr.to_owned(), contact));
+                }
+                None => {
+                    warn!("cannot find a unique contact matching {addr}");
+                    addrs.push(addr.to_owned());
+                }
+            }
+        }
+
+        expanded.push(format!("{name}: {}", addrs.join(", ")));
+    }
+
+    let mut tpl = expanded.join("\n");
+    if headers.ends_with('\n') {
+        tpl.push('\n');
+    }
+    tpl.push_str(body);
+
+    Ok((tpl, expansions))
+}
+
+/// Split the given header value on commas, ignoring commas found in
+/// quoted strings and angle brackets.
+fn split_addresses(value: &str) -> Vec<String> {
+    let mut addrs = Vec::new();
+    let mut addr = String::new();
+    let mut in_quotes = false;
+    let mut in_brackets = false;
+    let mut escaped = false;
+
+    for c in value.chars() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_quotes => escaped = true,
+            '"' => in_quotes = !in_quotes,
+            '<' if !in_quotes => in_brackets = true,
+            '>' if !in_quotes => in_brackets = false,
+            ',' if !in_quotes && !in_brackets => {
+                addrs.push(std::mem::take(&mut addr));
+                continue;
+            }
+            _ => (),
+        }
+
+        // unfolds header values
+        if c == '\n' {
+            continue;
+        }
+
+        addr.push(c);
+    }
+
+    addrs.push(addr);
+    addrs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::temporary_db;
+
+    fn book() -> AddressBook {
+        AddressBook::from_db(temporary_db(), "me@localhost").unwrap()
+    }
+
+    fn mailbox(name: Option<&str>, addr: &str) -> Mailbox {
+        Mailbox {
+            name: name.map(ToOwned::to_owned),
+            addr: addr.to_owned(),
+        }
+    }
+
+    #[test]
+    fn record_contacts() {
+        let book = book();
+        let alice = mailbox(Some("Alice"), "Alice@localhost");
+        let bob = mailbox(None, "bob@localhost");
+        let me = mailbox(Some("Me"), "me@localhost");
+
+        assert!(book.record("<1@localhost>", 100, [&alice, &me]).unwrap());
+        assert!(book
+            .record("<2@localhost>", 200, [&alice, &bob, &alice])
+            .unwrap());
+        // already recorded messages are not counted twice
+        assert!(!book.record("<2@localhost>", 200, [&alice]).unwrap());
+
+        let alice = book.get("alice@localhost").unwrap().unwrap();
+        assert_eq!(alice.count, 2);
+        assert_eq!(alice.last_seen, 200);
+        assert_eq!(alice.name.as_deref(), Some("Alice"));
+
+        assert!(book.get("me@localhost").unwrap().is_none());
+        assert_eq!(book.list().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn rank_contacts() {
+        let now = Local::now().timestamp();
+        let old = Contact {
+            addr: "old@localhost".into(),
+            count: 10,
+            last_seen: now - 365 * 86400,
+            ..Default::default()
+        };
+        let recent = Contact {
+            addr: "recent@localhost".into(),
+            count: 2,
+            last_seen: now,
+            ..Default::default()
+        };
+
+        assert!(recent.score(now) > old.score(now));
+    }
+
+    #[test]
+    fn expand_short_names() {
+        let book = book();
+        book.add(Contact {
+            addr: "alice@localhost".into(),
+            name: Some("Alice Doe".into()),
+            ..Default::default()
+        })
+        .unwrap();
+        book.add(Contact {
+            addr: "bob@localhost".into(),
+            name: Some("Bob, Jr.".into()),
+            ..Default::default()
+        })
+        .unwrap();
+
+        let tpl = concat!(
+            "From: me@localhost\n",
+            "To: alice, carol@localhost\n",
+            "Cc: \"Doe, John\" <john@localhost>,\n",
+            " bob\n",
+            "Subject: alice\n",
+            "\n",
+            "To: alice\n",
+        );
+
+        let (expanded, expansions) = expand_template(&book, tpl).unwrap();
+        assert_eq!(expansions.len(), 2);
+        assert_eq!(
+            expanded,
+            concat!(
+                "From: me@localhost\n",
+                "To: Alice Doe <alice@localhost>, carol@localhost\n",
+                "Cc: \"Doe, John\" <john@localhost>, \"Bob, Jr.\" <bob@localhost>\n",
+                "Subject: alice\n",
+                "\n",
+                "To: alice\n",
+            )
+        );
+    }
+
+    #[test]
+    fn find_unique_contacts() {
+        let book = book();
+        for (addr, name) in [
+            ("alice@localhost", "Alice"),
+            ("alice.doe@localhost", "Alice Doe"),
+            ("bob@localhost", "Bob"),
+        ] {
+            book.add(Contact {
+                addr: addr.into(),
+                name: Some(name.into()),
+                ..Default::default()
+            })
+            .unwrap();
+        }
+
+        // exact names win over partial matches
+        let alice = book.find("alice").unwrap().unwrap();
+        assert_eq!(alice.addr, "alice@localhost");
+        // a unique partial match is enough
+        assert_eq!(book.find("bo").unwrap().unwrap().addr, "bob@localhost");
+        // ambiguous partial matches are not expanded
+        assert!(book.find("ali").unwrap().is_none());
+        assert!(book.find("carol").unwrap().is_none());
+    }
+}
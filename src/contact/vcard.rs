@@ -0,0 +1,183 @@
+//! Module dedicated to vCard import and export.
+//!
+//! Only the properties needed by the address book are supported: the
+//! formatted name (FN) and the email addresses (EMAIL). Both vCard
+//! 3.0 and 4.0 can be read, vCard 3.0 is written.
+
+use super::Contact;
+
+/// Parse contacts from the given vCard content.
+///
+/// A vCard containing multiple email addresses gives one contact per
+/// address, all sharing the same name.
+pub fn parse(content: &str) -> Vec<Contact> {
+    let mut contacts = Vec::new();
+    let mut name = None;
+    let mut addrs = Vec::new();
+
+    for line in unfold(content) {
+        let (prop, value) = match line.split_once(':') {
+            Some((prop, value)) => (prop, value),
+            None => continue,
+        };
+
+        // drops the group and the parameters of the property, like
+        // in item1.EMAIL;TYPE=work
+        let prop = prop.split(';').next().unwrap_or_default();
+        let prop = prop.rsplit('.').next().unwrap_or_default();
+
+        match prop.to_uppercase().as_str() {
+            "BEGIN" => {
+                name = None;
+                addrs.clear();
+            }
+            "FN" => {
+                let value = unescape(value);
+                name = Some(value).filter(|name| !name.trim().is_empty());
+            }
+            "EMAIL" => {
+                let value = unescape(value);
+                let addr = value.trim().trim_start_matches("mailto:");
+                if !addr.is_empty() {
+                    addrs.push(addr.to_owned());
+                }
+            }
+            "END" => {
+                for addr in addrs.drain(..) {
+                    // exported contacts without name use their
+                    // address as name
+                    let name = name.clone().filter(|name| *name != addr);
+                    contacts.push(Contact {
+                        addr,
+                        name,
+                        ..Default::default()
+                    });
+                }
+            }
+            _ => (),
+        }
+    }
+
+    contacts
+}
+
+/// Write the given contacts as vCards, one vCard per contact.
+pub fn write(contacts: &[Contact]) -> String {
+    let mut content = String::new();
+
+    for contact in contacts {
+        let name = contact.name.as_deref().unwrap_or(&contact.addr);
+        content.push_str("BEGIN:VCARD\r\n");
+        content.push_str("VERSION:3.0\r\n");
+        content.push_str(&format!("FN:{}\r\n", escape(name)));
+        content.push_str(&format!(
+            "EMAIL;TYPE=INTERNET:{}\r\n",
+            escape(&contact.addr)
+        ));
+        content.push_str("END:VCARD\r\n");
+    }
+
+    content
+}
+
+/// Unfold content lines: lines starting with a space or a tab
+/// continue the previous line.
+fn unfold(content: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+
+    for line in content.lines() {
+        let line = line.trim_end_matches('\r');
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(continuation), Some(last)) => last.push_str(continuation),
+            _ => lines.push(line.to_owned()),
+        }
+    }
+
+    lines
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace(',', "\\,")
+        .replace(';', "\\;")
+        .replace('\n', "\\n")
+}
+
+fn unescape(value: &str) -> String {
+    let mut unescaped = String::new();
+    let mut chars = value.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('n') | Some('N') => unescaped.push('\n'),
+                Some(c) => unescaped.push(c),
+                None => unescaped.push('\\'),
+            },
+            c => unescaped.push(c),
+        }
+    }
+
+    unescaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_vcards() {
+        let content = concat!(
+            "BEGIN:VCARD\r\n",
+            "VERSION:4.0\r\n",
+            "FN:Doe\\, John\r\n",
+            "EMAIL;TYPE=work:john@work.example\r\n",
+            "item1.EMAIL:john@home.ex\r\n",
+            " ample\r\n",
+            "END:VCARD\r\n",
+            "BEGIN:VCARD\r\n",
+            "VERSION:3.0\r\n",
+            "EMAIL:alice@localhost\r\n",
+            "END:VCARD\r\n",
+        );
+
+        let contacts = parse(content);
+        let contacts: Vec<_> = contacts
+            .iter()
+            .map(|c| (c.name.as_deref(), c.addr.as_str()))
+            .collect();
+
+        assert_eq!(
+            contacts,
+            [
+                (Some("Doe, John"), "john@work.example"),
+                (Some("Doe, John"), "john@home.example"),
+                (None, "alice@localhost"),
+            ]
+        );
+    }
+
+    #[test]
+    fn write_then_parse_vcards() {
+        let contacts = vec![
+            Contact {
+                addr: "john@localhost".into(),
+                name: Some("Doe; John".into()),
+                ..Default::default()
+            },
+            Contact {
+                addr: "alice@localhost".into(),
+                ..Default::default()
+            },
+        ];
+
+        let parsed = parse(&write(&contacts));
+
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(parsed[0].name.as_deref(), Some("Doe; John"));
+        assert_eq!(parsed[0].addr, "john@localhost");
+        assert_eq!(parsed[1].name, None);
+        assert_eq!(parsed[1].addr, "alice@localhost");
+    }
+}
@@ -1,17 +1,155 @@
+use anyhow::{bail, Result};
 use clap::Parser;
+use std::{fmt, str::FromStr};
+
+/// Represents an envelope id.
+///
+/// The id can be qualified by the name of its account, like
+/// `work:42`, as shown when listing envelopes of multiple accounts.
+/// The qualifying account must match the account flag, if any.
+#[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd)]
+pub struct EnvelopeId {
+    pub account: Option<String>,
+    pub id: usize,
+}
+
+impl EnvelopeId {
+    /// Return the qualifying account of the id, or the given one.
+    ///
+    /// Returns an error if the id is qualified by another account
+    /// than the given one.
+    pub fn account_or<'a>(&'a self, account: Option<&'a str>) -> Result<Option<&'a str>> {
+        account_or([self], account)
+    }
+}
+
+impl FromStr for EnvelopeId {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (account, id) = match s.rsplit_once(':') {
+            Some((account, id)) => (Some(account.to_owned()), id),
+            None => (None, s),
+        };
+
+        let id = id
+            .parse()
+            .map_err(|err| format!("cannot parse envelope id {s}: {err}"))?;
+
+        Ok(Self { account, id })
+    }
+}
+
+impl fmt::Display for EnvelopeId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.account {
+            Some(account) => write!(f, "{account}:{}", self.id),
+            None => write!(f, "{}", self.id),
+        }
+    }
+}
+
+/// Return the account qualifying all the given ids, or the given
+/// account if none of them is qualified.
+///
+/// Returns an error if ids are qualified by different accounts, or by
+/// another account than the given one.
+pub fn account_or<'a>(
+    ids: impl IntoIterator<Item = &'a EnvelopeId>,
+    account: Option<&'a str>,
+) -> Result<Option<&'a str>> {
+    let mut qualified = None;
+
+    for id in ids {
+        match (qualified, id.account.as_deref()) {
+            (Some(a), Some(b)) if a != b => {
+                bail!("cannot use envelope ids from different accounts: {a} and {b}")
+            }
+            (_, Some(b)) => qualified = Some(b),
+            _ => (),
+        }
+    }
+
+    match (qualified, account) {
+        (Some(a), Some(b)) if a != b => {
+            bail!("cannot use envelope ids from account {a} with account {b}")
+        }
+        (Some(a), _) => Ok(Some(a)),
+        (None, b) => Ok(b),
+    }
+}
 
 /// The envelope id argument parser.
 #[derive(Debug, Parser)]
 pub struct EnvelopeIdArg {
     /// The envelope id.
-    #[arg(value_name = "ID", required = true)]
-    pub id: usize,
+    #[arg(name = "envelope_id", value_name = "ID", required = true)]
+    pub id: EnvelopeId,
 }
 
 /// The envelopes ids arguments parser.
 #[derive(Debug, Parser)]
 pub struct EnvelopeIdsArgs {
     /// The list of envelopes ids.
-    #[arg(value_name = "ID", required = true)]
-    pub ids: Vec<usize>,
+    #[arg(name = "envelope_ids", value_name = "ID", required = true)]
+    pub ids: Vec<EnvelopeId>,
+}
+
+impl EnvelopeIdsArgs {
+    /// Return the ids, without their qualifying account.
+    pub fn ids(&self) -> Vec<usize> {
+        self.ids.iter().map(|id| id.id).collect()
+    }
+
+    /// Return the account qualifying the ids, or the given one.
+    pub fn account_or<'a>(&'a self, account: Option<&'a str>) -> Result<Option<&'a str>> {
+        account_or(&self.ids, account)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_id() {
+        let id: EnvelopeId = "42".parse().unwrap();
+        assert_eq!(id.account, None);
+        assert_eq!(id.id, 42);
+        assert_eq!(id.account_or(Some("perso")).unwrap(), Some("perso"));
+
+        let id: EnvelopeId = "work:42".parse().unwrap();
+        assert_eq!(id.account.as_deref(), Some("work"));
+        assert_eq!(id.id, 42);
+        assert_eq!(id.account_or(Some("work")).unwrap(), Some("work"));
+        assert_eq!(id.account_or(None).unwrap(), Some("work"));
+        assert!(id.account_or(Some("perso")).is_err());
+        assert_eq!(id.to_string(), "work:42");
+
+        assert!("work:".parse::<EnvelopeId>().is_err());
+        assert!("abc".parse::<EnvelopeId>().is_err());
+    }
+
+    #[test]
+    fn resolve_account() {
+        let ids: Vec<EnvelopeId> = ["1", "work:2", "work:3"]
+            .into_iter()
+            .map(|id| id.parse().unwrap())
+            .collect();
+        assert_eq!(account_or(&ids, None).unwrap(), Some("work"));
+        assert_eq!(account_or(&ids, Some("work")).unwrap(), Some("work"));
+        assert!(account_or(&ids, Some("perso")).is_err());
+
+        let ids: Vec<EnvelopeId> = ["1", "2"]
+            .into_iter()
+            .map(|id| id.parse().unwrap())
+            .collect();
+        assert_eq!(account_or(&ids, None).unwrap(), None);
+
+        let ids: Vec<EnvelopeId> = ["work:1", "perso:2"]
+            .into_iter()
+            .map(|id| id.parse().unwrap())
+            .collect();
+        assert!(account_or(&ids, None).is_err());
+    }
 }
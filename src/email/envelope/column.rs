@@ -0,0 +1,608 @@
+//! Module dedicated to envelope table columns.
+//!
+//! Columns of the envelope table can be chosen, ordered, sized and
+//! colored using the `envelope.list.columns` option or the
+//! `--columns` flag. A column is described by a string of the form
+//! `NAME[:width=WIDTH][:color=COLOR]`, for example
+//! `subject:width=50:color=green` or `date:color=208`.
+
+use anyhow::{anyhow, bail, Error, Result};
+use mail_parser::{HeaderValue, MessageParser};
+use serde::{Deserialize, Serialize};
+use std::{fmt, ops, str::FromStr};
+use termcolor::Color;
+
+use crate::{
+    envelope::{Envelope, Mailbox},
+    flag::Flag,
+    printer::{PrintTableOpts, WriteColor},
+    ui::{print_rows, template::Template, Cell, Row},
+};
+
+/// Represents the kind of data displayed by a column.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum EnvelopeColumnKind {
+    Id,
+    Flags,
+    Subject,
+    From,
+    To,
+    Cc,
+    Date,
+    Size,
+    Attachment,
+    Account,
+    Folder,
+    /// The value of the given message header.
+    Header(String),
+}
+
+impl EnvelopeColumnKind {
+    /// Return true if the column needs data that only the message
+    /// carries, in which case messages need to be peeked.
+    pub fn needs_message(&self) -> bool {
+        matches!(
+            self,
+            Self::Cc | Self::Size | Self::Attachment | Self::Header(_)
+        )
+    }
+
+    /// Return true if the column needs the body of the message, and
+    /// not only its headers.
+    pub fn needs_body(&self) -> bool {
+        matches!(self, Self::Attachment)
+    }
+}
+
+impl FromStr for EnvelopeColumnKind {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let lowercased = s.trim().to_lowercase();
+
+        let kind = match lowercased.as_str() {
+            "id" => Self::Id,
+            "flags" => Self::Flags,
+            "subject" => Self::Subject,
+            "from" => Self::From,
+            "to" => Self::To,
+            "cc" => Self::Cc,
+            "date" => Self::Date,
+            "size" => Self::Size,
+            "attachment" => Self::Attachment,
+            "account" => Self::Account,
+            "folder" => Self::Folder,
+            _ => match s.trim().split_once('.') {
+                Some((prefix, name))
+                    if prefix.eq_ignore_ascii_case("header") && !name.is_empty() =>
+                {
+                    Self::Header(name.to_owned())
+                }
+                _ => bail!("invalid envelope column {s}"),
+            },
+        };
+
+        Ok(kind)
+    }
+}
+
+impl fmt::Display for EnvelopeColumnKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Id => write!(f, "id"),
+            Self::Flags => write!(f, "flags"),
+            Self::Subject => write!(f, "subject"),
+            Self::From => write!(f, "from"),
+            Self::To => write!(f, "to"),
+            Self::Cc => write!(f, "cc"),
+            Self::Date => write!(f, "date"),
+            Self::Size => write!(f, "size"),
+            Self::Attachment => write!(f, "attachment"),
+            Self::Account => write!(f, "account"),
+            Self::Folder => write!(f, "folder"),
+            Self::Header(name) => write!(f, "header.{name}"),
+        }
+    }
+}
+
+/// Represents a column of the envelope table.
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct EnvelopeColumn {
+    pub kind: EnvelopeColumnKind,
+    /// The maximum width of the column.
+    pub width: Option<usize>,
+    /// The color of the column, as a color name or an ANSI 256 code.
+    pub color: Option<String>,
+}
+
+impl EnvelopeColumn {
+    pub fn new(kind: EnvelopeColumnKind) -> Self {
+        Self {
+            kind,
+            width: None,
+            color: None,
+        }
+    }
+
+    fn head(&self) -> Cell {
+        let title = match &self.kind {
+            EnvelopeColumnKind::Header(name) => name.to_uppercase(),
+            kind => kind.to_string().to_uppercase(),
+        };
+
+        let cell = Cell::new(title).bold().underline().white();
+
+        match self.kind {
+            EnvelopeColumnKind::Subject if self.width.is_none() => cell.shrinkable(),
+            _ => cell,
+        }
+    }
+
+    fn cell(&self, envelope: &Envelope) -> Cell {
+        let unseen = !envelope.flags.contains(&Flag::Seen);
+
+        let cell = match &self.kind {
+            EnvelopeColumnKind::Id => Cell::new(&envelope.id).red(),
+            EnvelopeColumnKind::Flags => Cell::new(format_flags(envelope)).white(),
+            EnvelopeColumnKind::Subject => {
+                let cell = Cell::new(&envelope.subject).green();
+                if self.width.is_none() {
+                    cell.shrinkable()
+                } else {
+                    cell
+                }
+            }
+            EnvelopeColumnKind::From => Cell::new(format_mailbox(&envelope.from)).blue(),
+            EnvelopeColumnKind::To => Cell::new(format_mailbox(&envelope.to)).blue(),
+            EnvelopeColumnKind::Cc => {
+                let cc: Vec<_> = envelope.cc.iter().map(format_mailbox).collect();
+                Cell::new(cc.join(", ")).blue()
+            }
+            EnvelopeColumnKind::Date => Cell::new(&envelope.date).yellow(),
+            EnvelopeColumnKind::Size => {
+                Cell::new(envelope.size.map(format_size).unwrap_or_default()).yellow()
+            }
+            EnvelopeColumnKind::Attachment => {
+                let attachment = if envelope.has_attachment.unwrap_or_default() {
+                    "📎"
+                } else {
+                    " "
+                };
+                Cell::new(attachment).white()
+            }
+            EnvelopeColumnKind::Account => {
+                Cell::new(envelope.account.as_deref().unwrap_or_default()).magenta()
+            }
+            EnvelopeColumnKind::Folder => {
+                Cell::new(envelope.folder.as_deref().unwrap_or_default()).magenta()
+            }
+            EnvelopeColumnKind::Header(name) => {
+                let val = envelope
+                    .headers
+                    .iter()
+                    .find(|(key, _)| key.eq_ignore_ascii_case(name))
+                    .map(|(_, val)| val.as_str());
+                Cell::new(val.unwrap_or_default()).white()
+            }
+        };
+
+        let cell = match self.width {
+            Some(width) => cell.max_width(width),
+            None => cell,
+        };
+
+        let cell = match self.color.as_deref().map(Color::from_str) {
+            Some(Ok(color)) => cell.fg(color),
+            _ => cell,
+        };
+
+        cell.bold_if(unseen)
+    }
+}
+
+impl FromStr for EnvelopeColumn {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut parts = s.split(':');
+
+        let kind = parts.next().unwrap_or_default().parse()?;
+        let mut column = Self::new(kind);
+
+        for part in parts.map(str::trim).filter(|part| !part.is_empty()) {
+            let (key, val) = part
+                .split_once('=')
+                .map(|(key, val)| (key.trim(), val.trim()))
+                .ok_or_else(|| {
+                    anyhow!("invalid option {part} for column {s}, expected KEY=VALUE")
+                })?;
+
+            match key.to_lowercase().as_str() {
+                "width" if column.width.is_none() => {
+                    let width = val
+                        .parse()
+                        .map_err(|err| anyhow!("invalid width {val} for column {s}: {err}"))?;
+                    column.width = Some(width);
+                }
+                "color" if column.color.is_none() => {
+                    Color::from_str(val)
+                        .map_err(|err| anyhow!("invalid color {val} for column {s}: {err}"))?;
+                    column.color = Some(val.to_owned());
+                }
+                "width" | "color" => bail!("duplicate option {key} for column {s}"),
+                _ => bail!("invalid option {key} for column {s}, expected width or color"),
+            }
+        }
+
+        Ok(column)
+    }
+}
+
+impl TryFrom<String> for EnvelopeColumn {
+    type Error = Error;
+
+    fn try_from(s: String) -> Result<Self> {
+        s.parse()
+    }
+}
+
+impl fmt::Display for EnvelopeColumn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.kind)?;
+
+        if let Some(width) = self.width {
+            write!(f, ":width={width}")?;
+        }
+
+        if let Some(color) = &self.color {
+            write!(f, ":color={color}")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl From<EnvelopeColumn> for String {
+    fn from(column: EnvelopeColumn) -> Self {
+        column.to_string()
+    }
+}
+
+/// Represents the columns of the envelope table.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct EnvelopeColumns(Vec<EnvelopeColumn>);
+
+impl Default for EnvelopeColumns {
+    fn default() -> Self {
+        Self(vec![
+            EnvelopeColumn::new(EnvelopeColumnKind::Id),
+            EnvelopeColumn::new(EnvelopeColumnKind::Flags),
+            EnvelopeColumn::new(EnvelopeColumnKind::Subject),
+            EnvelopeColumn::new(EnvelopeColumnKind::From),
+            EnvelopeColumn::new(EnvelopeColumnKind::Date),
+        ])
+    }
+}
+
+impl From<Vec<EnvelopeColumn>> for EnvelopeColumns {
+    fn from(columns: Vec<EnvelopeColumn>) -> Self {
+        if columns.is_empty() {
+            Self::default()
+        } else {
+            Self(columns)
+        }
+    }
+}
+
+impl ops::Deref for EnvelopeColumns {
+    type Target = Vec<EnvelopeColumn>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl EnvelopeColumns {
+    /// Build the columns holding the data needed by the fields of
+    /// the given template.
+    ///
+    /// Columns are not displayed when using a template, but they
+    /// determine which envelope fields are filled.
+    pub fn from_template(template: &Template) -> Self {
+        let mut columns: Vec<EnvelopeColumn> = Vec::new();
+
+        for path in template.fields() {
+            let kind = match path {
+                [name, ..] if name == "cc" => EnvelopeColumnKind::Cc,
+                [name, ..] if name == "size" => EnvelopeColumnKind::Size,
+                [name, ..] if name == "has_attachment" => EnvelopeColumnKind::Attachment,
+                [name, ..] if name == "folder" => EnvelopeColumnKind::Folder,
+                [name, header, ..] if name == "headers" => {
+                    EnvelopeColumnKind::Header(header.to_owned())
+                }
+                _ => continue,
+            };
+
+            if !columns.iter().any(|column| column.kind == kind) {
+                columns.push(EnvelopeColumn::new(kind));
+            }
+        }
+
+        Self::from(columns)
+    }
+
+    /// Prepend the account column, unless it is already there.
+    pub fn with_account(mut self) -> Self {
+        if !self.has(&EnvelopeColumnKind::Account) {
+            let column = EnvelopeColumn::new(EnvelopeColumnKind::Account);
+            self.0.insert(0, column);
+        }
+
+        self
+    }
+
+    pub fn has(&self, kind: &EnvelopeColumnKind) -> bool {
+        self.iter().any(|column| column.kind == *kind)
+    }
+
+    /// Return true if at least one column needs messages to be
+    /// peeked.
+    pub fn needs_message(&self) -> bool {
+        self.iter().any(|column| column.kind.needs_message())
+    }
+
+    pub fn head(&self) -> Row {
+        Row(self.iter().map(EnvelopeColumn::head).collect())
+    }
+
+    pub fn row(&self, envelope: &Envelope) -> Row {
+        Row(self.iter().map(|column| column.cell(envelope)).collect())
+    }
+
+    /// Write the given envelopes as a table.
+    pub fn print(
+        &self,
+        writer: &mut dyn WriteColor,
+        envelopes: &[Envelope],
+        opts: PrintTableOpts,
+    ) -> Result<()> {
+        let mut rows = vec![self.head()];
+        rows.extend(envelopes.iter().map(|envelope| self.row(envelope)));
+        print_rows(writer, rows, opts)
+    }
+
+    /// Fill the envelope with the data of the given raw message
+    /// needed by the columns.
+    ///
+    /// Only headers are parsed, unless a column needs the body.
+    pub fn fill(&self, envelope: &mut Envelope, raw: &[u8]) {
+        let parser = MessageParser::new();
+        let msg = if self.iter().any(|column| column.kind.needs_body()) {
+            parser.parse(raw)
+        } else {
+            parser.parse_headers(raw)
+        };
+
+        let msg = match msg {
+            Some(msg) => msg,
+            None => return,
+        };
+
+        for column in self.iter() {
+            match &column.kind {
+                EnvelopeColumnKind::Cc => {
+                    envelope.cc = msg
+                        .cc()
+                        .map(|cc| {
+                            cc.iter()
+                                .filter_map(|addr| {
+                                    Some(Mailbox {
+                                        name: addr.name.as_ref().map(ToString::to_string),
+                                        addr: addr.address.as_ref()?.to_string(),
+                                    })
+                                })
+                                .collect()
+                        })
+                        .unwrap_or_default();
+                }
+                EnvelopeColumnKind::Size => {
+                    envelope.size = Some(raw.len());
+                }
+                EnvelopeColumnKind::Attachment => {
+                    envelope.has_attachment = Some(msg.attachment_count() > 0);
+                }
+                EnvelopeColumnKind::Header(name) => {
+                    let val = msg
+                        .headers()
+                        .iter()
+                        .find(|header| header.name.as_str().eq_ignore_ascii_case(name))
+                        .and_then(|header| format_header_value(&header.value))
+                        .unwrap_or_default();
+                    envelope.headers.insert(name.to_owned(), val);
+                }
+                _ => (),
+            }
+        }
+    }
+}
+
+fn format_flags(envelope: &Envelope) -> String {
+    let mut flags = String::new();
+    flags.push_str(if envelope.flags.contains(&Flag::Seen) {
+        " "
+    } else {
+        "✷"
+    });
+    flags.push_str(if envelope.flags.contains(&Flag::Answered) {
+        "↵"
+    } else {
+        " "
+    });
+    flags.push_str(if envelope.flags.contains(&Flag::Flagged) {
+        "⚑"
+    } else {
+        " "
+    });
+    flags
+}
+
+fn format_mailbox(mailbox: &Mailbox) -> &str {
+    match &mailbox.name {
+        Some(name) => name,
+        None => &mailbox.addr,
+    }
+}
+
+fn format_header_value(val: &HeaderValue) -> Option<String> {
+    if let Some(text) = val.as_text() {
+        return Some(text.to_owned());
+    }
+
+    if let Some(list) = val.as_text_list() {
+        return Some(list.join(", "));
+    }
+
+    if let Some(addr) = val.as_address() {
+        let addrs: Vec<_> = addr
+            .iter()
+            .filter_map(|addr| {
+                let name = addr.name.as_deref();
+                let email = addr.address.as_deref();
+                name.or(email).map(ToOwned::to_owned)
+            })
+            .collect();
+        return Some(addrs.join(", "));
+    }
+
+    val.as_datetime().map(|date| date.to_rfc3339())
+}
+
+/// Format the given size in bytes in a human-readable way.
+pub fn format_size(size: usize) -> String {
+    const UNITS: [&str; 4] = ["K", "M", "G", "T"];
+
+    if size < 1024 {
+        return format!("{size}B");
+    }
+
+    let mut size = size as f64 / 1024.0;
+    let mut unit = UNITS[0];
+
+    for next in &UNITS[1..] {
+        if size < 1024.0 {
+            break;
+        }
+        size /= 1024.0;
+        unit = next;
+    }
+
+    if size < 10.0 {
+        format!("{size:.1}{unit}")
+    } else {
+        format!("{size:.0}{unit}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_columns() {
+        let column: EnvelopeColumn = "subject".parse().unwrap();
+        assert_eq!(column, EnvelopeColumn::new(EnvelopeColumnKind::Subject));
+
+        let column: EnvelopeColumn = "FROM:width=20".parse().unwrap();
+        assert_eq!(column.kind, EnvelopeColumnKind::From);
+        assert_eq!(column.width, Some(20));
+        assert_eq!(column.color, None);
+
+        let column: EnvelopeColumn = "header.X-Mailer:width=15:color=cyan".parse().unwrap();
+        assert_eq!(
+            column.kind,
+            EnvelopeColumnKind::Header("X-Mailer".to_owned())
+        );
+        assert_eq!(column.width, Some(15));
+        assert_eq!(column.color.as_deref(), Some("cyan"));
+        assert_eq!(column.to_string(), "header.X-Mailer:width=15:color=cyan");
+
+        let column: EnvelopeColumn = "date:color=208".parse().unwrap();
+        assert_eq!(column.width, None);
+        assert_eq!(column.color.as_deref(), Some("208"));
+
+        let column: EnvelopeColumn = "date:color=yellow:width=0".parse().unwrap();
+        assert_eq!(column.width, Some(0));
+        assert_eq!(column.color.as_deref(), Some("yellow"));
+
+        assert!("unknown".parse::<EnvelopeColumn>().is_err());
+        assert!("header.".parse::<EnvelopeColumn>().is_err());
+        assert!("subject:20".parse::<EnvelopeColumn>().is_err());
+        assert!("subject:width=wide".parse::<EnvelopeColumn>().is_err());
+        assert!("subject:color=notacolor".parse::<EnvelopeColumn>().is_err());
+        assert!("subject:color=red:color=blue"
+            .parse::<EnvelopeColumn>()
+            .is_err());
+        assert!("subject:size=10".parse::<EnvelopeColumn>().is_err());
+    }
+
+    #[test]
+    fn columns_with_account() {
+        let columns = EnvelopeColumns::default().with_account();
+        assert_eq!(columns[0].kind, EnvelopeColumnKind::Account);
+        assert_eq!(columns.len(), 6);
+
+        let columns = columns.with_account();
+        assert_eq!(columns.len(), 6);
+    }
+
+    #[test]
+    fn fill_from_message() {
+        let raw = concat!(
+            "From: alice@localhost\r\n",
+            "Cc: Bob <bob@localhost>, carol@localhost\r\n",
+            "X-Mailer: himalaya\r\n",
+            "Subject: columns\r\n",
+            "\r\n",
+            "Hello!\r\n",
+        );
+
+        let columns = EnvelopeColumns::from(vec![
+            "cc".parse().unwrap(),
+            "size".parse().unwrap(),
+            "attachment".parse().unwrap(),
+            "header.x-mailer".parse().unwrap(),
+        ]);
+        assert!(columns.needs_message());
+
+        let mut envelope = Envelope::default();
+        columns.fill(&mut envelope, raw.as_bytes());
+
+        assert_eq!(envelope.cc.len(), 2);
+        assert_eq!(envelope.cc[0].name.as_deref(), Some("Bob"));
+        assert_eq!(envelope.cc[1].addr, "carol@localhost");
+        assert_eq!(envelope.size, Some(raw.len()));
+        assert_eq!(envelope.has_attachment, Some(false));
+        assert_eq!(
+            envelope.headers.get("x-mailer").map(String::as_str),
+            Some("himalaya")
+        );
+
+        let columns = EnvelopeColumns::from(vec!["cc".parse().unwrap(), "size".parse().unwrap()]);
+
+        let mut envelope = Envelope::default();
+        columns.fill(&mut envelope, raw.as_bytes());
+
+        assert_eq!(envelope.cc.len(), 2);
+        assert_eq!(envelope.size, Some(raw.len()));
+        assert_eq!(envelope.has_attachment, None);
+    }
+
+    #[test]
+    fn format_sizes() {
+        assert_eq!(format_size(512), "512B");
+        assert_eq!(format_size(2048), "2.0K");
+        assert_eq!(format_size(20 * 1024), "20K");
+        assert_eq!(format_size(3 * 1024 * 1024 + 512 * 1024), "3.5M");
+    }
+}
@@ -1,24 +1,36 @@
-use anyhow::Result;
+use anyhow::{anyhow, bail, Context, Result};
 use ariadne::{Color, Label, Report, ReportKind, Source};
 use clap::Parser;
 use email::{
     backend::feature::BackendFeatureSource, envelope::list::ListEnvelopesOptions,
     search_query::SearchEmailsQuery,
 };
-use log::info;
-use std::process::exit;
+use log::{debug, info, warn};
+use tokio::task::JoinSet;
 
 #[cfg(feature = "account-sync")]
 use crate::cache::arg::disable::CacheDisableFlag;
 use crate::{
-    account::arg::name::AccountNameFlag,
+    account::{arg::name::AccountNameFlag, config::TomlAccountConfig},
     backend::Backend,
     config::TomlConfig,
+    envelope::{
+        column::{EnvelopeColumn, EnvelopeColumns},
+        AccountEnvelopes, EnvelopeSorter, Envelopes,
+    },
+    error::{ErrorKind, ErrorKindExt},
     folder::arg::name::FolderNameOptionalFlag,
     printer::{PrintTableOpts, Printer},
-    ui::arg::max_width::TableMaxWidthFlag,
+    ui::{
+        arg::max_width::TableMaxWidthFlag,
+        template::{arg::format::TemplateFormatFlag, print_table_or_template, Template},
+    },
 };
 
+/// The number of envelopes printed at once when streaming NDJSON
+/// records.
+const NDJSON_CHUNK_SIZE: usize = 50;
+
 /// List all envelopes.
 ///
 /// This command allows you to list all envelopes included in the
@@ -44,6 +56,20 @@ pub struct ListEnvelopesCommand {
     #[command(flatten)]
     pub table: TableMaxWidthFlag,
 
+    /// The columns of the envelopes table.
+    ///
+    /// Columns are given as a comma-separated list of
+    /// NAME[:width=WIDTH][:color=COLOR], where NAME is one of id,
+    /// flags, subject, from, to, cc, date, size, attachment, account,
+    /// folder or header.<NAME> for a custom header, and COLOR is a
+    /// color name or an ANSI 256 code. Overrides the
+    /// envelope.list.columns option.
+    #[arg(long, value_name = "COLUMN", value_delimiter = ',')]
+    pub columns: Vec<EnvelopeColumn>,
+
+    #[command(flatten)]
+    pub format: TemplateFormatFlag,
+
     #[cfg(feature = "account-sync")]
     #[command(flatten)]
     pub cache: CacheDisableFlag,
@@ -51,6 +77,17 @@ pub struct ListEnvelopesCommand {
     #[command(flatten)]
     pub account: AccountNameFlag,
 
+    /// List envelopes of all accounts.
+    ///
+    /// Envelopes of all accounts are listed concurrently, then merged
+    /// by date (most recent first). Envelope ids are qualified by the
+    /// name of their account (like work:42), so that they can be used
+    /// by other commands. Envelopes of a subset of accounts can be
+    /// listed by giving a comma-separated list of accounts to the
+    /// account flag instead, like -a work,perso.
+    #[arg(long, short = 'A', conflicts_with = "account_name")]
+    pub all_accounts: bool,
+
     /// The list envelopes filter and sort query.
     ///
     /// The query can be a filter query, a sort query or both
@@ -129,9 +166,12 @@ impl Default for ListEnvelopesCommand {
             page: 1,
             page_size: Default::default(),
             table: Default::default(),
+            columns: Default::default(),
+            format: Default::default(),
             #[cfg(feature = "account-sync")]
             cache: Default::default(),
             account: Default::default(),
+            all_accounts: Default::default(),
             query: Default::default(),
         }
     }
@@ -141,8 +181,57 @@ impl ListEnvelopesCommand {
     pub async fn execute(self, printer: &mut impl Printer, config: &TomlConfig) -> Result<()> {
         info!("executing list envelopes command");
 
+        let accounts = if self.all_accounts {
+            let mut accounts: Vec<_> = config.accounts.keys().cloned().collect();
+            accounts.sort();
+            accounts
+        } else {
+            self.account
+                .name
+                .iter()
+                .flat_map(|names| names.split(','))
+                .map(|name| name.trim().to_owned())
+                .filter(|name| !name.is_empty())
+                .collect()
+        };
+
+        let query = self.query.as_ref().map(|query| query.join(" "));
+        let parsed_query = match query.as_deref().map(str::parse::<SearchEmailsQuery>) {
+            None => None,
+            Some(Ok(query)) => Some(query),
+            Some(Err(main_err)) => {
+                let source = "query";
+                let email::search_query::parser::Error::ParseError(errs, query) = &main_err;
+
+                // the location of errors is only shown to humans
+                if !printer.is_machine_readable() {
+                    for err in errs {
+                        Report::build(ReportKind::Error, source, err.span().start)
+                            .with_message(main_err.to_string())
+                            .with_label(
+                                Label::new((source, err.span().into_range()))
+                                    .with_message(err.reason().to_string())
+                                    .with_color(Color::Red),
+                            )
+                            .finish()
+                            .eprint((source, Source::from(&query)))
+                            .unwrap();
+                    }
+                }
+
+                let reasons: Vec<_> = errs.iter().map(|err| err.reason().to_string()).collect();
+                return Err(anyhow!(reasons.join(", ")))
+                    .context(main_err.to_string())
+                    .with_kind(ErrorKind::InvalidQuery);
+            }
+        };
+
+        if self.all_accounts || accounts.len() > 1 {
+            return self.execute_many(printer, config, accounts, query).await;
+        }
+
         let (toml_account_config, account_config) = config.clone().into_account_configs(
-            self.account.name.as_deref(),
+            accounts.first().map(String::as_str),
             #[cfg(feature = "account-sync")]
             self.cache.disable,
         )?;
@@ -163,48 +252,219 @@ impl ListEnvelopesCommand {
         )
         .await?;
 
-        let query = self
-            .query
-            .map(|query| query.join(" ").parse::<SearchEmailsQuery>());
-        let query = match query {
-            None => None,
-            Some(Ok(query)) => Some(query),
-            Some(Err(main_err)) => {
-                let source = "query";
-                let email::search_query::parser::Error::ParseError(errs, query) = &main_err;
-                for err in errs {
-                    Report::build(ReportKind::Error, source, err.span().start)
-                        .with_message(main_err.to_string())
-                        .with_label(
-                            Label::new((source, err.span().into_range()))
-                                .with_message(err.reason().to_string())
-                                .with_color(Color::Red),
-                        )
-                        .finish()
-                        .eprint((source, Source::from(&query)))
-                        .unwrap();
-                }
-
-                exit(0)
-            }
-        };
-
         let opts = ListEnvelopesOptions {
             page,
             page_size,
-            query,
+            query: parsed_query,
         };
 
-        let envelopes = backend.list_envelopes(folder, opts).await?;
+        let template = self.template_or(Some(&toml_account_config));
+        let columns = self.columns_or(Some(&toml_account_config), template.as_ref());
+        let template = template.map(|template| {
+            template.with_date_fmt(account_config.get_envelope_list_datetime_fmt())
+        });
+        let format = account_config.get_message_read_format();
+
+        // NDJSON records do not need a header, so envelopes are
+        // printed as soon as they are filled
+        if printer.is_ndjson() {
+            return backend
+                .list_envelopes_with_columns_by_chunk(
+                    folder,
+                    opts,
+                    &columns,
+                    NDJSON_CHUNK_SIZE,
+                    |envelopes| {
+                        print_table_or_template(
+                            printer,
+                            Envelopes::from(envelopes).with_columns(columns.clone()),
+                            template.clone(),
+                            PrintTableOpts {
+                                format: &format,
+                                max_width: self.table.max_width,
+                            },
+                        )
+                    },
+                )
+                .await;
+        }
+
+        let envelopes = backend
+            .list_envelopes_with_columns(folder, opts, columns)
+            .await?;
 
-        printer.print_table(
-            Box::new(envelopes),
+        print_table_or_template(
+            printer,
+            envelopes,
+            template,
             PrintTableOpts {
-                format: &account_config.get_message_read_format(),
+                format: &format,
                 max_width: self.table.max_width,
             },
-        )?;
+        )
+    }
+
+    /// Get the output template from the command line, or from the
+    /// given account configuration.
+    fn template_or(&self, toml_account_config: Option<&TomlAccountConfig>) -> Option<Template> {
+        self.format.template.clone().or_else(|| {
+            toml_account_config
+                .and_then(|config| config.envelope.as_ref())
+                .and_then(|envelope| envelope.list.as_ref())
+                .and_then(|list| list.format.clone())
+        })
+    }
+
+    /// Get the table columns from the command line, or from the
+    /// given account configuration.
+    ///
+    /// When a template is used, columns are deduced from its fields.
+    fn columns_or(
+        &self,
+        toml_account_config: Option<&TomlAccountConfig>,
+        template: Option<&Template>,
+    ) -> EnvelopeColumns {
+        if let Some(template) = template {
+            return EnvelopeColumns::from_template(template);
+        }
+
+        if !self.columns.is_empty() {
+            return EnvelopeColumns::from(self.columns.clone());
+        }
+
+        toml_account_config
+            .and_then(|config| config.envelope.as_ref())
+            .and_then(|envelope| envelope.list.as_ref())
+            .and_then(|list| list.columns.clone())
+            .map(EnvelopeColumns::from)
+            .unwrap_or_default()
+    }
+
+    /// List envelopes of the given accounts concurrently, then merge
+    /// them using the sort order of the query.
+    ///
+    /// Accounts that cannot be listed are reported, the others are
+    /// still printed.
+    async fn execute_many(
+        self,
+        printer: &mut impl Printer,
+        config: &TomlConfig,
+        accounts: Vec<String>,
+        query: Option<String>,
+    ) -> Result<()> {
+        let page = 1.max(self.page) - 1;
+        let mut tasks = JoinSet::new();
+
+        // all accounts share the same table, so columns and template
+        // are taken from the first account
+        let first_account = accounts.first().and_then(|name| config.accounts.get(name));
+        let mut template = self.template_or(first_account);
+        let columns = self.columns_or(first_account, template.as_ref());
+
+        for account in accounts {
+            let config = config.clone();
+            let folder = self.folder.name.clone();
+            let query = query.clone();
+            let page_size = self.page_size;
+            let columns = columns.clone();
+            #[cfg(feature = "account-sync")]
+            let disable_cache = self.cache.disable;
+
+            let name = account.clone();
+            let res = async move {
+                let (toml_account_config, account_config) = config.into_account_configs(
+                    Some(&account),
+                    #[cfg(feature = "account-sync")]
+                    disable_cache,
+                )?;
 
-        Ok(())
+                let page_size =
+                    page_size.unwrap_or_else(|| account_config.get_envelope_list_page_size());
+
+                let backend = Backend::new(
+                    toml_account_config.clone(),
+                    account_config.clone(),
+                    toml_account_config.list_envelopes_kind(),
+                    |builder| builder.set_list_envelopes(BackendFeatureSource::Context),
+                )
+                .await?;
+
+                let query = match query {
+                    Some(query) => Some(
+                        query
+                            .parse::<SearchEmailsQuery>()
+                            .map_err(|err| anyhow!("cannot parse query: {err}"))
+                            .with_kind(ErrorKind::InvalidQuery)?,
+                    ),
+                    None => None,
+                };
+
+                // the first pages of every account are needed to
+                // build the requested page of the merged list
+                let opts = ListEnvelopesOptions {
+                    page: 0,
+                    page_size: page_size * (page + 1),
+                    query,
+                };
+
+                let envelopes = backend
+                    .list_envelopes_with_columns(&folder, opts, columns)
+                    .await?
+                    .iter()
+                    .map(|envelope| (account.clone(), envelope.clone()))
+                    .collect::<Vec<_>>();
+
+                Result::<_>::Ok((account_config, page_size, envelopes))
+            };
+
+            tasks.spawn(async move {
+                res.await
+                    .with_context(|| format!("cannot list envelopes of account {name}"))
+            });
+        }
+
+        let mut format = None;
+        let mut max_page_size = 0;
+        let mut envelopes = Vec::new();
+        let mut errors = Vec::new();
+
+        while let Some(res) = tasks.join_next().await {
+            // a failing account does not prevent the others from
+            // being listed
+            let (account_config, page_size, account_envelopes) = match res? {
+                Ok(res) => res,
+                Err(err) => {
+                    warn!("{err}");
+                    debug!("{err:?}");
+                    errors.push(err);
+                    continue;
+                }
+            };
+            let date_fmt = account_config.get_envelope_list_datetime_fmt();
+            template = template.map(|template| template.with_date_fmt(date_fmt));
+            format.get_or_insert_with(|| account_config.get_message_read_format());
+            max_page_size = max_page_size.max(page_size);
+            envelopes.extend(account_envelopes);
+        }
+
+        let format = match format {
+            Some(format) => format,
+            None if errors.is_empty() => bail!("cannot find any account"),
+            None => return Err(errors.remove(0)),
+        };
+        let page_size = self.page_size.unwrap_or(max_page_size);
+        let sorters = EnvelopeSorter::from_query(query.as_deref());
+        let envelopes =
+            AccountEnvelopes::merge(envelopes, &sorters, page, page_size).with_columns(columns);
+
+        print_table_or_template(
+            printer,
+            envelopes,
+            template,
+            PrintTableOpts {
+                format: &format,
+                max_width: self.table.max_width,
+            },
+        )
     }
 }
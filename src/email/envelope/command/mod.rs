@@ -1,4 +1,7 @@
 pub mod list;
+#[cfg(feature = "account-sync")]
+pub mod search;
+pub mod thread;
 pub mod watch;
 
 use anyhow::Result;
@@ -6,7 +9,11 @@ use clap::Subcommand;
 
 use crate::{config::TomlConfig, printer::Printer};
 
-use self::{list::ListEnvelopesCommand, watch::WatchEnvelopesCommand};
+#[cfg(feature = "account-sync")]
+use self::search::SearchEnvelopesCommand;
+use self::{
+    list::ListEnvelopesCommand, thread::ThreadEnvelopesCommand, watch::WatchEnvelopesCommand,
+};
 
 /// Manage envelopes.
 ///
@@ -19,6 +26,13 @@ pub enum EnvelopeSubcommand {
     #[command(alias = "lst")]
     List(ListEnvelopesCommand),
 
+    #[command(alias = "threads")]
+    Thread(ThreadEnvelopesCommand),
+
+    #[cfg(feature = "account-sync")]
+    #[command(arg_required_else_help = true)]
+    Search(SearchEnvelopesCommand),
+
     #[command()]
     Watch(WatchEnvelopesCommand),
 }
@@ -28,6 +42,9 @@ impl EnvelopeSubcommand {
     pub async fn execute(self, printer: &mut impl Printer, config: &TomlConfig) -> Result<()> {
         match self {
             Self::List(cmd) => cmd.execute(printer, config).await,
+            Self::Thread(cmd) => cmd.execute(printer, config).await,
+            #[cfg(feature = "account-sync")]
+            Self::Search(cmd) => cmd.execute(printer, config).await,
             Self::Watch(cmd) => cmd.execute(printer, config).await,
         }
     }
@@ -0,0 +1,118 @@
+use anyhow::{bail, Result};
+use clap::Parser;
+use log::info;
+
+use crate::{
+    account::arg::name::AccountNameFlag,
+    cache::{arg::disable::CacheDisableFlag, IdMapper},
+    config::TomlConfig,
+    envelope::Envelopes,
+    folder::arg::name::FolderNameOptionalFlag,
+    index::SearchIndex,
+    printer::{PrintTableOpts, Printer},
+    ui::arg::max_width::TableMaxWidthFlag,
+};
+
+/// Search envelopes using the local full-text index.
+///
+/// This command allows you to search envelopes of the given folder
+/// using the local search index. The index is built and updated by
+/// the account sync command, which means it is only available for
+/// accounts having the synchronization enabled. Subjects, senders,
+/// recipients, text bodies and attachment file names are indexed.
+/// Results are ranked by relevance.
+#[derive(Debug, Parser)]
+pub struct SearchEnvelopesCommand {
+    #[command(flatten)]
+    pub folder: FolderNameOptionalFlag,
+
+    /// The page number.
+    ///
+    /// The page number starts from 1 (which is the default). Giving a
+    /// page number to big will result in a out of bound error.
+    #[arg(long, short, value_name = "NUMBER", default_value = "1")]
+    pub page: usize,
+
+    /// The page size.
+    ///
+    /// Determine the amount of envelopes a page should contain.
+    #[arg(long, short = 's', value_name = "NUMBER")]
+    pub page_size: Option<usize>,
+
+    #[command(flatten)]
+    pub table: TableMaxWidthFlag,
+
+    #[command(flatten)]
+    pub cache: CacheDisableFlag,
+
+    #[command(flatten)]
+    pub account: AccountNameFlag,
+
+    /// The full-text search query.
+    ///
+    /// The query is composed of terms separated by spaces. Only
+    /// envelopes matching all terms are returned. A term ending by
+    /// "*" matches all terms starting with it, for example "invoic*"
+    /// matches both "invoice" and "invoicing".
+    #[arg(value_name = "TERM", required = true)]
+    #[arg(allow_hyphen_values = true, trailing_var_arg = true)]
+    pub query: Vec<String>,
+}
+
+impl SearchEnvelopesCommand {
+    pub async fn execute(self, printer: &mut impl Printer, config: &TomlConfig) -> Result<()> {
+        info!("executing search envelopes command");
+
+        let (toml_account_config, account_config) = config
+            .clone()
+            .into_account_configs(self.account.name.as_deref(), self.cache.disable)?;
+
+        if !matches!(
+            toml_account_config.sync.as_ref().and_then(|c| c.enable),
+            Some(true)
+        ) {
+            let account = &account_config.name;
+            bail!("cannot search envelopes: synchronization is not enabled for account {account}");
+        }
+
+        if self.cache.disable {
+            bail!("cannot search envelopes: the search index is built from the synchronization cache, which is disabled");
+        }
+
+        let folder = &self.folder.name;
+        let page = 1.max(self.page) - 1;
+        let page_size = self
+            .page_size
+            .unwrap_or_else(|| account_config.get_envelope_list_page_size());
+
+        let index = SearchIndex::new(&account_config)?;
+        let id_mapper = IdMapper::new(&account_config, folder)?;
+
+        // the index stores folders by name, not by alias
+        let hits = index.search(
+            &account_config.get_folder_alias(folder),
+            &self.query.join(" "),
+        )?;
+        let hits = match page_size {
+            0 => hits,
+            n => hits.into_iter().skip(page * n).take(n).collect(),
+        };
+
+        let envelopes = hits
+            .into_iter()
+            .map(|hit| {
+                let mut envelope = hit.envelope;
+                envelope.id = id_mapper.get_or_create_alias(&hit.id)?;
+                Ok(envelope)
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        printer.print_table(
+            Box::new(Envelopes::from(envelopes)),
+            PrintTableOpts {
+                format: &account_config.get_message_read_format(),
+                max_width: self.table.max_width,
+            },
+        )
+    }
+}
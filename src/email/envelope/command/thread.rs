@@ -0,0 +1,101 @@
+use anyhow::Result;
+use clap::Parser;
+use email::{backend::feature::BackendFeatureSource, envelope::list::ListEnvelopesOptions};
+use log::info;
+
+#[cfg(feature = "account-sync")]
+use crate::cache::arg::disable::CacheDisableFlag;
+use crate::{
+    account::arg::name::AccountNameFlag,
+    backend::Backend,
+    config::TomlConfig,
+    folder::arg::name::FolderNameOptionalFlag,
+    printer::{PrintTableOpts, Printer},
+    ui::arg::max_width::TableMaxWidthFlag,
+};
+
+/// List envelopes grouped by conversations.
+///
+/// This command allows you to list envelopes of the given folder as
+/// threads. Envelopes are grouped using the Message-ID, In-Reply-To
+/// and References headers of their messages, then displayed as an
+/// indented tree (or as nested replies in JSON). Only envelopes of
+/// the given page are threaded together.
+#[derive(Debug, Parser)]
+pub struct ThreadEnvelopesCommand {
+    #[command(flatten)]
+    pub folder: FolderNameOptionalFlag,
+
+    /// The page number.
+    ///
+    /// The page number starts from 1 (which is the default). Giving a
+    /// page number to big will result in a out of bound error.
+    #[arg(long, short, value_name = "NUMBER", default_value = "1")]
+    pub page: usize,
+
+    /// The page size.
+    ///
+    /// Determine the amount of envelopes a page should contain.
+    #[arg(long, short = 's', value_name = "NUMBER")]
+    pub page_size: Option<usize>,
+
+    #[command(flatten)]
+    pub table: TableMaxWidthFlag,
+
+    #[cfg(feature = "account-sync")]
+    #[command(flatten)]
+    pub cache: CacheDisableFlag,
+
+    #[command(flatten)]
+    pub account: AccountNameFlag,
+}
+
+impl ThreadEnvelopesCommand {
+    pub async fn execute(self, printer: &mut impl Printer, config: &TomlConfig) -> Result<()> {
+        info!("executing thread envelopes command");
+
+        let (toml_account_config, account_config) = config.clone().into_account_configs(
+            self.account.name.as_deref(),
+            #[cfg(feature = "account-sync")]
+            self.cache.disable,
+        )?;
+
+        let folder = &self.folder.name;
+        let page = 1.max(self.page) - 1;
+        let page_size = self
+            .page_size
+            .unwrap_or_else(|| account_config.get_envelope_list_page_size());
+
+        let list_envelopes_kind = toml_account_config.list_envelopes_kind();
+        let peek_messages_kind = toml_account_config.peek_messages_kind();
+
+        let backend = Backend::new(
+            toml_account_config.clone(),
+            account_config.clone(),
+            list_envelopes_kind.into_iter().chain(peek_messages_kind),
+            |builder| {
+                builder.set_list_envelopes(BackendFeatureSource::Context);
+                builder.set_peek_messages(BackendFeatureSource::Context);
+            },
+        )
+        .await?;
+
+        let opts = ListEnvelopesOptions {
+            page,
+            page_size,
+            query: None,
+        };
+
+        let threads = backend.thread_envelopes(folder, opts).await?;
+
+        printer.print_table(
+            Box::new(threads),
+            PrintTableOpts {
+                format: &account_config.get_message_read_format(),
+                max_width: self.table.max_width,
+            },
+        )?;
+
+        Ok(())
+    }
+}
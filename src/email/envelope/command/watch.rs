@@ -7,13 +7,14 @@ use log::info;
 use crate::cache::arg::disable::CacheDisableFlag;
 use crate::{
     account::arg::name::AccountNameFlag, backend::Backend, config::TomlConfig,
-    folder::arg::name::FolderNameOptionalFlag, printer::Printer,
+    folder::arg::name::FolderNameOptionalFlag, printer::Printer, rule,
 };
 
 /// Watch envelopes for changes.
 ///
 /// This command allows you to watch a folder and execute hooks when
-/// changes occur on envelopes.
+/// changes occur on envelopes. If enabled in the configuration,
+/// rules are also applied to new envelopes.
 #[derive(Debug, Parser)]
 pub struct WatchEnvelopesCommand {
     #[command(flatten)]
@@ -38,11 +39,26 @@ impl WatchEnvelopesCommand {
             self.cache.disable,
         )?;
 
+        let rules = toml_account_config
+            .rules
+            .as_ref()
+            .filter(|config| config.is_apply_on_watch_enabled());
+
+        // rules are applied to envelopes reported by the received
+        // hook of the watch
+        let (watch_account_config, received) = match rules {
+            Some(_) => {
+                let (account_config, received) = rule::with_received_hook(&account_config);
+                (account_config, Some(received))
+            }
+            None => (account_config.clone(), None),
+        };
+
         let watch_envelopes_kind = toml_account_config.watch_envelopes_kind();
 
         let backend = Backend::new(
             toml_account_config.clone(),
-            account_config,
+            watch_account_config,
             watch_envelopes_kind,
             |builder| builder.set_watch_envelopes(BackendFeatureSource::Context),
         )
@@ -52,6 +68,17 @@ impl WatchEnvelopesCommand {
             "Start watching folder {folder} for envelopes changes…"
         ))?;
 
-        backend.watch_envelopes(folder).await
+        match rules.zip(received) {
+            Some((rules, received)) => {
+                let rules_backend =
+                    rule::build_backend(toml_account_config.clone(), account_config).await?;
+
+                tokio::select! {
+                    res = backend.watch_envelopes(folder) => res,
+                    res = rule::watch(&rules_backend, &rules.rules, folder, received) => res,
+                }
+            }
+            None => backend.watch_envelopes(folder).await,
+        }
     }
 }
@@ -3,7 +3,7 @@ use email::envelope::sync::config::EnvelopeSyncConfig;
 use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
 
-use crate::backend::BackendKind;
+use crate::{backend::BackendKind, envelope::column::EnvelopeColumn, ui::template::Template};
 
 #[derive(Clone, Debug, Default, Eq, PartialEq, Deserialize, Serialize)]
 pub struct EnvelopeConfig {
@@ -38,6 +38,17 @@ impl EnvelopeConfig {
 pub struct ListEnvelopesConfig {
     pub backend: Option<BackendKind>,
 
+    /// The columns of the envelopes table, in order.
+    ///
+    /// Each column is defined as `NAME[:WIDTH][:COLOR]`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub columns: Option<Vec<EnvelopeColumn>>,
+
+    /// The template used to render each envelope instead of the
+    /// table.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub format: Option<Template>,
+
     #[serde(flatten)]
     pub remote: email::envelope::list::config::EnvelopeListConfig,
 }
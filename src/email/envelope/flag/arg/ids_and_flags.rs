@@ -1,32 +1,39 @@
+use anyhow::Result;
 use clap::Parser;
 use email::flag::{Flag, Flags};
 use log::debug;
 
+use crate::envelope::arg::ids::{self, EnvelopeId};
+
 /// The ids and/or flags arguments parser.
 #[derive(Debug, Parser)]
 pub struct IdsAndFlagsArgs {
     /// The list of ids and/or flags.
     ///
-    /// Every argument that can be parsed as an integer is considered
-    /// an id, otherwise it is considered as a flag.
+    /// Every argument that can be parsed as an integer (optionally
+    /// qualified by an account, like work:42) is considered an id,
+    /// otherwise it is considered as a flag.
     #[arg(value_name = "ID-OR-FLAG", required = true)]
     pub ids_and_flags: Vec<IdOrFlag>,
 }
 
 #[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd)]
 pub enum IdOrFlag {
-    Id(usize),
+    Id(EnvelopeId),
     Flag(Flag),
 }
 
 impl From<&str> for IdOrFlag {
     fn from(value: &str) -> Self {
-        value.parse::<usize>().map(Self::Id).unwrap_or_else(|err| {
-            let flag = Flag::from(value);
-            debug!("cannot parse {value} as usize, parsing it as flag {flag}");
-            debug!("{err:?}");
-            Self::Flag(flag)
-        })
+        value
+            .parse::<EnvelopeId>()
+            .map(Self::Id)
+            .unwrap_or_else(|err| {
+                let flag = Flag::from(value);
+                debug!("cannot parse {value} as envelope id, parsing it as flag {flag}");
+                debug!("{err:?}");
+                Self::Flag(flag)
+            })
     }
 }
 
@@ -36,7 +43,7 @@ pub fn into_tuple(ids_and_flags: &[IdOrFlag]) -> (Vec<usize>, Flags) {
         |(mut ids, mut flags), arg| {
             match arg {
                 IdOrFlag::Id(id) => {
-                    ids.push(*id);
+                    ids.push(id.id);
                 }
                 IdOrFlag::Flag(flag) => {
                     flags.insert(flag.to_owned());
@@ -46,3 +53,16 @@ pub fn into_tuple(ids_and_flags: &[IdOrFlag]) -> (Vec<usize>, Flags) {
         },
     )
 }
+
+/// Return the account qualifying the ids, or the given one.
+pub fn account_or<'a>(
+    ids_and_flags: &'a [IdOrFlag],
+    account: Option<&'a str>,
+) -> Result<Option<&'a str>> {
+    let ids = ids_and_flags.iter().filter_map(|arg| match arg {
+        IdOrFlag::Id(id) => Some(id),
+        IdOrFlag::Flag(_) => None,
+    });
+
+    ids::account_or(ids, account)
+}
@@ -9,7 +9,7 @@ use crate::{
     account::arg::name::AccountNameFlag,
     backend::Backend,
     config::TomlConfig,
-    flag::arg::ids_and_flags::{into_tuple, IdsAndFlagsArgs},
+    flag::arg::ids_and_flags::{account_or, into_tuple, IdsAndFlagsArgs},
     folder::arg::name::FolderNameOptionalFlag,
     printer::Printer,
 };
@@ -40,8 +40,9 @@ impl FlagAddCommand {
 
         let folder = &self.folder.name;
         let (ids, flags) = into_tuple(&self.args.ids_and_flags);
+        let account = account_or(&self.args.ids_and_flags, self.account.name.as_deref())?;
         let (toml_account_config, account_config) = config.clone().into_account_configs(
-            self.account.name.as_deref(),
+            account,
             #[cfg(feature = "account-sync")]
             self.cache.disable,
         )?;
@@ -2,11 +2,11 @@ pub mod arg;
 pub mod command;
 pub mod config;
 
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use std::{collections::HashSet, ops};
 
 /// Represents the flag variants.
-#[derive(Clone, Debug, Eq, Hash, PartialEq, Ord, PartialOrd, Serialize)]
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Ord, PartialOrd, Serialize, Deserialize)]
 pub enum Flag {
     Seen,
     Answered,
@@ -30,7 +30,7 @@ impl From<&email::flag::Flag> for Flag {
     }
 }
 
-#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize)]
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
 pub struct Flags(pub HashSet<Flag>);
 
 impl ops::Deref for Flags {
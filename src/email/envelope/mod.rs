@@ -1,84 +1,117 @@
 pub mod arg;
+pub mod column;
 pub mod command;
 pub mod config;
 pub mod flag;
+pub mod thread;
 
 use anyhow::Result;
 use email::account::config::AccountConfig;
-use serde::Serialize;
-use std::ops;
+use serde::{Deserialize, Serialize};
+use std::{cmp::Ordering, collections::BTreeMap, ops};
 
 use crate::{
     cache::IdMapper,
-    flag::{Flag, Flags},
+    flag::Flags,
     printer::{PrintTable, PrintTableOpts, WriteColor},
-    ui::{Cell, Row, Table},
+    ui::{Row, Table},
 };
 
-#[derive(Clone, Debug, Default, Serialize)]
+use self::column::EnvelopeColumns;
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct Mailbox {
     pub name: Option<String>,
     pub addr: String,
 }
 
-#[derive(Clone, Debug, Default, Serialize)]
+/// Represents an envelope, as printed by the envelope list command.
+///
+/// The cc, size, has_attachment and headers fields are read from the
+/// message, and are only set when their column is displayed.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct Envelope {
+    /// The name of the account the envelope belongs to.
+    ///
+    /// Only set when listing envelopes of multiple accounts.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub account: Option<String>,
     pub id: String,
     pub flags: Flags,
     pub subject: String,
     pub from: Mailbox,
     pub to: Mailbox,
     pub date: String,
+    /// The date of the envelope as a UNIX timestamp, used to merge
+    /// envelopes of multiple accounts.
+    #[serde(skip)]
+    pub timestamp: i64,
+    /// The name of the folder the envelope belongs to.
+    ///
+    /// Only set when the folder column is displayed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub folder: Option<String>,
+    /// The Cc addresses of the message.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub cc: Vec<Mailbox>,
+    /// The size of the raw message, in bytes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<usize>,
+    /// Whether the message has at least one attachment.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub has_attachment: Option<bool>,
+    /// The headers shown in header columns, by header name.
+    ///
+    /// Headers missing from the message are empty, so that every
+    /// envelope has the same headers.
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+}
+
+impl Envelope {
+    pub fn from_backend(
+        config: &AccountConfig,
+        id_mapper: &IdMapper,
+        envelope: &email::envelope::Envelope,
+    ) -> Result<Envelope> {
+        Ok(Envelope {
+            account: None,
+            id: id_mapper.get_or_create_alias(&envelope.id)?,
+            flags: envelope.flags.clone().into(),
+            subject: envelope.subject.clone(),
+            from: Mailbox {
+                name: envelope.from.name.clone(),
+                addr: envelope.from.addr.clone(),
+            },
+            to: Mailbox {
+                name: envelope.to.name.clone(),
+                addr: envelope.to.addr.clone(),
+            },
+            date: envelope.format_date(config),
+            timestamp: envelope.date.timestamp(),
+            ..Default::default()
+        })
+    }
 }
 
 impl Table for Envelope {
     fn head() -> Row {
-        Row::new()
-            .cell(Cell::new("ID").bold().underline().white())
-            .cell(Cell::new("FLAGS").bold().underline().white())
-            .cell(Cell::new("SUBJECT").shrinkable().bold().underline().white())
-            .cell(Cell::new("FROM").bold().underline().white())
-            .cell(Cell::new("DATE").bold().underline().white())
+        EnvelopeColumns::default().head()
     }
 
     fn row(&self) -> Row {
-        let id = self.id.to_string();
-        let unseen = !self.flags.contains(&Flag::Seen);
-        let flags = {
-            let mut flags = String::new();
-            flags.push_str(if !unseen { " " } else { "✷" });
-            flags.push_str(if self.flags.contains(&Flag::Answered) {
-                "↵"
-            } else {
-                " "
-            });
-            flags.push_str(if self.flags.contains(&Flag::Flagged) {
-                "⚑"
-            } else {
-                " "
-            });
-            flags
-        };
-        let subject = &self.subject;
-        let sender = if let Some(name) = &self.from.name {
-            name
-        } else {
-            &self.from.addr
-        };
-        let date = &self.date;
-
-        Row::new()
-            .cell(Cell::new(id).bold_if(unseen).red())
-            .cell(Cell::new(flags).bold_if(unseen).white())
-            .cell(Cell::new(subject).shrinkable().bold_if(unseen).green())
-            .cell(Cell::new(sender).bold_if(unseen).blue())
-            .cell(Cell::new(date).bold_if(unseen).yellow())
+        EnvelopeColumns::default().row(self)
     }
 }
 
 /// Represents the list of envelopes.
 #[derive(Clone, Debug, Default, Serialize)]
-pub struct Envelopes(Vec<Envelope>);
+#[serde(transparent)]
+pub struct Envelopes {
+    envelopes: Vec<Envelope>,
+    #[serde(skip)]
+    columns: EnvelopeColumns,
+}
 
 impl Envelopes {
     pub fn from_backend(
@@ -88,25 +121,25 @@ impl Envelopes {
     ) -> Result<Envelopes> {
         let envelopes = envelopes
             .iter()
-            .map(|envelope| {
-                Ok(Envelope {
-                    id: id_mapper.get_or_create_alias(&envelope.id)?,
-                    flags: envelope.flags.clone().into(),
-                    subject: envelope.subject.clone(),
-                    from: Mailbox {
-                        name: envelope.from.name.clone(),
-                        addr: envelope.from.addr.clone(),
-                    },
-                    to: Mailbox {
-                        name: envelope.to.name.clone(),
-                        addr: envelope.to.addr.clone(),
-                    },
-                    date: envelope.format_date(config),
-                })
-            })
+            .map(|envelope| Envelope::from_backend(config, id_mapper, envelope))
             .collect::<Result<Vec<_>>>()?;
 
-        Ok(Envelopes(envelopes))
+        Ok(Envelopes::from(envelopes))
+    }
+
+    /// Set the columns used to display envelopes as a table.
+    pub fn with_columns(mut self, columns: EnvelopeColumns) -> Self {
+        self.columns = columns;
+        self
+    }
+}
+
+impl From<Vec<Envelope>> for Envelopes {
+    fn from(envelopes: Vec<Envelope>) -> Self {
+        Self {
+            envelopes,
+            columns: Default::default(),
+        }
     }
 }
 
@@ -114,15 +147,322 @@ impl ops::Deref for Envelopes {
     type Target = Vec<Envelope>;
 
     fn deref(&self) -> &Self::Target {
-        &self.0
+        &self.envelopes
+    }
+}
+
+impl ops::DerefMut for Envelopes {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.envelopes
     }
 }
 
 impl PrintTable for Envelopes {
     fn print_table(&self, writer: &mut dyn WriteColor, opts: PrintTableOpts) -> Result<()> {
         writeln!(writer)?;
-        Table::print(writer, self, opts)?;
+        self.columns.print(writer, self, opts)?;
+        writeln!(writer)?;
+        Ok(())
+    }
+}
+
+/// Represents the kind of a sort criterion of a query.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum EnvelopeSortKind {
+    Date,
+    From,
+    To,
+    Subject,
+}
+
+/// Represents a sort criterion of the `order by` clause of a query.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct EnvelopeSorter {
+    pub kind: EnvelopeSortKind,
+    pub descending: bool,
+}
+
+impl EnvelopeSorter {
+    /// Read the sort criteria of the `order by` clause of the given
+    /// query, which must be valid.
+    ///
+    /// Defaults to the most recent envelopes first, like backends do
+    /// when the query has no `order by` clause.
+    pub fn from_query(query: Option<&str>) -> Vec<Self> {
+        let query = query.unwrap_or_default().to_lowercase();
+        let tokens: Vec<_> = query.split_whitespace().collect();
+
+        let clause = tokens
+            .windows(2)
+            .position(|window| window == ["order", "by"])
+            .map(|i| &tokens[i + 2..])
+            .unwrap_or_default();
+
+        let mut sorters: Vec<Self> = Vec::new();
+
+        for token in clause {
+            let kind = match *token {
+                "date" => EnvelopeSortKind::Date,
+                "from" => EnvelopeSortKind::From,
+                "to" => EnvelopeSortKind::To,
+                "subject" => EnvelopeSortKind::Subject,
+                "desc" => {
+                    if let Some(sorter) = sorters.last_mut() {
+                        sorter.descending = true;
+                    }
+                    continue;
+                }
+                _ => continue,
+            };
+
+            sorters.push(Self {
+                kind,
+                descending: false,
+            });
+        }
+
+        if sorters.is_empty() {
+            sorters.push(Self {
+                kind: EnvelopeSortKind::Date,
+                descending: true,
+            });
+        }
+
+        sorters
+    }
+
+    fn cmp(&self, a: &Envelope, b: &Envelope) -> Ordering {
+        let mailbox = |mailbox: &Mailbox| {
+            mailbox
+                .name
+                .as_deref()
+                .unwrap_or(&mailbox.addr)
+                .to_lowercase()
+        };
+
+        let ordering = match self.kind {
+            EnvelopeSortKind::Date => a.timestamp.cmp(&b.timestamp),
+            EnvelopeSortKind::From => mailbox(&a.from).cmp(&mailbox(&b.from)),
+            EnvelopeSortKind::To => mailbox(&a.to).cmp(&mailbox(&b.to)),
+            EnvelopeSortKind::Subject => a.subject.to_lowercase().cmp(&b.subject.to_lowercase()),
+        };
+
+        if self.descending {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    }
+}
+
+/// Represents the list of envelopes of multiple accounts.
+///
+/// Envelopes are merged using the sort order of the query, and their
+/// ids are qualified by the name of their account so that they can be
+/// used by other commands.
+#[derive(Clone, Debug, Default, Serialize)]
+#[serde(transparent)]
+pub struct AccountEnvelopes {
+    envelopes: Vec<Envelope>,
+    #[serde(skip)]
+    columns: EnvelopeColumns,
+}
+
+impl AccountEnvelopes {
+    /// Merge envelopes of multiple accounts using the given sort
+    /// criteria.
+    ///
+    /// The returned list is paginated the same way a single account
+    /// list is (a page size of 0 disables pagination).
+    pub fn merge(
+        envelopes: impl IntoIterator<Item = (String, Envelope)>,
+        sorters: &[EnvelopeSorter],
+        page: usize,
+        page_size: usize,
+    ) -> Self {
+        let mut envelopes: Vec<_> = envelopes
+            .into_iter()
+            .map(|(account, mut envelope)| {
+                envelope.id = format!("{account}:{}", envelope.id);
+                envelope.account = Some(account);
+                envelope
+            })
+            .collect();
+
+        envelopes.sort_by(|a, b| {
+            sorters
+                .iter()
+                .map(|sorter| sorter.cmp(a, b))
+                .find(|ordering| ordering.is_ne())
+                .unwrap_or(Ordering::Equal)
+        });
+
+        let envelopes = envelopes.into_iter();
+        let envelopes = if page_size == 0 {
+            envelopes.collect()
+        } else {
+            envelopes.skip(page * page_size).take(page_size).collect()
+        };
+
+        Self {
+            envelopes,
+            columns: EnvelopeColumns::default().with_account(),
+        }
+    }
+
+    /// Set the columns used to display envelopes as a table.
+    ///
+    /// The account column is prepended when missing.
+    pub fn with_columns(mut self, columns: EnvelopeColumns) -> Self {
+        self.columns = columns.with_account();
+        self
+    }
+}
+
+impl ops::Deref for AccountEnvelopes {
+    type Target = Vec<Envelope>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.envelopes
+    }
+}
+
+impl PrintTable for AccountEnvelopes {
+    fn print_table(&self, writer: &mut dyn WriteColor, opts: PrintTableOpts) -> Result<()> {
+        writeln!(writer)?;
+        self.columns.print(writer, self, opts)?;
+        writeln!(writer)?;
+        Ok(())
+    }
+}
+
+/// Represents an envelope and the envelopes replying to it.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct ThreadedEnvelope {
+    #[serde(flatten)]
+    pub envelope: Envelope,
+    pub replies: Vec<ThreadedEnvelope>,
+}
+
+impl ThreadedEnvelope {
+    /// Flatten the thread into a list of envelopes, prefixing
+    /// subjects with tree branches so the thread can be displayed as
+    /// an indented tree inside a table.
+    fn flatten(&self, prefix: &str, is_last: bool, is_root: bool, envelopes: &mut Vec<Envelope>) {
+        let mut envelope = self.envelope.clone();
+
+        let prefix = if is_root {
+            String::new()
+        } else {
+            let branch = if is_last { "└─ " } else { "├─ " };
+            envelope.subject = format!("{prefix}{branch}{}", envelope.subject);
+            let indent = if is_last { "   " } else { "│  " };
+            format!("{prefix}{indent}")
+        };
+
+        envelopes.push(envelope);
+
+        let len = self.replies.len();
+        for (i, reply) in self.replies.iter().enumerate() {
+            reply.flatten(&prefix, i + 1 == len, false, envelopes);
+        }
+    }
+}
+
+/// Represents the list of envelopes grouped by threads.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct ThreadedEnvelopes(Vec<ThreadedEnvelope>);
+
+impl From<Vec<ThreadedEnvelope>> for ThreadedEnvelopes {
+    fn from(threads: Vec<ThreadedEnvelope>) -> Self {
+        Self(threads)
+    }
+}
+
+impl ops::Deref for ThreadedEnvelopes {
+    type Target = Vec<ThreadedEnvelope>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl PrintTable for ThreadedEnvelopes {
+    fn print_table(&self, writer: &mut dyn WriteColor, opts: PrintTableOpts) -> Result<()> {
+        let mut envelopes = Vec::new();
+
+        for thread in self.iter() {
+            thread.flatten("", true, true, &mut envelopes);
+        }
+
+        writeln!(writer)?;
+        Table::print(writer, &envelopes, opts)?;
         writeln!(writer)?;
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn envelope(id: &str, timestamp: i64, subject: &str) -> Envelope {
+        Envelope {
+            id: id.to_owned(),
+            timestamp,
+            subject: subject.to_owned(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn merge_account_envelopes() {
+        let envelopes = vec![
+            ("work".to_owned(), envelope("1", 10, "b")),
+            ("work".to_owned(), envelope("2", 30, "d")),
+            ("perso".to_owned(), envelope("1", 20, "a")),
+            ("perso".to_owned(), envelope("2", 40, "c")),
+        ];
+
+        let ids = |envelopes: &AccountEnvelopes| {
+            envelopes
+                .iter()
+                .map(|envelope| envelope.id.clone())
+                .collect::<Vec<_>>()
+        };
+
+        let sorters = EnvelopeSorter::from_query(None);
+        let merged = AccountEnvelopes::merge(envelopes.clone(), &sorters, 0, 0);
+        assert_eq!(ids(&merged), ["perso:2", "work:2", "perso:1", "work:1"]);
+        assert_eq!(merged[0].account.as_deref(), Some("perso"));
+
+        let merged = AccountEnvelopes::merge(envelopes.clone(), &sorters, 1, 3);
+        assert_eq!(ids(&merged), ["work:1"]);
+
+        let sorters = EnvelopeSorter::from_query(Some("from alice order by subject"));
+        let merged = AccountEnvelopes::merge(envelopes.clone(), &sorters, 0, 0);
+        assert_eq!(ids(&merged), ["perso:1", "work:1", "perso:2", "work:2"]);
+
+        let sorters = EnvelopeSorter::from_query(Some("order by date asc"));
+        let merged = AccountEnvelopes::merge(envelopes, &sorters, 0, 0);
+        assert_eq!(ids(&merged), ["work:1", "perso:1", "work:2", "perso:2"]);
+    }
+
+    #[test]
+    fn sorters_from_query() {
+        let sorters = EnvelopeSorter::from_query(Some("subject foo ORDER BY from desc date"));
+        assert_eq!(
+            sorters,
+            [
+                EnvelopeSorter {
+                    kind: EnvelopeSortKind::From,
+                    descending: true,
+                },
+                EnvelopeSorter {
+                    kind: EnvelopeSortKind::Date,
+                    descending: false,
+                },
+            ]
+        );
+    }
+}
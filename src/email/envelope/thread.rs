@@ -0,0 +1,195 @@
+//! Module dedicated to envelopes threading.
+//!
+//! This module groups envelopes into conversations using the
+//! Message-ID, In-Reply-To and References headers of their
+//! corresponding messages.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::envelope::{Envelope, ThreadedEnvelope};
+
+/// Normalize the given message id.
+///
+/// Message ids can be found with or without surrounding angle
+/// brackets depending on where they come from, so they are removed
+/// in order to compare them safely.
+pub fn normalize_message_id(id: &str) -> String {
+    id.trim()
+        .trim_start_matches('<')
+        .trim_end_matches('>')
+        .trim()
+        .to_owned()
+}
+
+/// Collect the parent message ids of a message, nearest first.
+///
+/// In-Reply-To ids come first, then References ids from the last
+/// (the direct parent) to the first (the thread root).
+pub fn parent_message_ids<'a>(
+    in_reply_to: impl IntoIterator<Item = &'a str>,
+    references: impl IntoIterator<Item = &'a str>,
+) -> Vec<String> {
+    let mut references: Vec<&str> = references.into_iter().collect();
+    references.reverse();
+
+    in_reply_to
+        .into_iter()
+        .chain(references)
+        .map(normalize_message_id)
+        .filter(|id| !id.is_empty())
+        .collect()
+}
+
+/// Group envelopes into threads.
+///
+/// Each item is composed of the message id of the envelope, its
+/// parent message ids (nearest first, see [`parent_message_ids`])
+/// and the envelope itself. An envelope is attached to the nearest
+/// parent found in the given items, otherwise it becomes the root of
+/// its own thread. Roots and replies keep the order of the given
+/// items.
+pub fn build_threads(items: Vec<(String, Vec<String>, Envelope)>) -> Vec<ThreadedEnvelope> {
+    let mut indexes: HashMap<String, usize> = HashMap::new();
+
+    for (i, (id, _, _)) in items.iter().enumerate() {
+        let id = normalize_message_id(id);
+        if !id.is_empty() {
+            indexes.entry(id).or_insert(i);
+        }
+    }
+
+    let mut parents: Vec<Option<usize>> = items
+        .iter()
+        .enumerate()
+        .map(|(i, (_, parent_ids, _))| {
+            parent_ids
+                .iter()
+                .filter_map(|id| indexes.get(id))
+                .find(|parent| **parent != i)
+                .copied()
+        })
+        .collect();
+
+    // break cycles that can be created by broken or malicious
+    // References headers, by removing only the edge closing the
+    // cycle so that envelopes leading into it stay attached
+    for i in 0..parents.len() {
+        let mut visited = HashSet::from([i]);
+        let mut current = i;
+
+        while let Some(parent) = parents[current] {
+            if !visited.insert(parent) {
+                parents[current] = None;
+                break;
+            }
+            current = parent;
+        }
+    }
+
+    let mut roots = Vec::new();
+    let mut children: Vec<Vec<usize>> = vec![Vec::new(); items.len()];
+
+    for (i, parent) in parents.iter().enumerate() {
+        match parent {
+            Some(parent) => children[*parent].push(i),
+            None => roots.push(i),
+        }
+    }
+
+    let mut envelopes: Vec<Option<Envelope>> = items
+        .into_iter()
+        .map(|(_, _, envelope)| Some(envelope))
+        .collect();
+
+    roots
+        .into_iter()
+        .filter_map(|i| build_thread(i, &children, &mut envelopes))
+        .collect()
+}
+
+fn build_thread(
+    i: usize,
+    children: &[Vec<usize>],
+    envelopes: &mut [Option<Envelope>],
+) -> Option<ThreadedEnvelope> {
+    let envelope = envelopes[i].take()?;
+    let replies = children[i]
+        .iter()
+        .filter_map(|child| build_thread(*child, children, envelopes))
+        .collect();
+
+    Some(ThreadedEnvelope { envelope, replies })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(id: &str, parents: &[&str]) -> (String, Vec<String>, Envelope) {
+        let envelope = Envelope {
+            id: id.to_owned(),
+            subject: id.to_owned(),
+            ..Default::default()
+        };
+        let parents = parents.iter().map(|id| id.to_string()).collect();
+        (format!("<{id}>"), parents, envelope)
+    }
+
+    fn ids(threads: &[ThreadedEnvelope]) -> Vec<(&str, Vec<&str>)> {
+        threads
+            .iter()
+            .map(|thread| {
+                let replies = thread
+                    .replies
+                    .iter()
+                    .map(|reply| reply.envelope.id.as_str())
+                    .collect();
+                (thread.envelope.id.as_str(), replies)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn parent_ids_nearest_first() {
+        let parents = parent_message_ids(["<c>"], ["<a>", "<b>", "<c>"]);
+        assert_eq!(parents, vec!["c", "c", "b", "a"]);
+    }
+
+    #[test]
+    fn thread_by_in_reply_to_and_references() {
+        let threads = build_threads(vec![
+            item("a", &[]),
+            item("b", &["a"]),
+            item("c", &["x", "b", "a"]),
+            item("d", &[]),
+        ]);
+
+        assert_eq!(ids(&threads), vec![("a", vec!["b"]), ("d", vec![])]);
+        assert_eq!(threads[0].replies[0].replies[0].envelope.id, "c");
+    }
+
+    #[test]
+    fn thread_with_missing_parent() {
+        let threads = build_threads(vec![item("b", &["a"]), item("c", &["b", "a"])]);
+        assert_eq!(ids(&threads), vec![("b", vec!["c"])]);
+    }
+
+    #[test]
+    fn thread_with_cycle() {
+        let threads = build_threads(vec![item("a", &["b"]), item("b", &["a"])]);
+        let count: usize = threads.iter().map(|t| 1 + t.replies.len()).sum();
+        assert_eq!(count, 2);
+    }
+
+    #[test]
+    fn thread_leading_into_cycle() {
+        let threads = build_threads(vec![
+            item("a", &["b"]),
+            item("b", &["a"]),
+            item("c", &["a"]),
+        ]);
+
+        assert_eq!(ids(&threads), vec![("b", vec!["a"])]);
+        assert_eq!(ids(&threads[0].replies), vec![("a", vec!["c"])]);
+    }
+}
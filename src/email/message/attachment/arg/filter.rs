@@ -0,0 +1,81 @@
+use clap::Parser;
+
+/// The attachment filter flags parser.
+///
+/// Attachments matching at least one value of each given filter are
+/// selected. When no filter is given, all attachments are selected.
+#[derive(Debug, Default, Parser)]
+pub struct AttachmentFilterFlags {
+    /// Select attachments by index, starting from 1.
+    ///
+    /// Indexes are the ones shown by the attachment list command.
+    #[arg(long = "index", value_name = "INDEX")]
+    pub indexes: Vec<usize>,
+
+    /// Select attachments whose file name contains the given text.
+    ///
+    /// The comparison is case-insensitive.
+    #[arg(long = "name", value_name = "NAME")]
+    pub names: Vec<String>,
+
+    /// Select attachments by MIME type.
+    ///
+    /// The subtype can be replaced by a wildcard, for example
+    /// image/*.
+    #[arg(long = "mime", value_name = "TYPE")]
+    pub mimes: Vec<String>,
+}
+
+impl AttachmentFilterFlags {
+    /// Return true if the attachment matches the filters.
+    pub fn matches(&self, index: usize, filename: Option<&str>, mime: &str) -> bool {
+        let index_matches = self.indexes.is_empty() || self.indexes.contains(&index);
+
+        let name_matches = self.names.is_empty()
+            || filename.map(str::to_lowercase).is_some_and(|filename| {
+                self.names
+                    .iter()
+                    .any(|name| filename.contains(&name.to_lowercase()))
+            });
+
+        let mime = mime.to_lowercase();
+        let mime_matches = self.mimes.is_empty()
+            || self.mimes.iter().any(|pattern| {
+                let pattern = pattern.to_lowercase();
+                match pattern.strip_suffix("/*") {
+                    Some(ctype) => mime.split('/').next() == Some(ctype),
+                    None => mime == pattern,
+                }
+            });
+
+        index_matches && name_matches && mime_matches
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AttachmentFilterFlags;
+
+    #[test]
+    fn matches() {
+        let all = AttachmentFilterFlags::default();
+        assert!(all.matches(1, None, "application/pdf"));
+
+        let filters = AttachmentFilterFlags {
+            indexes: vec![],
+            names: vec!["Report".into()],
+            mimes: vec!["application/*".into(), "image/png".into()],
+        };
+        assert!(filters.matches(1, Some("2024-report.pdf"), "application/pdf"));
+        assert!(!filters.matches(1, Some("2024-report.pdf"), "text/plain"));
+        assert!(!filters.matches(1, Some("photo.png"), "image/png"));
+        assert!(!filters.matches(1, None, "image/png"));
+
+        let filters = AttachmentFilterFlags {
+            indexes: vec![2, 3],
+            ..Default::default()
+        };
+        assert!(!filters.matches(1, None, "image/png"));
+        assert!(filters.matches(3, None, "image/png"));
+    }
+}
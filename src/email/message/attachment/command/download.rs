@@ -1,8 +1,13 @@
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use clap::Parser;
-use email::backend::feature::BackendFeatureSource;
+use email::{account::config::AccountConfig, backend::feature::BackendFeatureSource};
 use log::info;
-use std::{fs, path::PathBuf};
+use std::{
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 use uuid::Uuid;
 
 #[cfg(feature = "account-sync")]
@@ -10,13 +15,15 @@ use crate::cache::arg::disable::CacheDisableFlag;
 use crate::{
     account::arg::name::AccountNameFlag, backend::Backend, config::TomlConfig,
     envelope::arg::ids::EnvelopeIdsArgs, folder::arg::name::FolderNameOptionalFlag,
-    printer::Printer,
+    message::attachment::arg::filter::AttachmentFilterFlags, printer::Printer,
 };
 
-/// Download all attachments for the given message.
+/// Download attachments for the given messages.
 ///
-/// This command allows you to download all attachments found for the
-/// given message to your downloads directory.
+/// This command allows you to download attachments found for the
+/// given messages to your downloads directory. Attachments can be
+/// selected by index, name or MIME type. Existing files are never
+/// overwritten: the new file is renamed instead.
 #[derive(Debug, Parser)]
 pub struct AttachmentDownloadCommand {
     #[command(flatten)]
@@ -25,6 +32,22 @@ pub struct AttachmentDownloadCommand {
     #[command(flatten)]
     pub envelopes: EnvelopeIdsArgs,
 
+    #[command(flatten)]
+    pub filters: AttachmentFilterFlags,
+
+    /// Download attachments to the given directory instead of the
+    /// downloads directory.
+    #[arg(long, value_name = "PATH")]
+    #[arg(conflicts_with = "stdout")]
+    pub dir: Option<PathBuf>,
+
+    /// Write the attachment to the standard output instead of a
+    /// file.
+    ///
+    /// Filters must select exactly one attachment.
+    #[arg(long)]
+    pub stdout: bool,
+
     #[cfg(feature = "account-sync")]
     #[command(flatten)]
     pub cache: CacheDisableFlag,
@@ -38,10 +61,10 @@ impl AttachmentDownloadCommand {
         info!("executing download attachment(s) command");
 
         let folder = &self.folder.name;
-        let ids = &self.envelopes.ids;
+        let ids = &self.envelopes.ids();
 
         let (toml_account_config, account_config) = config.clone().into_account_configs(
-            self.account.name.as_deref(),
+            self.envelopes.account_or(self.account.name.as_deref())?,
             #[cfg(feature = "account-sync")]
             self.cache.disable,
         )?;
@@ -58,37 +81,78 @@ impl AttachmentDownloadCommand {
 
         let emails = backend.get_messages(folder, ids).await?;
 
-        let mut emails_count = 0;
-        let mut attachments_count = 0;
-
-        let mut ids = ids.iter();
-        for email in emails.to_vec() {
-            let id = ids.next().unwrap();
-            let attachments = email.attachments()?;
-
-            if attachments.is_empty() {
+        let mut selected = Vec::new();
+
+        for (id, email) in ids.iter().zip(emails.to_vec()) {
+            let attachments: Vec<_> = email
+                .attachments()?
+                .into_iter()
+                .enumerate()
+                .filter(|(i, attachment)| {
+                    self.filters
+                        .matches(i + 1, attachment.filename.as_deref(), &attachment.mime)
+                })
+                .map(|(_, attachment)| attachment)
+                .collect();
+
+            // logs would mix with the attachment written to stdout
+            if self.stdout {
+                selected.push(attachments);
+            } else if attachments.is_empty() {
                 printer.print_log(format!("No attachment found for message {id}!"))?;
-                continue;
             } else {
-                emails_count += 1;
+                printer.print_log(format!(
+                    "{} attachment(s) found for message {id}!",
+                    attachments.len()
+                ))?;
+                selected.push(attachments);
             }
+        }
 
-            printer.print_log(format!(
-                "{} attachment(s) found for message {id}!",
-                attachments.len()
-            ))?;
-
-            for attachment in attachments {
-                let filename: PathBuf = attachment
-                    .filename
-                    .unwrap_or_else(|| Uuid::new_v4().to_string())
-                    .into();
-                let filepath = account_config.get_download_file_path(&filename)?;
-                printer.print_log(format!("Downloading {:?}…", filepath))?;
-                fs::write(&filepath, &attachment.body)
-                    .with_context(|| format!("cannot save attachment at {filepath:?}"))?;
-                attachments_count += 1;
-            }
+        if self.stdout {
+            let mut attachments: Vec<_> = selected.into_iter().flatten().collect();
+            let attachment = match attachments.len() {
+                1 => attachments.remove(0),
+                0 => bail!("cannot find any attachment to write to stdout"),
+                n => bail!("cannot write {n} attachments to stdout, please select only one"),
+            };
+
+            let mut stdout = io::stdout().lock();
+            return stdout
+                .write_all(&attachment.body)
+                .and_then(|()| stdout.flush())
+                .context("cannot write attachment to stdout");
+        }
+
+        let account_config = match self.dir {
+            Some(dir) => Arc::new(AccountConfig {
+                downloads_dir: Some(dir),
+                ..(*account_config).clone()
+            }),
+            None => account_config,
+        };
+
+        let dir = account_config.get_downloads_dir();
+        fs::create_dir_all(&dir)
+            .with_context(|| format!("cannot create downloads directory at {dir:?}"))?;
+
+        let emails_count = selected.len();
+        let mut attachments_count = 0;
+
+        for attachment in selected.into_iter().flatten() {
+            // only keep the file name, so that attachments cannot
+            // be written outside of the downloads directory
+            let filename: PathBuf = attachment
+                .filename
+                .as_deref()
+                .and_then(|filename| Path::new(filename).file_name())
+                .map(PathBuf::from)
+                .unwrap_or_else(|| Uuid::new_v4().to_string().into());
+            let filepath = account_config.get_download_file_path(&filename)?;
+            printer.print_log(format!("Downloading {:?}…", filepath))?;
+            fs::write(&filepath, &attachment.body)
+                .with_context(|| format!("cannot save attachment at {filepath:?}"))?;
+            attachments_count += 1;
         }
 
         match attachments_count {
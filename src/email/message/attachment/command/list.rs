@@ -0,0 +1,91 @@
+use anyhow::Result;
+use clap::Parser;
+use email::backend::feature::BackendFeatureSource;
+use log::info;
+
+#[cfg(feature = "account-sync")]
+use crate::cache::arg::disable::CacheDisableFlag;
+use crate::{
+    account::arg::name::AccountNameFlag,
+    backend::Backend,
+    config::TomlConfig,
+    envelope::arg::ids::EnvelopeIdsArgs,
+    folder::arg::name::FolderNameOptionalFlag,
+    message::attachment::{AttachmentEntries, AttachmentEntry},
+    printer::{PrintTableOpts, Printer},
+    ui::arg::max_width::TableMaxWidthFlag,
+};
+
+/// List attachments of the given messages.
+///
+/// This command allows you to list attachments found for the given
+/// messages, with their name, MIME type, size and index. Indexes can
+/// then be given to the download command. Messages are not marked as
+/// seen.
+#[derive(Debug, Parser)]
+pub struct AttachmentListCommand {
+    #[command(flatten)]
+    pub folder: FolderNameOptionalFlag,
+
+    #[command(flatten)]
+    pub envelopes: EnvelopeIdsArgs,
+
+    #[command(flatten)]
+    pub table: TableMaxWidthFlag,
+
+    #[cfg(feature = "account-sync")]
+    #[command(flatten)]
+    pub cache: CacheDisableFlag,
+
+    #[command(flatten)]
+    pub account: AccountNameFlag,
+}
+
+impl AttachmentListCommand {
+    pub async fn execute(self, printer: &mut impl Printer, config: &TomlConfig) -> Result<()> {
+        info!("executing list attachments command");
+
+        let folder = &self.folder.name;
+        let ids = &self.envelopes.ids();
+
+        let (toml_account_config, account_config) = config.clone().into_account_configs(
+            self.envelopes.account_or(self.account.name.as_deref())?,
+            #[cfg(feature = "account-sync")]
+            self.cache.disable,
+        )?;
+
+        let peek_messages_kind = toml_account_config.peek_messages_kind();
+
+        let backend = Backend::new(
+            toml_account_config.clone(),
+            account_config.clone(),
+            peek_messages_kind,
+            |builder| builder.set_peek_messages(BackendFeatureSource::Context),
+        )
+        .await?;
+
+        let emails = backend.peek_messages(folder, ids).await?;
+
+        let mut attachments = Vec::new();
+
+        for (id, email) in ids.iter().zip(emails.to_vec()) {
+            for (i, attachment) in email.attachments()?.into_iter().enumerate() {
+                attachments.push(AttachmentEntry {
+                    id: id.to_string(),
+                    index: i + 1,
+                    name: attachment.filename,
+                    mime: attachment.mime,
+                    size: attachment.body.len(),
+                });
+            }
+        }
+
+        printer.print_table(
+            Box::new(AttachmentEntries::from(attachments)),
+            PrintTableOpts {
+                format: &Default::default(),
+                max_width: self.table.max_width,
+            },
+        )
+    }
+}
@@ -1,11 +1,15 @@
 mod download;
+mod list;
+mod open;
 
 use anyhow::Result;
 use clap::Subcommand;
 
 use crate::{config::TomlConfig, printer::Printer};
 
-use self::download::AttachmentDownloadCommand;
+use self::{
+    download::AttachmentDownloadCommand, list::AttachmentListCommand, open::AttachmentOpenCommand,
+};
 
 /// Manage attachments.
 ///
@@ -14,14 +18,23 @@ use self::download::AttachmentDownloadCommand;
 /// body.
 #[derive(Debug, Subcommand)]
 pub enum AttachmentSubcommand {
+    #[command(arg_required_else_help = true)]
+    #[command(alias = "lst")]
+    List(AttachmentListCommand),
+
     #[command(arg_required_else_help = true)]
     Download(AttachmentDownloadCommand),
+
+    #[command(arg_required_else_help = true)]
+    Open(AttachmentOpenCommand),
 }
 
 impl AttachmentSubcommand {
     pub async fn execute(self, printer: &mut impl Printer, config: &TomlConfig) -> Result<()> {
         match self {
+            Self::List(cmd) => cmd.execute(printer, config).await,
             Self::Download(cmd) => cmd.execute(printer, config).await,
+            Self::Open(cmd) => cmd.execute(printer, config).await,
         }
     }
 }
@@ -0,0 +1,202 @@
+use anyhow::{anyhow, bail, Context, Result};
+use clap::Parser;
+use email::backend::feature::BackendFeatureSource;
+use log::{debug, info};
+use process::SingleCommand;
+#[cfg(unix)]
+use std::os::unix::fs::DirBuilderExt;
+use std::{
+    fs::{self, DirBuilder, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use uuid::Uuid;
+
+#[cfg(feature = "account-sync")]
+use crate::cache::arg::disable::CacheDisableFlag;
+use crate::{
+    account::arg::name::AccountNameFlag,
+    backend::Backend,
+    config::TomlConfig,
+    envelope::arg::ids::EnvelopeIdArg,
+    folder::arg::name::FolderNameOptionalFlag,
+    message::attachment::config::{viewer_command, DEFAULT_VIEWER},
+    printer::Printer,
+};
+
+/// The age after which attachment directories are removed.
+const STALE_DIR_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Open an attachment of the given message.
+///
+/// This command allows you to open an attachment with the viewer
+/// matching its MIME type, as configured in the attachment.viewers
+/// option of your TOML configuration file. When no viewer matches,
+/// the attachment is opened with xdg-open. The attachment is written
+/// to a private directory of your cache directory first.
+#[derive(Debug, Parser)]
+pub struct AttachmentOpenCommand {
+    #[command(flatten)]
+    pub folder: FolderNameOptionalFlag,
+
+    #[command(flatten)]
+    pub envelope: EnvelopeIdArg,
+
+    /// The index of the attachment, starting from 1.
+    ///
+    /// Indexes are the ones shown by the attachment list command. It
+    /// can be omitted when the message has only one attachment.
+    #[arg(value_name = "INDEX")]
+    pub index: Option<usize>,
+
+    #[cfg(feature = "account-sync")]
+    #[command(flatten)]
+    pub cache: CacheDisableFlag,
+
+    #[command(flatten)]
+    pub account: AccountNameFlag,
+}
+
+impl AttachmentOpenCommand {
+    pub async fn execute(self, printer: &mut impl Printer, config: &TomlConfig) -> Result<()> {
+        info!("executing open attachment command");
+
+        let folder = &self.folder.name;
+        let id = self.envelope.id.id;
+
+        let (toml_account_config, account_config) = config.clone().into_account_configs(
+            self.envelope.id.account_or(self.account.name.as_deref())?,
+            #[cfg(feature = "account-sync")]
+            self.cache.disable,
+        )?;
+
+        let peek_messages_kind = toml_account_config.peek_messages_kind();
+
+        let backend = Backend::new(
+            toml_account_config.clone(),
+            account_config.clone(),
+            peek_messages_kind,
+            |builder| builder.set_peek_messages(BackendFeatureSource::Context),
+        )
+        .await?;
+
+        let emails = backend.peek_messages(folder, &[id]).await?;
+        let mut attachments = emails
+            .first()
+            .ok_or(anyhow!("cannot find message {id}"))?
+            .attachments()?;
+
+        let attachment = match (self.index, attachments.len()) {
+            (_, 0) => bail!("cannot find any attachment for message {id}"),
+            (None, 1) => attachments.remove(0),
+            (None, n) => bail!("message {id} has {n} attachments, please give an index"),
+            (Some(index), n) if index == 0 || index > n => {
+                bail!("cannot find attachment {index} for message {id}, found {n} attachment(s)")
+            }
+            (Some(index), _) => attachments.remove(index - 1),
+        };
+
+        let dir = attachments_dir()?;
+
+        let filename: PathBuf = attachment
+            .filename
+            .as_deref()
+            .and_then(|filename| Path::new(filename).file_name())
+            .map(PathBuf::from)
+            .unwrap_or_else(|| Uuid::new_v4().to_string().into());
+        let path = dir.join(filename);
+        OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+            .and_then(|mut file| file.write_all(&attachment.body))
+            .with_context(|| format!("cannot save attachment at {path:?}"))?;
+
+        let viewer = toml_account_config
+            .attachment
+            .as_ref()
+            .and_then(|config| config.find_viewer(&attachment.mime))
+            .unwrap_or(DEFAULT_VIEWER);
+        let cmd = viewer_command(viewer, &path.to_string_lossy());
+
+        debug!("opening {} attachment using command {cmd}", attachment.mime);
+        SingleCommand::from(detached(cmd))
+            .with_output_piped(false)
+            .run()
+            .await
+            .with_context(|| format!("cannot open attachment using command {viewer}"))?;
+
+        printer.print(format!("Attachment opened from {path:?}!"))
+    }
+}
+
+/// Make the given shell command run in the background.
+///
+/// Graphical viewers may only return once closed, so the shell starts
+/// the viewer and returns straight away.
+fn detached(cmd: String) -> String {
+    if cfg!(windows) {
+        format!("start \"\" {cmd}")
+    } else {
+        format!("{cmd} &")
+    }
+}
+
+/// Create a private directory for the attachment to open.
+///
+/// Each attachment is written to its own directory, readable by the
+/// current user only, inside the cache directory. Directories left by
+/// previous calls are removed after a day, since viewers like
+/// xdg-open may return before the attachment is read.
+fn attachments_dir() -> Result<PathBuf> {
+    let root = dirs::cache_dir()
+        .ok_or(anyhow!("cannot find cache directory"))?
+        .join("himalaya")
+        .join("attachments");
+
+    let mut builder = DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(unix)]
+    builder.mode(0o700);
+    builder
+        .create(&root)
+        .with_context(|| format!("cannot create attachments directory at {root:?}"))?;
+
+    remove_stale_dirs(&root);
+
+    let dir = root.join(Uuid::new_v4().to_string());
+    builder.recursive(false);
+    builder
+        .create(&dir)
+        .with_context(|| format!("cannot create attachment directory at {dir:?}"))?;
+
+    Ok(dir)
+}
+
+fn remove_stale_dirs(root: &Path) {
+    let entries = match fs::read_dir(root) {
+        Ok(entries) => entries,
+        Err(err) => {
+            debug!("cannot read attachments directory at {root:?}: {err}");
+            return;
+        }
+    };
+
+    for entry in entries.flatten() {
+        let is_stale_dir = entry
+            .metadata()
+            .ok()
+            .filter(|metadata| metadata.is_dir())
+            .and_then(|metadata| metadata.modified().ok())
+            .and_then(|modified| modified.elapsed().ok())
+            .is_some_and(|elapsed| elapsed > STALE_DIR_AGE);
+
+        if is_stale_dir {
+            let path = entry.path();
+            if let Err(err) = fs::remove_dir_all(&path) {
+                debug!("cannot remove attachment directory at {path:?}: {err}");
+            }
+        }
+    }
+}
@@ -0,0 +1,98 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// The fallback command used to open attachments.
+#[cfg(target_os = "macos")]
+pub const DEFAULT_VIEWER: &str = "open";
+#[cfg(not(target_os = "macos"))]
+pub const DEFAULT_VIEWER: &str = "xdg-open";
+
+/// The attachment configuration of an account.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct AttachmentConfig {
+    /// Mailcap-style mapping of MIME types to viewer commands.
+    ///
+    /// The subtype can be replaced by a wildcard, for example
+    /// image/*. The placeholder %s is replaced by the path of the
+    /// attachment, which is appended to the command otherwise. The
+    /// command is run by a shell.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub viewers: BTreeMap<String, String>,
+}
+
+impl AttachmentConfig {
+    /// Find the viewer command matching the given MIME type.
+    ///
+    /// Exact matches take precedence over wildcard ones.
+    pub fn find_viewer(&self, mime: &str) -> Option<&str> {
+        let mime = mime.to_lowercase();
+        let ctype = mime.split('/').next().unwrap_or_default();
+
+        let find = |pattern: &str| {
+            self.viewers
+                .iter()
+                .find(|(key, _)| key.to_lowercase() == pattern)
+                .map(|(_, cmd)| cmd.as_str())
+        };
+
+        find(&mime)
+            .or_else(|| find(&format!("{ctype}/*")))
+            .or_else(|| find("*/*"))
+    }
+}
+
+/// Build the shell command opening the given file with the given
+/// viewer.
+///
+/// The path is quoted, so quotes surrounding the placeholder are
+/// removed first.
+pub fn viewer_command(viewer: &str, path: &str) -> String {
+    let path = format!("'{}'", path.replace('\'', r"'\''"));
+
+    if viewer.contains("%s") {
+        viewer
+            .replace("'%s'", "%s")
+            .replace("\"%s\"", "%s")
+            .replace("%s", &path)
+    } else {
+        format!("{viewer} {path}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn find_viewer() {
+        let config = AttachmentConfig {
+            viewers: BTreeMap::from_iter([
+                ("application/pdf".into(), "zathura".into()),
+                ("image/*".into(), "feh %s".into()),
+                ("Image/SVG+XML".into(), "inkscape".into()),
+            ]),
+        };
+
+        assert_eq!(config.find_viewer("application/pdf"), Some("zathura"));
+        assert_eq!(config.find_viewer("image/png"), Some("feh %s"));
+        assert_eq!(config.find_viewer("image/svg+xml"), Some("inkscape"));
+        assert_eq!(config.find_viewer("text/plain"), None);
+    }
+
+    #[test]
+    fn build_viewer_command() {
+        assert_eq!(
+            viewer_command("feh %s", "/tmp/a b.png"),
+            "feh '/tmp/a b.png'"
+        );
+        assert_eq!(
+            viewer_command("feh --title \"%s\" '%s'", "/tmp/it's.png"),
+            r"feh --title '/tmp/it'\''s.png' '/tmp/it'\''s.png'"
+        );
+        assert_eq!(
+            viewer_command("zathura", "/tmp/it's.pdf"),
+            r"zathura '/tmp/it'\''s.pdf'"
+        );
+    }
+}
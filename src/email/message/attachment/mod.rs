@@ -1 +1,75 @@
+pub mod arg;
 pub mod command;
+pub mod config;
+
+use anyhow::Result;
+use serde::Serialize;
+use std::ops;
+
+use crate::{
+    printer::{PrintTable, PrintTableOpts, WriteColor},
+    ui::{Cell, Row, Table},
+};
+
+/// Represents an attachment of a message.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct AttachmentEntry {
+    /// The id of the envelope the attachment belongs to.
+    pub id: String,
+    /// The index of the attachment in the message, starting from 1.
+    pub index: usize,
+    pub name: Option<String>,
+    pub mime: String,
+    pub size: usize,
+}
+
+impl Table for AttachmentEntry {
+    fn head() -> Row {
+        Row::new()
+            .cell(Cell::new("ID").bold().underline().white())
+            .cell(Cell::new("INDEX").bold().underline().white())
+            .cell(Cell::new("NAME").shrinkable().bold().underline().white())
+            .cell(Cell::new("TYPE").bold().underline().white())
+            .cell(Cell::new("SIZE").bold().underline().white())
+    }
+
+    fn row(&self) -> Row {
+        Row::new()
+            .cell(Cell::new(&self.id).red())
+            .cell(Cell::new(self.index.to_string()).white())
+            .cell(
+                Cell::new(self.name.as_deref().unwrap_or_default())
+                    .shrinkable()
+                    .green(),
+            )
+            .cell(Cell::new(&self.mime).blue())
+            .cell(Cell::new(self.size.to_string()).yellow())
+    }
+}
+
+/// Represents the list of attachments of one or many messages.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct AttachmentEntries(Vec<AttachmentEntry>);
+
+impl From<Vec<AttachmentEntry>> for AttachmentEntries {
+    fn from(attachments: Vec<AttachmentEntry>) -> Self {
+        Self(attachments)
+    }
+}
+
+impl ops::Deref for AttachmentEntries {
+    type Target = Vec<AttachmentEntry>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl PrintTable for AttachmentEntries {
+    fn print_table(&self, writer: &mut dyn WriteColor, opts: PrintTableOpts) -> Result<()> {
+        writeln!(writer)?;
+        Table::print(writer, self, opts)?;
+        writeln!(writer)?;
+        Ok(())
+    }
+}
@@ -6,15 +6,20 @@ use log::info;
 #[cfg(feature = "account-sync")]
 use crate::cache::arg::disable::CacheDisableFlag;
 use crate::{
-    account::arg::name::AccountNameFlag,
+    account::arg::name::{AccountNameFlag, TargetAccountNameFlag},
     backend::Backend,
     config::TomlConfig,
     envelope::arg::ids::EnvelopeIdsArgs,
     folder::arg::name::{SourceFolderNameOptionalFlag, TargetFolderNameArg},
+    message::transfer::MessageTransfer,
     printer::Printer,
 };
 
 /// Copy a message from a source folder to a target folder.
+///
+/// The target folder can belong to another account (see the
+/// --to-account flag): the original messages are kept in the source
+/// account.
 #[derive(Debug, Parser)]
 pub struct MessageCopyCommand {
     #[command(flatten)]
@@ -26,6 +31,9 @@ pub struct MessageCopyCommand {
     #[command(flatten)]
     pub envelopes: EnvelopeIdsArgs,
 
+    #[command(flatten)]
+    pub target_account: TargetAccountNameFlag,
+
     #[cfg(feature = "account-sync")]
     #[command(flatten)]
     pub cache: CacheDisableFlag,
@@ -40,10 +48,28 @@ impl MessageCopyCommand {
 
         let source = &self.source_folder.name;
         let target = &self.target_folder.name;
-        let ids = &self.envelopes.ids;
+        let ids = &self.envelopes.ids();
+        let account = self.envelopes.account_or(self.account.name.as_deref())?;
+
+        if let Some(target_account) = self.target_account.name.as_deref() {
+            let transfer = MessageTransfer {
+                source_account: account,
+                source_folder: source,
+                target_account,
+                target_folder: target,
+                #[cfg(feature = "account-sync")]
+                disable_cache: self.cache.disable,
+            };
+
+            transfer.transfer(config, ids, false).await?;
+
+            return printer.print(format!(
+                "Message(s) successfully copied from {source} to {target_account}/{target}!"
+            ));
+        }
 
         let (toml_account_config, account_config) = config.clone().into_account_configs(
-            self.account.name.as_deref(),
+            account,
             #[cfg(feature = "account-sync")]
             self.cache.disable,
         )?;
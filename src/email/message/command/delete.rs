@@ -38,10 +38,10 @@ impl MessageDeleteCommand {
         info!("executing delete message(s) command");
 
         let folder = &self.folder.name;
-        let ids = &self.envelopes.ids;
+        let ids = &self.envelopes.ids();
 
         let (toml_account_config, account_config) = config.clone().into_account_configs(
-            self.account.name.as_deref(),
+            self.envelopes.account_or(self.account.name.as_deref())?,
             #[cfg(feature = "account-sync")]
             self.cache.disable,
         )?;
@@ -3,10 +3,12 @@ pub mod delete;
 pub mod forward;
 pub mod mailto;
 pub mod r#move;
+pub mod part;
 pub mod read;
 pub mod reply;
 pub mod save;
 pub mod send;
+pub mod structure;
 pub mod write;
 
 use anyhow::Result;
@@ -16,9 +18,9 @@ use crate::{config::TomlConfig, printer::Printer};
 
 use self::{
     copy::MessageCopyCommand, delete::MessageDeleteCommand, forward::MessageForwardCommand,
-    mailto::MessageMailtoCommand, r#move::MessageMoveCommand, read::MessageReadCommand,
-    reply::MessageReplyCommand, save::MessageSaveCommand, send::MessageSendCommand,
-    write::MessageWriteCommand,
+    mailto::MessageMailtoCommand, part::MessagePartCommand, r#move::MessageMoveCommand,
+    read::MessageReadCommand, reply::MessageReplyCommand, save::MessageSaveCommand,
+    send::MessageSendCommand, structure::MessageStructureCommand, write::MessageWriteCommand,
 };
 
 /// Manage messages.
@@ -32,6 +34,13 @@ pub enum MessageSubcommand {
     #[command(arg_required_else_help = true)]
     Read(MessageReadCommand),
 
+    #[command(arg_required_else_help = true)]
+    #[command(alias = "tree")]
+    Structure(MessageStructureCommand),
+
+    #[command(arg_required_else_help = true)]
+    Part(MessagePartCommand),
+
     #[command(aliases = ["add", "create", "new", "compose"])]
     Write(MessageWriteCommand),
 
@@ -66,6 +75,8 @@ impl MessageSubcommand {
     pub async fn execute(self, printer: &mut impl Printer, config: &TomlConfig) -> Result<()> {
         match self {
             Self::Read(cmd) => cmd.execute(printer, config).await,
+            Self::Structure(cmd) => cmd.execute(printer, config).await,
+            Self::Part(cmd) => cmd.execute(printer, config).await,
             Self::Write(cmd) => cmd.execute(printer, config).await,
             Self::Reply(cmd) => cmd.execute(printer, config).await,
             Self::Forward(cmd) => cmd.execute(printer, config).await,
@@ -7,15 +7,20 @@ use log::info;
 use crate::cache::arg::disable::CacheDisableFlag;
 #[allow(unused)]
 use crate::{
-    account::arg::name::AccountNameFlag,
+    account::arg::name::{AccountNameFlag, TargetAccountNameFlag},
     backend::Backend,
     config::TomlConfig,
     envelope::arg::ids::EnvelopeIdsArgs,
     folder::arg::name::{SourceFolderNameOptionalFlag, TargetFolderNameArg},
+    message::transfer::MessageTransfer,
     printer::Printer,
 };
 
 /// Move a message from a source folder to a target folder.
+///
+/// The target folder can belong to another account (see the
+/// --to-account flag): the original messages are then deleted from
+/// the source account.
 #[derive(Debug, Parser)]
 pub struct MessageMoveCommand {
     #[command(flatten)]
@@ -27,6 +32,9 @@ pub struct MessageMoveCommand {
     #[command(flatten)]
     pub envelopes: EnvelopeIdsArgs,
 
+    #[command(flatten)]
+    pub target_account: TargetAccountNameFlag,
+
     #[cfg(feature = "account-sync")]
     #[command(flatten)]
     pub cache: CacheDisableFlag,
@@ -41,10 +49,28 @@ impl MessageMoveCommand {
 
         let source = &self.source_folder.name;
         let target = &self.target_folder.name;
-        let ids = &self.envelopes.ids;
+        let ids = &self.envelopes.ids();
+        let account = self.envelopes.account_or(self.account.name.as_deref())?;
+
+        if let Some(target_account) = self.target_account.name.as_deref() {
+            let transfer = MessageTransfer {
+                source_account: account,
+                source_folder: source,
+                target_account,
+                target_folder: target,
+                #[cfg(feature = "account-sync")]
+                disable_cache: self.cache.disable,
+            };
+
+            transfer.transfer(config, ids, true).await?;
+
+            return printer.print(format!(
+                "Message(s) successfully moved from {source} to {target_account}/{target}!"
+            ));
+        }
 
         let (toml_account_config, account_config) = config.clone().into_account_configs(
-            self.account.name.as_deref(),
+            account,
             #[cfg(feature = "account-sync")]
             self.cache.disable,
         )?;
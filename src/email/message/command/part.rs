@@ -0,0 +1,104 @@
+use anyhow::{anyhow, bail, Context, Result};
+use clap::Parser;
+use email::backend::feature::BackendFeatureSource;
+use log::info;
+use mail_parser::MessageParser;
+use std::{
+    fs,
+    io::{self, Write},
+    path::PathBuf,
+};
+
+#[cfg(feature = "account-sync")]
+use crate::cache::arg::disable::CacheDisableFlag;
+use crate::{
+    account::arg::name::AccountNameFlag, backend::Backend, config::TomlConfig,
+    envelope::arg::ids::EnvelopeIdArg, folder::arg::name::FolderNameOptionalFlag,
+    message::structure::find_part, printer::Printer,
+};
+
+/// Extract a single part of a message.
+///
+/// This command allows you to extract the decoded body of the given
+/// MIME part, either to the standard output or to a file. Part
+/// numbers can be found using the structure command. The message is
+/// not marked as seen.
+#[derive(Debug, Parser)]
+pub struct MessagePartCommand {
+    #[command(flatten)]
+    pub folder: FolderNameOptionalFlag,
+
+    #[command(flatten)]
+    pub envelope: EnvelopeIdArg,
+
+    /// The number of the part to extract, for example 1.2.
+    #[arg(value_name = "PART", required = true)]
+    pub part: String,
+
+    /// Write the part to the given file instead of the standard
+    /// output.
+    #[arg(long, short, value_name = "PATH")]
+    pub output: Option<PathBuf>,
+
+    #[cfg(feature = "account-sync")]
+    #[command(flatten)]
+    pub cache: CacheDisableFlag,
+
+    #[command(flatten)]
+    pub account: AccountNameFlag,
+}
+
+impl MessagePartCommand {
+    pub async fn execute(self, printer: &mut impl Printer, config: &TomlConfig) -> Result<()> {
+        info!("executing message part command");
+
+        let folder = &self.folder.name;
+        let id = self.envelope.id.id;
+
+        let (toml_account_config, account_config) = config.clone().into_account_configs(
+            self.envelope.id.account_or(self.account.name.as_deref())?,
+            #[cfg(feature = "account-sync")]
+            self.cache.disable,
+        )?;
+
+        let peek_messages_kind = toml_account_config.peek_messages_kind();
+
+        let backend = Backend::new(
+            toml_account_config.clone(),
+            account_config.clone(),
+            peek_messages_kind,
+            |builder| builder.set_peek_messages(BackendFeatureSource::Context),
+        )
+        .await?;
+
+        let emails = backend.peek_messages(folder, &[id]).await?;
+        let email = emails.first().ok_or(anyhow!("cannot find message {id}"))?;
+        let msg = MessageParser::new()
+            .parse(email.raw()?)
+            .ok_or(anyhow!("cannot parse message {id}"))?;
+
+        let part = self.part.trim();
+        let contents = match find_part(&msg, part) {
+            Some(found) if found.is_multipart() => {
+                bail!("part {part} is a multipart, please select one of its sub-parts")
+            }
+            Some(found) => found.contents(),
+            None => bail!("cannot find part {part} in message {id}"),
+        };
+
+        match self.output {
+            Some(path) => {
+                fs::write(&path, contents)
+                    .with_context(|| format!("cannot write part {part} at {path:?}"))?;
+                printer.print(format!("Part {part} successfully saved at {path:?}!"))
+            }
+            None => {
+                let mut stdout = io::stdout().lock();
+                stdout
+                    .write_all(contents)
+                    .and_then(|()| stdout.flush())
+                    .with_context(|| format!("cannot write part {part} to stdout"))
+            }
+        }
+    }
+}
@@ -1,15 +1,22 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
 use clap::Parser;
 use email::backend::feature::BackendFeatureSource;
-use log::info;
+use log::{debug, info};
+use mail_parser::{MessageParser, MessagePart, MimeHeaders, PartType};
 use mml::message::FilterParts;
+use process::SingleCommand;
+use std::collections::BTreeMap;
 
 #[cfg(feature = "account-sync")]
 use crate::cache::arg::disable::CacheDisableFlag;
 #[allow(unused)]
 use crate::{
-    account::arg::name::AccountNameFlag, backend::Backend, config::TomlConfig,
-    envelope::arg::ids::EnvelopeIdsArgs, folder::arg::name::FolderNameOptionalFlag,
+    account::arg::name::AccountNameFlag,
+    backend::Backend,
+    config::TomlConfig,
+    envelope::arg::ids::EnvelopeIdsArgs,
+    folder::arg::name::FolderNameOptionalFlag,
+    message::{html, structure::mime_type},
     printer::Printer,
 };
 
@@ -18,6 +25,11 @@ use crate::{
 /// This command allows you to read a message. When reading a message,
 /// the "seen" flag is automatically applied to the corresponding
 /// envelope. To prevent this behaviour, use the --preview flag.
+///
+/// Messages without any text/plain part are rendered from their
+/// text/html parts. Parts can also be rendered by shell commands
+/// using the message.read.filters option of your TOML configuration
+/// file.
 #[derive(Debug, Parser)]
 pub struct MessageReadCommand {
     #[command(flatten)]
@@ -83,14 +95,21 @@ impl MessageReadCommand {
         info!("executing read message(s) command");
 
         let folder = &self.folder.name;
-        let ids = &self.envelopes.ids;
+        let ids = &self.envelopes.ids();
 
         let (toml_account_config, account_config) = config.clone().into_account_configs(
-            self.account.name.as_deref(),
+            self.envelopes.account_or(self.account.name.as_deref())?,
             #[cfg(feature = "account-sync")]
             self.cache.disable,
         )?;
 
+        let filters = toml_account_config
+            .message
+            .as_ref()
+            .and_then(|message| message.read.as_ref())
+            .map(|read| read.filters.clone())
+            .unwrap_or_default();
+
         let get_messages_kind = toml_account_config.get_messages_kind();
 
         let backend = Backend::new(
@@ -118,6 +137,12 @@ impl MessageReadCommand {
                 // display what can be displayed
                 bodies.push_str(&String::from_utf8_lossy(email.raw()?));
             } else {
+                let parts = if self.html {
+                    None
+                } else {
+                    render_parts(email.raw()?, &filters).await?
+                };
+
                 let tpl = email
                     .to_read_tpl(&account_config, |mut tpl| {
                         if self.no_headers {
@@ -128,12 +153,27 @@ impl MessageReadCommand {
 
                         if self.html {
                             tpl = tpl.with_filter_parts(FilterParts::Only("text/html".into()));
+                        } else if parts.is_some() {
+                            tpl = tpl.with_filter_parts(FilterParts::Only("text/plain".into()));
                         }
 
                         tpl
                     })
                     .await?;
-                bodies.push_str(&tpl);
+
+                match parts {
+                    Some(parts) => {
+                        let tpl = tpl.trim_end();
+                        bodies.push_str(tpl);
+                        if !tpl.is_empty() && !parts.is_empty() {
+                            bodies.push_str("\n\n");
+                        }
+                        bodies.push_str(&parts);
+                    }
+                    None => {
+                        bodies.push_str(&tpl);
+                    }
+                }
             }
 
             glue = "\n\n";
@@ -142,3 +182,71 @@ impl MessageReadCommand {
         printer.print(bodies)
     }
 }
+
+/// Render the parts the read template cannot render by itself.
+///
+/// Returns the rendered parts when the message has no text/plain
+/// part but has text/html ones, or when at least one part matches a
+/// configured filter. The text/html filter only applies to messages
+/// without text/plain part. Returns `None` otherwise, in which case
+/// the read template should be used as it is.
+async fn render_parts(raw: &[u8], filters: &BTreeMap<String, String>) -> Result<Option<String>> {
+    let msg = match MessageParser::new().parse(raw) {
+        Some(msg) => msg,
+        None => return Ok(None),
+    };
+
+    let parts: Vec<(String, &MessagePart)> = msg
+        .parts
+        .iter()
+        .filter(|part| !matches!(part.body, PartType::Multipart(_) | PartType::Message(_)))
+        .filter(|part| {
+            !part
+                .content_disposition()
+                .is_some_and(|disposition| disposition.is_attachment())
+        })
+        .map(|part| (mime_type(part), part))
+        .collect();
+
+    let has_plain = parts.iter().any(|(mime, _)| mime == "text/plain");
+    let has_html = parts.iter().any(|(mime, _)| mime == "text/html");
+
+    // text/html parts are alternatives to text/plain ones, so they
+    // are only rendered when there is no text/plain part, whether
+    // with a filter or not
+    let is_skipped = |mime: &str| mime == "text/plain" || (mime == "text/html" && has_plain);
+    let has_filter = parts
+        .iter()
+        .any(|(mime, _)| !is_skipped(mime) && filters.contains_key(mime));
+
+    if !has_filter && (has_plain || !has_html) {
+        return Ok(None);
+    }
+
+    let mut rendered = Vec::new();
+
+    for (mime, part) in parts {
+        if is_skipped(&mime) {
+            continue;
+        }
+
+        let text = match filters.get(&mime) {
+            Some(cmd) => {
+                debug!("rendering {mime} part using command {cmd}");
+                let output = SingleCommand::from(cmd.as_str())
+                    .run_with(part.contents())
+                    .await
+                    .with_context(|| format!("cannot render {mime} part using command {cmd}"))?;
+                String::from_utf8_lossy(&output).trim_end().to_owned()
+            }
+            None if mime == "text/html" => html::to_text(&String::from_utf8_lossy(part.contents())),
+            None => continue,
+        };
+
+        if !text.is_empty() {
+            rendered.push(text);
+        }
+    }
+
+    Ok(Some(rendered.join("\n\n")))
+}
@@ -54,8 +54,8 @@ impl MessageSaveCommand {
         .await?;
 
         let is_tty = io::stdin().is_terminal();
-        let is_json = printer.is_json();
-        let msg = if is_tty || is_json {
+        let is_machine_readable = printer.is_machine_readable();
+        let msg = if is_tty || is_machine_readable {
             self.message.raw()
         } else {
             io::stdin()
@@ -1,14 +1,16 @@
 use anyhow::Result;
 use clap::Parser;
-use email::{backend::feature::BackendFeatureSource, message::send::SendMessageThenSaveCopy};
 use log::info;
 use std::io::{self, BufRead, IsTerminal};
 
 #[cfg(feature = "account-sync")]
 use crate::cache::arg::disable::CacheDisableFlag;
 use crate::{
-    account::arg::name::AccountNameFlag, backend::Backend, config::TomlConfig,
-    message::arg::MessageRawArg, printer::Printer,
+    account::arg::name::AccountNameFlag,
+    config::TomlConfig,
+    message::arg::MessageRawArg,
+    outbox::{self, arg::queue::OutboxQueueFlag},
+    printer::Printer,
 };
 
 /// Send a message.
@@ -20,6 +22,9 @@ pub struct MessageSendCommand {
     #[command(flatten)]
     pub message: MessageRawArg,
 
+    #[command(flatten)]
+    pub queue: OutboxQueueFlag,
+
     #[cfg(feature = "account-sync")]
     #[command(flatten)]
     pub cache: CacheDisableFlag,
@@ -38,23 +43,6 @@ impl MessageSendCommand {
             self.cache.disable,
         )?;
 
-        let send_message_kind = toml_account_config.send_message_kind().into_iter().chain(
-            toml_account_config
-                .add_message_kind()
-                .filter(|_| account_config.should_save_copy_sent_message()),
-        );
-
-        let backend = Backend::new(
-            toml_account_config.clone(),
-            account_config,
-            send_message_kind,
-            |builder| {
-                builder.set_send_message(BackendFeatureSource::Context);
-                builder.set_add_message(BackendFeatureSource::Context);
-            },
-        )
-        .await?;
-
         let msg = if io::stdin().is_terminal() {
             self.message.raw()
         } else {
@@ -66,8 +54,14 @@ impl MessageSendCommand {
                 .join("\r\n")
         };
 
-        backend.send_message_then_save_copy(msg.as_bytes()).await?;
+        let res = outbox::send_or_queue(
+            toml_account_config,
+            account_config,
+            msg.as_bytes(),
+            self.queue.enable,
+        )
+        .await?;
 
-        printer.print("Message successfully sent!")
+        printer.print(res)
     }
 }
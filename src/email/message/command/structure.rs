@@ -0,0 +1,79 @@
+use anyhow::{anyhow, Result};
+use clap::Parser;
+use email::backend::feature::BackendFeatureSource;
+use log::info;
+
+#[cfg(feature = "account-sync")]
+use crate::cache::arg::disable::CacheDisableFlag;
+use crate::{
+    account::arg::name::AccountNameFlag,
+    backend::Backend,
+    config::TomlConfig,
+    envelope::arg::ids::EnvelopeIdArg,
+    folder::arg::name::FolderNameOptionalFlag,
+    message::structure::MimePart,
+    printer::{PrintTableOpts, Printer},
+    ui::arg::max_width::TableMaxWidthFlag,
+};
+
+/// Show the MIME structure of a message.
+///
+/// This command allows you to inspect the MIME part tree of a
+/// message: content types, charsets, transfer encodings, sizes,
+/// dispositions, file names and part numbers. Part numbers can then
+/// be given to the part command. The message is not marked as seen.
+#[derive(Debug, Parser)]
+pub struct MessageStructureCommand {
+    #[command(flatten)]
+    pub folder: FolderNameOptionalFlag,
+
+    #[command(flatten)]
+    pub envelope: EnvelopeIdArg,
+
+    #[command(flatten)]
+    pub table: TableMaxWidthFlag,
+
+    #[cfg(feature = "account-sync")]
+    #[command(flatten)]
+    pub cache: CacheDisableFlag,
+
+    #[command(flatten)]
+    pub account: AccountNameFlag,
+}
+
+impl MessageStructureCommand {
+    pub async fn execute(self, printer: &mut impl Printer, config: &TomlConfig) -> Result<()> {
+        info!("executing message structure command");
+
+        let folder = &self.folder.name;
+        let id = self.envelope.id.id;
+
+        let (toml_account_config, account_config) = config.clone().into_account_configs(
+            self.envelope.id.account_or(self.account.name.as_deref())?,
+            #[cfg(feature = "account-sync")]
+            self.cache.disable,
+        )?;
+
+        let peek_messages_kind = toml_account_config.peek_messages_kind();
+
+        let backend = Backend::new(
+            toml_account_config.clone(),
+            account_config.clone(),
+            peek_messages_kind,
+            |builder| builder.set_peek_messages(BackendFeatureSource::Context),
+        )
+        .await?;
+
+        let emails = backend.peek_messages(folder, &[id]).await?;
+        let email = emails.first().ok_or(anyhow!("cannot find message {id}"))?;
+        let structure = MimePart::from_raw(email.raw()?)?;
+
+        printer.print_table(
+            Box::new(structure),
+            PrintTableOpts {
+                format: &account_config.get_message_read_format(),
+                max_width: self.table.max_width,
+            },
+        )
+    }
+}
@@ -2,7 +2,7 @@ use email::message::delete::config::DeleteMessageStyle;
 #[cfg(feature = "account-sync")]
 use email::message::sync::config::MessageSyncConfig;
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashSet};
 
 use crate::backend::BackendKind;
 
@@ -112,6 +112,13 @@ impl MessagePeekConfig {
 pub struct MessageGetConfig {
     pub backend: Option<BackendKind>,
 
+    /// Shell commands used to render parts of the given MIME type.
+    ///
+    /// The body of the part is piped to the command standard input,
+    /// and the command standard output replaces it.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub filters: BTreeMap<String, String>,
+
     #[serde(flatten)]
     pub remote: email::message::get::config::MessageReadConfig,
 }
@@ -0,0 +1,433 @@
+//! Module dedicated to HTML rendering.
+//!
+//! This module contains a small HTML to plain text renderer, used to
+//! read messages that do not have any text/plain part. It does not
+//! aim to render HTML faithfully, only to make its content readable
+//! in a terminal: links are listed as footnotes, tables are flattened
+//! and images are replaced by their alternative text.
+
+/// Tags whose content is never rendered.
+const SKIPPED_TAGS: &[&str] = &["head", "script", "style", "title", "template"];
+
+/// Tags separated from their siblings by a blank line.
+const PARAGRAPH_TAGS: &[&str] = &[
+    "p",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "ul",
+    "ol",
+    "table",
+    "blockquote",
+    "pre",
+];
+
+/// Tags separated from their siblings by a line break.
+const BLOCK_TAGS: &[&str] = &[
+    "div",
+    "section",
+    "article",
+    "header",
+    "footer",
+    "nav",
+    "main",
+    "aside",
+    "form",
+    "center",
+    "dl",
+    "dt",
+    "dd",
+    "address",
+    "figure",
+    "figcaption",
+];
+
+#[derive(Debug, Default)]
+struct Renderer {
+    out: String,
+    pending_space: bool,
+    skip_depth: usize,
+    pre_depth: usize,
+    lists: Vec<Option<usize>>,
+    cells: usize,
+    link: Option<(String, usize)>,
+    links: Vec<String>,
+}
+
+impl Renderer {
+    fn ensure_newlines(&mut self, count: usize) {
+        self.pending_space = false;
+
+        if self.out.is_empty() {
+            return;
+        }
+
+        while self.out.ends_with(' ') {
+            self.out.pop();
+        }
+
+        let existing = self.out.chars().rev().take_while(|c| *c == '\n').count();
+        for _ in existing..count {
+            self.out.push('\n');
+        }
+    }
+
+    fn push_text(&mut self, text: &str) {
+        if self.skip_depth > 0 {
+            return;
+        }
+
+        if self.pre_depth > 0 {
+            self.out.push_str(text);
+            return;
+        }
+
+        for c in text.chars() {
+            if c.is_whitespace() {
+                self.pending_space = true;
+                continue;
+            }
+
+            if self.pending_space && !self.out.is_empty() && !self.out.ends_with([' ', '\n']) {
+                self.out.push(' ');
+            }
+
+            self.pending_space = false;
+            self.out.push(c);
+        }
+    }
+
+    fn push_inline(&mut self, text: &str) {
+        if self.pending_space && !self.out.is_empty() && !self.out.ends_with([' ', '\n']) {
+            self.out.push(' ');
+        }
+
+        self.pending_space = false;
+        self.out.push_str(text);
+    }
+
+    fn open_tag(&mut self, name: &str, attrs: &[(String, String)]) {
+        if SKIPPED_TAGS.contains(&name) {
+            self.skip_depth += 1;
+            return;
+        }
+
+        if self.skip_depth > 0 {
+            return;
+        }
+
+        if PARAGRAPH_TAGS.contains(&name) {
+            self.ensure_newlines(2);
+        } else if BLOCK_TAGS.contains(&name) {
+            self.ensure_newlines(1);
+        }
+
+        match name {
+            "br" => {
+                self.pending_space = false;
+                while self.out.ends_with(' ') {
+                    self.out.pop();
+                }
+                self.out.push('\n');
+            }
+            "hr" => {
+                self.ensure_newlines(1);
+                self.out.push_str("----");
+                self.ensure_newlines(1);
+            }
+            "pre" => {
+                self.pre_depth += 1;
+            }
+            "ul" => {
+                self.lists.push(None);
+            }
+            "ol" => {
+                self.lists.push(Some(0));
+            }
+            "li" => {
+                self.ensure_newlines(1);
+                let indent = "  ".repeat(self.lists.len().saturating_sub(1));
+                let bullet = match self.lists.last_mut() {
+                    Some(Some(n)) => {
+                        *n += 1;
+                        format!("{n}. ")
+                    }
+                    _ => String::from("- "),
+                };
+                self.out.push_str(&indent);
+                self.out.push_str(&bullet);
+            }
+            "tr" => {
+                self.ensure_newlines(1);
+                self.cells = 0;
+            }
+            "td" | "th" => {
+                if self.cells > 0 {
+                    self.pending_space = true;
+                    self.push_inline("|");
+                    self.pending_space = true;
+                }
+                self.cells += 1;
+            }
+            "a" => {
+                let href = attr(attrs, "href")
+                    .map(str::trim)
+                    .filter(|href| !href.is_empty() && !href.starts_with('#'));
+                self.link = href.map(|href| (href.to_owned(), self.out.len()));
+            }
+            "img" => {
+                if let Some(alt) = attr(attrs, "alt").map(str::trim) {
+                    if !alt.is_empty() {
+                        self.push_inline(&format!("[{alt}]"));
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn close_tag(&mut self, name: &str) {
+        if SKIPPED_TAGS.contains(&name) {
+            self.skip_depth = self.skip_depth.saturating_sub(1);
+            return;
+        }
+
+        if self.skip_depth > 0 {
+            return;
+        }
+
+        match name {
+            "pre" => {
+                self.pre_depth = self.pre_depth.saturating_sub(1);
+            }
+            "ul" | "ol" => {
+                self.lists.pop();
+            }
+            "a" => {
+                if let Some((href, start)) = self.link.take() {
+                    let text = self.out.get(start..).unwrap_or_default().trim();
+                    let href_text = href.strip_prefix("mailto:").unwrap_or(&href);
+                    if text != href && text != href_text {
+                        let n = match self.links.iter().position(|link| *link == href) {
+                            Some(i) => i + 1,
+                            None => {
+                                self.links.push(href);
+                                self.links.len()
+                            }
+                        };
+                        self.out.push_str(&format!("[{n}]"));
+                    }
+                }
+            }
+            _ => (),
+        }
+
+        if PARAGRAPH_TAGS.contains(&name) {
+            self.ensure_newlines(2);
+        } else if BLOCK_TAGS.contains(&name) || name == "li" || name == "tr" {
+            self.ensure_newlines(1);
+        }
+    }
+
+    fn finish(self) -> String {
+        let mut text = String::new();
+        let mut blank_lines = 0;
+
+        for line in self.out.lines() {
+            let line = line.trim_end();
+
+            if line.is_empty() {
+                blank_lines += 1;
+                if blank_lines > 1 || text.is_empty() {
+                    continue;
+                }
+            } else {
+                blank_lines = 0;
+            }
+
+            text.push_str(line);
+            text.push('\n');
+        }
+
+        let mut text = text.trim_end().to_owned();
+
+        if !self.links.is_empty() {
+            text.push_str("\n\n");
+            for (i, link) in self.links.iter().enumerate() {
+                text.push_str(&format!("[{}] {link}\n", i + 1));
+            }
+            text = text.trim_end().to_owned();
+        }
+
+        text
+    }
+}
+
+fn attr<'a>(attrs: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    attrs
+        .iter()
+        .find(|(key, _)| key == name)
+        .map(|(_, val)| val.as_str())
+}
+
+/// Parse the inside of a tag (without the angle brackets) into its
+/// lowercased name, its attributes and whether it is a closing tag.
+fn parse_tag(tag: &str) -> (String, Vec<(String, String)>, bool) {
+    let tag = tag.trim().trim_end_matches('/');
+    let (closing, tag) = match tag.strip_prefix('/') {
+        Some(tag) => (true, tag.trim_start()),
+        None => (false, tag),
+    };
+
+    let name_end = tag.find(|c: char| c.is_whitespace()).unwrap_or(tag.len());
+    let name = tag[..name_end].to_lowercase();
+
+    let mut attrs = Vec::new();
+    let mut rest = tag[name_end..].trim_start();
+
+    while !rest.is_empty() {
+        let key_end = rest
+            .find(|c: char| c.is_whitespace() || c == '=')
+            .unwrap_or(rest.len());
+        let key = rest[..key_end].to_lowercase();
+        rest = rest[key_end..].trim_start();
+
+        let val = match rest.strip_prefix('=') {
+            Some(val) => {
+                let val = val.trim_start();
+                let (val, next) = match val.chars().next() {
+                    Some(quote @ ('"' | '\'')) => {
+                        let val = &val[1..];
+                        let end = val.find(quote).unwrap_or(val.len());
+                        (&val[..end], val.get(end + 1..).unwrap_or_default())
+                    }
+                    _ => {
+                        let end = val.find(|c: char| c.is_whitespace()).unwrap_or(val.len());
+                        (&val[..end], &val[end..])
+                    }
+                };
+                rest = next.trim_start();
+                decode_entities(val)
+            }
+            None => String::new(),
+        };
+
+        if !key.is_empty() {
+            attrs.push((key, val));
+        }
+    }
+
+    (name, attrs, closing)
+}
+
+/// Decode HTML character references of the given text.
+///
+/// Non-breaking spaces are decoded as regular spaces, so that they
+/// are collapsed like any other whitespace.
+pub fn decode_entities(text: &str) -> String {
+    html_escape::decode_html_entities(text).replace('\u{a0}', " ")
+}
+
+/// Render the given HTML as plain text.
+pub fn to_text(html: &str) -> String {
+    let mut renderer = Renderer::default();
+    let mut rest = html;
+
+    while !rest.is_empty() {
+        match rest.find('<') {
+            None => {
+                renderer.push_text(&decode_entities(rest));
+                break;
+            }
+            Some(start) => {
+                renderer.push_text(&decode_entities(&rest[..start]));
+                rest = &rest[start..];
+
+                if let Some(comment) = rest.strip_prefix("<!--") {
+                    rest = match comment.find("-->") {
+                        Some(end) => &comment[end + 3..],
+                        None => "",
+                    };
+                    continue;
+                }
+
+                match rest.find('>') {
+                    Some(end) => {
+                        let tag = &rest[1..end];
+                        rest = &rest[end + 1..];
+
+                        if tag.starts_with(['!', '?']) {
+                            continue;
+                        }
+
+                        let (name, attrs, closing) = parse_tag(tag);
+                        if closing {
+                            renderer.close_tag(&name);
+                        } else {
+                            renderer.open_tag(&name, &attrs);
+                        }
+                    }
+                    None => {
+                        renderer.push_text(rest);
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    renderer.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paragraphs_and_whitespaces() {
+        let html = "<html><head><title>t</title><style>p{}</style></head><body>\n<p>Hello   <b>world</b>!</p><p>Second<br>line</p></body></html>";
+        assert_eq!(to_text(html), "Hello world!\n\nSecond\nline");
+    }
+
+    #[test]
+    fn links_as_footnotes() {
+        let html = r#"<p>See <a href="https://a.org">this</a> and <a href='https://b.org'>that</a>, or <a href="https://a.org">this again</a>.</p><p><a href="https://c.org">https://c.org</a></p>"#;
+        assert_eq!(
+            to_text(html),
+            "See this[1] and that[2], or this again[1].\n\nhttps://c.org\n\n[1] https://a.org\n[2] https://b.org"
+        );
+    }
+
+    #[test]
+    fn tables_lists_and_images() {
+        let html = r#"<table><tr><th>Name</th><th>Qty</th></tr><tr><td>Apple</td><td>3</td></tr></table><ul><li>one</li><li>two<ol><li>a</li></ol></li></ul><img src="x.png" alt="Logo"><img src="y.png">"#;
+        assert_eq!(
+            to_text(html),
+            "Name | Qty\nApple | 3\n\n- one\n- two\n\n  1. a\n\n[Logo]"
+        );
+    }
+
+    #[test]
+    fn entities() {
+        assert_eq!(
+            decode_entities("a &amp; b &lt;c&gt; &#233;&#x41; &unknown; & done"),
+            "a & b <c> éA &unknown; & done"
+        );
+        assert_eq!(
+            to_text("<p>caf&eacute; &amp; t&#233;&nbsp;&hearts;</p>"),
+            "café & té ♥"
+        );
+    }
+
+    #[test]
+    fn preformatted() {
+        let html = "<p>code:</p><pre>fn main() {\n    exit(0);\n}</pre><!-- comment --><p>end</p>";
+        assert_eq!(
+            to_text(html),
+            "code:\n\nfn main() {\n    exit(0);\n}\n\nend"
+        );
+    }
+}
@@ -2,4 +2,7 @@ pub mod arg;
 pub mod attachment;
 pub mod command;
 pub mod config;
+pub mod html;
+pub mod structure;
 pub mod template;
+pub mod transfer;
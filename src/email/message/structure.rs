@@ -0,0 +1,291 @@
+//! Module dedicated to the MIME structure of messages.
+//!
+//! This module contains the representation of the MIME part tree of
+//! a message, used to inspect its structure and to extract single
+//! parts. Parts are numbered the IMAP way: the children of a
+//! multipart are numbered from 1, nested parts are separated by dots
+//! and the root multipart has an empty number.
+
+use anyhow::{anyhow, Result};
+use mail_parser::{Message, MessageParser, MessagePart, MimeHeaders, PartType};
+use serde::Serialize;
+
+use crate::{
+    printer::{PrintTable, PrintTableOpts, WriteColor},
+    ui::{Cell, Row, Table},
+};
+
+/// Get the lowercased MIME type of the given part.
+pub fn mime_type(part: &MessagePart) -> String {
+    match part.content_type() {
+        Some(ctype) => match ctype.subtype() {
+            Some(subtype) => format!("{}/{}", ctype.ctype(), subtype).to_lowercase(),
+            None => ctype.ctype().to_lowercase(),
+        },
+        None => match part.body {
+            PartType::Html(_) => String::from("text/html"),
+            PartType::Text(_) => String::from("text/plain"),
+            PartType::Message(_) => String::from("message/rfc822"),
+            PartType::Multipart(_) => String::from("multipart/mixed"),
+            _ => String::from("application/octet-stream"),
+        },
+    }
+}
+
+fn child_number(parent: &str, index: usize) -> String {
+    if parent.is_empty() {
+        index.to_string()
+    } else {
+        format!("{parent}.{index}")
+    }
+}
+
+fn root_number(msg: &Message, prefix: &str) -> String {
+    match msg.parts.first() {
+        Some(root) if root.is_multipart() => prefix.to_owned(),
+        _ => child_number(prefix, 1),
+    }
+}
+
+/// Represents a MIME part and its sub-parts.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct MimePart {
+    pub number: String,
+    pub content_type: String,
+    pub charset: Option<String>,
+    pub encoding: Option<String>,
+    /// The size of the part body, as found in the raw message.
+    pub size: usize,
+    pub disposition: Option<String>,
+    pub filename: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub parts: Vec<MimePart>,
+}
+
+impl MimePart {
+    /// Parse the MIME part tree of the given raw message.
+    pub fn from_raw(raw: &[u8]) -> Result<Self> {
+        let msg = MessageParser::new()
+            .parse(raw)
+            .ok_or(anyhow!("cannot parse message"))?;
+        Self::from_message(&msg, "").ok_or(anyhow!("cannot find any MIME part"))
+    }
+
+    fn from_message(msg: &Message, prefix: &str) -> Option<Self> {
+        let root = msg.parts.first()?;
+        Some(Self::from_part(msg, root, root_number(msg, prefix)))
+    }
+
+    fn from_part(msg: &Message, part: &MessagePart, number: String) -> Self {
+        let parts = match &part.body {
+            PartType::Multipart(ids) => ids
+                .iter()
+                .enumerate()
+                .filter_map(|(i, id)| {
+                    let part = msg.parts.get(*id)?;
+                    Some(Self::from_part(msg, part, child_number(&number, i + 1)))
+                })
+                .collect(),
+            PartType::Message(nested) => Self::from_message(nested, &number).into_iter().collect(),
+            _ => Vec::new(),
+        };
+
+        Self {
+            content_type: mime_type(part),
+            charset: part
+                .content_type()
+                .and_then(|ctype| ctype.attribute("charset"))
+                .map(ToOwned::to_owned),
+            encoding: part.content_transfer_encoding().map(str::to_lowercase),
+            size: part.offset_end.saturating_sub(part.offset_body),
+            disposition: part
+                .content_disposition()
+                .map(|disposition| disposition.ctype().to_lowercase()),
+            filename: part.attachment_name().map(ToOwned::to_owned),
+            number,
+            parts,
+        }
+    }
+
+    /// Flatten the tree into a list of parts, prefixing content
+    /// types with tree branches so the tree can be displayed as an
+    /// indented tree inside a table.
+    fn flatten(&self, prefix: &str, is_last: bool, is_root: bool, parts: &mut Vec<MimePart>) {
+        let mut part = self.clone();
+        part.parts = Vec::new();
+
+        let prefix = if is_root {
+            String::new()
+        } else {
+            let branch = if is_last { "└─ " } else { "├─ " };
+            part.content_type = format!("{prefix}{branch}{}", part.content_type);
+            let indent = if is_last { "   " } else { "│  " };
+            format!("{prefix}{indent}")
+        };
+
+        parts.push(part);
+
+        let len = self.parts.len();
+        for (i, child) in self.parts.iter().enumerate() {
+            child.flatten(&prefix, i + 1 == len, false, parts);
+        }
+    }
+}
+
+impl Table for MimePart {
+    fn head() -> Row {
+        Row::new()
+            .cell(Cell::new("PART").bold().underline().white())
+            .cell(Cell::new("TYPE").bold().underline().white())
+            .cell(Cell::new("CHARSET").bold().underline().white())
+            .cell(Cell::new("ENCODING").bold().underline().white())
+            .cell(Cell::new("SIZE").bold().underline().white())
+            .cell(Cell::new("DISPOSITION").bold().underline().white())
+            .cell(
+                Cell::new("FILENAME")
+                    .shrinkable()
+                    .bold()
+                    .underline()
+                    .white(),
+            )
+    }
+
+    fn row(&self) -> Row {
+        Row::new()
+            .cell(Cell::new(&self.number).red())
+            .cell(Cell::new(&self.content_type).green())
+            .cell(Cell::new(self.charset.as_deref().unwrap_or_default()).white())
+            .cell(Cell::new(self.encoding.as_deref().unwrap_or_default()).white())
+            .cell(Cell::new(self.size.to_string()).yellow())
+            .cell(Cell::new(self.disposition.as_deref().unwrap_or_default()).white())
+            .cell(
+                Cell::new(self.filename.as_deref().unwrap_or_default())
+                    .shrinkable()
+                    .blue(),
+            )
+    }
+}
+
+impl PrintTable for MimePart {
+    fn print_table(&self, writer: &mut dyn WriteColor, opts: PrintTableOpts) -> Result<()> {
+        let mut parts = Vec::new();
+        self.flatten("", true, true, &mut parts);
+
+        writeln!(writer)?;
+        Table::print(writer, &parts, opts)?;
+        writeln!(writer)?;
+        Ok(())
+    }
+}
+
+/// Find the part matching the given number in the given message.
+pub fn find_part<'a, 'x>(msg: &'a Message<'x>, number: &str) -> Option<&'a MessagePart<'x>> {
+    find_in_message(msg, "", number)
+}
+
+fn find_in_message<'a, 'x>(
+    msg: &'a Message<'x>,
+    prefix: &str,
+    number: &str,
+) -> Option<&'a MessagePart<'x>> {
+    let root = msg.parts.first()?;
+    find_in_part(msg, root, root_number(msg, prefix), number)
+}
+
+fn find_in_part<'a, 'x>(
+    msg: &'a Message<'x>,
+    part: &'a MessagePart<'x>,
+    part_number: String,
+    number: &str,
+) -> Option<&'a MessagePart<'x>> {
+    if part_number == number {
+        return Some(part);
+    }
+
+    match &part.body {
+        PartType::Multipart(ids) => ids.iter().enumerate().find_map(|(i, id)| {
+            let child = msg.parts.get(*id)?;
+            find_in_part(msg, child, child_number(&part_number, i + 1), number)
+        }),
+        PartType::Message(nested) => find_in_message(nested, &part_number, number),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RAW: &[u8] = concat!(
+        "From: alice@localhost\r\n",
+        "Subject: structure\r\n",
+        "Content-Type: multipart/mixed; boundary=\"outer\"\r\n",
+        "\r\n",
+        "--outer\r\n",
+        "Content-Type: multipart/alternative; boundary=\"inner\"\r\n",
+        "\r\n",
+        "--inner\r\n",
+        "Content-Type: text/plain; charset=utf-8\r\n",
+        "\r\n",
+        "Hello!\r\n",
+        "--inner\r\n",
+        "Content-Type: text/html; charset=utf-8\r\n",
+        "\r\n",
+        "<p>Hello!</p>\r\n",
+        "--inner--\r\n",
+        "--outer\r\n",
+        "Content-Type: application/pdf\r\n",
+        "Content-Transfer-Encoding: base64\r\n",
+        "Content-Disposition: attachment; filename=\"doc.pdf\"\r\n",
+        "\r\n",
+        "SGVsbG8=\r\n",
+        "--outer--\r\n",
+    )
+    .as_bytes();
+
+    #[test]
+    fn parse_structure() {
+        let root = MimePart::from_raw(RAW).unwrap();
+
+        assert_eq!(root.number, "");
+        assert_eq!(root.content_type, "multipart/mixed");
+        assert_eq!(root.parts.len(), 2);
+
+        let alternative = &root.parts[0];
+        assert_eq!(alternative.number, "1");
+        assert_eq!(alternative.parts[0].number, "1.1");
+        assert_eq!(alternative.parts[0].content_type, "text/plain");
+        assert_eq!(alternative.parts[0].charset.as_deref(), Some("utf-8"));
+        assert_eq!(alternative.parts[1].number, "1.2");
+        assert_eq!(alternative.parts[1].content_type, "text/html");
+
+        let pdf = &root.parts[1];
+        assert_eq!(pdf.number, "2");
+        assert_eq!(pdf.encoding.as_deref(), Some("base64"));
+        assert_eq!(pdf.disposition.as_deref(), Some("attachment"));
+        assert_eq!(pdf.filename.as_deref(), Some("doc.pdf"));
+    }
+
+    #[test]
+    fn find_parts() {
+        let msg = MessageParser::new().parse(RAW).unwrap();
+
+        let part = find_part(&msg, "1.2").unwrap();
+        assert_eq!(part.text_contents().map(str::trim), Some("<p>Hello!</p>"));
+
+        let part = find_part(&msg, "2").unwrap();
+        assert_eq!(part.contents(), b"Hello");
+
+        assert!(find_part(&msg, "3").is_none());
+        assert!(find_part(&msg, "1.3").is_none());
+    }
+
+    #[test]
+    fn single_part_message() {
+        let raw = b"Subject: single\r\n\r\nHello!\r\n";
+        let root = MimePart::from_raw(raw).unwrap();
+        assert_eq!(root.number, "1");
+        assert_eq!(root.content_type, "text/plain");
+        assert!(root.parts.is_empty());
+    }
+}
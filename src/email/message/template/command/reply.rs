@@ -10,6 +10,7 @@ use crate::{
     backend::Backend,
     config::TomlConfig,
     envelope::arg::ids::EnvelopeIdArg,
+    error::ErrorKind,
     folder::arg::name::FolderNameOptionalFlag,
     message::arg::{body::MessageRawBodyArg, header::HeaderRawArgs, reply::MessageReplyAllArg},
     printer::Printer,
@@ -51,10 +52,10 @@ impl TemplateReplyCommand {
         info!("executing reply template command");
 
         let folder = &self.folder.name;
-        let id = self.envelope.id;
+        let id = self.envelope.id.id;
 
         let (toml_account_config, account_config) = config.clone().into_account_configs(
-            self.account.name.as_deref(),
+            self.envelope.id.account_or(self.account.name.as_deref())?,
             #[cfg(feature = "account-sync")]
             self.cache.disable,
         )?;
@@ -73,7 +74,7 @@ impl TemplateReplyCommand {
             .get_messages(folder, &[id])
             .await?
             .first()
-            .ok_or(anyhow!("cannot find message {id}"))?
+            .ok_or_else(|| ErrorKind::NotFound.attach(anyhow!("cannot find message {id}")))?
             .to_reply_tpl_builder(account_config)
             .with_headers(self.headers.raw)
             .with_body(self.body.raw())
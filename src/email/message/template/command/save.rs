@@ -58,8 +58,8 @@ impl TemplateSaveCommand {
         .await?;
 
         let is_tty = io::stdin().is_terminal();
-        let is_json = printer.is_json();
-        let tpl = if is_tty || is_json {
+        let is_machine_readable = printer.is_machine_readable();
+        let tpl = if is_tty || is_machine_readable {
             self.template.raw()
         } else {
             io::stdin()
@@ -1,6 +1,5 @@
 use anyhow::Result;
 use clap::Parser;
-use email::{backend::feature::BackendFeatureSource, message::send::SendMessageThenSaveCopy};
 use log::info;
 use mml::MmlCompilerBuilder;
 use std::io::{self, BufRead, IsTerminal};
@@ -8,8 +7,12 @@ use std::io::{self, BufRead, IsTerminal};
 #[cfg(feature = "account-sync")]
 use crate::cache::arg::disable::CacheDisableFlag;
 use crate::{
-    account::arg::name::AccountNameFlag, backend::Backend, config::TomlConfig,
-    email::template::arg::TemplateRawArg, printer::Printer,
+    account::arg::name::AccountNameFlag,
+    config::TomlConfig,
+    email::template::arg::TemplateRawArg,
+    outbox::{self, arg::queue::OutboxQueueFlag, Outbox, SendOutcome},
+    printer::Printer,
+    schedule::arg::send_at::ScheduleSendAtFlags,
 };
 
 /// Send a template.
@@ -23,6 +26,12 @@ pub struct TemplateSendCommand {
     #[command(flatten)]
     pub template: TemplateRawArg,
 
+    #[command(flatten)]
+    pub queue: OutboxQueueFlag,
+
+    #[command(flatten)]
+    pub schedule: ScheduleSendAtFlags,
+
     #[cfg(feature = "account-sync")]
     #[command(flatten)]
     pub cache: CacheDisableFlag,
@@ -41,23 +50,6 @@ impl TemplateSendCommand {
             self.cache.disable,
         )?;
 
-        let send_message_kind = toml_account_config.send_message_kind().into_iter().chain(
-            toml_account_config
-                .add_message_kind()
-                .filter(|_| account_config.should_save_copy_sent_message()),
-        );
-
-        let backend = Backend::new(
-            toml_account_config.clone(),
-            account_config.clone(),
-            send_message_kind,
-            |builder| {
-                builder.set_send_message(BackendFeatureSource::Context);
-                builder.set_add_message(BackendFeatureSource::Context);
-            },
-        )
-        .await?;
-
         let tpl = if io::stdin().is_terminal() {
             self.template.raw()
         } else {
@@ -77,8 +69,18 @@ impl TemplateSendCommand {
 
         let msg = compiler.build(tpl.as_str())?.compile().await?.into_vec()?;
 
-        backend.send_message_then_save_copy(&msg).await?;
+        if let Some(send_at) = self.schedule.send_at() {
+            let queued = Outbox::new(&account_config)?.schedule(&msg, send_at)?;
+            return printer.print(SendOutcome::Scheduled {
+                id: queued.id,
+                send_at,
+            });
+        }
+
+        let res =
+            outbox::send_or_queue(toml_account_config, account_config, &msg, self.queue.enable)
+                .await?;
 
-        printer.print("Message successfully sent!")
+        printer.print(res)
     }
 }
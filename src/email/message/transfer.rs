@@ -0,0 +1,191 @@
+//! Module dedicated to message transfers between accounts.
+
+use anyhow::{anyhow, Context, Result};
+use email::{
+    backend::feature::BackendFeatureSource, envelope::Id, message::delete::DeleteMessages,
+};
+use log::{debug, warn};
+use mail_parser::MessageParser;
+use std::collections::HashMap;
+
+use crate::{backend::Backend, config::TomlConfig, envelope::thread};
+
+/// Represents a transfer of messages from a folder of a source
+/// account to a folder of a target account.
+pub struct MessageTransfer<'a> {
+    pub source_account: Option<&'a str>,
+    pub source_folder: &'a str,
+    pub target_account: &'a str,
+    pub target_folder: &'a str,
+    #[cfg(feature = "account-sync")]
+    pub disable_cache: bool,
+}
+
+impl MessageTransfer<'_> {
+    /// Transfer the given messages.
+    ///
+    /// Raw messages are peeked at once from the source account, then
+    /// added to the target account with the flags of their envelope.
+    /// If a message cannot be added, the messages already added are
+    /// deleted from the target account, so that the transfer can be
+    /// run again without duplicating them. If delete is enabled, the
+    /// original messages are deleted once they have all been added to
+    /// the target account.
+    pub async fn transfer(&self, config: &TomlConfig, ids: &[usize], delete: bool) -> Result<()> {
+        let (source_toml_account_config, source_account_config) =
+            config.clone().into_account_configs(
+                self.source_account,
+                #[cfg(feature = "account-sync")]
+                self.disable_cache,
+            )?;
+
+        let (target_toml_account_config, target_account_config) =
+            config.clone().into_account_configs(
+                Some(self.target_account),
+                #[cfg(feature = "account-sync")]
+                self.disable_cache,
+            )?;
+
+        let source_kinds = [
+            source_toml_account_config.get_envelope_kind(),
+            source_toml_account_config.peek_messages_kind(),
+            source_toml_account_config
+                .delete_messages_kind()
+                .filter(|_| delete),
+        ];
+
+        let source = Backend::new(
+            source_toml_account_config.clone(),
+            source_account_config,
+            source_kinds.into_iter().flatten(),
+            |builder| {
+                builder.set_get_envelope(BackendFeatureSource::Context);
+                builder.set_peek_messages(BackendFeatureSource::Context);
+                if delete {
+                    builder.set_delete_messages(BackendFeatureSource::Context);
+                }
+            },
+        )
+        .await?;
+
+        let target_kinds = [
+            target_toml_account_config.add_message_kind(),
+            target_toml_account_config.delete_messages_kind(),
+        ];
+
+        let target = Backend::new(
+            target_toml_account_config.clone(),
+            target_account_config,
+            target_kinds.into_iter().flatten(),
+            |builder| {
+                builder.set_add_message(BackendFeatureSource::Context);
+                builder.set_delete_messages(BackendFeatureSource::Context);
+            },
+        )
+        .await?;
+
+        let mut envelopes = Vec::with_capacity(ids.len());
+        for id in ids {
+            envelopes.push(source.get_envelope(self.source_folder, *id).await?);
+        }
+
+        let msgs = source.peek_messages(self.source_folder, ids).await?;
+        let raws = msgs
+            .iter()
+            .map(|msg| msg.raw())
+            .collect::<Result<Vec<_>, _>>()?;
+        let msgs = index_by_message_id(raws);
+
+        let mut added = Vec::with_capacity(ids.len());
+
+        for (id, envelope) in ids.iter().zip(&envelopes) {
+            let res = match msgs.get(&thread::normalize_message_id(&envelope.message_id)) {
+                Some(msg) => target
+                    .add_message_with_flags_and_alias(self.target_folder, msg, &envelope.flags)
+                    .await
+                    .with_context(|| format!("cannot add message {id} to {}", self.target_account)),
+                None => Err(anyhow!("cannot find message {id}")),
+            };
+
+            match res {
+                Ok(added_id) => {
+                    debug!("message {id} added to {}", self.target_account);
+                    added.push((*added_id).to_owned());
+                }
+                Err(err) => {
+                    self.rollback(&target, added).await;
+                    return Err(err);
+                }
+            }
+        }
+
+        if delete {
+            source.delete_messages(self.source_folder, ids).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Delete the given messages from the target account.
+    ///
+    /// Errors are only logged, since the error that caused the
+    /// rollback matters more.
+    async fn rollback(&self, target: &Backend, added: Vec<String>) {
+        if added.is_empty() {
+            return;
+        }
+
+        let count = added.len();
+        debug!(
+            "deleting {count} message(s) added to {}",
+            self.target_account
+        );
+
+        let ids = Id::multiple(added);
+        if let Err(err) = target
+            .backend
+            .delete_messages(self.target_folder, &ids)
+            .await
+        {
+            warn!(
+                "cannot delete {count} message(s) already added to {}: {err}",
+                self.target_account
+            );
+            debug!("{err:?}");
+        }
+    }
+}
+
+/// Index the given raw messages by their normalized Message-ID.
+///
+/// Backends do not necessarily return peeked messages in the order
+/// of the requested ids, so messages are matched to their envelope
+/// using their Message-ID.
+fn index_by_message_id<'a>(raws: impl IntoIterator<Item = &'a [u8]>) -> HashMap<String, &'a [u8]> {
+    raws.into_iter()
+        .filter_map(|raw| Some((message_id(raw)?, raw)))
+        .collect()
+}
+
+fn message_id(raw: &[u8]) -> Option<String> {
+    let msg = MessageParser::new().parse_headers(raw)?;
+    msg.message_id().map(thread::normalize_message_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::index_by_message_id;
+
+    #[test]
+    fn match_messages_by_id() {
+        let a: &[u8] = b"Message-ID: <a@localhost>\r\nSubject: a\r\n\r\nbody";
+        let b: &[u8] = b"Message-Id: b@localhost\r\nSubject: b\r\n\r\nbody";
+        let c: &[u8] = b"Subject: c\r\n\r\nbody";
+
+        let index = index_by_message_id([b, c, a]);
+
+        assert_eq!(index.len(), 2);
+        assert_eq!(index.get("a@localhost"), Some(&a));
+        assert_eq!(index.get("b@localhost"), Some(&b));
+    }
+}
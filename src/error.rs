@@ -0,0 +1,300 @@
+//! Module dedicated to errors reported by the CLI.
+//!
+//! Errors are classified into kinds, each kind having its own exit
+//! code so that wrappers can react accordingly. In machine-readable
+//! output modes, errors are printed to stderr as a JSON object:
+//!
+//! ```json
+//! {"error": {"kind": "auth", "message": "…", "causes": ["…"]}}
+//! ```
+
+use anyhow::Error;
+use serde::Serialize;
+use std::{error, fmt, io, process::ExitCode};
+
+/// The exit codes documentation, displayed by the long help.
+pub const EXIT_CODES_HELP: &str = "\
+Exit codes:
+  0  success
+  1  any other error
+  2  invalid command line arguments
+  3  configuration error
+  4  authentication error
+  5  network error
+  6  item not found (account, folder, message…)
+  7  invalid search query";
+
+/// Represents the kind of an error.
+///
+/// The exit code of each kind is part of the public interface and
+/// must not change:
+///
+///  - 1: any other error
+///  - 2: invalid command line arguments (reported by clap)
+///  - 3: configuration error
+///  - 4: authentication error
+///  - 5: network error
+///  - 6: item not found (account, folder, message…)
+///  - 7: invalid search query
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    #[default]
+    Other,
+    InvalidArgs,
+    Config,
+    Auth,
+    Network,
+    NotFound,
+    InvalidQuery,
+}
+
+impl ErrorKind {
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Self::Other => 1,
+            Self::InvalidArgs => 2,
+            Self::Config => 3,
+            Self::Auth => 4,
+            Self::Network => 5,
+            Self::NotFound => 6,
+            Self::InvalidQuery => 7,
+        }
+    }
+
+    /// Find the kind of the given error.
+    ///
+    /// Kinds explicitly attached with [`ErrorKindExt`] take
+    /// precedence. Otherwise network and not found errors are
+    /// detected from the I/O errors of the chain, and any other error
+    /// is reported as [`ErrorKind::Other`].
+    pub fn of(err: &Error) -> Self {
+        if let Some(kind) = err
+            .chain()
+            .find_map(|cause| cause.downcast_ref::<KindError>())
+            .map(|err| err.kind)
+        {
+            return kind;
+        }
+
+        err.chain()
+            .filter_map(|cause| cause.downcast_ref::<io::Error>())
+            .find_map(|err| Self::from_io_error_kind(err.kind()))
+            .unwrap_or_default()
+    }
+
+    /// Find the kind of an error that occurred while connecting then
+    /// authenticating to a remote server.
+    ///
+    /// Errors caused by I/O come from the connection, and errors
+    /// caused by the server rejecting the credentials come from the
+    /// authentication. Any other error (TLS, protocol…) is reported
+    /// as [`ErrorKind::Other`].
+    pub fn of_remote(err: &Error) -> Self {
+        match Self::of(err) {
+            Self::Other if err.chain().any(|cause| cause.is::<io::Error>()) => Self::Network,
+            Self::Other if err.chain().any(is_auth_failure) => Self::Auth,
+            kind => kind,
+        }
+    }
+
+    /// Attach the kind to the given error.
+    pub fn attach(self, err: Error) -> Error {
+        Error::new(KindError { kind: self, err })
+    }
+
+    fn from_io_error_kind(kind: io::ErrorKind) -> Option<Self> {
+        use io::ErrorKind::*;
+
+        match kind {
+            ConnectionRefused | ConnectionReset | ConnectionAborted | NotConnected
+            | AddrNotAvailable | TimedOut => Some(Self::Network),
+            NotFound => Some(Self::NotFound),
+            _ => None,
+        }
+    }
+}
+
+/// Check if the given error is the rejection of the credentials by
+/// a remote server.
+fn is_auth_failure(err: &(dyn error::Error + 'static)) -> bool {
+    #[cfg(feature = "imap")]
+    if let Some(imap::Error::No(_)) = err.downcast_ref::<imap::Error>() {
+        // the only commands sent while connecting are the ones
+        // authenticating the client
+        return true;
+    }
+
+    #[cfg(feature = "smtp")]
+    if let Some(mail_send::Error::AuthenticationFailed(_)) = err.downcast_ref::<mail_send::Error>()
+    {
+        return true;
+    }
+
+    #[cfg(not(any(feature = "imap", feature = "smtp")))]
+    let _ = err;
+
+    false
+}
+
+/// Error wrapper attaching a kind to an error.
+///
+/// The wrapper is transparent: it displays as the wrapped error and
+/// exposes the same causes, so that it does not alter error messages.
+#[derive(Debug)]
+struct KindError {
+    kind: ErrorKind,
+    err: Error,
+}
+
+impl fmt::Display for KindError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.err)
+    }
+}
+
+impl error::Error for KindError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        self.err.chain().nth(1)
+    }
+}
+
+/// Extension trait attaching kinds to errors.
+pub trait ErrorKindExt<T> {
+    fn with_kind(self, kind: ErrorKind) -> anyhow::Result<T>;
+
+    /// Attach the kind of an error that occurred while connecting
+    /// then authenticating to a remote server, see
+    /// [`ErrorKind::of_remote`].
+    fn with_remote_kind(self) -> anyhow::Result<T>;
+}
+
+impl<T> ErrorKindExt<T> for anyhow::Result<T> {
+    fn with_kind(self, kind: ErrorKind) -> anyhow::Result<T> {
+        self.map_err(|err| kind.attach(err))
+    }
+
+    fn with_remote_kind(self) -> anyhow::Result<T> {
+        self.map_err(|err| ErrorKind::of_remote(&err).attach(err))
+    }
+}
+
+/// Represents the JSON error object printed in machine-readable
+/// output modes.
+#[derive(Debug, Serialize)]
+pub struct ErrorReport {
+    error: ErrorReportInner,
+}
+
+#[derive(Debug, Serialize)]
+struct ErrorReportInner {
+    kind: ErrorKind,
+    message: String,
+    causes: Vec<String>,
+}
+
+impl From<&Error> for ErrorReport {
+    fn from(err: &Error) -> Self {
+        Self {
+            error: ErrorReportInner {
+                kind: ErrorKind::of(err),
+                message: err.to_string(),
+                causes: err.chain().skip(1).map(ToString::to_string).collect(),
+            },
+        }
+    }
+}
+
+/// Report the given error to stderr, then return the exit code
+/// matching its kind.
+pub fn report(err: &Error, json: bool) -> ExitCode {
+    if json {
+        let report = ErrorReport::from(err);
+        match serde_json::to_string(&report) {
+            Ok(report) => eprintln!("{report}"),
+            Err(_) => eprintln!("Error: {err:?}"),
+        }
+    } else {
+        eprintln!("Error: {err:?}");
+    }
+
+    ExitCode::from(ErrorKind::of(err).exit_code())
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::{anyhow, Context, Result};
+    use std::io;
+
+    use super::{ErrorKind, ErrorKindExt, ErrorReport};
+
+    #[test]
+    fn explicit_kinds() {
+        let res: Result<()> = Err(anyhow!("cannot parse query")).with_kind(ErrorKind::InvalidQuery);
+        let err = res.context("cannot list envelopes").unwrap_err();
+
+        assert_eq!(ErrorKind::of(&err), ErrorKind::InvalidQuery);
+        assert_eq!(
+            err.chain()
+                .map(|cause| cause.to_string())
+                .collect::<Vec<_>>(),
+            ["cannot list envelopes", "cannot parse query"]
+        );
+    }
+
+    #[test]
+    fn io_kinds() {
+        let err = anyhow!(io::Error::from(io::ErrorKind::ConnectionRefused))
+            .context("cannot build imap client");
+        assert_eq!(ErrorKind::of(&err), ErrorKind::Network);
+
+        // messages are never inspected
+        let err = anyhow!("authentication failed").context("cannot connect to imap server");
+        assert_eq!(ErrorKind::of(&err), ErrorKind::Other);
+        assert_eq!(
+            ErrorKind::of(&anyhow!("cannot find envelope 42")),
+            ErrorKind::Other
+        );
+
+        let err =
+            anyhow!(io::Error::from(io::ErrorKind::NotFound)).context("cannot read maildir folder");
+        assert_eq!(ErrorKind::of(&err), ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn remote_kinds() {
+        let res: Result<()> = Err(anyhow!(io::Error::from(io::ErrorKind::InvalidData)));
+        let err = res.with_remote_kind().unwrap_err();
+        assert_eq!(ErrorKind::of(&err), ErrorKind::Network);
+
+        // only rejected credentials are authentication errors
+        let res: Result<()> = Err(anyhow!("cannot establish tls session"));
+        let err = res
+            .with_remote_kind()
+            .context("cannot build imap context")
+            .unwrap_err();
+        assert_eq!(ErrorKind::of(&err), ErrorKind::Other);
+
+        let res: Result<()> = Err(anyhow!("oops")).with_kind(ErrorKind::Config);
+        let err = res.with_remote_kind().unwrap_err();
+        assert_eq!(ErrorKind::of(&err), ErrorKind::Config);
+    }
+
+    #[test]
+    fn json_report() {
+        let res: Result<()> = Err(anyhow!("mailbox does not exist")).with_kind(ErrorKind::NotFound);
+        let err = res.context("cannot select folder").unwrap_err();
+        let report = serde_json::to_value(ErrorReport::from(&err)).unwrap();
+
+        assert_eq!(
+            report,
+            serde_json::json!({
+                "error": {
+                    "kind": "not_found",
+                    "message": "cannot select folder",
+                    "causes": ["mailbox does not exist"],
+                }
+            })
+        );
+    }
+}
@@ -0,0 +1,624 @@
+//! Module dedicated to folder archives.
+//!
+//! An archive is a file or a directory containing messages, used to
+//! export or import folders in bulk. Three formats are supported:
+//! mbox (mboxrd variant), Maildir and directory of .eml files.
+
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use clap::ValueEnum;
+use email::flag::{Flag, Flags};
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    vec,
+};
+use uuid::Uuid;
+
+/// Represents the available archive formats.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, ValueEnum)]
+pub enum ArchiveFormat {
+    /// A single file containing all messages, flags are stored in
+    /// Status and X-Status headers.
+    #[default]
+    Mbox,
+    /// A Maildir folder, flags are stored in file names.
+    Maildir,
+    /// A directory containing one .eml file per message, flags are
+    /// not preserved.
+    EmlDir,
+}
+
+impl ArchiveFormat {
+    /// Guess the format of the archive located at the given path.
+    pub fn detect(path: &Path) -> Result<Self> {
+        if path.is_file() {
+            Ok(Self::Mbox)
+        } else if path.join("cur").is_dir() {
+            Ok(Self::Maildir)
+        } else if path.is_dir() {
+            Ok(Self::EmlDir)
+        } else {
+            bail!("cannot find archive at {path:?}")
+        }
+    }
+}
+
+/// The archive writer.
+pub enum ArchiveWriter {
+    Mbox(BufWriter<File>),
+    Maildir(PathBuf),
+    EmlDir {
+        root: PathBuf,
+        count: usize,
+        overwrite: bool,
+    },
+}
+
+impl ArchiveWriter {
+    /// Create a new archive of the given format at the given path.
+    ///
+    /// Existing files are only overwritten if `overwrite` is true,
+    /// otherwise an error is returned.
+    pub fn create(format: ArchiveFormat, path: &Path, overwrite: bool) -> Result<Self> {
+        match format {
+            ArchiveFormat::Mbox => {
+                if let Some(parent) = path.parent() {
+                    fs::create_dir_all(parent)
+                        .with_context(|| format!("cannot create directory {parent:?}"))?;
+                }
+                let file = create_file(path, overwrite)
+                    .with_context(|| format!("cannot create mbox file {path:?}"))?;
+                Ok(Self::Mbox(BufWriter::new(file)))
+            }
+            ArchiveFormat::Maildir => {
+                for dir in ["cur", "new", "tmp"] {
+                    let dir = path.join(dir);
+                    fs::create_dir_all(&dir)
+                        .with_context(|| format!("cannot create maildir directory {dir:?}"))?;
+                }
+                Ok(Self::Maildir(path.to_owned()))
+            }
+            ArchiveFormat::EmlDir => {
+                fs::create_dir_all(path)
+                    .with_context(|| format!("cannot create directory {path:?}"))?;
+
+                // messages of a previous, larger export would be
+                // imported back along with the new ones
+                if overwrite {
+                    for path in read_eml_dir(path)? {
+                        fs::remove_file(&path)
+                            .with_context(|| format!("cannot remove message {path:?}"))?;
+                    }
+                }
+
+                Ok(Self::EmlDir {
+                    root: path.to_owned(),
+                    count: 0,
+                    overwrite,
+                })
+            }
+        }
+    }
+
+    /// Write the given raw message with its flags to the archive.
+    ///
+    /// The date is only used by the mbox format, in order to build
+    /// the separator line.
+    pub fn write(&mut self, msg: &[u8], flags: &Flags, date: Option<DateTime<Utc>>) -> Result<()> {
+        match self {
+            Self::Mbox(writer) => {
+                let date = date.unwrap_or_else(Utc::now);
+                let msg = strip_headers(msg, &["status", "x-status"]);
+                writer.write_all(to_mbox_message(&msg, flags, date).as_slice())?;
+            }
+            Self::Maildir(root) => {
+                let name = format!("{}.{}.himalaya", Utc::now().timestamp(), Uuid::new_v4());
+                let tmp = root.join("tmp").join(&name);
+                let cur = root
+                    .join("cur")
+                    .join(format!("{name}:2,{}", to_maildir_info(flags)));
+
+                fs::write(&tmp, msg).with_context(|| format!("cannot write message {tmp:?}"))?;
+                fs::rename(&tmp, &cur)
+                    .with_context(|| format!("cannot move message {tmp:?} to {cur:?}"))?;
+            }
+            Self::EmlDir {
+                root,
+                count,
+                overwrite,
+            } => {
+                *count += 1;
+                let path = root.join(format!("{count}.eml"));
+                create_file(&path, *overwrite)
+                    .and_then(|mut file| file.write_all(msg))
+                    .with_context(|| format!("cannot write message {path:?}"))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Flush the archive.
+    pub fn finish(self) -> Result<()> {
+        if let Self::Mbox(mut writer) = self {
+            writer.flush().context("cannot flush mbox file")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The archive reader.
+///
+/// It iterates over the raw messages of the archive, along with
+/// their flags.
+pub struct ArchiveReader {
+    inner: ArchiveReaderInner,
+    count: Option<usize>,
+}
+
+enum ArchiveReaderInner {
+    Mbox(MboxReader<BufReader<File>>),
+    Files(vec::IntoIter<(PathBuf, Flags)>),
+}
+
+impl ArchiveReader {
+    /// Open the archive of the given format located at the given
+    /// path.
+    pub fn open(format: ArchiveFormat, path: &Path) -> Result<Self> {
+        match format {
+            ArchiveFormat::Mbox => {
+                let file = File::open(path)
+                    .map(BufReader::new)
+                    .with_context(|| format!("cannot open mbox file {path:?}"))?;
+
+                // counting messages would require to read the whole
+                // file twice
+                let inner = ArchiveReaderInner::Mbox(MboxReader::new(file));
+                Ok(Self { inner, count: None })
+            }
+            ArchiveFormat::Maildir => {
+                let mut files = Vec::new();
+
+                for dir in ["new", "cur"] {
+                    for path in read_dir_sorted(&path.join(dir))? {
+                        let flags = path
+                            .file_name()
+                            .and_then(|name| name.to_str())
+                            .and_then(|name| name.rsplit_once(":2,"))
+                            .map(|(_, info)| from_maildir_info(info))
+                            .unwrap_or_default();
+                        files.push((path, flags));
+                    }
+                }
+
+                let count = Some(files.len());
+                let inner = ArchiveReaderInner::Files(files.into_iter());
+                Ok(Self { inner, count })
+            }
+            ArchiveFormat::EmlDir => {
+                let files: Vec<_> = read_eml_dir(path)?
+                    .into_iter()
+                    .map(|path| (path, Flags::default()))
+                    .collect();
+
+                let count = Some(files.len());
+                let inner = ArchiveReaderInner::Files(files.into_iter());
+                Ok(Self { inner, count })
+            }
+        }
+    }
+
+    /// Return the number of messages of the archive, if it is known
+    /// without reading the archive.
+    pub fn count(&self) -> Option<usize> {
+        self.count
+    }
+}
+
+impl Iterator for ArchiveReader {
+    type Item = Result<(Vec<u8>, Flags)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match &mut self.inner {
+            ArchiveReaderInner::Mbox(reader) => {
+                let msg = reader.next()?;
+                Some(msg.map(|msg| from_mbox_message(&msg)))
+            }
+            ArchiveReaderInner::Files(files) => {
+                let (path, flags) = files.next()?;
+                let msg = fs::read(&path).with_context(|| format!("cannot read message {path:?}"));
+                Some(msg.map(|msg| (msg, flags)))
+            }
+        }
+    }
+}
+
+/// The mbox reader.
+///
+/// It splits the given mbox stream into raw messages, with their
+/// Status headers still included and their "From " lines unquoted.
+pub struct MboxReader<R: BufRead> {
+    reader: R,
+    next_line: Option<Vec<u8>>,
+}
+
+impl<R: BufRead> MboxReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            next_line: None,
+        }
+    }
+
+    fn read_line(&mut self) -> Result<Option<Vec<u8>>> {
+        if let Some(line) = self.next_line.take() {
+            return Ok(Some(line));
+        }
+
+        let mut line = Vec::new();
+        match self.reader.read_until(b'\n', &mut line)? {
+            0 => Ok(None),
+            _ => Ok(Some(line)),
+        }
+    }
+
+    fn read_msg(&mut self) -> Result<Option<Vec<u8>>> {
+        // skip everything until the first separator line
+        loop {
+            match self.read_line()? {
+                None => return Ok(None),
+                Some(line) if line.starts_with(b"From ") => break,
+                Some(_) => continue,
+            }
+        }
+
+        let mut msg = Vec::new();
+
+        while let Some(line) = self.read_line()? {
+            if line.starts_with(b"From ") {
+                self.next_line = Some(line);
+                break;
+            }
+
+            msg.extend_from_slice(&unquote_from_line(&line));
+        }
+
+        // remove the blank line separating messages
+        if msg.ends_with(b"\r\n\r\n") {
+            msg.truncate(msg.len() - 2);
+        } else if msg.ends_with(b"\n\n") {
+            msg.truncate(msg.len() - 1);
+        }
+
+        Ok(Some(msg))
+    }
+}
+
+impl<R: BufRead> Iterator for MboxReader<R> {
+    type Item = Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_msg().transpose()
+    }
+}
+
+fn create_file(path: &Path, overwrite: bool) -> std::io::Result<File> {
+    let mut opts = OpenOptions::new();
+    opts.write(true);
+
+    if overwrite {
+        opts.create(true).truncate(true);
+    } else {
+        opts.create_new(true);
+    }
+
+    opts.open(path)
+}
+
+fn read_dir_sorted(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut paths = Vec::new();
+
+    for entry in fs::read_dir(dir).with_context(|| format!("cannot read directory {dir:?}"))? {
+        let path = entry?.path();
+        if path.is_file() {
+            paths.push(path);
+        }
+    }
+
+    paths.sort();
+    Ok(paths)
+}
+
+fn read_eml_dir(dir: &Path) -> Result<Vec<PathBuf>> {
+    let paths = read_dir_sorted(dir)?
+        .into_iter()
+        .filter(|path| {
+            path.extension()
+                .map(|ext| ext.eq_ignore_ascii_case("eml"))
+                .unwrap_or_default()
+        })
+        .collect();
+
+    Ok(paths)
+}
+
+/// Quote the lines starting with "From " (optionally preceded by
+/// ">"), as defined by the mboxrd format.
+fn quote_from_line(line: &[u8]) -> Vec<u8> {
+    let quoted = line
+        .iter()
+        .position(|c| *c != b'>')
+        .map(|i| line[i..].starts_with(b"From "))
+        .unwrap_or_default();
+
+    if quoted {
+        [b">", line].concat()
+    } else {
+        line.to_vec()
+    }
+}
+
+/// Unquote lines previously quoted by [`quote_from_line`].
+fn unquote_from_line(line: &[u8]) -> Vec<u8> {
+    let quoted = line.starts_with(b">")
+        && line
+            .iter()
+            .position(|c| *c != b'>')
+            .map(|i| line[i..].starts_with(b"From "))
+            .unwrap_or_default();
+
+    if quoted {
+        line[1..].to_vec()
+    } else {
+        line.to_vec()
+    }
+}
+
+/// Build an mbox entry from the given raw message.
+fn to_mbox_message(msg: &[u8], flags: &Flags, date: DateTime<Utc>) -> Vec<u8> {
+    let mut entry = Vec::with_capacity(msg.len() + 128);
+
+    let date = date.format("%a %b %e %H:%M:%S %Y");
+    entry.extend(format!("From MAILER-DAEMON {date}\n").into_bytes());
+
+    let mut status = String::new();
+    if flags.contains(&Flag::Seen) {
+        status.push('R');
+    }
+    status.push('O');
+    entry.extend(format!("Status: {status}\n").into_bytes());
+
+    let mut x_status = String::new();
+    for (flag, c) in [
+        (Flag::Answered, 'A'),
+        (Flag::Flagged, 'F'),
+        (Flag::Deleted, 'D'),
+        (Flag::Draft, 'T'),
+    ] {
+        if flags.contains(&flag) {
+            x_status.push(c);
+        }
+    }
+    if !x_status.is_empty() {
+        entry.extend(format!("X-Status: {x_status}\n").into_bytes());
+    }
+
+    for line in msg.split_inclusive(|c| *c == b'\n') {
+        entry.extend(quote_from_line(line));
+    }
+
+    if !msg.ends_with(b"\n") {
+        entry.push(b'\n');
+    }
+    entry.push(b'\n');
+
+    entry
+}
+
+/// Extract the flags from the Status and X-Status headers of the
+/// given mbox message, then remove those headers.
+fn from_mbox_message(msg: &[u8]) -> (Vec<u8>, Flags) {
+    let mut flags = Flags::default();
+
+    for (name, value) in headers(msg) {
+        let flags_map: &[(char, Flag)] = if name.eq_ignore_ascii_case("status") {
+            &[('R', Flag::Seen)]
+        } else if name.eq_ignore_ascii_case("x-status") {
+            &[
+                ('A', Flag::Answered),
+                ('F', Flag::Flagged),
+                ('D', Flag::Deleted),
+                ('T', Flag::Draft),
+            ]
+        } else {
+            continue;
+        };
+
+        for (c, flag) in flags_map {
+            if value.contains(*c) {
+                flags.insert(flag.clone());
+            }
+        }
+    }
+
+    (strip_headers(msg, &["status", "x-status"]), flags)
+}
+
+/// Build the Maildir info suffix of the given flags.
+fn to_maildir_info(flags: &Flags) -> String {
+    [
+        (Flag::Draft, 'D'),
+        (Flag::Flagged, 'F'),
+        (Flag::Answered, 'R'),
+        (Flag::Seen, 'S'),
+        (Flag::Deleted, 'T'),
+    ]
+    .into_iter()
+    .filter(|(flag, _)| flags.contains(flag))
+    .map(|(_, c)| c)
+    .collect()
+}
+
+/// Parse the flags of the given Maildir info suffix.
+fn from_maildir_info(info: &str) -> Flags {
+    let mut flags = Flags::default();
+
+    for c in info.chars() {
+        let flag = match c {
+            'D' => Flag::Draft,
+            'F' => Flag::Flagged,
+            'R' => Flag::Answered,
+            'S' => Flag::Seen,
+            'T' => Flag::Deleted,
+            _ => continue,
+        };
+        flags.insert(flag);
+    }
+
+    flags
+}
+
+/// Iterate over the header lines of the given raw message, without
+/// following folded lines.
+fn headers(msg: &[u8]) -> impl Iterator<Item = (String, String)> + '_ {
+    msg.split_inclusive(|c| *c == b'\n')
+        .take_while(|line| !is_blank_line(line))
+        .filter_map(|line| {
+            let line = String::from_utf8_lossy(line);
+            let (name, value) = line.split_once(':')?;
+            Some((name.trim().to_owned(), value.trim().to_owned()))
+        })
+}
+
+/// Remove the headers matching the given names (case-insensitive)
+/// from the given raw message, including their folded lines.
+pub(crate) fn strip_headers(msg: &[u8], names: &[&str]) -> Vec<u8> {
+    let mut stripped = Vec::with_capacity(msg.len());
+    let mut lines = msg.split_inclusive(|c| *c == b'\n');
+    let mut skipping = false;
+
+    for line in lines.by_ref() {
+        if is_blank_line(line) {
+            stripped.extend_from_slice(line);
+            break;
+        }
+
+        let folded = line.starts_with(b" ") || line.starts_with(b"\t");
+        if !folded {
+            let name = String::from_utf8_lossy(line);
+            let name = name.split(':').next().unwrap_or_default().trim();
+            skipping = names.iter().any(|n| n.eq_ignore_ascii_case(name));
+        }
+
+        if !skipping {
+            stripped.extend_from_slice(line);
+        }
+    }
+
+    for line in lines {
+        stripped.extend_from_slice(line);
+    }
+
+    stripped
+}
+
+fn is_blank_line(line: &[u8]) -> bool {
+    line == b"\n" || line == b"\r\n"
+}
+
+/// Parse the date of the given raw message.
+pub fn parse_date(msg: &[u8]) -> Option<DateTime<Utc>> {
+    let msg = mail_parser::MessageParser::new().parse_headers(msg)?;
+    let timestamp = msg.date()?.to_timestamp();
+    DateTime::from_timestamp(timestamp, 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date() -> DateTime<Utc> {
+        DateTime::from_timestamp(0, 0).unwrap()
+    }
+
+    #[test]
+    fn mbox_roundtrip() {
+        let a = b"Subject: a\nStatus: O\n\nFrom here\n>From there\n".to_vec();
+        let b = b"Subject: b\r\n\r\nbody".to_vec();
+
+        let flags_a = Flags::from_iter([Flag::Seen, Flag::Flagged]);
+        let flags_b = Flags::default();
+
+        let mut mbox = to_mbox_message(&strip_headers(&a, &["status"]), &flags_a, date());
+        mbox.extend(to_mbox_message(&b, &flags_b, date()));
+
+        let text = String::from_utf8_lossy(&mbox);
+        assert!(text.starts_with("From MAILER-DAEMON Thu Jan  1 00:00:00 1970\n"));
+        assert!(text.contains("\n>From here\n>>From there\n"));
+
+        let msgs: Vec<_> = MboxReader::new(mbox.as_slice())
+            .map(|msg| from_mbox_message(&msg.unwrap()))
+            .collect();
+
+        assert_eq!(msgs.len(), 2);
+        assert_eq!(msgs[0].0, b"Subject: a\n\nFrom here\n>From there\n");
+        assert!(msgs[0].1.contains(&Flag::Seen));
+        assert!(msgs[0].1.contains(&Flag::Flagged));
+        assert_eq!(msgs[1].0, b"Subject: b\r\n\r\nbody\n");
+        assert!(!msgs[1].1.contains(&Flag::Seen));
+    }
+
+    #[test]
+    fn create_without_overwrite() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("archive.mbox");
+        fs::write(&path, "existing").unwrap();
+
+        assert!(ArchiveWriter::create(ArchiveFormat::Mbox, &path, false).is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "existing");
+
+        let writer = ArchiveWriter::create(ArchiveFormat::Mbox, &path, true).unwrap();
+        writer.finish().unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "");
+    }
+
+    #[test]
+    fn overwrite_eml_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let flags = Flags::default();
+
+        let mut writer = ArchiveWriter::create(ArchiveFormat::EmlDir, dir.path(), false).unwrap();
+        writer.write(b"first", &flags, None).unwrap();
+        writer.write(b"second", &flags, None).unwrap();
+        writer.finish().unwrap();
+
+        let mut writer = ArchiveWriter::create(ArchiveFormat::EmlDir, dir.path(), true).unwrap();
+        writer.write(b"third", &flags, None).unwrap();
+        writer.finish().unwrap();
+
+        let msgs = ArchiveReader::open(ArchiveFormat::EmlDir, dir.path())
+            .unwrap()
+            .map(|msg| msg.unwrap().0)
+            .collect::<Vec<_>>();
+        assert_eq!(msgs, [b"third".to_vec()]);
+    }
+
+    #[test]
+    fn maildir_info() {
+        let flags = Flags::from_iter([Flag::Seen, Flag::Answered, Flag::Draft]);
+        assert_eq!(to_maildir_info(&flags), "DRS");
+        assert_eq!(to_maildir_info(&from_maildir_info("SRDx")), "DRS");
+    }
+
+    #[test]
+    fn strip_folded_headers() {
+        let msg = b"A: 1\nStatus: RO\n folded\nB: 2\n\nStatus: body\n";
+        assert_eq!(
+            strip_headers(msg, &["status"]),
+            b"A: 1\nB: 2\n\nStatus: body\n"
+        );
+    }
+}
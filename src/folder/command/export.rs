@@ -0,0 +1,141 @@
+use anyhow::{anyhow, Result};
+use clap::Parser;
+use email::{
+    backend::feature::BackendFeatureSource,
+    envelope::{
+        list::{ListEnvelopes, ListEnvelopesOptions},
+        Id,
+    },
+    message::peek::PeekMessages,
+};
+use indicatif::{ProgressBar, ProgressStyle};
+use log::info;
+use once_cell::sync::Lazy;
+use std::path::PathBuf;
+
+#[cfg(feature = "account-sync")]
+use crate::cache::arg::disable::CacheDisableFlag;
+use crate::{
+    account::arg::name::AccountNameFlag,
+    backend::Backend,
+    config::TomlConfig,
+    folder::{
+        archive::{self, ArchiveFormat, ArchiveWriter},
+        arg::name::FolderNameArg,
+    },
+    printer::Printer,
+};
+
+pub(crate) static PROGRESS_STYLE: Lazy<ProgressStyle> = Lazy::new(|| {
+    ProgressStyle::with_template(
+        " {prefix:.bold} — {wide_msg:.dim} \n {wide_bar:.cyan/blue} {pos}/{len} ",
+    )
+    .unwrap()
+});
+
+/// Export a folder.
+///
+/// This command allows you to export all messages of the given
+/// folder to an archive. Flags are preserved by the mbox format (in
+/// Status and X-Status headers) and by the Maildir format (in file
+/// names), but not by the eml-dir format.
+#[derive(Debug, Parser)]
+pub struct FolderExportCommand {
+    #[command(flatten)]
+    pub folder: FolderNameArg,
+
+    /// The path of the archive to create.
+    #[arg(value_name = "PATH")]
+    pub path: PathBuf,
+
+    /// The format of the archive.
+    #[arg(long, value_name = "FORMAT", value_enum, default_value_t = Default::default())]
+    pub format: ArchiveFormat,
+
+    /// Overwrite existing archive files.
+    ///
+    /// Without this flag, the export fails if the mbox file (or one
+    /// of the eml files) already exists.
+    #[arg(long)]
+    pub force: bool,
+
+    #[cfg(feature = "account-sync")]
+    #[command(flatten)]
+    pub cache: CacheDisableFlag,
+
+    #[command(flatten)]
+    pub account: AccountNameFlag,
+}
+
+impl FolderExportCommand {
+    pub async fn execute(self, printer: &mut impl Printer, config: &TomlConfig) -> Result<()> {
+        info!("executing export folder command");
+
+        let folder = &self.folder.name;
+        let (toml_account_config, account_config) = config.clone().into_account_configs(
+            self.account.name.as_deref(),
+            #[cfg(feature = "account-sync")]
+            self.cache.disable,
+        )?;
+
+        let mut writer = ArchiveWriter::create(self.format, &self.path, self.force)?;
+
+        let list_envelopes_kind = toml_account_config.list_envelopes_kind();
+        let peek_messages_kind = toml_account_config.peek_messages_kind();
+
+        let backend = Backend::new(
+            toml_account_config.clone(),
+            account_config,
+            list_envelopes_kind.into_iter().chain(peek_messages_kind),
+            |builder| {
+                builder.set_list_envelopes(BackendFeatureSource::Context);
+                builder.set_peek_messages(BackendFeatureSource::Context);
+            },
+        )
+        .await?;
+
+        let opts = ListEnvelopesOptions {
+            page: 0,
+            page_size: 0,
+            query: None,
+        };
+        let envelopes = backend.backend.list_envelopes(folder, opts).await?;
+
+        let progress = if printer.is_machine_readable() {
+            ProgressBar::hidden()
+        } else {
+            ProgressBar::new(envelopes.len() as u64)
+                .with_style(PROGRESS_STYLE.clone())
+                .with_prefix(format!("Exporting {folder}"))
+        };
+
+        for envelope in envelopes.iter() {
+            progress.set_message(envelope.subject.clone());
+
+            let msgs = backend
+                .backend
+                .peek_messages(folder, &Id::single(&envelope.id))
+                .await?;
+            // bodies of partially synchronized messages are fetched
+            // on demand
+            #[cfg(feature = "account-sync")]
+            let msgs = backend.fetch_partial_bodies(msgs).await?;
+            let msg = msgs
+                .first()
+                .ok_or(anyhow!("cannot find message {}", envelope.id))?;
+            let raw = msg.raw()?;
+
+            writer.write(raw, &envelope.flags, archive::parse_date(raw))?;
+            progress.inc(1);
+        }
+
+        writer.finish()?;
+        progress.finish_and_clear();
+
+        let count = envelopes.len();
+        let path = self.path.display();
+        printer.print(format!(
+            "Folder {folder} successfully exported to {path} ({count} message(s))!"
+        ))
+    }
+}
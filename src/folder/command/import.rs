@@ -0,0 +1,129 @@
+use anyhow::Result;
+use clap::Parser;
+use email::{backend::feature::BackendFeatureSource, message::add::AddMessage};
+use indicatif::ProgressBar;
+use log::{debug, info, warn};
+use std::path::PathBuf;
+
+#[cfg(feature = "account-sync")]
+use crate::cache::arg::disable::CacheDisableFlag;
+use crate::{
+    account::arg::name::AccountNameFlag,
+    backend::Backend,
+    config::TomlConfig,
+    folder::{
+        archive::{ArchiveFormat, ArchiveReader},
+        arg::name::FolderNameOptionalFlag,
+        command::export::PROGRESS_STYLE,
+    },
+    printer::Printer,
+};
+
+/// Import messages into a folder.
+///
+/// This command allows you to import all messages of the given
+/// archive into a folder. The format of the archive is detected from
+/// the path: a file is considered as mbox, a directory containing a
+/// cur directory as Maildir, any other directory as eml-dir. Flags
+/// are restored when the format preserves them.
+#[derive(Debug, Parser)]
+pub struct FolderImportCommand {
+    /// The path of the archive to import.
+    #[arg(value_name = "PATH")]
+    pub path: PathBuf,
+
+    #[command(flatten)]
+    pub folder: FolderNameOptionalFlag,
+
+    /// The format of the archive.
+    ///
+    /// If omitted, the format is detected from the given path.
+    #[arg(long, value_name = "FORMAT", value_enum)]
+    pub format: Option<ArchiveFormat>,
+
+    #[cfg(feature = "account-sync")]
+    #[command(flatten)]
+    pub cache: CacheDisableFlag,
+
+    #[command(flatten)]
+    pub account: AccountNameFlag,
+}
+
+impl FolderImportCommand {
+    pub async fn execute(self, printer: &mut impl Printer, config: &TomlConfig) -> Result<()> {
+        info!("executing import folder command");
+
+        let folder = &self.folder.name;
+        let (toml_account_config, account_config) = config.clone().into_account_configs(
+            self.account.name.as_deref(),
+            #[cfg(feature = "account-sync")]
+            self.cache.disable,
+        )?;
+
+        let add_message_kind = toml_account_config.add_message_kind();
+
+        let backend = Backend::new(
+            toml_account_config.clone(),
+            account_config,
+            add_message_kind,
+            |builder| builder.set_add_message(BackendFeatureSource::Context),
+        )
+        .await?;
+
+        let format = match self.format {
+            Some(format) => format,
+            None => ArchiveFormat::detect(&self.path)?,
+        };
+        let reader = ArchiveReader::open(format, &self.path)?;
+
+        let progress = if printer.is_machine_readable() {
+            ProgressBar::hidden()
+        } else {
+            match reader.count() {
+                Some(count) => ProgressBar::new(count as u64),
+                None => ProgressBar::no_length(),
+            }
+            .with_style(PROGRESS_STYLE.clone())
+            .with_prefix(format!("Importing into {folder}"))
+        };
+
+        let mut imported = 0;
+        let mut failed = 0;
+
+        for (i, msg) in reader.enumerate() {
+            let res = match msg {
+                Ok((msg, flags)) => backend
+                    .backend
+                    .add_message_with_flags(folder, &msg, &flags)
+                    .await
+                    .map(|_| ()),
+                Err(err) => Err(err),
+            };
+
+            match res {
+                Ok(()) => imported += 1,
+                Err(err) => {
+                    failed += 1;
+                    let n = i + 1;
+                    progress.set_message(format!("cannot import message {n}: {err}"));
+                    warn!("cannot import message {n} into folder {folder}: {err}");
+                    debug!("{err:?}");
+                }
+            }
+
+            progress.inc(1);
+        }
+
+        progress.finish_and_clear();
+
+        if failed > 0 {
+            printer.print(format!(
+                "{imported} message(s) imported into {folder}, {failed} message(s) failed!"
+            ))
+        } else {
+            printer.print(format!(
+                "{imported} message(s) successfully imported into {folder}!"
+            ))
+        }
+    }
+}
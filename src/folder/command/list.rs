@@ -11,7 +11,10 @@ use crate::{
     config::TomlConfig,
     folder::Folders,
     printer::{PrintTableOpts, Printer},
-    ui::arg::max_width::TableMaxWidthFlag,
+    ui::{
+        arg::max_width::TableMaxWidthFlag,
+        template::{arg::format::TemplateFormatFlag, print_table_or_template},
+    },
 };
 
 /// List all folders.
@@ -22,6 +25,9 @@ pub struct FolderListCommand {
     #[command(flatten)]
     pub table: TableMaxWidthFlag,
 
+    #[command(flatten)]
+    pub format: TemplateFormatFlag,
+
     #[cfg(feature = "account-sync")]
     #[command(flatten)]
     pub cache: CacheDisableFlag,
@@ -52,8 +58,10 @@ impl FolderListCommand {
 
         let folders: Folders = backend.list_folders().await?.into();
 
-        printer.print_table(
-            Box::new(folders),
+        print_table_or_template(
+            printer,
+            folders,
+            self.format.template,
             PrintTableOpts {
                 format: &account_config.get_message_read_format(),
                 max_width: self.table.max_width,
@@ -1,6 +1,8 @@
 mod add;
 mod delete;
+mod export;
 mod expunge;
+mod import;
 mod list;
 mod purge;
 
@@ -10,8 +12,9 @@ use clap::Subcommand;
 use crate::{config::TomlConfig, printer::Printer};
 
 use self::{
-    add::AddFolderCommand, delete::FolderDeleteCommand, expunge::FolderExpungeCommand,
-    list::FolderListCommand, purge::FolderPurgeCommand,
+    add::AddFolderCommand, delete::FolderDeleteCommand, export::FolderExportCommand,
+    expunge::FolderExpungeCommand, import::FolderImportCommand, list::FolderListCommand,
+    purge::FolderPurgeCommand,
 };
 
 /// Manage folders.
@@ -32,6 +35,12 @@ pub enum FolderSubcommand {
     #[command()]
     Purge(FolderPurgeCommand),
 
+    #[command(arg_required_else_help = true)]
+    Export(FolderExportCommand),
+
+    #[command(arg_required_else_help = true)]
+    Import(FolderImportCommand),
+
     #[command(alias = "remove", alias = "rm")]
     Delete(FolderDeleteCommand),
 }
@@ -44,6 +53,8 @@ impl FolderSubcommand {
             Self::List(cmd) => cmd.execute(printer, config).await,
             Self::Expunge(cmd) => cmd.execute(printer, config).await,
             Self::Purge(cmd) => cmd.execute(printer, config).await,
+            Self::Export(cmd) => cmd.execute(printer, config).await,
+            Self::Import(cmd) => cmd.execute(printer, config).await,
             Self::Delete(cmd) => cmd.execute(printer, config).await,
         }
     }
@@ -0,0 +1,578 @@
+//! Module dedicated to the local full-text search index.
+//!
+//! The index is built from the local Maildir used by the account
+//! synchronization, and is incrementally updated after every
+//! synchronization. It is stored as a sled database composed of 4
+//! trees:
+//!
+//! - `docs`: indexed envelopes, by folder and id
+//! - `postings`: weighted term frequencies, by term, folder and id
+//! - `doc-terms`: terms of every indexed envelope, used to clean up
+//!   postings when an envelope disappears
+//! - `counts`: number of indexed envelopes, by folder
+
+use anyhow::{Context, Result};
+use email::{
+    account::config::AccountConfig,
+    backend::feature::BackendFeatureSource,
+    envelope::{
+        list::{ListEnvelopes, ListEnvelopesOptions},
+        Id,
+    },
+    folder::list::ListFolders,
+    message::peek::PeekMessages,
+};
+use log::{debug, warn};
+use mail_parser::MimeHeaders;
+use serde::{Deserialize, Serialize};
+use sled::{Config, Db, Tree};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use crate::{
+    account::{self, config::TomlAccountConfig},
+    backend::{Backend, BackendKind},
+    cache::IdMapper,
+    envelope::{Envelope, Envelopes},
+};
+
+/// The minimum length of an indexed term.
+const MIN_TERM_LEN: usize = 2;
+
+/// The maximum length of an indexed term.
+const MAX_TERM_LEN: usize = 64;
+
+/// The weight of terms found in subjects.
+const SUBJECT_WEIGHT: u32 = 4;
+
+/// The weight of terms found in senders and recipients.
+const ADDRESS_WEIGHT: u32 = 3;
+
+/// The weight of terms found in attachment file names.
+const ATTACHMENT_WEIGHT: u32 = 2;
+
+/// The weight of terms found in text bodies.
+const BODY_WEIGHT: u32 = 1;
+
+/// Split the given text into lowercased terms.
+///
+/// A term is a sequence of alphanumeric characters. Too short and too
+/// long terms are discarded.
+pub fn tokenize(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|term| {
+            let len = term.chars().count();
+            (MIN_TERM_LEN..=MAX_TERM_LEN).contains(&len)
+        })
+        .map(str::to_lowercase)
+}
+
+/// Represents an envelope stored in the index.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct IndexedEnvelope {
+    envelope: Envelope,
+    /// The date of the envelope, used to rank hits of equal score.
+    timestamp: i64,
+    terms_count: u32,
+}
+
+/// Represents a search result.
+#[derive(Clone, Debug)]
+pub struct SearchHit {
+    pub id: String,
+    pub score: f64,
+    pub envelope: Envelope,
+}
+
+/// Represents the report of an index update.
+#[derive(Clone, Debug, Default)]
+pub struct IndexUpdateReport {
+    pub added: usize,
+    pub updated: usize,
+    pub removed: usize,
+    pub errors: Vec<(String, String, String)>,
+}
+
+/// The local full-text search index of an account.
+pub struct SearchIndex {
+    db: Db,
+    docs: Tree,
+    postings: Tree,
+    doc_terms: Tree,
+    counts: Tree,
+}
+
+impl SearchIndex {
+    /// Open the search index of the given account.
+    pub fn new(account_config: &AccountConfig) -> Result<Self> {
+        let db_path = account::data_dir(".search-indexes", &account_config.name)?;
+
+        let db = Config::new()
+            .path(&db_path)
+            .open()
+            .with_context(|| format!("cannot open search index at {db_path:?}"))?;
+
+        Self::from_db(db)
+    }
+
+    fn from_db(db: Db) -> Result<Self> {
+        Ok(Self {
+            docs: db.open_tree("docs")?,
+            postings: db.open_tree("postings")?,
+            doc_terms: db.open_tree("doc-terms")?,
+            counts: db.open_tree("counts")?,
+            db,
+        })
+    }
+
+    fn doc_key(folder: &str, id: &str) -> String {
+        format!("{folder}\n{id}")
+    }
+
+    fn posting_key(term: &str, folder: &str, id: &str) -> String {
+        format!("{term}\0{folder}\n{id}")
+    }
+
+    /// Return the ids of the envelopes indexed for the given folder.
+    fn indexed_ids(&self, folder: &str) -> Result<HashSet<String>> {
+        let prefix = format!("{folder}\n");
+        self.docs
+            .scan_prefix(&prefix)
+            .keys()
+            .map(|key| {
+                let key = key?;
+                Ok(String::from_utf8_lossy(&key[prefix.len()..]).to_string())
+            })
+            .collect()
+    }
+
+    /// Return the number of envelopes indexed for the given folder.
+    fn docs_count(&self, folder: &str) -> Result<u64> {
+        match self.counts.get(folder)? {
+            Some(count) => Ok(u64::from_be_bytes(count.as_ref().try_into()?)),
+            None => Ok(0),
+        }
+    }
+
+    fn set_docs_count(&self, folder: &str, count: u64) -> Result<()> {
+        self.counts.insert(folder, &count.to_be_bytes())?;
+        Ok(())
+    }
+
+    /// Index the given envelope and its associated terms.
+    ///
+    /// Terms are given with their weighted frequency.
+    fn insert(
+        &self,
+        folder: &str,
+        envelope: Envelope,
+        timestamp: i64,
+        terms: HashMap<String, u32>,
+    ) -> Result<()> {
+        let id = envelope.id.clone();
+        self.remove(folder, &id)?;
+
+        let doc_key = Self::doc_key(folder, &id);
+        let terms_count = terms.values().sum();
+
+        for (term, freq) in &terms {
+            let key = Self::posting_key(term, folder, &id);
+            self.postings.insert(key, freq.to_be_bytes().to_vec())?;
+        }
+
+        let terms: Vec<&String> = terms.keys().collect();
+        self.doc_terms
+            .insert(&doc_key, serde_json::to_vec(&terms)?)?;
+
+        let doc = IndexedEnvelope {
+            envelope,
+            timestamp,
+            terms_count,
+        };
+        let count = self.docs_count(folder)?;
+        self.docs.insert(&doc_key, serde_json::to_vec(&doc)?)?;
+        self.set_docs_count(folder, count + 1)?;
+
+        Ok(())
+    }
+
+    /// Update the envelope metadata (flags, date etc) of an already
+    /// indexed envelope, without touching its terms.
+    fn update(&self, folder: &str, envelope: Envelope, timestamp: i64) -> Result<()> {
+        let doc_key = Self::doc_key(folder, &envelope.id);
+
+        if let Some(doc) = self.docs.get(&doc_key)? {
+            let mut doc: IndexedEnvelope = serde_json::from_slice(&doc)?;
+            doc.envelope = envelope;
+            doc.timestamp = timestamp;
+            self.docs.insert(&doc_key, serde_json::to_vec(&doc)?)?;
+        }
+
+        Ok(())
+    }
+
+    /// Remove the given envelope and its associated terms.
+    fn remove(&self, folder: &str, id: &str) -> Result<()> {
+        let doc_key = Self::doc_key(folder, id);
+
+        if let Some(terms) = self.doc_terms.remove(&doc_key)? {
+            let terms: Vec<String> = serde_json::from_slice(&terms)?;
+            for term in terms {
+                self.postings.remove(Self::posting_key(&term, folder, id))?;
+            }
+        }
+
+        if self.docs.contains_key(&doc_key)? {
+            let count = self.docs_count(folder)?;
+            self.docs.remove(&doc_key)?;
+            self.set_docs_count(folder, count.saturating_sub(1))?;
+        }
+
+        Ok(())
+    }
+
+    /// Search envelopes of the given folder matching all terms of the
+    /// given query.
+    ///
+    /// A term ending by `*` matches all terms starting with it. Hits
+    /// are ranked using a TF-IDF score, where terms found in subjects
+    /// and addresses weigh more than terms found in bodies. Hits of
+    /// equal score are ranked by date, most recent first, then by id.
+    pub fn search(&self, folder: &str, query: &str) -> Result<Vec<SearchHit>> {
+        let docs_count = self.docs_count(folder)?.max(1) as f64;
+
+        let mut scores: Option<HashMap<String, f64>> = None;
+
+        for term in query.split_whitespace() {
+            let (term, is_prefix) = match term.strip_suffix('*') {
+                Some(term) => (term, true),
+                None => (term, false),
+            };
+
+            let terms: Vec<String> = tokenize(term).collect();
+            if terms.is_empty() {
+                continue;
+            }
+
+            for (i, term) in terms.iter().enumerate() {
+                let is_prefix = is_prefix && i + 1 == terms.len();
+                let freqs = self.term_freqs(folder, term, is_prefix)?;
+                let idf = (1.0 + docs_count / (freqs.len().max(1) as f64)).ln();
+
+                let term_scores: HashMap<String, f64> = freqs
+                    .into_iter()
+                    .map(|(id, freq)| (id, freq as f64 * idf))
+                    .collect();
+
+                scores = Some(match scores {
+                    None => term_scores,
+                    Some(scores) => scores
+                        .into_iter()
+                        .filter_map(|(id, score)| {
+                            let term_score = term_scores.get(&id)?;
+                            Some((id, score + term_score))
+                        })
+                        .collect(),
+                });
+            }
+        }
+
+        let mut hits = Vec::new();
+        let mut timestamps = HashMap::new();
+
+        for (id, score) in scores.unwrap_or_default() {
+            let doc_key = Self::doc_key(folder, &id);
+            if let Some(doc) = self.docs.get(&doc_key)? {
+                let doc: IndexedEnvelope = serde_json::from_slice(&doc)?;
+                // normalizes the score so that long messages do not
+                // always win
+                let score = score / (1.0 + (doc.terms_count.max(1) as f64).ln());
+                timestamps.insert(id.clone(), doc.timestamp);
+                hits.push(SearchHit {
+                    id,
+                    score,
+                    envelope: doc.envelope,
+                });
+            }
+        }
+
+        hits.sort_by(|a, b| {
+            b.score
+                .total_cmp(&a.score)
+                .then_with(|| timestamps[&b.id].cmp(&timestamps[&a.id]))
+                .then_with(|| a.id.cmp(&b.id))
+        });
+
+        Ok(hits)
+    }
+
+    /// Return the weighted frequency of the given term for every
+    /// envelope of the given folder containing it.
+    fn term_freqs(
+        &self,
+        folder: &str,
+        term: &str,
+        is_prefix: bool,
+    ) -> Result<HashMap<String, u32>> {
+        let mut freqs = HashMap::new();
+        let suffix = format!("\0{folder}\n");
+
+        let prefix = if is_prefix {
+            term.to_owned()
+        } else {
+            format!("{term}{suffix}")
+        };
+
+        for entry in self.postings.scan_prefix(&prefix) {
+            let (key, freq) = entry?;
+            let key = String::from_utf8_lossy(&key);
+
+            let id = match key.split_once(&suffix) {
+                Some((_, id)) => id,
+                // prefix matched a term from another folder
+                None => continue,
+            };
+
+            let freq = freq
+                .as_ref()
+                .try_into()
+                .map(u32::from_be_bytes)
+                .unwrap_or_default();
+
+            *freqs.entry(id.to_owned()).or_default() += freq;
+        }
+
+        Ok(freqs)
+    }
+
+    /// Update the index from the local Maildir of the synchronized
+    /// account.
+    ///
+    /// Only envelopes that are not indexed yet get their message
+    /// parsed. Envelopes that disappeared from the Maildir are
+    /// removed from the index, others get their metadata refreshed.
+    pub async fn update_from_sync_dir(
+        &self,
+        toml_account_config: &TomlAccountConfig,
+        account_config: Arc<AccountConfig>,
+    ) -> Result<IndexUpdateReport> {
+        // features must all target the local Maildir, whatever the
+        // backend overrides of the account are
+        let toml_account_config = Arc::new(TomlAccountConfig {
+            backend: Some(BackendKind::MaildirForSync),
+            folder: None,
+            envelope: None,
+            flag: None,
+            message: None,
+            ..toml_account_config.clone()
+        });
+
+        let backend = Backend::new(
+            toml_account_config,
+            account_config.clone(),
+            [&BackendKind::MaildirForSync],
+            |builder| {
+                builder.set_list_folders(BackendFeatureSource::Context);
+                builder.set_list_envelopes(BackendFeatureSource::Context);
+                builder.set_peek_messages(BackendFeatureSource::Context);
+            },
+        )
+        .await?;
+
+        let mut report = IndexUpdateReport::default();
+
+        for folder in backend.list_folders().await?.iter() {
+            let folder = folder.name.as_str();
+            let mut indexed_ids = self.indexed_ids(folder)?;
+
+            let opts = ListEnvelopesOptions {
+                page: 0,
+                page_size: 0,
+                query: None,
+            };
+            let envelopes = backend.backend.list_envelopes(folder, opts).await?;
+            let timestamps: HashMap<String, i64> = envelopes
+                .iter()
+                .map(|envelope| (envelope.id.clone(), envelope.date.timestamp()))
+                .collect();
+            let envelopes = Envelopes::from_backend(&account_config, &IdMapper::Dummy, envelopes)?;
+
+            for envelope in envelopes.iter() {
+                let timestamp = timestamps.get(&envelope.id).copied().unwrap_or_default();
+
+                if indexed_ids.remove(&envelope.id) {
+                    self.update(folder, envelope.clone(), timestamp)?;
+                    report.updated += 1;
+                    continue;
+                }
+
+                let id = envelope.id.clone();
+                match self
+                    .index_envelope(&backend, folder, envelope.clone(), timestamp)
+                    .await
+                {
+                    Ok(()) => report.added += 1,
+                    Err(err) => {
+                        warn!("cannot index envelope {id} from folder {folder}: {err}");
+                        debug!("{err:?}");
+                        report.errors.push((folder.to_owned(), id, err.to_string()));
+                    }
+                }
+            }
+
+            for id in indexed_ids {
+                self.remove(folder, &id)?;
+                report.removed += 1;
+            }
+        }
+
+        self.db.flush_async().await?;
+
+        Ok(report)
+    }
+
+    async fn index_envelope(
+        &self,
+        backend: &Backend,
+        folder: &str,
+        envelope: Envelope,
+        timestamp: i64,
+    ) -> Result<()> {
+        let mut terms: HashMap<String, u32> = HashMap::new();
+
+        let mut add_terms = |text: &str, weight: u32| {
+            for term in tokenize(text) {
+                *terms.entry(term).or_default() += weight;
+            }
+        };
+
+        add_terms(&envelope.subject, SUBJECT_WEIGHT);
+
+        for mailbox in [&envelope.from, &envelope.to] {
+            add_terms(&mailbox.addr, ADDRESS_WEIGHT);
+            if let Some(name) = &mailbox.name {
+                add_terms(name, ADDRESS_WEIGHT);
+            }
+        }
+
+        let msgs = backend
+            .backend
+            .peek_messages(folder, &Id::single(&envelope.id))
+            .await?;
+
+        if let Some(msg) = msgs.first() {
+            let msg = msg.parsed()?;
+
+            for i in 0.. {
+                match msg.body_text(i) {
+                    Some(text) => add_terms(&text, BODY_WEIGHT),
+                    None => break,
+                }
+            }
+
+            for attachment in msg.attachments() {
+                if let Some(name) = attachment.attachment_name() {
+                    add_terms(name, ATTACHMENT_WEIGHT);
+                }
+            }
+        }
+
+        self.insert(folder, envelope, timestamp, terms)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::temporary_db;
+
+    fn index() -> SearchIndex {
+        SearchIndex::from_db(temporary_db()).unwrap()
+    }
+
+    fn envelope(id: &str, subject: &str) -> Envelope {
+        Envelope {
+            id: id.to_owned(),
+            subject: subject.to_owned(),
+            ..Default::default()
+        }
+    }
+
+    fn terms(text: &str, weight: u32) -> HashMap<String, u32> {
+        let mut terms = HashMap::new();
+        for term in tokenize(text) {
+            *terms.entry(term).or_default() += weight;
+        }
+        terms
+    }
+
+    fn ids(hits: Vec<SearchHit>) -> Vec<String> {
+        hits.into_iter().map(|hit| hit.id).collect()
+    }
+
+    #[test]
+    fn tokenize_text() {
+        let terms: Vec<String> = tokenize("Hello, World! a  rust-lang 2024").collect();
+        assert_eq!(terms, vec!["hello", "world", "rust", "lang", "2024"]);
+    }
+
+    #[test]
+    fn search_ranked() {
+        let index = index();
+        let body = "rust rust rust is great";
+
+        index
+            .insert("INBOX", envelope("1", "hello"), 0, terms("hello rust", 1))
+            .unwrap();
+        index
+            .insert("INBOX", envelope("2", "rust"), 0, terms(body, 4))
+            .unwrap();
+        index
+            .insert("INBOX", envelope("3", "other"), 0, terms("nothing", 1))
+            .unwrap();
+        index
+            .insert("Sent", envelope("4", "rust"), 0, terms("rust", 1))
+            .unwrap();
+
+        assert_eq!(ids(index.search("INBOX", "rust").unwrap()), vec!["2", "1"]);
+        assert_eq!(ids(index.search("INBOX", "hello rust").unwrap()), vec!["1"]);
+        assert_eq!(ids(index.search("INBOX", "gre*").unwrap()), vec!["2"]);
+        assert_eq!(ids(index.search("Sent", "rust").unwrap()), vec!["4"]);
+        assert!(index.search("INBOX", "missing").unwrap().is_empty());
+        assert_eq!(index.docs_count("INBOX").unwrap(), 3);
+        assert_eq!(index.docs_count("Sent").unwrap(), 1);
+    }
+
+    #[test]
+    fn search_ties_ranked_by_date_then_id() {
+        let index = index();
+
+        for (id, timestamp) in [("3", 10), ("1", 20), ("2", 10)] {
+            index
+                .insert("INBOX", envelope(id, "rust"), timestamp, terms("rust", 1))
+                .unwrap();
+        }
+
+        assert_eq!(
+            ids(index.search("INBOX", "rust").unwrap()),
+            vec!["1", "2", "3"]
+        );
+    }
+
+    #[test]
+    fn remove_cleans_postings() {
+        let index = index();
+
+        index
+            .insert("INBOX", envelope("1", "hello"), 0, terms("hello", 1))
+            .unwrap();
+        index.remove("INBOX", "1").unwrap();
+
+        assert!(index.search("INBOX", "hello").unwrap().is_empty());
+        assert!(index.postings.is_empty());
+        assert!(index.indexed_ids("INBOX").unwrap().is_empty());
+        assert_eq!(index.docs_count("INBOX").unwrap(), 0);
+    }
+}
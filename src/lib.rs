@@ -4,21 +4,30 @@ pub mod cache;
 pub mod cli;
 pub mod completion;
 pub mod config;
+pub mod contact;
 pub mod email;
+pub mod error;
 pub mod folder;
 #[cfg(feature = "imap")]
 pub mod imap;
+#[cfg(feature = "account-sync")]
+pub mod index;
 #[cfg(feature = "maildir")]
 pub mod maildir;
 pub mod manual;
 #[cfg(feature = "notmuch")]
 pub mod notmuch;
+pub mod outbox;
 pub mod output;
 pub mod printer;
+pub mod rule;
+pub mod schedule;
 #[cfg(feature = "sendmail")]
 pub mod sendmail;
+pub mod server;
 #[cfg(feature = "smtp")]
 pub mod smtp;
+pub mod tui;
 pub mod ui;
 
 #[doc(inline)]
@@ -1,14 +1,25 @@
 use anyhow::Result;
-use clap::Parser;
+use clap::{CommandFactory, Parser};
+use console::Term;
 use env_logger::{Builder as LoggerBuilder, Env, DEFAULT_FILTER_ENV};
 use himalaya::{
-    cli::Cli, config::TomlConfig, envelope::command::list::ListEnvelopesCommand,
-    message::command::mailto::MessageMailtoCommand, printer::StdoutPrinter,
+    cli::Cli,
+    config::TomlConfig,
+    envelope::command::list::ListEnvelopesCommand,
+    error::{self, ErrorKind},
+    message::command::mailto::MessageMailtoCommand,
+    output::OutputFmt,
+    printer::{Printer, StdoutPrinter},
+    tui::command::TuiCommand,
 };
 use log::{debug, trace};
+use std::{
+    io::{self, IsTerminal},
+    process::ExitCode,
+};
 
 #[tokio::main]
-async fn main() -> Result<()> {
+async fn main() -> ExitCode {
     #[cfg(not(target_os = "windows"))]
     if let Err((_, err)) = coredump::register_panic_handler() {
         debug!("cannot register coredump panic handler: {err}");
@@ -27,24 +38,70 @@ async fn main() -> Result<()> {
         .filter(|arg| arg.starts_with("mailto:"));
 
     if let Some(ref url) = mailto {
-        let mut printer = StdoutPrinter::default();
-        let config = TomlConfig::from_default_paths().await?;
+        return match execute_mailto(url).await {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(err) => error::report(&err, false),
+        };
+    }
+
+    // argument errors are reported as JSON as well when a JSON
+    // output was requested, help and version are printed as usual
+    let cli = match Cli::try_parse() {
+        Ok(cli) => cli,
+        Err(err) if err.use_stderr() && json_requested() => {
+            return error::report(&ErrorKind::InvalidArgs.attach(err.into()), true);
+        }
+        Err(err) => err.exit(),
+    };
+    let json = matches!(cli.output, OutputFmt::Json | OutputFmt::Ndjson);
 
-        return MessageMailtoCommand::new(url)?
-            .execute(&mut printer, &config)
-            .await;
+    match execute(cli).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => error::report(&err, json),
     }
+}
 
-    let cli = Cli::parse();
+/// Return true if a JSON output was requested, even if the command
+/// line is invalid.
+fn json_requested() -> bool {
+    Cli::command()
+        .ignore_errors(true)
+        .try_get_matches()
+        .ok()
+        .and_then(|matches| matches.get_one::<OutputFmt>("output").cloned())
+        .is_some_and(|output| matches!(output, OutputFmt::Json | OutputFmt::Ndjson))
+}
+
+async fn execute_mailto(url: &str) -> Result<()> {
+    let mut printer = StdoutPrinter::default();
+    let config = TomlConfig::from_default_paths().await?;
+
+    MessageMailtoCommand::new(url)?
+        .execute(&mut printer, &config)
+        .await
+}
+
+async fn execute(cli: Cli) -> Result<()> {
     let mut printer = StdoutPrinter::new(cli.output, cli.color);
 
     match cli.command {
         Some(cmd) => cmd.execute(&mut printer, cli.config_paths.as_ref()).await,
         None => {
             let config = TomlConfig::from_paths_or_default(cli.config_paths.as_ref()).await?;
-            ListEnvelopesCommand::default()
-                .execute(&mut printer, &config)
-                .await
+
+            // open the interactive interface only when both stdin and
+            // stdout are terminals, so that scripts keep getting the
+            // envelopes listing
+            if !printer.is_machine_readable()
+                && io::stdin().is_terminal()
+                && Term::stdout().is_term()
+            {
+                TuiCommand::default().execute(&mut printer, &config).await
+            } else {
+                ListEnvelopesCommand::default()
+                    .execute(&mut printer, &config)
+                    .await
+            }
         }
     }
 }
@@ -0,0 +1,13 @@
+use clap::Parser;
+
+/// The outbox queue flag parser.
+#[derive(Debug, Default, Parser)]
+pub struct OutboxQueueFlag {
+    /// Queue the message in the outbox instead of sending it.
+    ///
+    /// Queued messages are sent later on by the outbox flush command.
+    /// Messages that cannot be delivered because of a network error
+    /// are queued automatically.
+    #[arg(long = "queue", name = "outbox_queue")]
+    pub enable: bool,
+}
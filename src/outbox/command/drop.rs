@@ -0,0 +1,43 @@
+use anyhow::{bail, Result};
+use clap::Parser;
+use log::info;
+
+use crate::{
+    account::arg::name::AccountNameFlag, config::TomlConfig, outbox::Outbox, printer::Printer,
+};
+
+/// Drop messages from the outbox.
+///
+/// This command allows you to remove messages from the outbox
+/// without sending them.
+#[derive(Debug, Parser)]
+pub struct OutboxDropCommand {
+    /// The identifiers of the outbox messages to drop.
+    #[arg(value_name = "ID", required = true)]
+    pub ids: Vec<u64>,
+
+    #[command(flatten)]
+    pub account: AccountNameFlag,
+}
+
+impl OutboxDropCommand {
+    pub async fn execute(self, printer: &mut impl Printer, config: &TomlConfig) -> Result<()> {
+        info!("executing drop outbox command");
+
+        let (_, account_config) = config.clone().into_account_configs(
+            self.account.name.as_deref(),
+            #[cfg(feature = "account-sync")]
+            false,
+        )?;
+
+        let outbox = Outbox::new(&account_config)?;
+
+        for id in &self.ids {
+            if !outbox.remove(*id)? {
+                bail!("cannot find outbox message {id}");
+            }
+        }
+
+        printer.print("Message(s) successfully dropped from the outbox!")
+    }
+}
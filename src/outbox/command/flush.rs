@@ -0,0 +1,77 @@
+use anyhow::Result;
+use chrono::Local;
+use clap::Parser;
+use log::info;
+
+#[cfg(feature = "account-sync")]
+use crate::cache::arg::disable::CacheDisableFlag;
+use crate::{
+    account::arg::name::AccountNameFlag,
+    config::TomlConfig,
+    outbox::{self, Outbox},
+    printer::Printer,
+};
+
+/// Send messages of the outbox.
+///
+/// This command tries to send messages waiting in the outbox. Sent
+/// messages are removed from the outbox, the other ones stay there
+/// until the next flush.
+#[derive(Debug, Parser)]
+pub struct OutboxFlushCommand {
+    /// The identifiers of the outbox messages to send.
+    ///
+    /// If omitted, all messages of the outbox are sent, except the
+    /// scheduled ones that are not due yet.
+    #[arg(value_name = "ID")]
+    pub ids: Vec<u64>,
+
+    #[cfg(feature = "account-sync")]
+    #[command(flatten)]
+    pub cache: CacheDisableFlag,
+
+    #[command(flatten)]
+    pub account: AccountNameFlag,
+}
+
+impl OutboxFlushCommand {
+    pub async fn execute(self, printer: &mut impl Printer, config: &TomlConfig) -> Result<()> {
+        info!("executing flush outbox command");
+
+        let (toml_account_config, account_config) = config.clone().into_account_configs(
+            self.account.name.as_deref(),
+            #[cfg(feature = "account-sync")]
+            self.cache.disable,
+        )?;
+
+        let outbox = Outbox::new(&account_config)?;
+        let now = Local::now().timestamp();
+        let queued: Vec<_> = outbox
+            .list()?
+            .iter()
+            .filter(|queued| {
+                if self.ids.is_empty() {
+                    queued.is_due(now)
+                } else {
+                    self.ids.contains(&queued.id)
+                }
+            })
+            .cloned()
+            .collect();
+
+        if queued.is_empty() {
+            return printer.print("Outbox is empty, nothing to send!");
+        }
+
+        let (sent, failed) =
+            outbox::flush(toml_account_config, account_config, &outbox, queued).await?;
+
+        if failed > 0 {
+            printer.print(format!(
+                "{sent} message(s) sent, {failed} message(s) still queued in the outbox!"
+            ))
+        } else {
+            printer.print(format!("{sent} message(s) successfully sent!"))
+        }
+    }
+}
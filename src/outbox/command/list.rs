@@ -0,0 +1,46 @@
+use anyhow::Result;
+use clap::Parser;
+use log::info;
+
+use crate::{
+    account::arg::name::AccountNameFlag,
+    config::TomlConfig,
+    outbox::Outbox,
+    printer::{PrintTableOpts, Printer},
+    ui::arg::max_width::TableMaxWidthFlag,
+};
+
+/// List messages of the outbox.
+///
+/// This command allows you to list messages waiting to be sent, with
+/// the number of delivery attempts and the last delivery error.
+#[derive(Debug, Parser)]
+pub struct OutboxListCommand {
+    #[command(flatten)]
+    pub table: TableMaxWidthFlag,
+
+    #[command(flatten)]
+    pub account: AccountNameFlag,
+}
+
+impl OutboxListCommand {
+    pub async fn execute(self, printer: &mut impl Printer, config: &TomlConfig) -> Result<()> {
+        info!("executing list outbox command");
+
+        let (_, account_config) = config.clone().into_account_configs(
+            self.account.name.as_deref(),
+            #[cfg(feature = "account-sync")]
+            false,
+        )?;
+
+        let queued = Outbox::new(&account_config)?.list()?;
+
+        printer.print_table(
+            Box::new(queued),
+            PrintTableOpts {
+                format: &account_config.get_message_read_format(),
+                max_width: self.table.max_width,
+            },
+        )
+    }
+}
@@ -0,0 +1,39 @@
+mod drop;
+mod flush;
+mod list;
+
+use anyhow::Result;
+use clap::Subcommand;
+
+use crate::{config::TomlConfig, printer::Printer};
+
+use self::{drop::OutboxDropCommand, flush::OutboxFlushCommand, list::OutboxListCommand};
+
+/// Manage the outbox.
+///
+/// The outbox contains messages waiting to be sent, either because
+/// their delivery failed or because they were explicitly queued. This
+/// subcommand allows you to list, send or drop them.
+#[derive(Debug, Subcommand)]
+pub enum OutboxSubcommand {
+    #[command(alias = "lst")]
+    List(OutboxListCommand),
+
+    #[command(alias = "send")]
+    Flush(OutboxFlushCommand),
+
+    #[command(arg_required_else_help = true)]
+    #[command(alias = "remove", alias = "rm")]
+    Drop(OutboxDropCommand),
+}
+
+impl OutboxSubcommand {
+    #[allow(unused)]
+    pub async fn execute(self, printer: &mut impl Printer, config: &TomlConfig) -> Result<()> {
+        match self {
+            Self::List(cmd) => cmd.execute(printer, config).await,
+            Self::Flush(cmd) => cmd.execute(printer, config).await,
+            Self::Drop(cmd) => cmd.execute(printer, config).await,
+        }
+    }
+}
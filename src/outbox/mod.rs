@@ -0,0 +1,560 @@
+//! Module dedicated to the outbox.
+//!
+//! The outbox is a persistent queue of messages waiting to be sent.
+//! Messages end up there when their delivery fails or when they are
+//! explicitly queued, and are sent later on by the outbox flush
+//! command. Scheduled messages are also stored there, until they are
+//! delivered by the schedule run command.
+
+pub mod arg;
+pub mod command;
+
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Local};
+use email::{
+    account::config::AccountConfig,
+    backend::feature::BackendFeatureSource,
+    flag::{Flag, Flags},
+    message::{add::AddMessage, send::SendMessage},
+};
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use sled::{Config, Db, Tree};
+use std::{io, ops, sync::Arc};
+
+use crate::{
+    account::{self, config::TomlAccountConfig},
+    backend::Backend,
+    folder::archive,
+    printer::{Print, PrintTable, PrintTableOpts, WriteColor},
+    ui::{Cell, Row, Table},
+};
+
+/// Represents the outcome of sending a message.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+#[serde(tag = "status", rename_all = "kebab-case")]
+pub enum SendOutcome {
+    /// The message has been sent. The error is set when the copy to
+    /// the sent folder failed.
+    Sent { save_copy_error: Option<String> },
+    /// The message has been queued in the outbox instead of being
+    /// sent, on demand or after a network error.
+    Queued { id: u64, error: Option<String> },
+    /// The message has been queued in the outbox, to be sent at the
+    /// given date.
+    Scheduled { id: u64, send_at: i64 },
+}
+
+impl Print for SendOutcome {
+    fn print(&self, writer: &mut dyn WriteColor) -> Result<()> {
+        match self {
+            Self::Sent {
+                save_copy_error: None,
+            } => writeln!(writer, "Message successfully sent!")?,
+            Self::Sent {
+                save_copy_error: Some(err),
+            } => writeln!(
+                writer,
+                "Message successfully sent, but its copy cannot be saved: {err}"
+            )?,
+            Self::Queued { id, error: None } => writeln!(
+                writer,
+                "Message not sent, queued in the outbox with id {id}!"
+            )?,
+            Self::Queued {
+                id,
+                error: Some(err),
+            } => writeln!(
+                writer,
+                "Cannot send message: {err}. Message queued in the outbox with id {id}, use outbox flush to retry."
+            )?,
+            Self::Scheduled { id, send_at } => writeln!(
+                writer,
+                "Message scheduled in the outbox with id {id}, to be sent at {}!",
+                format_timestamp(*send_at)
+            )?,
+        }
+
+        Ok(writer.reset()?)
+    }
+}
+
+/// Represents a message waiting in the outbox.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct QueuedMessage {
+    pub id: u64,
+    pub subject: String,
+    pub to: String,
+    /// The queuing date, as a UNIX timestamp.
+    pub queued_at: i64,
+    pub attempts: u32,
+    pub last_error: Option<String>,
+    /// The scheduled sending date, as a UNIX timestamp.
+    #[serde(default)]
+    pub send_at: Option<i64>,
+}
+
+impl QueuedMessage {
+    /// Return true if the message can be sent at the given date.
+    pub fn is_due(&self, now: i64) -> bool {
+        self.send_at.map(|send_at| send_at <= now).unwrap_or(true)
+    }
+}
+
+fn format_timestamp(timestamp: i64) -> String {
+    DateTime::from_timestamp(timestamp, 0)
+        .map(|date| {
+            date.with_timezone(&Local)
+                .format("%Y-%m-%d %H:%M")
+                .to_string()
+        })
+        .unwrap_or_default()
+}
+
+impl Table for QueuedMessage {
+    fn head() -> Row {
+        Row::new()
+            .cell(Cell::new("ID").bold().underline().white())
+            .cell(Cell::new("SUBJECT").shrinkable().bold().underline().white())
+            .cell(Cell::new("TO").bold().underline().white())
+            .cell(Cell::new("QUEUED AT").bold().underline().white())
+            .cell(Cell::new("SEND AT").bold().underline().white())
+            .cell(Cell::new("ATTEMPTS").bold().underline().white())
+            .cell(
+                Cell::new("LAST ERROR")
+                    .shrinkable()
+                    .bold()
+                    .underline()
+                    .white(),
+            )
+    }
+
+    fn row(&self) -> Row {
+        let queued_at = format_timestamp(self.queued_at);
+        let send_at = self.send_at.map(format_timestamp).unwrap_or_default();
+
+        Row::new()
+            .cell(Cell::new(self.id.to_string()).red())
+            .cell(Cell::new(&self.subject).shrinkable().green())
+            .cell(Cell::new(&self.to).blue())
+            .cell(Cell::new(queued_at).yellow())
+            .cell(Cell::new(send_at).yellow())
+            .cell(Cell::new(self.attempts.to_string()).white())
+            .cell(Cell::new(self.last_error.as_deref().unwrap_or_default()).shrinkable())
+    }
+}
+
+/// Represents the list of messages waiting in the outbox.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct QueuedMessages(Vec<QueuedMessage>);
+
+impl ops::Deref for QueuedMessages {
+    type Target = Vec<QueuedMessage>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl PrintTable for QueuedMessages {
+    fn print_table(&self, writer: &mut dyn WriteColor, opts: PrintTableOpts) -> Result<()> {
+        writeln!(writer)?;
+        Table::print(writer, self, opts)?;
+        writeln!(writer)?;
+        Ok(())
+    }
+}
+
+/// The outbox of an account.
+///
+/// Metadata are stored in the default tree, raw messages are stored
+/// in the `messages` tree. Both are indexed by the big-endian bytes
+/// of the message id, so that iterating keeps the queuing order.
+pub struct Outbox {
+    db: Db,
+    messages: Tree,
+}
+
+impl Outbox {
+    pub fn new(account_config: &AccountConfig) -> Result<Self> {
+        let db_path = account::data_dir(".outbox", &account_config.name)?;
+
+        let db = Config::new()
+            .path(&db_path)
+            .open()
+            .with_context(|| format!("cannot open outbox database at {db_path:?}"))?;
+
+        Self::from_db(db)
+    }
+
+    fn from_db(db: Db) -> Result<Self> {
+        let messages = db
+            .open_tree("messages")
+            .context("cannot open outbox messages")?;
+        Ok(Self { db, messages })
+    }
+
+    /// Add the given raw message to the outbox.
+    pub fn push(&self, msg: &[u8], error: Option<String>) -> Result<QueuedMessage> {
+        self.insert(msg, error, None)
+    }
+
+    /// Add the given raw message to the outbox, to be sent at the
+    /// given date.
+    pub fn schedule(&self, msg: &[u8], send_at: i64) -> Result<QueuedMessage> {
+        self.insert(msg, None, Some(send_at))
+    }
+
+    fn insert(
+        &self,
+        msg: &[u8],
+        error: Option<String>,
+        send_at: Option<i64>,
+    ) -> Result<QueuedMessage> {
+        let id = self.db.generate_id().context("cannot generate outbox id")?;
+        let headers = mail_parser::MessageParser::new().parse_headers(msg);
+
+        let queued = QueuedMessage {
+            id,
+            subject: headers
+                .as_ref()
+                .and_then(|msg| msg.subject())
+                .unwrap_or_default()
+                .to_owned(),
+            to: headers
+                .as_ref()
+                .and_then(|msg| msg.to())
+                .and_then(|to| to.first())
+                .and_then(|to| to.address())
+                .unwrap_or_default()
+                .to_owned(),
+            queued_at: Local::now().timestamp(),
+            attempts: if error.is_some() { 1 } else { 0 },
+            last_error: error,
+            send_at,
+        };
+
+        self.messages.insert(id.to_be_bytes(), msg)?;
+        self.save(&queued)?;
+        self.db.flush()?;
+
+        debug!("message {id} added to the outbox");
+        Ok(queued)
+    }
+
+    fn save(&self, queued: &QueuedMessage) -> Result<()> {
+        let meta = serde_json::to_vec(queued)?;
+        self.db.insert(queued.id.to_be_bytes(), meta)?;
+        Ok(())
+    }
+
+    /// List messages of the outbox, in queuing order.
+    pub fn list(&self) -> Result<QueuedMessages> {
+        let queued = self
+            .db
+            .iter()
+            .map(|entry| {
+                let (_, meta) = entry?;
+                Ok(serde_json::from_slice(&meta)?)
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(QueuedMessages(queued))
+    }
+
+    /// Get the raw message matching the given id.
+    pub fn get_raw(&self, id: u64) -> Result<Vec<u8>> {
+        let msg = self
+            .messages
+            .get(id.to_be_bytes())?
+            .ok_or(anyhow!("cannot find outbox message {id}"))?;
+        Ok(msg.to_vec())
+    }
+
+    /// Record a failed delivery attempt of the given message.
+    pub fn record_failure(&self, queued: &mut QueuedMessage, error: String) -> Result<()> {
+        queued.attempts += 1;
+        queued.last_error = Some(error);
+        self.save(queued)?;
+        self.db.flush()?;
+        Ok(())
+    }
+
+    /// Remove the message matching the given id from the outbox.
+    ///
+    /// Returns false if the message was not found.
+    pub fn remove(&self, id: u64) -> Result<bool> {
+        let found = self.db.remove(id.to_be_bytes())?.is_some();
+        self.messages.remove(id.to_be_bytes())?;
+        self.db.flush()?;
+        Ok(found)
+    }
+}
+
+/// Build a backend able to send messages and to save copies of them.
+pub async fn build_backend(
+    toml_account_config: Arc<TomlAccountConfig>,
+    account_config: Arc<AccountConfig>,
+) -> Result<Backend> {
+    let send_message_kind = toml_account_config.send_message_kind().into_iter().chain(
+        toml_account_config
+            .add_message_kind()
+            .filter(|_| account_config.should_save_copy_sent_message()),
+    );
+
+    Backend::new(
+        toml_account_config.clone(),
+        account_config,
+        send_message_kind,
+        |builder| {
+            builder.set_send_message(BackendFeatureSource::Context);
+            builder.set_add_message(BackendFeatureSource::Context);
+        },
+    )
+    .await
+}
+
+/// Send the given message, then save a copy of it to the sent folder.
+///
+/// Only delivery errors are returned: a message that failed to be
+/// copied to the sent folder has still been sent, so it must not be
+/// queued again. The copy error is part of the outcome instead.
+pub async fn deliver(
+    backend: &Backend,
+    account_config: &AccountConfig,
+    msg: &[u8],
+) -> Result<SendOutcome> {
+    backend.send_message(msg).await?;
+
+    let mut save_copy_error = None;
+
+    if account_config.should_save_copy_sent_message() {
+        let folder = account_config.get_sent_folder_alias();
+        let flags = Flags::from_iter([Flag::Seen]);
+        if let Err(err) = backend.add_message_with_flags(&folder, msg, &flags).await {
+            warn!("cannot save copy of sent message to folder {folder}: {err}");
+            debug!("{err:?}");
+            save_copy_error = Some(format!("cannot save message to folder {folder}: {err}"));
+        }
+    }
+
+    Ok(SendOutcome::Sent { save_copy_error })
+}
+
+/// Return true if the given delivery error is worth retrying later,
+/// which is the case of network errors only.
+pub fn is_transient(err: &anyhow::Error) -> bool {
+    use io::ErrorKind::*;
+
+    err.chain()
+        .filter_map(|cause| cause.downcast_ref::<io::Error>())
+        .any(|err| {
+            matches!(
+                err.kind(),
+                ConnectionRefused
+                    | ConnectionReset
+                    | ConnectionAborted
+                    | NotConnected
+                    | AddrNotAvailable
+                    | TimedOut
+            )
+        })
+}
+
+/// Replace the Date header of the given raw message by the given
+/// date.
+///
+/// Queued messages get their Date header updated when they are
+/// actually sent, instead of keeping their composition date.
+pub fn with_date(msg: &[u8], date: DateTime<Local>) -> Vec<u8> {
+    let eol = if msg.windows(2).any(|w| w == b"\r\n") {
+        "\r\n"
+    } else {
+        "\n"
+    };
+
+    let mut dated = format!("Date: {}{eol}", date.to_rfc2822()).into_bytes();
+    dated.extend(archive::strip_headers(msg, &["date"]));
+    dated
+}
+
+/// Send the given outbox messages.
+///
+/// Sent messages are removed from the outbox, the other ones stay
+/// there with their failure recorded. Returns the number of sent and
+/// failed messages.
+pub async fn flush(
+    toml_account_config: Arc<TomlAccountConfig>,
+    account_config: Arc<AccountConfig>,
+    outbox: &Outbox,
+    queued: Vec<QueuedMessage>,
+) -> Result<(usize, usize)> {
+    let backend = build_backend(toml_account_config, account_config.clone()).await;
+
+    let mut sent = 0;
+    let mut failed = 0;
+
+    for mut queued in queued {
+        let id = queued.id;
+
+        let res = match &backend {
+            Ok(backend) => {
+                let msg = with_date(&outbox.get_raw(id)?, Local::now());
+                deliver(backend, &account_config, &msg).await
+            }
+            Err(err) => Err(anyhow!("cannot build backend: {err}")),
+        };
+
+        match res {
+            Ok(_) => {
+                outbox.remove(id)?;
+                sent += 1;
+            }
+            Err(err) => {
+                warn!("cannot send outbox message {id}: {err}");
+                debug!("{err:?}");
+                outbox.record_failure(&mut queued, err.to_string())?;
+                failed += 1;
+            }
+        }
+    }
+
+    Ok((sent, failed))
+}
+
+/// Send the given message, or queue it in the outbox if queuing is
+/// requested or if its delivery fails because of a network error.
+pub async fn send_or_queue(
+    toml_account_config: Arc<TomlAccountConfig>,
+    account_config: Arc<AccountConfig>,
+    msg: &[u8],
+    queue: bool,
+) -> Result<SendOutcome> {
+    if queue {
+        let queued = Outbox::new(&account_config)?.push(msg, None)?;
+        return Ok(SendOutcome::Queued {
+            id: queued.id,
+            error: None,
+        });
+    }
+
+    let res = match build_backend(toml_account_config, account_config.clone()).await {
+        Ok(backend) => deliver(&backend, &account_config, msg).await,
+        Err(err) => Err(err),
+    };
+
+    match res {
+        Ok(outcome) => Ok(outcome),
+        Err(err) => queue_if_transient(&account_config, msg, err),
+    }
+}
+
+/// Queue the given message after a delivery failure caused by a
+/// network error, otherwise return the error.
+pub fn queue_if_transient(
+    account_config: &AccountConfig,
+    msg: &[u8],
+    err: anyhow::Error,
+) -> Result<SendOutcome> {
+    if !is_transient(&err) {
+        return Err(err);
+    }
+
+    warn!("cannot send message, queuing it in the outbox: {err}");
+    debug!("{err:?}");
+
+    let queued = Outbox::new(account_config)?.push(msg, Some(err.to_string()))?;
+
+    Ok(SendOutcome::Queued {
+        id: queued.id,
+        error: Some(err.to_string()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::temporary_db;
+
+    fn outbox() -> Outbox {
+        Outbox::from_db(temporary_db()).unwrap()
+    }
+
+    #[test]
+    fn push_then_remove() {
+        let outbox = outbox();
+
+        let msg = b"To: bob@localhost\r\nSubject: hello\r\n\r\nbody";
+        let a = outbox.push(msg, None).unwrap();
+        let mut b = outbox.push(msg, Some("offline".into())).unwrap();
+
+        assert_eq!(a.subject, "hello");
+        assert_eq!(a.to, "bob@localhost");
+        assert_eq!(a.attempts, 0);
+        assert_eq!(b.attempts, 1);
+
+        outbox
+            .record_failure(&mut b, "still offline".into())
+            .unwrap();
+
+        let queued = outbox.list().unwrap();
+        assert_eq!(queued.len(), 2);
+        assert_eq!(queued[0].id, a.id);
+        assert_eq!(queued[1].attempts, 2);
+        assert_eq!(queued[1].last_error.as_deref(), Some("still offline"));
+        assert_eq!(outbox.get_raw(a.id).unwrap(), msg);
+
+        assert!(outbox.remove(a.id).unwrap());
+        assert!(!outbox.remove(a.id).unwrap());
+        assert!(outbox.get_raw(a.id).is_err());
+        assert_eq!(outbox.list().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn schedule() {
+        let outbox = outbox();
+
+        let msg = b"Subject: later\r\n\r\nbody";
+        let queued = outbox.schedule(msg, 100).unwrap();
+        let pushed = outbox.push(msg, None).unwrap();
+
+        assert_eq!(queued.send_at, Some(100));
+        assert!(!queued.is_due(99));
+        assert!(queued.is_due(100));
+        assert!(pushed.is_due(0));
+        assert_eq!(outbox.list().unwrap()[0].send_at, Some(100));
+    }
+
+    #[test]
+    fn date_at_send_time() {
+        let date = DateTime::parse_from_rfc3339("2026-11-02T09:00:00+01:00")
+            .unwrap()
+            .with_timezone(&Local);
+        let expected = format!("Date: {}\r\nSubject: later\r\n\r\nbody", date.to_rfc2822());
+
+        let msg = b"Date: Mon, 1 Jan 2024 00:00:00 +0000\r\nSubject: later\r\n\r\nbody";
+        assert_eq!(String::from_utf8(with_date(msg, date)).unwrap(), expected);
+
+        let msg = b"Subject: later\r\n\r\nbody";
+        assert_eq!(String::from_utf8(with_date(msg, date)).unwrap(), expected);
+    }
+
+    #[test]
+    fn transient_errors() {
+        let err = anyhow!(std::io::Error::from(std::io::ErrorKind::ConnectionRefused))
+            .context("cannot connect to smtp server");
+        assert!(is_transient(&err));
+
+        let err = anyhow!("recipient rejected").context("cannot send message");
+        assert!(!is_transient(&err));
+    }
+
+    #[test]
+    fn send_outcome_json() {
+        let outcome = SendOutcome::Queued { id: 3, error: None };
+        assert_eq!(
+            serde_json::to_value(outcome).unwrap(),
+            serde_json::json!({"status": "queued", "id": 3, "error": null})
+        );
+    }
+}
@@ -16,7 +16,7 @@ pub fn global_args() -> impl IntoIterator<Item = Arg> {
             .short('o')
             .global(true)
             .value_name("format")
-            .value_parser(["plain", "json"])
+            .value_parser(["plain", "json", "csv", "tsv", "ndjson"])
             .default_value("plain"),
         Arg::new(ARG_COLOR)
             .help("Control when to use colors")
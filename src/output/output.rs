@@ -14,6 +14,17 @@ pub enum OutputFmt {
     #[default]
     Plain,
     Json,
+    Csv,
+    Tsv,
+    Ndjson,
+}
+
+impl OutputFmt {
+    /// Return `true` if the format is meant to be consumed by
+    /// programs rather than humans.
+    pub fn is_machine_readable(&self) -> bool {
+        !matches!(self, Self::Plain)
+    }
 }
 
 impl FromStr for OutputFmt {
@@ -23,6 +34,9 @@ impl FromStr for OutputFmt {
         match fmt {
             fmt if fmt.eq_ignore_ascii_case("json") => Ok(Self::Json),
             fmt if fmt.eq_ignore_ascii_case("plain") => Ok(Self::Plain),
+            fmt if fmt.eq_ignore_ascii_case("csv") => Ok(Self::Csv),
+            fmt if fmt.eq_ignore_ascii_case("tsv") => Ok(Self::Tsv),
+            fmt if fmt.eq_ignore_ascii_case("ndjson") => Ok(Self::Ndjson),
             unknown => Err(anyhow!("cannot parse output format {}", unknown)),
         }
     }
@@ -33,6 +47,9 @@ impl fmt::Display for OutputFmt {
         let fmt = match *self {
             OutputFmt::Json => "JSON",
             OutputFmt::Plain => "Plain",
+            OutputFmt::Csv => "CSV",
+            OutputFmt::Tsv => "TSV",
+            OutputFmt::Ndjson => "NDJSON",
         };
         write!(f, "{}", fmt)
     }
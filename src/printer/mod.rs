@@ -2,6 +2,7 @@ pub mod print;
 pub mod print_table;
 #[allow(clippy::module_inception)]
 pub mod printer;
+pub mod record;
 
 pub use print::*;
 pub use print_table::*;
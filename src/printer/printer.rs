@@ -5,7 +5,10 @@ use termcolor::StandardStream;
 
 use crate::{
     output::{args, ColorFmt, OutputFmt},
-    printer::{Print, PrintTable, PrintTableOpts, WriteColor},
+    printer::{
+        record::{self, Delimiter},
+        Print, PrintTable, PrintTableOpts, WriteColor,
+    },
 };
 
 pub trait Printer {
@@ -14,13 +17,18 @@ pub trait Printer {
     // TODO: rename log
     fn print_log<T: Debug + Print>(&mut self, data: T) -> Result<()>;
     // TODO: rename table
-    fn print_table<T: Debug + erased_serde::Serialize + PrintTable + ?Sized>(
+    fn print_table<T: Debug + serde::Serialize + PrintTable + ?Sized>(
         &mut self,
         // TODO: remove Box
         data: Box<T>,
         opts: PrintTableOpts,
     ) -> Result<()>;
-    fn is_json(&self) -> bool;
+    /// Whether the output format is meant to be read by programs,
+    /// like JSON, NDJSON, CSV or TSV.
+    fn is_machine_readable(&self) -> bool;
+    /// Whether tables can be printed in several parts, as records
+    /// are printed one per line without any header.
+    fn is_ndjson(&self) -> bool;
 }
 
 pub struct StdoutPrinter {
@@ -43,11 +51,25 @@ impl StdoutPrinter {
     }
 }
 
+impl StdoutPrinter {
+    /// Print the given value as records, using the record-based
+    /// output format of the printer.
+    fn print_records<T: serde::Serialize + ?Sized>(&mut self, data: &T) -> Result<()> {
+        let writer = self.writer.as_mut();
+
+        match self.fmt {
+            OutputFmt::Csv => record::write_dsv(writer, data, Delimiter::Comma),
+            OutputFmt::Tsv => record::write_dsv(writer, data, Delimiter::Tab),
+            _ => record::write_ndjson(writer, data),
+        }
+    }
+}
+
 impl Printer for StdoutPrinter {
     fn print_log<T: Debug + Print>(&mut self, data: T) -> Result<()> {
         match self.fmt {
             OutputFmt::Plain => data.print(self.writer.as_mut()),
-            OutputFmt::Json => Ok(()),
+            _ => Ok(()),
         }
     }
 
@@ -56,27 +78,29 @@ impl Printer for StdoutPrinter {
             OutputFmt::Plain => data.print(self.writer.as_mut()),
             OutputFmt::Json => serde_json::to_writer(self.writer.as_mut(), &data)
                 .context("cannot write json to writer"),
+            _ => self.print_records(&data),
         }
     }
 
-    fn print_table<T: fmt::Debug + erased_serde::Serialize + PrintTable + ?Sized>(
+    fn print_table<T: fmt::Debug + serde::Serialize + PrintTable + ?Sized>(
         &mut self,
         data: Box<T>,
         opts: PrintTableOpts,
     ) -> Result<()> {
         match self.fmt {
             OutputFmt::Plain => data.print_table(self.writer.as_mut(), opts),
-            OutputFmt::Json => {
-                let json = &mut serde_json::Serializer::new(self.writer.as_mut());
-                let ser = &mut <dyn erased_serde::Serializer>::erase(json);
-                data.erased_serialize(ser).unwrap();
-                Ok(())
-            }
+            OutputFmt::Json => serde_json::to_writer(self.writer.as_mut(), data.as_ref())
+                .context("cannot write json to writer"),
+            _ => self.print_records(data.as_ref()),
         }
     }
 
-    fn is_json(&self) -> bool {
-        self.fmt == OutputFmt::Json
+    fn is_machine_readable(&self) -> bool {
+        self.fmt.is_machine_readable()
+    }
+
+    fn is_ndjson(&self) -> bool {
+        self.fmt == OutputFmt::Ndjson
     }
 }
 
@@ -0,0 +1,771 @@
+//! Module dedicated to record-based output formats.
+//!
+//! A sequence is printed as a list of records, any other value as a
+//! single record. NDJSON records are serialized one by one, straight
+//! to the writer. Delimiter-separated records are serialized to JSON
+//! first, and nested objects are flattened using dotted keys (like
+//! `from.addr`) so that every record fits in a single row.
+
+use anyhow::{Context, Result};
+use serde::{
+    ser::{self, Impossible, SerializeMap, SerializeSeq, SerializeStruct},
+    Serialize, Serializer,
+};
+use serde_json::{Map, Value};
+use std::io::Write;
+
+/// Represents the delimiter-separated output formats.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Delimiter {
+    Comma,
+    Tab,
+}
+
+/// Split the given value into records.
+fn to_records(value: Value) -> Vec<Value> {
+    match value {
+        Value::Array(values) => values,
+        Value::Null => Vec::new(),
+        value => vec![value],
+    }
+}
+
+/// Write the given value as newline-delimited JSON, one record per
+/// line.
+///
+/// Records are written as soon as they are serialized, and the
+/// writer is flushed after every record, so that the output can be
+/// consumed incrementally.
+pub fn write_ndjson<W, T>(writer: &mut W, value: &T) -> Result<()>
+where
+    W: Write + ?Sized,
+    T: Serialize + ?Sized,
+{
+    let mut ser = NdjsonSerializer {
+        writer: &mut *writer,
+        is_seq: false,
+    };
+
+    match value.serialize(&mut ser) {
+        Ok(()) => Ok(()),
+        // the value is not a sequence: nothing has been written yet,
+        // so it can be written as a single record
+        Err(_) if !ser.is_seq => write_ndjson_record(writer, value),
+        Err(err) => Err(err).context("cannot write ndjson record"),
+    }
+}
+
+fn write_ndjson_record<W, T>(writer: &mut W, value: &T) -> Result<()>
+where
+    W: Write + ?Sized,
+    T: Serialize + ?Sized,
+{
+    serde_json::to_writer(&mut *writer, value).context("cannot write ndjson record")?;
+    writeln!(writer).context("cannot write ndjson record")?;
+    writer.flush().context("cannot flush ndjson record")
+}
+
+/// Serializer writing the elements of a sequence as NDJSON records.
+///
+/// Any other value is rejected, leaving the writer untouched.
+struct NdjsonSerializer<'a, W: ?Sized> {
+    writer: &'a mut W,
+    is_seq: bool,
+}
+
+fn not_seq() -> serde_json::Error {
+    ser::Error::custom("value is not a sequence")
+}
+
+impl<'a, 'b, W: Write + ?Sized> Serializer for &'b mut NdjsonSerializer<'a, W> {
+    type Ok = ();
+    type Error = serde_json::Error;
+    type SerializeSeq = Self;
+    type SerializeTuple = Impossible<(), serde_json::Error>;
+    type SerializeTupleStruct = Impossible<(), serde_json::Error>;
+    type SerializeTupleVariant = Impossible<(), serde_json::Error>;
+    type SerializeMap = Impossible<(), serde_json::Error>;
+    type SerializeStruct = Impossible<(), serde_json::Error>;
+    type SerializeStructVariant = Impossible<(), serde_json::Error>;
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self, Self::Error> {
+        self.is_seq = true;
+        Ok(self)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<(), Self::Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<(), Self::Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_none(self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn serialize_unit(self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn serialize_bool(self, _v: bool) -> Result<(), Self::Error> {
+        Err(not_seq())
+    }
+
+    fn serialize_i8(self, _v: i8) -> Result<(), Self::Error> {
+        Err(not_seq())
+    }
+
+    fn serialize_i16(self, _v: i16) -> Result<(), Self::Error> {
+        Err(not_seq())
+    }
+
+    fn serialize_i32(self, _v: i32) -> Result<(), Self::Error> {
+        Err(not_seq())
+    }
+
+    fn serialize_i64(self, _v: i64) -> Result<(), Self::Error> {
+        Err(not_seq())
+    }
+
+    fn serialize_u8(self, _v: u8) -> Result<(), Self::Error> {
+        Err(not_seq())
+    }
+
+    fn serialize_u16(self, _v: u16) -> Result<(), Self::Error> {
+        Err(not_seq())
+    }
+
+    fn serialize_u32(self, _v: u32) -> Result<(), Self::Error> {
+        Err(not_seq())
+    }
+
+    fn serialize_u64(self, _v: u64) -> Result<(), Self::Error> {
+        Err(not_seq())
+    }
+
+    fn serialize_f32(self, _v: f32) -> Result<(), Self::Error> {
+        Err(not_seq())
+    }
+
+    fn serialize_f64(self, _v: f64) -> Result<(), Self::Error> {
+        Err(not_seq())
+    }
+
+    fn serialize_char(self, _v: char) -> Result<(), Self::Error> {
+        Err(not_seq())
+    }
+
+    fn serialize_str(self, _v: &str) -> Result<(), Self::Error> {
+        Err(not_seq())
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<(), Self::Error> {
+        Err(not_seq())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), Self::Error> {
+        Err(not_seq())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+    ) -> Result<(), Self::Error> {
+        Err(not_seq())
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<(), Self::Error> {
+        Err(not_seq())
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, Self::Error> {
+        Err(not_seq())
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Self::Error> {
+        Err(not_seq())
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Self::Error> {
+        Err(not_seq())
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
+        Err(not_seq())
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, Self::Error> {
+        Err(not_seq())
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, Self::Error> {
+        Err(not_seq())
+    }
+}
+
+impl<'a, 'b, W: Write + ?Sized> SerializeSeq for &'b mut NdjsonSerializer<'a, W> {
+    type Ok = ();
+    type Error = serde_json::Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+        serde_json::to_writer(&mut *self.writer, value)?;
+        self.writer
+            .write_all(b"\n")
+            .and_then(|()| self.writer.flush())
+            .map_err(serde_json::Error::io)
+    }
+
+    fn end(self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+/// Write the given value as delimiter-separated values.
+///
+/// The header row contains the keys of all the fields of the records,
+/// including the ones skipped from the serialization, so that columns
+/// do not depend on the data. It is omitted when records are not
+/// objects.
+pub fn write_dsv<W, T>(writer: &mut W, value: &T, delimiter: Delimiter) -> Result<()>
+where
+    W: Write + ?Sized,
+    T: Serialize + ?Sized,
+{
+    let mut keys = Vec::new();
+    value
+        .serialize(KeySerializer {
+            prefix: None,
+            keys: &mut keys,
+        })
+        .context("cannot collect record keys")?;
+
+    let value = serde_json::to_value(value).context("cannot serialize records")?;
+    let rows: Vec<_> = to_records(value).into_iter().map(flatten).collect();
+
+    // keys of maps only exist in the data
+    for row in &rows {
+        for (key, _) in row.iter().flatten() {
+            push_key(&mut keys, key.clone());
+        }
+    }
+
+    if !keys.is_empty() {
+        write_row(writer, keys.iter().map(String::as_str), delimiter)?;
+    }
+
+    for row in rows {
+        let fields: Vec<String> = match row {
+            Ok(fields) => keys
+                .iter()
+                .map(|key| {
+                    fields
+                        .iter()
+                        .find(|(k, _)| k == key)
+                        .map(|(_, field)| field.clone())
+                        .unwrap_or_default()
+                })
+                .collect(),
+            Err(field) => vec![field],
+        };
+        write_row(writer, fields.iter().map(String::as_str), delimiter)?;
+    }
+
+    Ok(())
+}
+
+fn push_key(keys: &mut Vec<String>, key: String) {
+    if !keys.contains(&key) {
+        keys.push(key);
+    }
+}
+
+fn join_key(prefix: &str, key: &str) -> String {
+    if prefix.is_empty() {
+        key.to_owned()
+    } else {
+        format!("{prefix}.{key}")
+    }
+}
+
+/// Serializer collecting the keys of records.
+///
+/// Struct fields skipped by `skip_serializing_if` are collected as
+/// well, which is not possible from their JSON value. The prefix is
+/// `None` for the value holding the records, and the dotted key of
+/// the current field otherwise.
+struct KeySerializer<'a> {
+    prefix: Option<String>,
+    keys: &'a mut Vec<String>,
+}
+
+impl KeySerializer<'_> {
+    /// Collect the key of the current field, if any.
+    fn leaf(self) -> Result<(), serde_json::Error> {
+        if let Some(key) = self.prefix.filter(|key| !key.is_empty()) {
+            push_key(self.keys, key);
+        }
+        Ok(())
+    }
+
+    /// Get the prefix of the fields of the current value.
+    ///
+    /// Objects held by the root value are records, their fields have
+    /// no prefix.
+    fn into_object(self) -> KeyObject<'a> {
+        KeyObject {
+            prefix: self.prefix.unwrap_or_default(),
+            keys: self.keys,
+            key: None,
+        }
+    }
+}
+
+impl<'a> Serializer for KeySerializer<'a> {
+    type Ok = ();
+    type Error = serde_json::Error;
+    type SerializeSeq = KeyRecords<'a>;
+    type SerializeTuple = Impossible<(), serde_json::Error>;
+    type SerializeTupleStruct = Impossible<(), serde_json::Error>;
+    type SerializeTupleVariant = Impossible<(), serde_json::Error>;
+    type SerializeMap = KeyObject<'a>;
+    type SerializeStruct = KeyObject<'a>;
+    type SerializeStructVariant = Impossible<(), serde_json::Error>;
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<KeyRecords<'a>, Self::Error> {
+        let is_root = self.prefix.is_none();
+        if !is_root {
+            self.leaf()?;
+        }
+        Ok(KeyRecords {
+            keys: if is_root { Some(self.keys) } else { None },
+        })
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<(), Self::Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<(), Self::Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<KeyObject<'a>, Self::Error> {
+        Ok(self.into_object())
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<KeyObject<'a>, Self::Error> {
+        Ok(self.into_object())
+    }
+
+    fn serialize_none(self) -> Result<(), Self::Error> {
+        self.leaf()
+    }
+
+    fn serialize_unit(self) -> Result<(), Self::Error> {
+        self.leaf()
+    }
+
+    fn serialize_bool(self, _v: bool) -> Result<(), Self::Error> {
+        self.leaf()
+    }
+
+    fn serialize_i8(self, _v: i8) -> Result<(), Self::Error> {
+        self.leaf()
+    }
+
+    fn serialize_i16(self, _v: i16) -> Result<(), Self::Error> {
+        self.leaf()
+    }
+
+    fn serialize_i32(self, _v: i32) -> Result<(), Self::Error> {
+        self.leaf()
+    }
+
+    fn serialize_i64(self, _v: i64) -> Result<(), Self::Error> {
+        self.leaf()
+    }
+
+    fn serialize_u8(self, _v: u8) -> Result<(), Self::Error> {
+        self.leaf()
+    }
+
+    fn serialize_u16(self, _v: u16) -> Result<(), Self::Error> {
+        self.leaf()
+    }
+
+    fn serialize_u32(self, _v: u32) -> Result<(), Self::Error> {
+        self.leaf()
+    }
+
+    fn serialize_u64(self, _v: u64) -> Result<(), Self::Error> {
+        self.leaf()
+    }
+
+    fn serialize_f32(self, _v: f32) -> Result<(), Self::Error> {
+        self.leaf()
+    }
+
+    fn serialize_f64(self, _v: f64) -> Result<(), Self::Error> {
+        self.leaf()
+    }
+
+    fn serialize_char(self, _v: char) -> Result<(), Self::Error> {
+        self.leaf()
+    }
+
+    fn serialize_str(self, _v: &str) -> Result<(), Self::Error> {
+        self.leaf()
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<(), Self::Error> {
+        self.leaf()
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), Self::Error> {
+        self.leaf()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+    ) -> Result<(), Self::Error> {
+        self.leaf()
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<(), Self::Error> {
+        self.leaf()
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, Self::Error> {
+        Err(ser::Error::custom("cannot use tuples as records"))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Self::Error> {
+        Err(ser::Error::custom("cannot use tuple structs as records"))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Self::Error> {
+        Err(ser::Error::custom("cannot use tuple variants as records"))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, Self::Error> {
+        Err(ser::Error::custom("cannot use struct variants as records"))
+    }
+}
+
+/// Collector of the keys of the elements of a sequence.
+///
+/// Only elements of the root sequence are records, the keys of any
+/// other sequence are ignored since it is a single field.
+struct KeyRecords<'a> {
+    keys: Option<&'a mut Vec<String>>,
+}
+
+impl SerializeSeq for KeyRecords<'_> {
+    type Ok = ();
+    type Error = serde_json::Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+        match self.keys.as_deref_mut() {
+            Some(keys) => value.serialize(KeySerializer {
+                prefix: Some(String::new()),
+                keys,
+            }),
+            None => Ok(()),
+        }
+    }
+
+    fn end(self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+/// Collector of the keys of the fields of an object.
+struct KeyObject<'a> {
+    prefix: String,
+    keys: &'a mut Vec<String>,
+    key: Option<String>,
+}
+
+impl KeyObject<'_> {
+    fn field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &str,
+        value: &T,
+    ) -> Result<(), serde_json::Error> {
+        value.serialize(KeySerializer {
+            prefix: Some(join_key(&self.prefix, key)),
+            keys: self.keys,
+        })
+    }
+}
+
+impl SerializeStruct for KeyObject<'_> {
+    type Ok = ();
+    type Error = serde_json::Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Self::Error> {
+        self.field(key, value)
+    }
+
+    fn skip_field(&mut self, key: &'static str) -> Result<(), Self::Error> {
+        push_key(self.keys, join_key(&self.prefix, key));
+        Ok(())
+    }
+
+    fn end(self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+impl SerializeMap for KeyObject<'_> {
+    type Ok = ();
+    type Error = serde_json::Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Self::Error> {
+        self.key = Some(to_field(serde_json::to_value(key)?));
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+        let key = self.key.take().unwrap_or_default();
+        self.field(&key, value)
+    }
+
+    fn end(self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+fn write_row<'a, W: Write + ?Sized>(
+    writer: &mut W,
+    fields: impl IntoIterator<Item = &'a str>,
+    delimiter: Delimiter,
+) -> Result<()> {
+    let (sep, fields): (_, Vec<_>) = match delimiter {
+        Delimiter::Comma => (",", fields.into_iter().map(quote_csv).collect()),
+        Delimiter::Tab => ("\t", fields.into_iter().map(escape_tsv).collect()),
+    };
+
+    writeln!(writer, "{}", fields.join(sep)).context("cannot write record")
+}
+
+/// Quote the given CSV field, following RFC 4180.
+fn quote_csv(field: &str) -> String {
+    if field.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
+
+/// Escape the given TSV field, since TSV fields cannot contain tabs
+/// nor line breaks.
+fn escape_tsv(field: &str) -> String {
+    field
+        .replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+}
+
+/// Flatten the given record into a list of key-field pairs, or into a
+/// single field if the record is not an object.
+fn flatten(record: Value) -> Result<Vec<(String, String)>, String> {
+    match record {
+        Value::Object(map) => {
+            let mut fields = Vec::new();
+            flatten_object("", map, &mut fields);
+            Ok(fields)
+        }
+        value => Err(to_field(value)),
+    }
+}
+
+fn flatten_object(prefix: &str, map: Map<String, Value>, fields: &mut Vec<(String, String)>) {
+    for (key, value) in map {
+        let key = join_key(prefix, &key);
+
+        match value {
+            Value::Object(map) => flatten_object(&key, map, fields),
+            value => fields.push((key, to_field(value))),
+        }
+    }
+}
+
+fn to_field(value: Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(string) => string,
+        Value::Array(values) if values.iter().all(is_scalar) => values
+            .into_iter()
+            .map(to_field)
+            .collect::<Vec<_>>()
+            .join(","),
+        value => value.to_string(),
+    }
+}
+
+fn is_scalar(value: &Value) -> bool {
+    !matches!(value, Value::Array(_) | Value::Object(_))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn dsv<T: Serialize + ?Sized>(value: &T, delimiter: Delimiter) -> String {
+        let mut buf = Vec::new();
+        write_dsv(&mut buf, value, delimiter).unwrap();
+        String::from_utf8(buf).unwrap()
+    }
+
+    #[test]
+    fn csv_records() {
+        let value = json!([
+            {"id": "1", "flags": ["Seen", "Flagged"], "subject": "Hello, \"world\"", "from": {"name": null, "addr": "a@b"}},
+            {"id": "2", "flags": [], "subject": "Line\nbreak", "from": {"name": "Bob", "addr": "b@c"}, "folder": "INBOX"},
+        ]);
+
+        assert_eq!(
+            dsv(&value, Delimiter::Comma),
+            concat!(
+                "id,flags,subject,from.name,from.addr,folder\n",
+                "1,\"Seen,Flagged\",\"Hello, \"\"world\"\"\",,a@b,\n",
+                "2,,\"Line\nbreak\",Bob,b@c,INBOX\n",
+            )
+        );
+    }
+
+    #[test]
+    fn tsv_records() {
+        let value = json!([{"name": "a\tb", "desc": "c\nd", "nested": [{"z": 1, "a": 2}]}]);
+        assert_eq!(
+            dsv(&value, Delimiter::Tab),
+            "name\tdesc\tnested\na\\tb\tc\\nd\t[{\"z\":1,\"a\":2}]\n"
+        );
+    }
+
+    #[test]
+    fn dsv_skipped_fields() {
+        #[derive(Serialize)]
+        struct Record {
+            id: usize,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            size: Option<usize>,
+            #[serde(skip_serializing_if = "Vec::is_empty")]
+            cc: Vec<String>,
+        }
+
+        let records = [
+            Record {
+                id: 1,
+                size: None,
+                cc: vec![],
+            },
+            Record {
+                id: 2,
+                size: Some(42),
+                cc: vec![],
+            },
+        ];
+
+        assert_eq!(dsv(&records, Delimiter::Comma), "id,size,cc\n1,,\n2,42,\n");
+        assert_eq!(dsv(&records[..1], Delimiter::Comma), "id,size,cc\n1,,\n");
+        assert_eq!(dsv(&[] as &[Record], Delimiter::Comma), "");
+    }
+
+    #[test]
+    fn ndjson_records() {
+        let mut buf = Vec::new();
+        write_ndjson(&mut buf, &json!([{"id": 1}, {"id": 2}])).unwrap();
+        assert_eq!(String::from_utf8(buf).unwrap(), "{\"id\":1}\n{\"id\":2}\n");
+
+        #[derive(Serialize)]
+        struct Records(Vec<usize>);
+
+        let mut buf = Vec::new();
+        write_ndjson(&mut buf, &Records(vec![1, 2])).unwrap();
+        assert_eq!(String::from_utf8(buf).unwrap(), "1\n2\n");
+
+        let mut buf = Vec::new();
+        write_ndjson(&mut buf, "Done!").unwrap();
+        assert_eq!(String::from_utf8(buf).unwrap(), "\"Done!\"\n");
+
+        let mut buf = Vec::new();
+        write_ndjson(&mut buf, &json!({"id": 1})).unwrap();
+        assert_eq!(String::from_utf8(buf).unwrap(), "{\"id\":1}\n");
+    }
+}
@@ -0,0 +1,92 @@
+use anyhow::Result;
+use clap::Parser;
+use log::info;
+
+#[cfg(feature = "account-sync")]
+use crate::cache::arg::disable::CacheDisableFlag;
+use crate::{
+    account::arg::name::AccountNameFlag,
+    backend,
+    cache::IdMapper,
+    config::TomlConfig,
+    folder::arg::name::FolderNameOptionalArg,
+    printer::{PrintTableOpts, Printer},
+    rule,
+    ui::arg::max_width::TableMaxWidthFlag,
+};
+
+/// Apply rules to a folder.
+///
+/// This command applies the rules of the account to all envelopes of
+/// the given folder, in order. Messages moved or deleted by a rule are
+/// not processed by the next rules.
+#[derive(Debug, Parser)]
+pub struct RuleApplyCommand {
+    #[command(flatten)]
+    pub folder: FolderNameOptionalArg,
+
+    /// Print matching messages without applying actions.
+    #[arg(long, short)]
+    pub dry_run: bool,
+
+    #[command(flatten)]
+    pub table: TableMaxWidthFlag,
+
+    #[cfg(feature = "account-sync")]
+    #[command(flatten)]
+    pub cache: CacheDisableFlag,
+
+    #[command(flatten)]
+    pub account: AccountNameFlag,
+}
+
+impl RuleApplyCommand {
+    pub async fn execute(self, printer: &mut impl Printer, config: &TomlConfig) -> Result<()> {
+        info!("executing apply rules command");
+
+        let folder = &self.folder.name;
+        let (toml_account_config, account_config) = config.clone().into_account_configs(
+            self.account.name.as_deref(),
+            #[cfg(feature = "account-sync")]
+            self.cache.disable,
+        )?;
+
+        let rules = toml_account_config
+            .rules
+            .as_ref()
+            .map(|config| config.rules.as_slice())
+            .unwrap_or_default();
+
+        if rules.is_empty() {
+            return printer.print("No rule defined for this account, nothing to apply!");
+        }
+
+        let backend =
+            rule::build_backend(toml_account_config.clone(), account_config.clone()).await?;
+        let matches = rule::apply(&backend, rules, folder, None, self.dry_run).await?;
+
+        if self.dry_run {
+            let id_mapper = if backend::uses_id_mapper(
+                &toml_account_config,
+                toml_account_config.list_envelopes_kind(),
+            ) {
+                IdMapper::new(&account_config, folder)?
+            } else {
+                IdMapper::Dummy
+            };
+
+            printer.print_table(
+                Box::new(matches.with_aliases(&id_mapper)?),
+                PrintTableOpts {
+                    format: &account_config.get_message_read_format(),
+                    max_width: self.table.max_width,
+                },
+            )
+        } else {
+            let count = matches.len();
+            printer.print(format!(
+                "Rules successfully applied to folder {folder} ({count} match(es))!"
+            ))
+        }
+    }
+}
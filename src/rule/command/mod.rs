@@ -0,0 +1,29 @@
+mod apply;
+
+use anyhow::Result;
+use clap::Subcommand;
+
+use crate::{config::TomlConfig, printer::Printer};
+
+use self::apply::RuleApplyCommand;
+
+/// Manage rules.
+///
+/// Rules are declared per account in the configuration file. They
+/// match envelopes using a filter query and/or message headers, then
+/// apply actions to matching messages: move, copy, add or remove
+/// flags, delete or run a shell command.
+#[derive(Debug, Subcommand)]
+pub enum RuleSubcommand {
+    #[command()]
+    Apply(RuleApplyCommand),
+}
+
+impl RuleSubcommand {
+    #[allow(unused)]
+    pub async fn execute(self, printer: &mut impl Printer, config: &TomlConfig) -> Result<()> {
+        match self {
+            Self::Apply(cmd) => cmd.execute(printer, config).await,
+        }
+    }
+}
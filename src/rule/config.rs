@@ -0,0 +1,105 @@
+use email::folder::INBOX;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fmt};
+
+/// The rules configuration of an account.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct RulesConfig {
+    /// Apply rules to new envelopes of the watched folder.
+    pub apply_on_watch: Option<bool>,
+
+    /// Apply rules to new envelopes after a synchronization.
+    pub apply_on_sync: Option<bool>,
+
+    /// The folder rules are applied to after a synchronization.
+    ///
+    /// Defaults to INBOX.
+    pub sync_folder: Option<String>,
+
+    /// The list of rules, applied in order.
+    #[serde(default, rename = "rule", skip_serializing_if = "Vec::is_empty")]
+    pub rules: Vec<RuleConfig>,
+}
+
+impl RulesConfig {
+    pub fn is_apply_on_watch_enabled(&self) -> bool {
+        self.apply_on_watch.unwrap_or_default() && !self.rules.is_empty()
+    }
+
+    pub fn is_apply_on_sync_enabled(&self) -> bool {
+        self.apply_on_sync.unwrap_or_default() && !self.rules.is_empty()
+    }
+
+    pub fn get_sync_folder(&self) -> &str {
+        self.sync_folder.as_deref().unwrap_or(INBOX)
+    }
+}
+
+/// The configuration of a rule.
+///
+/// A rule matches envelopes using a filter query (same grammar as the
+/// envelope list query) and/or message headers, then applies its
+/// actions to matching messages.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct RuleConfig {
+    /// The name of the rule, used in reports.
+    pub name: Option<String>,
+
+    /// The filter query envelopes need to match.
+    pub query: Option<String>,
+
+    /// The headers messages need to match.
+    ///
+    /// Keys are header names and values are patterns, both matched
+    /// case-insensitively. A header matches if its value contains
+    /// the pattern.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub headers: BTreeMap<String, String>,
+
+    /// The actions to apply to matching messages.
+    pub actions: Vec<RuleAction>,
+
+    /// Do not apply next rules to matching messages.
+    pub stop: Option<bool>,
+}
+
+/// The action of a rule.
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RuleAction {
+    /// Move messages to the given folder.
+    Move(String),
+    /// Copy messages to the given folder.
+    Copy(String),
+    /// Add the given flags to messages.
+    AddFlags(Vec<String>),
+    /// Remove the given flags from messages.
+    RemoveFlags(Vec<String>),
+    /// Delete messages.
+    Delete,
+    /// Run the given shell command, with the raw message piped to
+    /// its standard input.
+    Cmd(String),
+}
+
+impl RuleAction {
+    /// Return true if messages leave the folder after the action.
+    pub fn is_final(&self) -> bool {
+        matches!(self, Self::Move(_) | Self::Delete)
+    }
+}
+
+impl fmt::Display for RuleAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Move(folder) => write!(f, "move to {folder}"),
+            Self::Copy(folder) => write!(f, "copy to {folder}"),
+            Self::AddFlags(flags) => write!(f, "add flags {}", flags.join(", ")),
+            Self::RemoveFlags(flags) => write!(f, "remove flags {}", flags.join(", ")),
+            Self::Delete => write!(f, "delete"),
+            Self::Cmd(cmd) => write!(f, "run {cmd}"),
+        }
+    }
+}
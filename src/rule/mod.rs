@@ -0,0 +1,509 @@
+//! Module dedicated to rules.
+//!
+//! Rules are declared per account in the configuration file. They
+//! match envelopes of a folder, then apply actions to the matching
+//! messages, like moving them to another folder or flagging them.
+
+pub mod command;
+pub mod config;
+
+use anyhow::{anyhow, Context, Result};
+use email::{
+    account::config::AccountConfig,
+    backend::feature::BackendFeatureSource,
+    envelope::{
+        list::{ListEnvelopes, ListEnvelopesOptions},
+        watch::config::WatchFn,
+        Envelope, Id,
+    },
+    flag::{add::AddFlags, remove::RemoveFlags, Flag, Flags},
+    message::{
+        copy::CopyMessages, delete::DeleteMessages, peek::PeekMessages, r#move::MoveMessages,
+    },
+    search_query::SearchEmailsQuery,
+};
+use log::{debug, info, warn};
+use process::SingleCommand;
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    ops,
+    sync::Arc,
+};
+use tokio::sync::mpsc::{self, UnboundedReceiver};
+
+#[cfg(feature = "account-sync")]
+use crate::backend::BackendKind;
+use crate::{
+    account::config::TomlAccountConfig,
+    backend::Backend,
+    cache::IdMapper,
+    envelope::thread,
+    printer::{PrintTable, PrintTableOpts, WriteColor},
+    ui::{Cell, Row, Table},
+};
+
+use self::config::{RuleAction, RuleConfig};
+
+/// Represents a message matched by a rule.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct RuleMatch {
+    pub rule: String,
+    pub id: String,
+    pub subject: String,
+    pub actions: String,
+}
+
+impl Table for RuleMatch {
+    fn head() -> Row {
+        Row::new()
+            .cell(Cell::new("RULE").bold().underline().white())
+            .cell(Cell::new("ID").bold().underline().white())
+            .cell(Cell::new("SUBJECT").shrinkable().bold().underline().white())
+            .cell(Cell::new("ACTIONS").bold().underline().white())
+    }
+
+    fn row(&self) -> Row {
+        Row::new()
+            .cell(Cell::new(&self.rule).blue())
+            .cell(Cell::new(&self.id).red())
+            .cell(Cell::new(&self.subject).shrinkable().green())
+            .cell(Cell::new(&self.actions).yellow())
+    }
+}
+
+/// Represents the list of messages matched by rules.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct RuleMatches(Vec<RuleMatch>);
+
+impl ops::Deref for RuleMatches {
+    type Target = Vec<RuleMatch>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl RuleMatches {
+    /// Replace the backend ids of the matched messages by their
+    /// alias.
+    pub fn with_aliases(mut self, id_mapper: &IdMapper) -> Result<Self> {
+        for m in self.0.iter_mut() {
+            m.id = id_mapper.get_or_create_alias(&m.id)?;
+        }
+        Ok(self)
+    }
+}
+
+impl PrintTable for RuleMatches {
+    fn print_table(&self, writer: &mut dyn WriteColor, opts: PrintTableOpts) -> Result<()> {
+        writeln!(writer)?;
+        Table::print(writer, self, opts)?;
+        writeln!(writer)?;
+        Ok(())
+    }
+}
+
+/// Build a backend able to apply any rule action.
+pub async fn build_backend(
+    toml_account_config: Arc<TomlAccountConfig>,
+    account_config: Arc<AccountConfig>,
+) -> Result<Backend> {
+    let kinds = [
+        toml_account_config.list_envelopes_kind(),
+        toml_account_config.peek_messages_kind(),
+        toml_account_config.add_flags_kind(),
+        toml_account_config.remove_flags_kind(),
+        toml_account_config.copy_messages_kind(),
+        toml_account_config.move_messages_kind(),
+        toml_account_config.delete_messages_kind(),
+    ];
+
+    Backend::new(
+        toml_account_config.clone(),
+        account_config,
+        kinds.into_iter().flatten(),
+        |builder| {
+            builder.set_list_envelopes(BackendFeatureSource::Context);
+            builder.set_peek_messages(BackendFeatureSource::Context);
+            builder.set_add_flags(BackendFeatureSource::Context);
+            builder.set_remove_flags(BackendFeatureSource::Context);
+            builder.set_copy_messages(BackendFeatureSource::Context);
+            builder.set_move_messages(BackendFeatureSource::Context);
+            builder.set_delete_messages(BackendFeatureSource::Context);
+        },
+    )
+    .await
+}
+
+/// Build a backend targeting the synchronized local Maildir.
+#[cfg(feature = "account-sync")]
+pub async fn build_sync_backend(
+    toml_account_config: &TomlAccountConfig,
+    account_config: Arc<AccountConfig>,
+) -> Result<Backend> {
+    // features must all target the local Maildir, whatever the
+    // backend overrides of the account are
+    let toml_account_config = Arc::new(TomlAccountConfig {
+        backend: Some(BackendKind::MaildirForSync),
+        folder: None,
+        envelope: None,
+        flag: None,
+        message: None,
+        ..toml_account_config.clone()
+    });
+
+    build_backend(toml_account_config, account_config).await
+}
+
+/// List the backend ids of all envelopes of the given folder.
+pub async fn list_ids(backend: &Backend, folder: &str) -> Result<HashSet<String>> {
+    let opts = ListEnvelopesOptions {
+        page: 0,
+        page_size: 0,
+        query: None,
+    };
+    let envelopes = backend.backend.list_envelopes(folder, opts).await?;
+    Ok(envelopes.iter().map(|e| e.id.clone()).collect())
+}
+
+/// Apply the given rules to envelopes of the given folder.
+///
+/// If ids are given, only envelopes matching them are taken into
+/// account. When dry run is enabled, matching messages are returned
+/// but actions are not applied.
+///
+/// Rules sharing the same query share the same listing, and messages
+/// needed by header patterns are peeked at once for all rules.
+pub async fn apply(
+    backend: &Backend,
+    rules: &[RuleConfig],
+    folder: &str,
+    ids: Option<&HashSet<String>>,
+    dry_run: bool,
+) -> Result<RuleMatches> {
+    let names: Vec<_> = rules
+        .iter()
+        .enumerate()
+        .map(|(i, rule)| rule.name.clone().unwrap_or_else(|| format!("#{}", i + 1)))
+        .collect();
+
+    // envelopes matching each rule query, listed once per query
+    let mut listings: HashMap<Option<&str>, Vec<Envelope>> = HashMap::new();
+
+    for (rule, name) in rules.iter().zip(&names) {
+        let query = rule.query.as_deref();
+
+        if listings.contains_key(&query) {
+            continue;
+        }
+
+        let envelopes = list_envelopes(backend, folder, query)
+            .await
+            .with_context(|| format!("cannot list envelopes of rule {name}"))?
+            .into_iter()
+            .filter(|envelope| ids.map(|ids| ids.contains(&envelope.id)).unwrap_or(true))
+            .collect();
+
+        listings.insert(query, envelopes);
+    }
+
+    // headers of the messages needed by header patterns, indexed by
+    // Message-ID since backends do not return peeked messages in the
+    // requested order
+    let peek_ids: HashSet<_> = rules
+        .iter()
+        .filter(|rule| !rule.headers.is_empty())
+        .flat_map(|rule| &listings[&rule.query.as_deref()])
+        .map(|envelope| envelope.id.clone())
+        .collect();
+    let headers = peek_headers(backend, folder, peek_ids).await?;
+
+    let mut matches = Vec::new();
+    // ids of messages that must not be processed by next rules
+    let mut done = HashSet::new();
+
+    for (rule, name) in rules.iter().zip(names) {
+        let matched: Vec<_> = listings[&rule.query.as_deref()]
+            .iter()
+            .filter(|envelope| !done.contains(&envelope.id))
+            .filter(|envelope| {
+                rule.headers.is_empty()
+                    || headers
+                        .get(&thread::normalize_message_id(&envelope.message_id))
+                        .map(|raw| match_headers(raw, &rule.headers))
+                        .unwrap_or_default()
+            })
+            .collect();
+
+        if matched.is_empty() {
+            debug!("rule {name} did not match any envelope");
+            continue;
+        }
+
+        info!("rule {name} matched {} envelope(s)", matched.len());
+
+        let actions = rule
+            .actions
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        for envelope in &matched {
+            matches.push(RuleMatch {
+                rule: name.clone(),
+                id: envelope.id.clone(),
+                subject: envelope.subject.clone(),
+                actions: actions.clone(),
+            });
+        }
+
+        let matched_ids: Vec<_> = matched.iter().map(|e| e.id.clone()).collect();
+
+        if !dry_run {
+            apply_actions(backend, folder, &rule.actions, &matched_ids)
+                .await
+                .with_context(|| format!("cannot apply rule {name}"))?;
+        }
+
+        if rule.stop.unwrap_or_default() || rule.actions.iter().any(RuleAction::is_final) {
+            done.extend(matched_ids);
+        }
+    }
+
+    Ok(RuleMatches(matches))
+}
+
+async fn list_envelopes(
+    backend: &Backend,
+    folder: &str,
+    query: Option<&str>,
+) -> Result<Vec<Envelope>> {
+    let query = match query {
+        Some(query) => Some(
+            query
+                .parse::<SearchEmailsQuery>()
+                .map_err(|err| anyhow!("cannot parse query {query}: {err}"))?,
+        ),
+        None => None,
+    };
+    let opts = ListEnvelopesOptions {
+        page: 0,
+        page_size: 0,
+        query,
+    };
+    let envelopes = backend.backend.list_envelopes(folder, opts).await?;
+    Ok(envelopes.iter().cloned().collect())
+}
+
+/// Peek the messages matching the given ids, then return their raw
+/// content indexed by their normalized Message-ID.
+async fn peek_headers(
+    backend: &Backend,
+    folder: &str,
+    ids: HashSet<String>,
+) -> Result<HashMap<String, Vec<u8>>> {
+    let mut headers = HashMap::new();
+
+    if ids.is_empty() {
+        return Ok(headers);
+    }
+
+    let ids = Id::multiple(ids.into_iter().collect::<Vec<_>>());
+    let msgs = backend.backend.peek_messages(folder, &ids).await?;
+
+    for msg in msgs.to_vec() {
+        let raw = msg.raw()?;
+        if let Some(id) = msg.parsed()?.message_id() {
+            headers.insert(thread::normalize_message_id(id), raw.to_vec());
+        }
+    }
+
+    Ok(headers)
+}
+
+async fn apply_actions(
+    backend: &Backend,
+    folder: &str,
+    actions: &[RuleAction],
+    ids: &[String],
+) -> Result<()> {
+    let id = Id::multiple(ids.to_vec());
+
+    for action in actions {
+        debug!("applying action {action} to {} message(s)", ids.len());
+
+        match action {
+            RuleAction::Move(target) => {
+                backend.backend.move_messages(folder, target, &id).await?;
+            }
+            RuleAction::Copy(target) => {
+                backend.backend.copy_messages(folder, target, &id).await?;
+            }
+            RuleAction::AddFlags(flags) => {
+                let flags = Flags::from_iter(flags.iter().map(|flag| Flag::from(flag.as_str())));
+                backend.backend.add_flags(folder, &id, &flags).await?;
+            }
+            RuleAction::RemoveFlags(flags) => {
+                let flags = Flags::from_iter(flags.iter().map(|flag| Flag::from(flag.as_str())));
+                backend.backend.remove_flags(folder, &id, &flags).await?;
+            }
+            RuleAction::Delete => {
+                backend.backend.delete_messages(folder, &id).await?;
+            }
+            RuleAction::Cmd(cmd) => {
+                for id in ids {
+                    let raw = peek_raw(backend, folder, id).await?;
+                    SingleCommand::from(cmd.as_str())
+                        .run_with(raw)
+                        .await
+                        .with_context(|| format!("cannot run command {cmd}"))?;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn peek_raw(backend: &Backend, folder: &str, id: &str) -> Result<Vec<u8>> {
+    let msgs = backend
+        .backend
+        .peek_messages(folder, &Id::single(id))
+        .await?;
+    let msg = msgs.first().ok_or(anyhow!("cannot find message {id}"))?;
+    Ok(msg.raw()?.to_vec())
+}
+
+/// Return true if the headers of the given raw message match all the
+/// given header patterns.
+pub fn match_headers(raw: &[u8], patterns: &BTreeMap<String, String>) -> bool {
+    let msg = match mail_parser::MessageParser::new().parse_headers(raw) {
+        Some(msg) => msg,
+        None => return false,
+    };
+
+    patterns.iter().all(|(name, pattern)| {
+        let pattern = pattern.to_lowercase();
+        msg.headers_raw().any(|(key, val)| {
+            key.eq_ignore_ascii_case(name) && val.to_lowercase().contains(&pattern)
+        })
+    })
+}
+
+/// Add a hook to the watch configuration of the given account,
+/// sending the id of every envelope received in the watched folder
+/// through the returned channel.
+///
+/// Hooks of the account configuration are kept.
+pub fn with_received_hook(
+    account_config: &AccountConfig,
+) -> (Arc<AccountConfig>, UnboundedReceiver<String>) {
+    let (sender, receiver) = mpsc::unbounded_channel();
+    let mut account_config = account_config.clone();
+
+    let envelope_config = account_config.envelope.get_or_insert_with(Default::default);
+    let watch_config = envelope_config.watch.get_or_insert_with(Default::default);
+    let hook = watch_config.received.get_or_insert_with(Default::default);
+
+    hook.callback = Some(WatchFn::new(move |envelope| {
+        let _ = sender.send(envelope.id.clone());
+        async { Ok(()) }
+    }));
+
+    (Arc::new(account_config), receiver)
+}
+
+/// Apply the given rules to the envelopes received in the given
+/// folder, as reported by the hook of [`with_received_hook`].
+///
+/// Envelopes received while rules are being applied are processed
+/// together. Errors are logged, so that a transient failure does not
+/// stop the watch. This function returns once the watch stops.
+pub async fn watch(
+    backend: &Backend,
+    rules: &[RuleConfig],
+    folder: &str,
+    mut received: UnboundedReceiver<String>,
+) -> Result<()> {
+    while let Some(id) = received.recv().await {
+        let mut ids = HashSet::from_iter([id]);
+
+        while let Ok(id) = received.try_recv() {
+            ids.insert(id);
+        }
+
+        match apply(backend, rules, folder, Some(&ids), false).await {
+            Ok(matches) => info!("rules applied to {} new message(s)", matches.len()),
+            Err(err) => {
+                warn!("cannot apply rules to new envelopes of folder {folder}: {err}");
+                debug!("{err:?}");
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn headers() {
+        let raw = b"From: alice@localhost\r\nList-Id: Rust Users <users.rust-lang.org>\r\n\r\nbody";
+
+        let patterns = BTreeMap::from_iter([("list-id".into(), "rust-lang".into())]);
+        assert!(match_headers(raw, &patterns));
+
+        let patterns = BTreeMap::from_iter([
+            ("List-Id".into(), "RUST".into()),
+            ("from".into(), "alice".into()),
+        ]);
+        assert!(match_headers(raw, &patterns));
+
+        let patterns = BTreeMap::from_iter([("List-Id".into(), "python".into())]);
+        assert!(!match_headers(raw, &patterns));
+
+        let patterns = BTreeMap::from_iter([("X-Spam".into(), "yes".into())]);
+        assert!(!match_headers(raw, &patterns));
+    }
+
+    #[test]
+    fn config() {
+        let config: config::RulesConfig = toml::from_str(
+            r#"
+            apply-on-watch = true
+
+            [[rule]]
+            name = "newsletters"
+            query = "from news@localhost"
+            headers = { List-Id = "weekly" }
+            actions = [{ add-flags = ["seen"] }, { move = "Newsletters" }]
+
+            [[rule]]
+            query = "subject spam"
+            actions = ["delete"]
+            stop = true
+            "#,
+        )
+        .unwrap();
+
+        assert!(config.is_apply_on_watch_enabled());
+        assert!(!config.is_apply_on_sync_enabled());
+        assert_eq!(config.rules.len(), 2);
+        assert_eq!(
+            config.rules[0].actions,
+            vec![
+                RuleAction::AddFlags(vec!["seen".into()]),
+                RuleAction::Move("Newsletters".into()),
+            ]
+        );
+        assert_eq!(config.rules[1].actions, vec![RuleAction::Delete]);
+        assert_eq!(
+            config.rules[0].actions[1].to_string(),
+            "move to Newsletters"
+        );
+    }
+}
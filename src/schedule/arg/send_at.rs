@@ -0,0 +1,38 @@
+use chrono::{DateTime, Local};
+use clap::Parser;
+
+use crate::schedule::{parse_delay, parse_future_send_date};
+
+/// The scheduled sending flags parser.
+#[derive(Debug, Default, Parser)]
+pub struct ScheduleSendAtFlags {
+    /// Send the message at the given date.
+    ///
+    /// The date can be either RFC 3339 or a local date with an
+    /// optional time, like "2026-11-02 09:00". The message is stored
+    /// in the outbox, then sent by the schedule run command.
+    #[arg(long = "at", name = "schedule_at", value_name = "DATE")]
+    #[arg(value_parser = parse_future_send_date)]
+    #[arg(conflicts_with_all = ["schedule_delay", "outbox_queue"])]
+    pub at: Option<DateTime<Local>>,
+
+    /// Send the message after the given delay.
+    ///
+    /// The delay is a number followed by an optional unit: s for
+    /// seconds (default), m for minutes, h for hours or d for days.
+    /// Until then, the message can be dropped from the outbox.
+    #[arg(long = "delay", name = "schedule_delay", value_name = "DURATION")]
+    #[arg(value_parser = parse_delay, conflicts_with = "outbox_queue")]
+    pub delay: Option<i64>,
+}
+
+impl ScheduleSendAtFlags {
+    /// Return the scheduled sending date as a UNIX timestamp, if any.
+    pub fn send_at(&self) -> Option<i64> {
+        match (self.at, self.delay) {
+            (Some(at), _) => Some(at.timestamp()),
+            (None, Some(delay)) => Some(Local::now().timestamp().saturating_add(delay)),
+            (None, None) => None,
+        }
+    }
+}
@@ -0,0 +1,28 @@
+mod run;
+
+use anyhow::Result;
+use clap::Subcommand;
+
+use crate::{config::TomlConfig, printer::Printer};
+
+use self::run::ScheduleRunCommand;
+
+/// Manage scheduled messages.
+///
+/// Scheduled messages are sent with the --at or --delay flags of the
+/// template send command. They wait in the outbox until their sending
+/// date, then are delivered by this subcommand.
+#[derive(Debug, Subcommand)]
+pub enum ScheduleSubcommand {
+    #[command()]
+    Run(ScheduleRunCommand),
+}
+
+impl ScheduleSubcommand {
+    #[allow(unused)]
+    pub async fn execute(self, printer: &mut impl Printer, config: &TomlConfig) -> Result<()> {
+        match self {
+            Self::Run(cmd) => cmd.execute(printer, config).await,
+        }
+    }
+}
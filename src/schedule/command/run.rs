@@ -0,0 +1,96 @@
+use anyhow::Result;
+use chrono::Local;
+use clap::Parser;
+use log::{debug, info};
+use std::time::Duration;
+
+#[cfg(feature = "account-sync")]
+use crate::cache::arg::disable::CacheDisableFlag;
+use crate::{
+    account::arg::name::AccountNameFlag,
+    config::TomlConfig,
+    outbox::{self, Outbox},
+    printer::Printer,
+};
+
+/// Send scheduled messages that are due.
+///
+/// This command sends the scheduled messages of the outbox whose
+/// sending date is reached. A copy is saved to the sent folder only
+/// once the message has actually been delivered. Messages that fail
+/// to be sent stay in the outbox and are retried on the next run.
+#[derive(Debug, Parser)]
+pub struct ScheduleRunCommand {
+    /// Keep running, checking for due messages at regular interval.
+    #[arg(long = "loop")]
+    pub looping: bool,
+
+    /// The interval between two checks, in seconds.
+    ///
+    /// Only used in loop mode.
+    #[arg(long, short, value_name = "SECS", default_value_t = 60)]
+    pub interval: u64,
+
+    #[cfg(feature = "account-sync")]
+    #[command(flatten)]
+    pub cache: CacheDisableFlag,
+
+    #[command(flatten)]
+    pub account: AccountNameFlag,
+}
+
+impl ScheduleRunCommand {
+    pub async fn execute(self, printer: &mut impl Printer, config: &TomlConfig) -> Result<()> {
+        info!("executing run schedule command");
+
+        let (toml_account_config, account_config) = config.clone().into_account_configs(
+            self.account.name.as_deref(),
+            #[cfg(feature = "account-sync")]
+            self.cache.disable,
+        )?;
+
+        loop {
+            // the outbox is opened for each run only, so that its
+            // lock is released while sleeping
+            let outbox = Outbox::new(&account_config)?;
+
+            let now = Local::now().timestamp();
+            let queued: Vec<_> = outbox
+                .list()?
+                .iter()
+                .filter(|queued| queued.send_at.is_some() && queued.is_due(now))
+                .cloned()
+                .collect();
+
+            if queued.is_empty() {
+                debug!("no scheduled message due");
+            } else {
+                let (sent, failed) = outbox::flush(
+                    toml_account_config.clone(),
+                    account_config.clone(),
+                    &outbox,
+                    queued,
+                )
+                .await?;
+
+                if failed > 0 {
+                    printer.print_log(format!(
+                        "{sent} scheduled message(s) sent, {failed} message(s) will be retried!"
+                    ))?;
+                } else {
+                    printer.print_log(format!("{sent} scheduled message(s) successfully sent!"))?;
+                }
+            }
+
+            drop(outbox);
+
+            if !self.looping {
+                break;
+            }
+
+            tokio::time::sleep(Duration::from_secs(self.interval)).await;
+        }
+
+        printer.print("Done!")
+    }
+}
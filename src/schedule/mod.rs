@@ -0,0 +1,115 @@
+//! Module dedicated to scheduled sending.
+//!
+//! Scheduled messages are stored in the outbox together with their
+//! sending date, and are delivered by the schedule run command once
+//! this date is reached.
+
+pub mod arg;
+pub mod command;
+
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, TimeZone};
+
+/// Parse the given sending date, which must be in the future.
+pub fn parse_future_send_date(date: &str) -> Result<DateTime<Local>> {
+    let parsed = parse_send_date(date)?;
+
+    if parsed <= Local::now() {
+        bail!(
+            "cannot schedule message: date {} is in the past",
+            date.trim()
+        );
+    }
+
+    Ok(parsed)
+}
+
+/// Parse the given sending date.
+///
+/// The date can be either RFC 3339 or a local date with an optional
+/// time, like `2026-11-02 09:00`.
+pub fn parse_send_date(date: &str) -> Result<DateTime<Local>> {
+    let date = date.trim();
+
+    if let Ok(date) = DateTime::parse_from_rfc3339(date) {
+        return Ok(date.with_timezone(&Local));
+    }
+
+    let naive = ["%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M", "%Y-%m-%dT%H:%M"]
+        .iter()
+        .find_map(|fmt| NaiveDateTime::parse_from_str(date, fmt).ok())
+        .or_else(|| {
+            NaiveDate::parse_from_str(date, "%Y-%m-%d")
+                .ok()
+                .and_then(|date| date.and_hms_opt(0, 0, 0))
+        })
+        .ok_or(anyhow!("cannot parse date {date}"))?;
+
+    Local
+        .from_local_datetime(&naive)
+        .earliest()
+        .ok_or(anyhow!("date {date} does not exist in local time zone"))
+}
+
+/// Parse the given delay, as a number of seconds.
+///
+/// The delay is a number followed by an optional unit: `s` for
+/// seconds (default), `m` for minutes, `h` for hours or `d` for days.
+pub fn parse_delay(delay: &str) -> Result<i64> {
+    let delay = delay.trim();
+    let split = delay
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(delay.len());
+    let (count, unit) = delay.split_at(split);
+
+    let count: i64 = count
+        .parse()
+        .with_context(|| format!("cannot parse delay {delay}"))?;
+
+    let factor = match unit.trim() {
+        "" | "s" | "sec" | "secs" => 1,
+        "m" | "min" | "mins" => 60,
+        "h" | "hour" | "hours" => 60 * 60,
+        "d" | "day" | "days" => 24 * 60 * 60,
+        unit => bail!("cannot parse delay {delay}: unknown unit {unit}"),
+    };
+
+    count
+        .checked_mul(factor)
+        .ok_or(anyhow!("cannot parse delay {delay}: delay too large"))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Datelike, Timelike};
+
+    use super::*;
+
+    #[test]
+    fn send_date() {
+        let date = parse_send_date("2026-11-02 09:00").unwrap();
+        assert_eq!((date.year(), date.month(), date.day()), (2026, 11, 2));
+        assert_eq!((date.hour(), date.minute()), (9, 0));
+
+        let date = parse_send_date("2026-11-02").unwrap();
+        assert_eq!((date.hour(), date.minute()), (0, 0));
+
+        let date = parse_send_date("2026-11-02T09:00:00Z").unwrap();
+        assert_eq!(date.timestamp(), 1793610000);
+
+        assert!(parse_send_date("tomorrow").is_err());
+        assert!(parse_future_send_date("2000-01-01 09:00").is_err());
+    }
+
+    #[test]
+    fn delay() {
+        assert_eq!(parse_delay("30").unwrap(), 30);
+        assert_eq!(parse_delay("30s").unwrap(), 30);
+        assert_eq!(parse_delay("10m").unwrap(), 600);
+        assert_eq!(parse_delay("2h").unwrap(), 7200);
+        assert_eq!(parse_delay("1d").unwrap(), 86400);
+        assert!(parse_delay("m").is_err());
+        assert!(parse_delay("10w").is_err());
+        assert!(parse_delay("9223372036854775807d").is_err());
+    }
+}
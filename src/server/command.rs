@@ -0,0 +1,35 @@
+use anyhow::Result;
+use clap::Parser;
+use log::info;
+use std::path::PathBuf;
+
+use crate::{config::TomlConfig, server::Server};
+
+/// Start a JSON-RPC server over the standard input and output.
+///
+/// This command is meant to be used by editor integrations. Requests
+/// are read from the standard input and responses are written to the
+/// standard output, one JSON-RPC 2.0 message per line. Backends are
+/// kept alive between requests, so that the configuration parsing
+/// and the backend authentication only happen once per account.
+///
+/// Available methods are folder.list, folder.add, folder.expunge,
+/// folder.purge, folder.delete, envelope.list, envelope.thread,
+/// flag.add, flag.set, flag.remove, message.read, message.copy,
+/// message.move, message.delete, message.save, message.send,
+/// template.save and template.send. Envelope ids can be qualified
+/// by their account, like "work:42". Any other command can be
+/// executed with the command method by giving its arguments in the
+/// args parameter. The shutdown method stops the server.
+#[derive(Debug, Parser)]
+pub struct ServerCommand {}
+
+impl ServerCommand {
+    pub async fn execute(self, config_paths: &[PathBuf]) -> Result<()> {
+        info!("executing server command");
+
+        let config = TomlConfig::from_paths_or_default(config_paths).await?;
+
+        Server::new(config, config_paths.to_vec()).serve().await
+    }
+}
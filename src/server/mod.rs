@@ -0,0 +1,841 @@
+//! Module dedicated to the JSON-RPC server.
+//!
+//! The server reads JSON-RPC 2.0 requests from the standard input,
+//! one request per line, and writes responses to the standard output,
+//! one response per line. Backends are built once per account then
+//! kept alive for the whole session, which saves the configuration
+//! parsing and the backend authentication on every request.
+
+pub mod command;
+
+use anyhow::{anyhow, bail, Context, Result};
+use clap::Parser;
+use email::{
+    account::config::AccountConfig,
+    backend::feature::BackendFeatureSource,
+    envelope::list::ListEnvelopesOptions,
+    flag::{Flag, Flags},
+    folder::{
+        add::AddFolder, delete::DeleteFolder, expunge::ExpungeFolder, list::ListFolders,
+        purge::PurgeFolder, INBOX,
+    },
+    message::send::SendMessageThenSaveCopy,
+    search_query::SearchEmailsQuery,
+};
+use log::{debug, info};
+use mml::MmlCompilerBuilder;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    io::{self, Write},
+    path::PathBuf,
+    sync::Arc,
+};
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
+
+use crate::{
+    account::{command::AccountSubcommand, config::TomlAccountConfig},
+    backend::Backend,
+    cli::{Cli, HimalayaCommand},
+    config::TomlConfig,
+    contact::command::ContactSubcommand,
+    envelope::{
+        arg::ids::{self, EnvelopeId},
+        command::EnvelopeSubcommand,
+    },
+    folder::Folders,
+    message::{
+        attachment::command::AttachmentSubcommand, command::MessageSubcommand,
+        template::command::TemplateSubcommand,
+    },
+    printer::{Print, PrintTable, PrintTableOpts, Printer},
+    schedule::command::ScheduleSubcommand,
+};
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const SERVER_ERROR: i64 = -32000;
+
+/// Represents a JSON-RPC request.
+#[derive(Debug, Deserialize)]
+struct Request {
+    jsonrpc: Option<String>,
+    /// The request id, `None` for notifications. A `null` id is
+    /// still an id, which expects a response.
+    #[serde(default, deserialize_with = "deserialize_id")]
+    id: Option<Value>,
+    method: String,
+    #[serde(default)]
+    params: Option<Value>,
+}
+
+/// Represents a JSON-RPC response.
+#[derive(Debug, Serialize)]
+struct Response {
+    jsonrpc: &'static str,
+    id: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<ResponseError>,
+}
+
+impl Response {
+    fn result(id: Value, result: Value) -> Self {
+        Self {
+            jsonrpc: "2.0",
+            id,
+            result: Some(result),
+            error: None,
+        }
+    }
+
+    fn error(id: Value, error: ResponseError) -> Self {
+        Self {
+            jsonrpc: "2.0",
+            id,
+            result: None,
+            error: Some(error),
+        }
+    }
+}
+
+/// Represents a JSON-RPC error.
+#[derive(Debug, Serialize)]
+struct ResponseError {
+    code: i64,
+    message: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    data: Vec<String>,
+}
+
+impl ResponseError {
+    fn new(code: i64, message: impl ToString) -> Self {
+        Self {
+            code,
+            message: message.to_string(),
+            data: Vec::new(),
+        }
+    }
+
+    fn from_err(code: i64, err: anyhow::Error) -> Self {
+        Self {
+            code,
+            message: err.to_string(),
+            data: err.chain().skip(1).map(ToString::to_string).collect(),
+        }
+    }
+}
+
+fn deserialize_id<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Value>, D::Error> {
+    Value::deserialize(deserializer).map(Some)
+}
+
+/// Represents an envelope id parameter, given either as a number or
+/// as a string accepting the same syntax as the command line, like
+/// `work:42`.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum ParamsId {
+    Number(usize),
+    String(String),
+}
+
+impl TryFrom<&ParamsId> for EnvelopeId {
+    type Error = anyhow::Error;
+
+    fn try_from(id: &ParamsId) -> Result<Self> {
+        match id {
+            ParamsId::Number(id) => Ok(EnvelopeId {
+                account: None,
+                id: *id,
+            }),
+            ParamsId::String(id) => id.parse().map_err(|err: String| anyhow!(err)),
+        }
+    }
+}
+
+/// Represents the parameters of a request.
+///
+/// The same structure is shared by all methods, each method only
+/// reading the parameters it needs.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Params {
+    account: Option<String>,
+    #[cfg(feature = "account-sync")]
+    disable_cache: bool,
+    folder: Option<String>,
+    target: Option<String>,
+    ids: Vec<ParamsId>,
+    flags: Vec<String>,
+    page: Option<usize>,
+    page_size: Option<usize>,
+    query: Option<String>,
+    preview: bool,
+    raw: bool,
+    message: Option<String>,
+    template: Option<String>,
+    args: Vec<String>,
+}
+
+impl Params {
+    fn folder(&self) -> &str {
+        self.folder.as_deref().unwrap_or(INBOX)
+    }
+
+    fn target(&self) -> Result<&str> {
+        self.target
+            .as_deref()
+            .ok_or(anyhow!("missing parameter target"))
+    }
+
+    /// Return the account of the request, which is the account
+    /// qualifying the ids if any, or the account parameter.
+    fn account(&self) -> Result<Option<String>> {
+        let ids = self.envelope_ids()?;
+        let account = ids::account_or(&ids, self.account.as_deref())?;
+        Ok(account.map(ToOwned::to_owned))
+    }
+
+    fn envelope_ids(&self) -> Result<Vec<EnvelopeId>> {
+        self.ids.iter().map(EnvelopeId::try_from).collect()
+    }
+
+    fn ids(&self) -> Result<Vec<usize>> {
+        if self.ids.is_empty() {
+            bail!("missing parameter ids");
+        }
+        Ok(self.envelope_ids()?.into_iter().map(|id| id.id).collect())
+    }
+
+    fn flags(&self) -> Result<Flags> {
+        if self.flags.is_empty() {
+            bail!("missing parameter flags");
+        }
+        Ok(Flags::from_iter(
+            self.flags.iter().map(|flag| Flag::from(flag.as_str())),
+        ))
+    }
+
+    fn message(&self) -> Result<&str> {
+        self.message
+            .as_deref()
+            .ok_or(anyhow!("missing parameter message"))
+    }
+
+    fn template(&self) -> Result<&str> {
+        self.template
+            .as_deref()
+            .ok_or(anyhow!("missing parameter template"))
+    }
+}
+
+/// Represents an account session kept alive by the server.
+struct Session {
+    account_config: Arc<AccountConfig>,
+    backend: Backend,
+}
+
+/// The printer used by the server.
+///
+/// It collects the JSON values of everything printed by commands, so
+/// that they can be sent back as the result of the request.
+#[derive(Debug, Default)]
+struct ServerPrinter {
+    values: Vec<Value>,
+}
+
+impl ServerPrinter {
+    fn into_result(mut self) -> Value {
+        match self.values.len() {
+            0 => Value::Null,
+            1 => self.values.remove(0),
+            _ => Value::Array(self.values),
+        }
+    }
+}
+
+impl Printer for ServerPrinter {
+    fn print<T: Debug + Print + serde::Serialize>(&mut self, data: T) -> Result<()> {
+        let value = serde_json::to_value(&data).context("cannot serialize result")?;
+        self.values.push(value);
+        Ok(())
+    }
+
+    fn print_log<T: Debug + Print>(&mut self, _data: T) -> Result<()> {
+        Ok(())
+    }
+
+    fn print_table<T: Debug + serde::Serialize + PrintTable + ?Sized>(
+        &mut self,
+        data: Box<T>,
+        _opts: PrintTableOpts,
+    ) -> Result<()> {
+        let value = serde_json::to_value(data.as_ref()).context("cannot serialize result")?;
+        self.values.push(value);
+        Ok(())
+    }
+
+    fn is_machine_readable(&self) -> bool {
+        true
+    }
+
+    fn is_ndjson(&self) -> bool {
+        false
+    }
+}
+
+/// The JSON-RPC server.
+pub struct Server {
+    config: TomlConfig,
+    config_paths: Vec<PathBuf>,
+    sessions: HashMap<(Option<String>, bool), Session>,
+}
+
+impl Server {
+    pub fn new(config: TomlConfig, config_paths: Vec<PathBuf>) -> Self {
+        Self {
+            config,
+            config_paths,
+            sessions: HashMap::new(),
+        }
+    }
+
+    /// Serve requests until the standard input is closed or until
+    /// the shutdown method is called.
+    pub async fn serve(&mut self) -> Result<()> {
+        let mut lines = BufReader::new(take_stdin()?).lines();
+
+        loop {
+            let line = match lines.next_line().await? {
+                Some(line) => line,
+                None => {
+                    debug!("standard input closed, stopping server");
+                    break;
+                }
+            };
+
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let req = match serde_json::from_str::<Request>(&line) {
+                Ok(req) => req,
+                Err(err) => {
+                    let err = match serde_json::from_str::<Value>(&line) {
+                        Ok(_) => ResponseError::new(INVALID_REQUEST, err),
+                        Err(err) => ResponseError::new(PARSE_ERROR, err),
+                    };
+                    write_response(&Response::error(Value::Null, err))?;
+                    continue;
+                }
+            };
+
+            if req.method == "shutdown" {
+                if let Some(id) = req.id {
+                    write_response(&Response::result(id, Value::Null))?;
+                }
+                break;
+            }
+
+            let res = self.handle(&req).await;
+
+            // requests without id are notifications, which do not
+            // expect any response
+            if let Some(id) = req.id {
+                let res = match res {
+                    Ok(result) => Response::result(id, result),
+                    Err(err) => Response::error(id, err),
+                };
+                write_response(&res)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn handle(&mut self, req: &Request) -> Result<Value, ResponseError> {
+        info!("handling request {}", req.method);
+
+        if let Some(version) = &req.jsonrpc {
+            if version != "2.0" {
+                let err = format!("unsupported JSON-RPC version {version}");
+                return Err(ResponseError::new(INVALID_REQUEST, err));
+            }
+        }
+
+        let params: Params = match &req.params {
+            None | Some(Value::Null) => Params::default(),
+            Some(params) => serde_json::from_value(params.clone())
+                .map_err(|err| ResponseError::new(INVALID_PARAMS, err))?,
+        };
+
+        if req.method == "command" {
+            return self
+                .execute_command(&params)
+                .await
+                .map_err(|err| ResponseError::from_err(SERVER_ERROR, err));
+        }
+
+        if !METHODS.contains(&req.method.as_str()) {
+            let err = format!("method {} not found", req.method);
+            return Err(ResponseError::new(METHOD_NOT_FOUND, err));
+        }
+
+        self.execute_method(&req.method, &params)
+            .await
+            .map_err(|err| ResponseError::from_err(SERVER_ERROR, err))
+    }
+
+    async fn session(&mut self, params: &Params) -> Result<&Session> {
+        #[cfg(feature = "account-sync")]
+        let disable_cache = params.disable_cache;
+        #[cfg(not(feature = "account-sync"))]
+        let disable_cache = false;
+
+        let key = (params.account()?, disable_cache);
+
+        if !self.sessions.contains_key(&key) {
+            let session = self.build_session(key.0.as_deref(), disable_cache).await?;
+            self.sessions.insert(key.clone(), session);
+        }
+
+        self.sessions
+            .get(&key)
+            .ok_or(anyhow!("cannot get account session"))
+    }
+
+    #[cfg_attr(not(feature = "account-sync"), allow(unused_variables))]
+    async fn build_session(&self, account: Option<&str>, disable_cache: bool) -> Result<Session> {
+        debug!("building session for account {account:?}");
+
+        let (toml_account_config, account_config) = self.config.clone().into_account_configs(
+            account,
+            #[cfg(feature = "account-sync")]
+            disable_cache,
+        )?;
+
+        let backend = build_backend(toml_account_config, account_config.clone()).await?;
+
+        Ok(Session {
+            account_config,
+            backend,
+        })
+    }
+
+    async fn execute_method(&mut self, method: &str, params: &Params) -> Result<Value> {
+        let session = self.session(params).await?;
+        let config = &session.account_config;
+        let backend = &session.backend;
+        let folder = params.folder();
+
+        let mut printer = ServerPrinter::default();
+        let format = config.get_message_read_format();
+        let opts = || PrintTableOpts {
+            format: &format,
+            max_width: None,
+        };
+
+        match method {
+            "folder.list" => {
+                let folders = Folders::from(backend.list_folders().await?);
+                printer.print_table(Box::new(folders), opts())?;
+            }
+            "folder.add" => {
+                backend.add_folder(folder).await?;
+                printer.print(format!("Folder {folder} successfully created!"))?;
+            }
+            "folder.expunge" => {
+                backend.expunge_folder(folder).await?;
+                printer.print(format!("Folder {folder} successfully expunged!"))?;
+            }
+            "folder.purge" => {
+                backend.purge_folder(folder).await?;
+                printer.print(format!("Folder {folder} successfully purged!"))?;
+            }
+            "folder.delete" => {
+                backend.delete_folder(folder).await?;
+                printer.print(format!("Folder {folder} successfully deleted!"))?;
+            }
+            "envelope.list" | "envelope.thread" => {
+                let page = 1.max(params.page.unwrap_or(1)) - 1;
+                let page_size = params
+                    .page_size
+                    .unwrap_or_else(|| config.get_envelope_list_page_size());
+                let query = match &params.query {
+                    Some(query) => Some(
+                        query
+                            .parse::<SearchEmailsQuery>()
+                            .map_err(|err| anyhow!("cannot parse query: {err}"))?,
+                    ),
+                    None => None,
+                };
+                let opts_ = ListEnvelopesOptions {
+                    page,
+                    page_size,
+                    query,
+                };
+
+                if method == "envelope.thread" {
+                    let envelopes = backend.thread_envelopes(folder, opts_).await?;
+                    printer.print_table(Box::new(envelopes), opts())?;
+                } else {
+                    let envelopes = backend.list_envelopes(folder, opts_).await?;
+                    printer.print_table(Box::new(envelopes), opts())?;
+                }
+            }
+            "flag.add" => {
+                backend
+                    .add_flags(folder, &params.ids()?, &params.flags()?)
+                    .await?;
+                printer.print("Flag(s) successfully added!")?;
+            }
+            "flag.set" => {
+                backend
+                    .set_flags(folder, &params.ids()?, &params.flags()?)
+                    .await?;
+                printer.print("Flag(s) successfully replaced!")?;
+            }
+            "flag.remove" => {
+                backend
+                    .remove_flags(folder, &params.ids()?, &params.flags()?)
+                    .await?;
+                printer.print("Flag(s) successfully removed!")?;
+            }
+            "message.read" => {
+                let ids = params.ids()?;
+                let msgs = if params.preview {
+                    backend.peek_messages(folder, &ids).await
+                } else {
+                    backend.get_messages(folder, &ids).await
+                }?;
+
+                let mut bodies = Vec::new();
+                for msg in msgs.to_vec() {
+                    if params.raw {
+                        bodies.push(String::from_utf8_lossy(msg.raw()?).to_string());
+                    } else {
+                        let tpl = msg.to_read_tpl(config, |tpl| tpl).await?;
+                        bodies.push(tpl.as_str().to_owned());
+                    }
+                }
+
+                printer.print(bodies.join("\n\n"))?;
+            }
+            "message.copy" => {
+                let target = params.target()?;
+                backend
+                    .copy_messages(folder, target, &params.ids()?)
+                    .await?;
+                printer.print(format!(
+                    "Message(s) successfully copied from {folder} to {target}!"
+                ))?;
+            }
+            "message.move" => {
+                let target = params.target()?;
+                backend
+                    .move_messages(folder, target, &params.ids()?)
+                    .await?;
+                printer.print(format!(
+                    "Message(s) successfully moved from {folder} to {target}!"
+                ))?;
+            }
+            "message.delete" => {
+                backend.delete_messages(folder, &params.ids()?).await?;
+                printer.print(format!("Message(s) successfully removed from {folder}!"))?;
+            }
+            "message.save" => {
+                backend
+                    .add_message(folder, params.message()?.as_bytes())
+                    .await?;
+                printer.print(format!("Message successfully saved to {folder}!"))?;
+            }
+            "message.send" => {
+                backend
+                    .send_message_then_save_copy(params.message()?.as_bytes())
+                    .await?;
+                printer.print("Message successfully sent!")?;
+            }
+            "template.save" => {
+                let msg = compile_template(config, params.template()?).await?;
+                backend.add_message(folder, &msg).await?;
+                printer.print(format!("Template successfully saved to {folder}!"))?;
+            }
+            "template.send" => {
+                let msg = compile_template(config, params.template()?).await?;
+                backend.send_message_then_save_copy(&msg).await?;
+                printer.print("Message successfully sent!")?;
+            }
+            method => bail!("method {method} not found"),
+        }
+
+        Ok(printer.into_result())
+    }
+
+    /// Execute any command from the given command line arguments.
+    ///
+    /// Commands executed this way do not benefit from the sessions
+    /// kept alive by the server.
+    async fn execute_command(&self, params: &Params) -> Result<Value> {
+        let args = std::iter::once("himalaya").chain(params.args.iter().map(String::as_str));
+        let cli = Cli::try_parse_from(args).map_err(|err| anyhow!(err.to_string()))?;
+
+        let cmd = match cli.command {
+            Some(cmd) => cmd,
+            None => bail!("missing command"),
+        };
+
+        match &cmd {
+            HimalayaCommand::Server(_)
+            | HimalayaCommand::Tui(_)
+            | HimalayaCommand::Account(AccountSubcommand::Configure(_))
+            | HimalayaCommand::Message(
+                MessageSubcommand::Write(_)
+                | MessageSubcommand::Reply(_)
+                | MessageSubcommand::Forward(_)
+                | MessageSubcommand::Mailto(_)
+                | MessageSubcommand::Save(_)
+                | MessageSubcommand::Send(_),
+            )
+            | HimalayaCommand::Template(
+                TemplateSubcommand::Save(_) | TemplateSubcommand::Send(_),
+            ) => {
+                bail!("interactive or stdin-based commands are not supported by the server")
+            }
+            HimalayaCommand::Message(MessageSubcommand::Part(cmd)) if cmd.output.is_none() => {
+                bail!("message part needs an --output file when used by the server")
+            }
+            HimalayaCommand::Attachment(AttachmentSubcommand::Open(_)) => {
+                bail!("attachment open is not supported by the server")
+            }
+            HimalayaCommand::Attachment(AttachmentSubcommand::Download(cmd)) if cmd.stdout => {
+                bail!("attachment download cannot write to stdout when used by the server")
+            }
+            #[cfg(feature = "account-sync")]
+            HimalayaCommand::Account(AccountSubcommand::Sync(cmd)) if cmd.daemon => {
+                bail!("account sync cannot run as a daemon when used by the server")
+            }
+            #[cfg(feature = "account-sync")]
+            HimalayaCommand::Account(AccountSubcommand::Sync(cmd)) if cmd.events.is_some() => {
+                bail!("account sync cannot stream events when used by the server")
+            }
+            HimalayaCommand::Envelope(EnvelopeSubcommand::Watch(_)) => {
+                bail!("envelope watch is not supported by the server")
+            }
+            HimalayaCommand::Schedule(ScheduleSubcommand::Run(cmd)) if cmd.looping => {
+                bail!("schedule run cannot loop when used by the server")
+            }
+            HimalayaCommand::Contact(ContactSubcommand::Export(cmd)) if cmd.file.is_none() => {
+                bail!("contact export needs a --file when used by the server")
+            }
+            HimalayaCommand::Complete(_) => {
+                bail!("dynamic completion is not supported by the server")
+            }
+            _ => (),
+        }
+
+        let config_paths = if cli.config_paths.is_empty() {
+            &self.config_paths
+        } else {
+            &cli.config_paths
+        };
+
+        let mut printer = ServerPrinter::default();
+        cmd.execute(&mut printer, config_paths).await?;
+
+        Ok(printer.into_result())
+    }
+}
+
+/// The methods supported by the server, in addition to the generic
+/// `command` and `shutdown` methods.
+const METHODS: &[&str] = &[
+    "folder.list",
+    "folder.add",
+    "folder.expunge",
+    "folder.purge",
+    "folder.delete",
+    "envelope.list",
+    "envelope.thread",
+    "flag.add",
+    "flag.set",
+    "flag.remove",
+    "message.read",
+    "message.copy",
+    "message.move",
+    "message.delete",
+    "message.save",
+    "message.send",
+    "template.save",
+    "template.send",
+];
+
+async fn build_backend(
+    toml_account_config: Arc<TomlAccountConfig>,
+    account_config: Arc<AccountConfig>,
+) -> Result<Backend> {
+    let kinds = [
+        toml_account_config.add_folder_kind(),
+        toml_account_config.list_folders_kind(),
+        toml_account_config.expunge_folder_kind(),
+        toml_account_config.purge_folder_kind(),
+        toml_account_config.delete_folder_kind(),
+        toml_account_config.list_envelopes_kind(),
+        toml_account_config.add_flags_kind(),
+        toml_account_config.set_flags_kind(),
+        toml_account_config.remove_flags_kind(),
+        toml_account_config.add_message_kind(),
+        toml_account_config.peek_messages_kind(),
+        toml_account_config.get_messages_kind(),
+        toml_account_config.copy_messages_kind(),
+        toml_account_config.move_messages_kind(),
+        toml_account_config.delete_messages_kind(),
+        toml_account_config.send_message_kind(),
+    ];
+
+    Backend::new(
+        toml_account_config.clone(),
+        account_config,
+        kinds.into_iter().flatten(),
+        |builder| {
+            builder.set_add_folder(BackendFeatureSource::Context);
+            builder.set_list_folders(BackendFeatureSource::Context);
+            builder.set_expunge_folder(BackendFeatureSource::Context);
+            builder.set_purge_folder(BackendFeatureSource::Context);
+            builder.set_delete_folder(BackendFeatureSource::Context);
+            builder.set_list_envelopes(BackendFeatureSource::Context);
+            builder.set_add_flags(BackendFeatureSource::Context);
+            builder.set_set_flags(BackendFeatureSource::Context);
+            builder.set_remove_flags(BackendFeatureSource::Context);
+            builder.set_add_message(BackendFeatureSource::Context);
+            builder.set_peek_messages(BackendFeatureSource::Context);
+            builder.set_get_messages(BackendFeatureSource::Context);
+            builder.set_copy_messages(BackendFeatureSource::Context);
+            builder.set_move_messages(BackendFeatureSource::Context);
+            builder.set_delete_messages(BackendFeatureSource::Context);
+            builder.set_send_message(BackendFeatureSource::Context);
+        },
+    )
+    .await
+}
+
+#[cfg_attr(not(feature = "pgp"), allow(unused_variables))]
+async fn compile_template(config: &AccountConfig, tpl: &str) -> Result<Vec<u8>> {
+    #[allow(unused_mut)]
+    let mut compiler = MmlCompilerBuilder::new();
+
+    #[cfg(feature = "pgp")]
+    compiler.set_some_pgp(config.pgp.clone());
+
+    Ok(compiler.build(tpl)?.compile().await?.into_vec()?)
+}
+
+/// Take the standard input for the protocol.
+///
+/// The standard input is then replaced by `/dev/null`, so that
+/// password commands or prompts run while authenticating backends
+/// cannot read requests, nor wait for an input that never comes.
+#[cfg(not(windows))]
+fn take_stdin() -> Result<Box<dyn AsyncRead + Send + Unpin>> {
+    use std::{
+        fs::File,
+        os::fd::{AsFd, AsRawFd},
+    };
+
+    let stdin = io::stdin()
+        .as_fd()
+        .try_clone_to_owned()
+        .context("cannot duplicate standard input")?;
+    let null = File::open("/dev/null").context("cannot open /dev/null")?;
+
+    if unsafe { libc::dup2(null.as_raw_fd(), libc::STDIN_FILENO) } < 0 {
+        let err = io::Error::last_os_error();
+        return Err(err).context("cannot detach standard input");
+    }
+
+    Ok(Box::new(tokio::fs::File::from_std(File::from(stdin))))
+}
+
+#[cfg(windows)]
+fn take_stdin() -> Result<Box<dyn AsyncRead + Send + Unpin>> {
+    Ok(Box::new(tokio::io::stdin()))
+}
+
+fn write_response(res: &Response) -> Result<()> {
+    let mut stdout = io::stdout().lock();
+    serde_json::to_writer(&mut stdout, res).context("cannot write response")?;
+    writeln!(stdout)?;
+    stdout.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn printer_collects_values() {
+        let mut printer = ServerPrinter::default();
+        assert_eq!(printer.values.len(), 0);
+
+        printer.print("first").unwrap();
+        printer.print_log("ignored").unwrap();
+        assert_eq!(printer.values, vec![Value::from("first")]);
+
+        printer.print("second").unwrap();
+        assert_eq!(
+            printer.into_result(),
+            serde_json::json!(["first", "second"])
+        );
+    }
+
+    #[test]
+    fn params_defaults() {
+        let params: Params = serde_json::from_value(serde_json::json!({
+            "ids": [1, 2],
+            "flags": ["seen"],
+        }))
+        .unwrap();
+
+        assert_eq!(params.folder(), INBOX);
+        assert_eq!(params.ids().unwrap(), vec![1, 2]);
+        assert_eq!(params.account().unwrap(), None);
+        assert!(params.flags().is_ok());
+        assert!(params.target().is_err());
+    }
+
+    #[test]
+    fn params_qualified_ids() {
+        let params: Params = serde_json::from_value(serde_json::json!({
+            "account": "perso",
+            "ids": ["work:1", 2, "3"],
+        }))
+        .unwrap();
+
+        assert_eq!(params.ids().unwrap(), vec![1, 2, 3]);
+        assert_eq!(params.account().unwrap().as_deref(), Some("work"));
+
+        let params: Params = serde_json::from_value(serde_json::json!({
+            "ids": ["work:1", "perso:2"],
+        }))
+        .unwrap();
+
+        assert!(params.account().is_err());
+    }
+
+    #[test]
+    fn request_null_id() {
+        let req: Request = serde_json::from_str(r#"{"method":"folder.list","id":null}"#).unwrap();
+        assert_eq!(req.id, Some(Value::Null));
+
+        let req: Request = serde_json::from_str(r#"{"method":"folder.list"}"#).unwrap();
+        assert_eq!(req.id, None);
+    }
+}
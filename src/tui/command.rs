@@ -0,0 +1,81 @@
+use anyhow::Result;
+use clap::Parser;
+use email::backend::feature::BackendFeatureSource;
+use log::info;
+
+#[cfg(feature = "account-sync")]
+use crate::cache::arg::disable::CacheDisableFlag;
+use crate::{
+    account::arg::name::AccountNameFlag, backend::Backend, config::TomlConfig,
+    folder::arg::name::FolderNameOptionalFlag, printer::Printer, tui::Tui,
+};
+
+/// Browse emails using the interactive terminal interface.
+///
+/// This command opens a full-screen interface composed of a folder
+/// pane, an envelope list and a message preview, from where messages
+/// can be read, replied, forwarded, moved, flagged or deleted. This
+/// command is also executed when Himalaya is launched from a terminal
+/// without any subcommand.
+#[derive(Debug, Default, Parser)]
+pub struct TuiCommand {
+    #[command(flatten)]
+    pub folder: FolderNameOptionalFlag,
+
+    #[cfg(feature = "account-sync")]
+    #[command(flatten)]
+    pub cache: CacheDisableFlag,
+
+    #[command(flatten)]
+    pub account: AccountNameFlag,
+}
+
+impl TuiCommand {
+    pub async fn execute(self, printer: &mut impl Printer, config: &TomlConfig) -> Result<()> {
+        info!("executing tui command");
+
+        let (toml_account_config, account_config) = config.clone().into_account_configs(
+            self.account.name.as_deref(),
+            #[cfg(feature = "account-sync")]
+            self.cache.disable,
+        )?;
+
+        let kinds = [
+            toml_account_config.list_folders_kind(),
+            toml_account_config.list_envelopes_kind(),
+            toml_account_config.peek_messages_kind(),
+            toml_account_config.get_messages_kind(),
+            toml_account_config.add_flags_kind(),
+            toml_account_config.remove_flags_kind(),
+            toml_account_config.copy_messages_kind(),
+            toml_account_config.move_messages_kind(),
+            toml_account_config.delete_messages_kind(),
+            toml_account_config.add_message_kind(),
+            toml_account_config.send_message_kind(),
+        ];
+
+        let backend = Backend::new(
+            toml_account_config.clone(),
+            account_config.clone(),
+            kinds.into_iter().flatten(),
+            |builder| {
+                builder.set_list_folders(BackendFeatureSource::Context);
+                builder.set_list_envelopes(BackendFeatureSource::Context);
+                builder.set_peek_messages(BackendFeatureSource::Context);
+                builder.set_get_messages(BackendFeatureSource::Context);
+                builder.set_add_flags(BackendFeatureSource::Context);
+                builder.set_remove_flags(BackendFeatureSource::Context);
+                builder.set_copy_messages(BackendFeatureSource::Context);
+                builder.set_move_messages(BackendFeatureSource::Context);
+                builder.set_delete_messages(BackendFeatureSource::Context);
+                builder.set_add_message(BackendFeatureSource::Context);
+                builder.set_send_message(BackendFeatureSource::Context);
+            },
+        )
+        .await?;
+
+        Tui::new(account_config, &backend, &self.folder.name)
+            .run(printer)
+            .await
+    }
+}
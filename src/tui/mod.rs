@@ -0,0 +1,737 @@
+//! Module dedicated to the interactive terminal user interface.
+//!
+//! The interface is composed of a folder pane, an envelope list and
+//! a message preview. It relies on the same backend features as the
+//! other commands, and on the same editor flow for composing
+//! messages.
+
+pub mod command;
+
+use anyhow::{anyhow, Result};
+use console::{pad_str, style, Alignment, Key, Term};
+use email::{
+    account::config::AccountConfig,
+    envelope::list::ListEnvelopesOptions,
+    folder::list::ListFolders,
+    message::{Message, Messages},
+};
+use std::{collections::HashMap, sync::Arc};
+use unicode_width::UnicodeWidthChar;
+
+use crate::{
+    backend::Backend,
+    envelope::Envelope,
+    flag::Flag,
+    folder::{Folder, Folders},
+    printer::Printer,
+    ui::editor,
+};
+
+const HELP: &str = "q:quit ⏎:read r/R:reply f:forward w:write m/c:move/copy s:seen !:flag d:delete n/p:page u:refresh";
+
+/// The pane having the focus.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Focus {
+    Folders,
+    Envelopes,
+}
+
+/// The action waiting for a target folder.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum PickAction {
+    Move,
+    Copy,
+}
+
+/// The interface mode.
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum Mode {
+    /// Browse folders and envelopes.
+    Browse,
+    /// Pick a folder from the folder pane.
+    Pick(PickAction),
+    /// Confirm the deletion of the message with the given id.
+    ConfirmDelete(usize),
+    /// Read the selected message in full screen.
+    Read { lines: Vec<String>, scroll: usize },
+}
+
+/// The interactive terminal user interface state.
+pub struct Tui<'a> {
+    account_config: Arc<AccountConfig>,
+    backend: &'a Backend,
+    term: Term,
+
+    folders: Vec<Folder>,
+    folder_cursor: usize,
+    folder_offset: usize,
+    folder: String,
+
+    envelopes: Vec<Envelope>,
+    cursor: usize,
+    offset: usize,
+    page: usize,
+    page_size: usize,
+
+    previews: HashMap<String, String>,
+    focus: Focus,
+    mode: Mode,
+    status: String,
+}
+
+impl<'a> Tui<'a> {
+    pub fn new(account_config: Arc<AccountConfig>, backend: &'a Backend, folder: &str) -> Self {
+        let page_size = account_config.get_envelope_list_page_size();
+
+        Self {
+            account_config,
+            backend,
+            term: Term::stdout(),
+            folders: Vec::new(),
+            folder_cursor: 0,
+            folder_offset: 0,
+            folder: folder.to_owned(),
+            envelopes: Vec::new(),
+            cursor: 0,
+            offset: 0,
+            page: 0,
+            page_size,
+            previews: HashMap::new(),
+            focus: Focus::Envelopes,
+            mode: Mode::Browse,
+            status: String::from(HELP),
+        }
+    }
+
+    /// Run the interface until the user quits.
+    ///
+    /// The terminal is switched to the alternate screen during the
+    /// whole session, and restored even if an error occurs.
+    pub async fn run(&mut self, printer: &mut impl Printer) -> Result<()> {
+        self.enter_screen()?;
+        let res = self.run_loop(printer).await;
+        self.leave_screen()?;
+        res
+    }
+
+    async fn run_loop(&mut self, printer: &mut impl Printer) -> Result<()> {
+        self.load_folders().await?;
+        self.load_envelopes().await?;
+
+        loop {
+            self.load_preview().await;
+            self.draw()?;
+
+            let key = self.term.read_key()?;
+
+            let res = match self.mode.clone() {
+                Mode::Read { .. } => self.handle_read_key(key, printer).await,
+                Mode::Pick(action) => self.handle_pick_key(key, action).await,
+                Mode::ConfirmDelete(id) => self.handle_confirm_delete_key(key, id).await,
+                Mode::Browse => self.handle_browse_key(key, printer).await,
+            };
+
+            match res {
+                Ok(true) => break,
+                Ok(false) => (),
+                Err(err) => self.status = format!("Error: {err}"),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn enter_screen(&self) -> Result<()> {
+        self.term.write_str("\x1b[?1049h")?;
+        self.term.hide_cursor()?;
+        Ok(())
+    }
+
+    fn leave_screen(&self) -> Result<()> {
+        self.term.show_cursor()?;
+        self.term.write_str("\x1b[?1049l")?;
+        Ok(())
+    }
+
+    async fn load_folders(&mut self) -> Result<()> {
+        let folders = self.backend.list_folders().await?;
+        self.folders = Folders::from(folders).to_vec();
+        self.folder_cursor = self
+            .folders
+            .iter()
+            .position(|f| f.name.eq_ignore_ascii_case(&self.folder))
+            .unwrap_or_default();
+        Ok(())
+    }
+
+    async fn load_envelopes(&mut self) -> Result<()> {
+        let opts = ListEnvelopesOptions {
+            page: self.page,
+            page_size: self.page_size,
+            query: None,
+        };
+
+        self.envelopes = self
+            .backend
+            .list_envelopes(&self.folder, opts)
+            .await?
+            .to_vec();
+        self.cursor = 0;
+        self.offset = 0;
+        self.previews.clear();
+
+        Ok(())
+    }
+
+    /// Load the preview of the selected envelope, if not already
+    /// loaded. Messages are peeked so that they are not marked as
+    /// seen.
+    async fn load_preview(&mut self) {
+        let envelope = match self.envelopes.get(self.cursor) {
+            Some(envelope) => envelope,
+            None => return,
+        };
+
+        if self.previews.contains_key(&envelope.id) {
+            return;
+        }
+
+        let id = envelope.id.clone();
+        let preview = match self.read_tpl(false).await {
+            Ok(tpl) => tpl,
+            Err(err) => format!("Cannot preview message {id}: {err}"),
+        };
+
+        self.previews.insert(id, preview);
+    }
+
+    fn selected_id(&self) -> Result<usize> {
+        let envelope = self
+            .envelopes
+            .get(self.cursor)
+            .ok_or(anyhow!("no envelope selected"))?;
+
+        envelope
+            .id
+            .parse()
+            .map_err(|_| anyhow!("invalid envelope id {}", envelope.id))
+    }
+
+    async fn selected_messages(&self, peek: bool) -> Result<Messages> {
+        let id = self.selected_id()?;
+
+        if peek {
+            self.backend.peek_messages(&self.folder, &[id]).await
+        } else {
+            self.backend.get_messages(&self.folder, &[id]).await
+        }
+    }
+
+    async fn read_tpl(&self, mark_as_seen: bool) -> Result<String> {
+        let msgs = self.selected_messages(!mark_as_seen).await?;
+        let msg = msgs.first().ok_or(anyhow!("cannot find message"))?;
+        let tpl = msg.to_read_tpl(&self.account_config, |tpl| tpl).await?;
+        Ok(tpl.as_str().to_owned())
+    }
+
+    fn update_selected_flag(&mut self, flag: Flag, add: bool) {
+        if let Some(envelope) = self.envelopes.get_mut(self.cursor) {
+            if add {
+                envelope.flags.0.insert(flag);
+            } else {
+                envelope.flags.0.remove(&flag);
+            }
+        }
+    }
+
+    fn remove_selected(&mut self) {
+        if self.cursor < self.envelopes.len() {
+            let envelope = self.envelopes.remove(self.cursor);
+            self.previews.remove(&envelope.id);
+        }
+
+        self.cursor = self.cursor.min(self.envelopes.len().saturating_sub(1));
+    }
+
+    async fn handle_browse_key(&mut self, key: Key, printer: &mut impl Printer) -> Result<bool> {
+        match key {
+            Key::Char('q') => return Ok(true),
+            Key::Char('?') => self.status = String::from(HELP),
+            Key::Tab | Key::BackTab => {
+                self.focus = match self.focus {
+                    Focus::Folders => Focus::Envelopes,
+                    Focus::Envelopes => Focus::Folders,
+                };
+            }
+            Key::ArrowLeft | Key::Char('h') => self.focus = Focus::Folders,
+            Key::ArrowRight | Key::Char('l') => self.focus = Focus::Envelopes,
+            Key::ArrowDown | Key::Char('j') => self.move_cursor(1),
+            Key::ArrowUp | Key::Char('k') => self.move_cursor(-1),
+            Key::PageDown => self.move_cursor(10),
+            Key::PageUp => self.move_cursor(-10),
+            Key::Home | Key::Char('g') => self.move_cursor(isize::MIN),
+            Key::End | Key::Char('G') => self.move_cursor(isize::MAX),
+            Key::Enter if self.focus == Focus::Folders => {
+                if let Some(folder) = self.folders.get(self.folder_cursor) {
+                    self.folder = folder.name.clone();
+                    self.page = 0;
+                    self.focus = Focus::Envelopes;
+                    self.load_envelopes().await?;
+                    self.status = format!("Folder {} opened", self.folder);
+                }
+            }
+            Key::Enter => {
+                let tpl = self.read_tpl(true).await?;
+                self.update_selected_flag(Flag::Seen, true);
+                let (_, width) = self.term.size();
+                let lines = wrap(&tpl, width as usize);
+                self.mode = Mode::Read { lines, scroll: 0 };
+            }
+            Key::Char('n') => {
+                if self.page_size > 0 && self.envelopes.len() >= self.page_size {
+                    self.page += 1;
+                    self.load_envelopes().await?;
+                    self.status = format!("Page {}", self.page + 1);
+                }
+            }
+            Key::Char('p') => {
+                if self.page > 0 {
+                    self.page -= 1;
+                    self.load_envelopes().await?;
+                    self.status = format!("Page {}", self.page + 1);
+                }
+            }
+            Key::Char('u') => {
+                self.load_folders().await?;
+                self.load_envelopes().await?;
+                self.status = String::from("Envelopes refreshed");
+            }
+            Key::Char('w') => {
+                let tpl = Message::new_tpl_builder(self.account_config.clone())
+                    .build()
+                    .await?;
+                self.edit(printer, tpl).await?;
+            }
+            Key::Char(c @ ('r' | 'R')) => {
+                let msgs = self.selected_messages(false).await?;
+                let tpl = msgs
+                    .first()
+                    .ok_or(anyhow!("cannot find message"))?
+                    .to_reply_tpl_builder(self.account_config.clone())
+                    .with_reply_all(c == 'R')
+                    .build()
+                    .await?;
+                self.update_selected_flag(Flag::Seen, true);
+                self.edit(printer, tpl).await?;
+            }
+            Key::Char('f') => {
+                let msgs = self.selected_messages(false).await?;
+                let tpl = msgs
+                    .first()
+                    .ok_or(anyhow!("cannot find message"))?
+                    .to_forward_tpl_builder(self.account_config.clone())
+                    .build()
+                    .await?;
+                self.update_selected_flag(Flag::Seen, true);
+                self.edit(printer, tpl).await?;
+            }
+            Key::Char('m') => {
+                self.selected_id()?;
+                self.mode = Mode::Pick(PickAction::Move);
+                self.status = String::from("Move to folder: ⏎ to confirm, esc to cancel");
+            }
+            Key::Char('c') => {
+                self.selected_id()?;
+                self.mode = Mode::Pick(PickAction::Copy);
+                self.status = String::from("Copy to folder: ⏎ to confirm, esc to cancel");
+            }
+            Key::Char('s') => self.toggle_flag(Flag::Seen).await?,
+            Key::Char('!') => self.toggle_flag(Flag::Flagged).await?,
+            Key::Char('d') if self.focus == Focus::Envelopes => {
+                let id = self.selected_id()?;
+                self.mode = Mode::ConfirmDelete(id);
+                self.status = format!("Delete message {id}? y to confirm, any other key to cancel");
+            }
+            _ => (),
+        }
+
+        Ok(false)
+    }
+
+    async fn handle_confirm_delete_key(&mut self, key: Key, id: usize) -> Result<bool> {
+        self.mode = Mode::Browse;
+
+        match key {
+            Key::Char('y' | 'Y') => {
+                self.backend.delete_messages(&self.folder, &[id]).await?;
+                self.remove_selected();
+                self.status = format!("Message {id} deleted");
+            }
+            _ => self.status = String::from(HELP),
+        }
+
+        Ok(false)
+    }
+
+    async fn handle_read_key(&mut self, key: Key, printer: &mut impl Printer) -> Result<bool> {
+        let (height, _) = self.term.size();
+        let height = (height as usize).saturating_sub(2).max(1);
+
+        let (lines, scroll) = match &mut self.mode {
+            Mode::Read { lines, scroll } => (lines, scroll),
+            _ => return Ok(false),
+        };
+        let max_scroll = lines.len().saturating_sub(height);
+
+        match key {
+            Key::Char('q') | Key::Escape => self.mode = Mode::Browse,
+            Key::ArrowDown | Key::Char('j') => *scroll = (*scroll + 1).min(max_scroll),
+            Key::ArrowUp | Key::Char('k') => *scroll = scroll.saturating_sub(1),
+            Key::PageDown | Key::Char(' ') => *scroll = (*scroll + height).min(max_scroll),
+            Key::PageUp | Key::Char('b') => *scroll = scroll.saturating_sub(height),
+            Key::Home | Key::Char('g') => *scroll = 0,
+            Key::End | Key::Char('G') => *scroll = max_scroll,
+            Key::Char('r' | 'R' | 'f' | 'm' | 'c' | 's' | '!' | 'd') => {
+                return self.handle_browse_key(key, printer).await;
+            }
+            _ => (),
+        }
+
+        Ok(false)
+    }
+
+    async fn handle_pick_key(&mut self, key: Key, action: PickAction) -> Result<bool> {
+        match key {
+            Key::Escape | Key::Char('q') => {
+                self.mode = Mode::Browse;
+                self.status = String::from(HELP);
+            }
+            Key::ArrowDown | Key::Char('j') => self.move_folder_cursor(1),
+            Key::ArrowUp | Key::Char('k') => self.move_folder_cursor(-1),
+            Key::Home | Key::Char('g') => self.move_folder_cursor(isize::MIN),
+            Key::End | Key::Char('G') => self.move_folder_cursor(isize::MAX),
+            Key::Enter => {
+                self.mode = Mode::Browse;
+
+                let target = match self.folders.get(self.folder_cursor) {
+                    Some(folder) => folder.name.clone(),
+                    None => return Ok(false),
+                };
+                let id = self.selected_id()?;
+
+                match action {
+                    PickAction::Move => {
+                        self.backend
+                            .move_messages(&self.folder, &target, &[id])
+                            .await?;
+                        self.remove_selected();
+                        self.status = format!("Message {id} moved to {target}");
+                    }
+                    PickAction::Copy => {
+                        self.backend
+                            .copy_messages(&self.folder, &target, &[id])
+                            .await?;
+                        self.status = format!("Message {id} copied to {target}");
+                    }
+                }
+
+                self.folder_cursor = self
+                    .folders
+                    .iter()
+                    .position(|f| f.name == self.folder)
+                    .unwrap_or_default();
+            }
+            _ => (),
+        }
+
+        Ok(false)
+    }
+
+    async fn toggle_flag(&mut self, flag: Flag) -> Result<()> {
+        let id = self.selected_id()?;
+        let has_flag = self
+            .envelopes
+            .get(self.cursor)
+            .map(|e| e.flags.contains(&flag))
+            .unwrap_or_default();
+
+        let backend_flag = match flag {
+            Flag::Seen => email::flag::Flag::Seen,
+            Flag::Flagged => email::flag::Flag::Flagged,
+            _ => return Ok(()),
+        };
+
+        if has_flag {
+            self.backend
+                .remove_flag(&self.folder, &[id], backend_flag)
+                .await?;
+        } else {
+            self.backend
+                .add_flag(&self.folder, &[id], backend_flag)
+                .await?;
+        }
+
+        self.update_selected_flag(flag, !has_flag);
+
+        Ok(())
+    }
+
+    /// Leave the interface to edit the given template with the
+    /// editor flow, then come back.
+    async fn edit(
+        &mut self,
+        printer: &mut impl Printer,
+        tpl: email::template::Template,
+    ) -> Result<()> {
+        self.leave_screen()?;
+        let res =
+            editor::edit_tpl_with_editor(self.account_config.clone(), printer, self.backend, tpl)
+                .await;
+        self.enter_screen()?;
+
+        res?;
+        self.mode = Mode::Browse;
+        self.status = String::from("Message edition done");
+
+        Ok(())
+    }
+
+    fn move_cursor(&mut self, delta: isize) {
+        match self.focus {
+            Focus::Folders => self.move_folder_cursor(delta),
+            Focus::Envelopes => {
+                self.cursor = move_index(self.cursor, delta, self.envelopes.len());
+            }
+        }
+    }
+
+    fn move_folder_cursor(&mut self, delta: isize) {
+        self.folder_cursor = move_index(self.folder_cursor, delta, self.folders.len());
+    }
+
+    fn draw(&mut self) -> Result<()> {
+        let (height, width) = self.term.size();
+        let lines = self.render(width as usize, height as usize);
+
+        self.term.move_cursor_to(0, 0)?;
+        self.term.write_str(&lines.join("\r\n"))?;
+        self.term.flush()?;
+
+        Ok(())
+    }
+
+    fn render(&mut self, width: usize, height: usize) -> Vec<String> {
+        let body_height = height.saturating_sub(2);
+        let mut lines = Vec::with_capacity(height);
+
+        let title = format!(
+            " himalaya · {} · {} · page {}",
+            self.account_config.name,
+            self.folder,
+            self.page + 1
+        );
+        lines.push(style(pad(&title, width)).reverse().bold().to_string());
+
+        match &self.mode {
+            Mode::Read {
+                lines: content,
+                scroll,
+            } => {
+                for i in 0..body_height {
+                    let line = content.get(scroll + i).map(String::as_str).unwrap_or("");
+                    lines.push(pad(line, width));
+                }
+            }
+            Mode::Browse | Mode::Pick(_) | Mode::ConfirmDelete(_) => {
+                let folders_width = (width / 4).clamp(10, 24).min(width);
+                let right_width = width.saturating_sub(folders_width + 1);
+                let list_height = (body_height / 2).max(1);
+                let preview_height = body_height.saturating_sub(list_height + 1);
+
+                self.folder_offset =
+                    scroll_offset(self.folder_offset, self.folder_cursor, body_height);
+                self.offset = scroll_offset(self.offset, self.cursor, list_height);
+
+                let folders_focused =
+                    self.focus == Focus::Folders || matches!(self.mode, Mode::Pick(_));
+                let envelopes_focused = !folders_focused;
+
+                let preview = self
+                    .envelopes
+                    .get(self.cursor)
+                    .and_then(|e| self.previews.get(&e.id))
+                    .map(String::as_str)
+                    .unwrap_or_default();
+                let preview = wrap(preview, right_width);
+
+                for i in 0..body_height {
+                    let folder = match self.folders.get(self.folder_offset + i) {
+                        Some(folder) => {
+                            let idx = self.folder_offset + i;
+                            let cell = pad(&format!(" {}", folder.name), folders_width);
+                            let cell = if folder.name == self.folder {
+                                style(cell).bold()
+                            } else {
+                                style(cell)
+                            };
+                            if idx == self.folder_cursor && folders_focused {
+                                cell.reverse().to_string()
+                            } else {
+                                cell.blue().to_string()
+                            }
+                        }
+                        None => pad("", folders_width),
+                    };
+
+                    let right = if i < list_height {
+                        let idx = self.offset + i;
+                        match self.envelopes.get(idx) {
+                            Some(envelope) => {
+                                let row = render_envelope(envelope, right_width);
+                                if idx == self.cursor && envelopes_focused {
+                                    style(row).reverse().to_string()
+                                } else if idx == self.cursor {
+                                    style(row).underlined().to_string()
+                                } else if !envelope.flags.contains(&Flag::Seen) {
+                                    style(row).bold().to_string()
+                                } else {
+                                    row
+                                }
+                            }
+                            None if idx == 0 => {
+                                style(pad(" No envelopes", right_width)).dim().to_string()
+                            }
+                            None => pad("", right_width),
+                        }
+                    } else if i == list_height {
+                        style("─".repeat(right_width)).dim().to_string()
+                    } else {
+                        let line = i - list_height - 1;
+                        if line < preview_height {
+                            pad(
+                                preview.get(line).map(String::as_str).unwrap_or(""),
+                                right_width,
+                            )
+                        } else {
+                            pad("", right_width)
+                        }
+                    };
+
+                    lines.push(format!("{folder}{}{right}", style("│").dim()));
+                }
+            }
+        }
+
+        lines.push(
+            style(pad(&format!(" {}", self.status), width))
+                .reverse()
+                .to_string(),
+        );
+        lines
+    }
+}
+
+fn render_envelope(envelope: &Envelope, width: usize) -> String {
+    let flags = format!(
+        "{}{}{}",
+        if envelope.flags.contains(&Flag::Seen) {
+            " "
+        } else {
+            "✷"
+        },
+        if envelope.flags.contains(&Flag::Answered) {
+            "↵"
+        } else {
+            " "
+        },
+        if envelope.flags.contains(&Flag::Flagged) {
+            "⚑"
+        } else {
+            " "
+        },
+    );
+    let sender = envelope.from.name.as_ref().unwrap_or(&envelope.from.addr);
+
+    let row = format!(
+        " {} {} {} {} {}",
+        pad(&envelope.id, 4),
+        flags,
+        pad(&envelope.date, 16),
+        pad(sender, 20),
+        envelope.subject,
+    );
+
+    pad(&row, width)
+}
+
+/// Pad or truncate the given text so that it fits exactly the given
+/// width.
+fn pad(text: &str, width: usize) -> String {
+    pad_str(text, width, Alignment::Left, Some("…")).to_string()
+}
+
+/// Move the given index by the given delta, bounded by the given
+/// length.
+fn move_index(index: usize, delta: isize, len: usize) -> usize {
+    if len == 0 {
+        return 0;
+    }
+
+    index.saturating_add_signed(delta).min(len - 1)
+}
+
+/// Compute the scroll offset so that the cursor stays visible.
+fn scroll_offset(offset: usize, cursor: usize, height: usize) -> usize {
+    if cursor < offset {
+        cursor
+    } else if height > 0 && cursor >= offset + height {
+        cursor + 1 - height
+    } else {
+        offset
+    }
+}
+
+/// Wrap the given text so that each line fits the given width.
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let width = width.max(1);
+    let mut lines = Vec::new();
+
+    for line in text.replace('\t', "    ").lines() {
+        let mut current = String::new();
+        let mut current_width = 0;
+
+        for c in line.chars() {
+            let w = c.width().unwrap_or_default();
+            if current_width + w > width {
+                lines.push(std::mem::take(&mut current));
+                current_width = 0;
+            }
+            current.push(c);
+            current_width += w;
+        }
+
+        lines.push(current);
+    }
+
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wrap_long_lines() {
+        assert_eq!(wrap("abcdef\n\nab", 4), vec!["abcd", "ef", "", "ab"]);
+        assert_eq!(wrap("a\tb", 10), vec!["a    b"]);
+    }
+
+    #[test]
+    fn scroll_follows_cursor() {
+        assert_eq!(scroll_offset(0, 3, 5), 0);
+        assert_eq!(scroll_offset(0, 7, 5), 3);
+        assert_eq!(scroll_offset(4, 2, 5), 2);
+        assert_eq!(move_index(0, -1, 3), 0);
+        assert_eq!(move_index(1, isize::MAX, 3), 2);
+    }
+}
@@ -4,16 +4,18 @@ use email::{
     email::utils::{local_draft_path, remove_local_draft},
     flag::{Flag, Flags},
     folder::DRAFTS,
-    message::{add::AddMessage, send::SendMessageThenSaveCopy},
+    message::add::AddMessage,
     template::Template,
 };
-use log::debug;
+use log::{debug, warn};
 use mml::MmlCompilerBuilder;
 use process::SingleCommand;
 use std::{env, fs, sync::Arc};
 
 use crate::{
     backend::Backend,
+    contact::{self, config::ContactConfig, AddressBook},
+    outbox,
     printer::Printer,
     ui::choice::{self, PostEditChoice, PreEditChoice},
 };
@@ -46,6 +48,47 @@ pub async fn open_with_local_draft() -> Result<Template> {
     open_with_tpl(content.into()).await
 }
 
+/// Expand short names found in the recipients of the given template
+/// using the address book, if enabled.
+///
+/// Expanded recipients are printed, so that they can be reviewed
+/// before sending. The template is kept as it is if the address book
+/// cannot be read.
+fn expand_contacts(
+    config: &AccountConfig,
+    printer: &mut impl Printer,
+    backend: &Backend,
+    tpl: Template,
+) -> Result<Template> {
+    let enabled = backend
+        .toml_account_config
+        .contact
+        .as_ref()
+        .map(ContactConfig::is_expand_enabled)
+        .unwrap_or(true);
+
+    if !enabled {
+        return Ok(tpl);
+    }
+
+    match AddressBook::new(config).and_then(|book| contact::expand_template(&book, tpl.as_str())) {
+        Ok((expanded, expansions)) => {
+            for (name, contact) in expansions {
+                printer.print_log(format!(
+                    "Expanded {name} into {}",
+                    contact.to_mailbox_string()
+                ))?;
+            }
+            Ok(expanded.into())
+        }
+        Err(err) => {
+            warn!("cannot expand contacts of template: {err}");
+            debug!("{err:?}");
+            Ok(tpl)
+        }
+    }
+}
+
 #[allow(unused)]
 pub async fn edit_tpl_with_editor<P: Printer>(
     config: Arc<AccountConfig>,
@@ -79,6 +122,10 @@ pub async fn edit_tpl_with_editor<P: Printer>(
     }
 
     loop {
+        // expands recipients before asking what to do with the
+        // draft, so that the user can review them
+        tpl = expand_contacts(&config, printer, backend, tpl)?;
+
         match choice::post_edit() {
             Ok(PostEditChoice::Send) => {
                 printer.print_log("Sending email…")?;
@@ -91,10 +138,13 @@ pub async fn edit_tpl_with_editor<P: Printer>(
 
                 let email = compiler.build(tpl.as_str())?.compile().await?.into_vec()?;
 
-                backend.send_message_then_save_copy(&email).await?;
+                let outcome = match outbox::deliver(backend, &config, &email).await {
+                    Ok(outcome) => outcome,
+                    Err(err) => outbox::queue_if_transient(&config, &email, err)?,
+                };
 
                 remove_local_draft()?;
-                printer.print("Done!")?;
+                printer.print(outcome)?;
                 break;
             }
             Ok(PostEditChoice::Edit) => {
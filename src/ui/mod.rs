@@ -2,6 +2,7 @@ pub mod choice;
 pub mod editor;
 pub(crate) mod prompt;
 pub mod table;
+pub mod template;
 
 use dialoguer::theme::ColorfulTheme;
 use once_cell::sync::Lazy;
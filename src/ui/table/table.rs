@@ -110,6 +110,12 @@ impl Cell {
         self
     }
 
+    /// Applies the magenta color to the cell.
+    pub fn magenta(mut self) -> Self {
+        self.style.set_fg(Some(Color::Magenta));
+        self
+    }
+
     /// Applies the white color to the cell.
     pub fn white(mut self) -> Self {
         self.style.set_fg(Some(Color::White));
@@ -121,6 +127,42 @@ impl Cell {
         self.style.set_fg(Some(Color::Ansi256(code)));
         self
     }
+
+    /// Applies the given color to the cell.
+    pub fn fg(mut self, color: Color) -> Self {
+        self.style.set_fg(Some(color));
+        self
+    }
+
+    /// Truncates the cell's value so that it does not exceed the
+    /// given width, ending it with an ellipsis.
+    pub fn max_width(mut self, width: usize) -> Self {
+        if self.unicode_width() <= width {
+            return self;
+        }
+
+        if width == 0 {
+            self.value.clear();
+            return self;
+        }
+
+        let mut value = String::new();
+        let mut chars_width = 0;
+
+        for c in self.value.chars() {
+            let char_width = UnicodeWidthStr::width(c.to_string().as_str());
+            if chars_width + char_width >= width {
+                break;
+            }
+
+            chars_width += char_width;
+            value.push(c);
+        }
+
+        value.push('…');
+        self.value = value;
+        self
+    }
 }
 
 /// Makes the cell printable.
@@ -169,100 +211,108 @@ where
 
     /// Writes the table to the writer.
     fn print(writer: &mut dyn WriteColor, items: &[Self], opts: PrintTableOpts) -> Result<()> {
-        let is_format_flowed = matches!(opts.format, EmailTextPlainFormat::Flowed);
-        let max_width = match opts.format {
-            EmailTextPlainFormat::Fixed(width) => opts.max_width.unwrap_or(*width),
-            EmailTextPlainFormat::Flowed => 0,
-            EmailTextPlainFormat::Auto => opts
-                .max_width
-                .or_else(|| terminal_size().map(|(w, _)| w.0 as usize))
-                .unwrap_or(DEFAULT_TERM_WIDTH),
-        };
-        let mut table = vec![Self::head()];
-        let mut cell_widths: Vec<usize> =
-            table[0].0.iter().map(|cell| cell.unicode_width()).collect();
-        table.extend(
-            items
-                .iter()
-                .map(|item| {
-                    let row = item.row();
-                    row.0.iter().enumerate().for_each(|(i, cell)| {
-                        cell_widths[i] = cell_widths[i].max(cell.unicode_width());
-                    });
-                    row
-                })
-                .collect::<Vec<_>>(),
-        );
-        trace!("cell widths: {:?}", cell_widths);
-
-        let spaces_plus_separators_len = cell_widths.len() * 2 - 1;
-        let table_width = cell_widths.iter().sum::<usize>() + spaces_plus_separators_len;
-        trace!("table width: {}", table_width);
-
-        for row in table.iter_mut() {
-            let mut glue = Cell::default();
-            for (i, cell) in row.0.iter_mut().enumerate() {
-                glue.print(writer)?;
-
-                let table_is_overflowing = table_width > max_width;
-                if table_is_overflowing && !is_format_flowed && cell.is_shrinkable() {
-                    trace!("table is overflowing and cell is shrinkable");
-
-                    let shrink_width = table_width - max_width;
-                    trace!("shrink width: {}", shrink_width);
-                    let cell_width = if shrink_width + MAX_SHRINK_WIDTH < cell_widths[i] {
-                        cell_widths[i] - shrink_width
-                    } else {
-                        MAX_SHRINK_WIDTH
-                    };
-                    trace!("cell width: {}", cell_width);
-                    trace!("cell unicode width: {}", cell.unicode_width());
-
-                    let cell_is_overflowing = cell.unicode_width() > cell_width;
-                    if cell_is_overflowing {
-                        trace!("cell is overflowing");
-
-                        let mut value = String::new();
-                        let mut chars_width = 0;
-
-                        for c in cell.value.chars() {
-                            let char_width = UnicodeWidthStr::width(c.to_string().as_str());
-                            if chars_width + char_width >= cell_width {
-                                break;
-                            }
-
-                            chars_width += char_width;
-                            value.push(c);
+        let mut rows = vec![Self::head()];
+        rows.extend(items.iter().map(Self::row));
+        print_rows(writer, rows, opts)
+    }
+}
+
+/// Writes the given rows to the writer, the first row being the
+/// header row.
+///
+/// This is useful for tables whose columns are only known at runtime.
+pub fn print_rows(
+    writer: &mut dyn WriteColor,
+    mut table: Vec<Row>,
+    opts: PrintTableOpts,
+) -> Result<()> {
+    let is_format_flowed = matches!(opts.format, EmailTextPlainFormat::Flowed);
+    let max_width = match opts.format {
+        EmailTextPlainFormat::Fixed(width) => opts.max_width.unwrap_or(*width),
+        EmailTextPlainFormat::Flowed => 0,
+        EmailTextPlainFormat::Auto => opts
+            .max_width
+            .or_else(|| terminal_size().map(|(w, _)| w.0 as usize))
+            .unwrap_or(DEFAULT_TERM_WIDTH),
+    };
+    let mut cell_widths: Vec<usize> = Vec::new();
+    for row in table.iter() {
+        for (i, cell) in row.0.iter().enumerate() {
+            match cell_widths.get_mut(i) {
+                Some(width) => *width = (*width).max(cell.unicode_width()),
+                None => cell_widths.push(cell.unicode_width()),
+            }
+        }
+    }
+    trace!("cell widths: {:?}", cell_widths);
+
+    let spaces_plus_separators_len = cell_widths.len() * 2 - 1;
+    let table_width = cell_widths.iter().sum::<usize>() + spaces_plus_separators_len;
+    trace!("table width: {}", table_width);
+
+    for row in table.iter_mut() {
+        let mut glue = Cell::default();
+        for (i, cell) in row.0.iter_mut().enumerate() {
+            glue.print(writer)?;
+
+            let table_is_overflowing = table_width > max_width;
+            if table_is_overflowing && !is_format_flowed && cell.is_shrinkable() {
+                trace!("table is overflowing and cell is shrinkable");
+
+                let shrink_width = table_width - max_width;
+                trace!("shrink width: {}", shrink_width);
+                let cell_width = if shrink_width + MAX_SHRINK_WIDTH < cell_widths[i] {
+                    cell_widths[i] - shrink_width
+                } else {
+                    MAX_SHRINK_WIDTH
+                };
+                trace!("cell width: {}", cell_width);
+                trace!("cell unicode width: {}", cell.unicode_width());
+
+                let cell_is_overflowing = cell.unicode_width() > cell_width;
+                if cell_is_overflowing {
+                    trace!("cell is overflowing");
+
+                    let mut value = String::new();
+                    let mut chars_width = 0;
+
+                    for c in cell.value.chars() {
+                        let char_width = UnicodeWidthStr::width(c.to_string().as_str());
+                        if chars_width + char_width >= cell_width {
+                            break;
                         }
 
-                        value.push_str("… ");
-                        trace!("chars width: {}", chars_width);
-                        trace!("shrunk value: {}", value);
-                        let spaces_count = cell_width - chars_width - 1;
-                        trace!("number of spaces added to shrunk value: {}", spaces_count);
-                        value.push_str(&" ".repeat(spaces_count));
-                        cell.value = value;
-                    } else {
-                        trace!("cell is not overflowing");
-                        let spaces_count = cell_width - cell.unicode_width() + 1;
-                        trace!("number of spaces added to value: {}", spaces_count);
-                        cell.value.push_str(&" ".repeat(spaces_count));
+                        chars_width += char_width;
+                        value.push(c);
                     }
+
+                    value.push_str("… ");
+                    trace!("chars width: {}", chars_width);
+                    trace!("shrunk value: {}", value);
+                    let spaces_count = cell_width - chars_width - 1;
+                    trace!("number of spaces added to shrunk value: {}", spaces_count);
+                    value.push_str(&" ".repeat(spaces_count));
+                    cell.value = value;
                 } else {
-                    trace!("table is not overflowing or cell is not shrinkable");
-                    trace!("cell width: {}", cell_widths[i]);
-                    trace!("cell unicode width: {}", cell.unicode_width());
-                    let spaces_count = cell_widths[i] - cell.unicode_width() + 1;
+                    trace!("cell is not overflowing");
+                    let spaces_count = cell_width - cell.unicode_width() + 1;
                     trace!("number of spaces added to value: {}", spaces_count);
                     cell.value.push_str(&" ".repeat(spaces_count));
                 }
-                cell.print(writer)?;
-                glue = Cell::new("│").ansi_256(8);
+            } else {
+                trace!("table is not overflowing or cell is not shrinkable");
+                trace!("cell width: {}", cell_widths[i]);
+                trace!("cell unicode width: {}", cell.unicode_width());
+                let spaces_count = cell_widths[i] - cell.unicode_width() + 1;
+                trace!("number of spaces added to value: {}", spaces_count);
+                cell.value.push_str(&" ".repeat(spaces_count));
             }
-            writeln!(writer)?;
+            cell.print(writer)?;
+            glue = Cell::new("│").ansi_256(8);
         }
-        Ok(())
+        writeln!(writer)?;
     }
+    Ok(())
 }
 
 #[cfg(test)]
@@ -443,4 +493,12 @@ mod tests {
         ];
         assert_eq!(expected, writer.content);
     }
+
+    #[test]
+    fn cell_max_width() {
+        assert_eq!(Cell::new("short").max_width(5).value, "short");
+        assert_eq!(Cell::new("truncated").max_width(5).value, "trun…");
+        assert_eq!(Cell::new("truncated").max_width(1).value, "…");
+        assert_eq!(Cell::new("truncated").max_width(0).value, "");
+    }
 }
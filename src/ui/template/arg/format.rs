@@ -0,0 +1,16 @@
+use clap::Parser;
+
+use crate::ui::template::Template;
+
+/// The output template flag parser.
+#[derive(Debug, Default, Parser)]
+pub struct TemplateFormatFlag {
+    /// Render each item using the given template instead of a table.
+    ///
+    /// Fields are written between curly braces, like {id} or
+    /// {from.addr}, and can be transformed using filters separated
+    /// by pipes: pad:N, lpad:N, truncate:N and date:FORMAT (strftime
+    /// syntax). For example: '{id|lpad:4}\t{date|date:%F}\t{subject}'.
+    #[arg(long = "format", name = "template_format", value_name = "TEMPLATE")]
+    pub template: Option<Template>,
+}
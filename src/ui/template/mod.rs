@@ -0,0 +1,492 @@
+//! Module dedicated to user-defined output templates.
+//!
+//! A template renders every item of a listing on its own line. Items
+//! are serialized to JSON first, so templates can access any field
+//! exposed by the JSON output.
+//!
+//! Fields are written between curly braces, using dots to access
+//! nested fields and array items, like `{from.addr}` or `{cc.0.addr}`.
+//! Fields can be transformed by filters separated by pipes:
+//!
+//!  - `pad:N` pads the value with spaces to the given width
+//!  - `lpad:N` same as `pad`, but aligns the value to the right
+//!  - `truncate:N` truncates the value to the given width
+//!  - `date:FORMAT` formats the date using the strftime syntax
+//!
+//! Dates are parsed using the date formats of the listing, like the
+//! `envelope.list.datetime-fmt` option of the account, then using
+//! common date formats.
+//!
+//! Literal curly braces are written `{{` and `}}`, and `\t` and `\n`
+//! are replaced by tabulations and line breaks.
+
+pub mod arg;
+
+use anyhow::{anyhow, bail, Error, Result};
+use chrono::{
+    format::{Item, StrftimeItems},
+    DateTime, FixedOffset, NaiveDate, NaiveDateTime,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    fmt::{self, Write},
+    str::FromStr,
+};
+use unicode_width::UnicodeWidthChar;
+
+use crate::printer::{PrintTable, PrintTableOpts, Printer, WriteColor};
+
+/// The date formats the date filter knows how to parse, on top of
+/// RFC 3339 and RFC 2822.
+const DATE_FORMATS: &[&str] = &["%F %R%:z", "%F %T%:z", "%F %R%z", "%F %T%z"];
+
+/// Represents a filter applied to a template field.
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum Filter {
+    Pad(usize),
+    LeftPad(usize),
+    Truncate(usize),
+    Date(String),
+}
+
+impl FromStr for Filter {
+    type Err = Error;
+
+    fn from_str(filter: &str) -> Result<Self> {
+        let (name, arg) = match filter.split_once(':') {
+            Some((name, arg)) => (name.trim(), Some(arg)),
+            None => (filter.trim(), None),
+        };
+
+        let width = || -> Result<usize> {
+            let arg = arg.ok_or(anyhow!("missing width for template filter {name}"))?;
+            arg.trim()
+                .parse()
+                .map_err(|err| anyhow!("invalid width {arg:?} for template filter {name}: {err}"))
+        };
+
+        match name {
+            "pad" => Ok(Self::Pad(width()?)),
+            "lpad" => Ok(Self::LeftPad(width()?)),
+            "truncate" => Ok(Self::Truncate(width()?)),
+            "date" => match arg {
+                Some(fmt) if StrftimeItems::new(fmt).any(|item| item == Item::Error) => {
+                    bail!("invalid format {fmt:?} for template filter date")
+                }
+                Some(fmt) if !fmt.is_empty() => Ok(Self::Date(fmt.to_owned())),
+                _ => bail!("missing format for template filter date"),
+            },
+            name => bail!("unknown template filter {name:?}"),
+        }
+    }
+}
+
+impl Filter {
+    fn apply(&self, value: String, date_fmts: &[String]) -> Result<String> {
+        let value = match self {
+            Self::Pad(width) => {
+                let padding = width.saturating_sub(text_width(&value));
+                value + &" ".repeat(padding)
+            }
+            Self::LeftPad(width) => {
+                let padding = width.saturating_sub(text_width(&value));
+                " ".repeat(padding) + &value
+            }
+            Self::Truncate(width) => truncate(value, *width),
+            Self::Date(_) if value.is_empty() => value,
+            Self::Date(fmt) => format_date(&value, fmt, date_fmts)?,
+        };
+
+        Ok(value)
+    }
+}
+
+/// Represents a piece of template.
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum Token {
+    Text(String),
+    Field {
+        path: Vec<String>,
+        filters: Vec<Filter>,
+    },
+}
+
+/// Represents a parsed output template.
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Template {
+    raw: String,
+    tokens: Vec<Token>,
+    /// The formats used to parse dates before the ones known by the
+    /// date filter.
+    date_fmts: Vec<String>,
+}
+
+impl Template {
+    /// Add a format used by the date filter to parse dates, like the
+    /// date format of the listing.
+    pub fn with_date_fmt(mut self, fmt: impl ToString) -> Self {
+        let fmt = fmt.to_string();
+        if !self.date_fmts.contains(&fmt) {
+            self.date_fmts.push(fmt);
+        }
+        self
+    }
+
+    /// Iterate over the paths of the fields used by the template.
+    pub fn fields(&self) -> impl Iterator<Item = &[String]> {
+        self.tokens.iter().filter_map(|token| match token {
+            Token::Field { path, .. } => Some(path.as_slice()),
+            Token::Text(_) => None,
+        })
+    }
+
+    /// Render the given value using the template.
+    pub fn render(&self, value: &Value) -> Result<String> {
+        let mut output = String::new();
+
+        for token in &self.tokens {
+            match token {
+                Token::Text(text) => output.push_str(text),
+                Token::Field { path, filters } => {
+                    let field = path
+                        .iter()
+                        .try_fold(value, |value, key| match value {
+                            Value::Array(values) => values.get(key.parse::<usize>().ok()?),
+                            value => value.get(key),
+                        })
+                        .map(to_text)
+                        .unwrap_or_default();
+                    let field = filters
+                        .iter()
+                        .try_fold(field, |field, f| f.apply(field, &self.date_fmts))?;
+                    output.push_str(&field);
+                }
+            }
+        }
+
+        Ok(output)
+    }
+
+    /// Render every item of the given value on its own line.
+    ///
+    /// Arrays are rendered item by item, any other value is rendered
+    /// as a single item.
+    pub fn render_items(&self, value: &Value) -> Result<Vec<String>> {
+        match value {
+            Value::Array(values) => values.iter().map(|value| self.render(value)).collect(),
+            Value::Null => Ok(Vec::new()),
+            value => Ok(vec![self.render(value)?]),
+        }
+    }
+}
+
+impl FromStr for Template {
+    type Err = Error;
+
+    fn from_str(raw: &str) -> Result<Self> {
+        let mut tokens = Vec::new();
+        let mut text = String::new();
+        let mut chars = raw.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '}' => bail!("unexpected '}}' in template, use '}}}}' to print it"),
+                '\\' => match chars.next() {
+                    Some('t') => text.push('\t'),
+                    Some('n') => text.push('\n'),
+                    Some('\\') => text.push('\\'),
+                    Some(c) => {
+                        text.push('\\');
+                        text.push(c);
+                    }
+                    None => text.push('\\'),
+                },
+                '{' => {
+                    let mut field = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => field.push(c),
+                            None => bail!("missing '}}' at the end of template field {field:?}"),
+                        }
+                    }
+
+                    if !text.is_empty() {
+                        tokens.push(Token::Text(std::mem::take(&mut text)));
+                    }
+
+                    let mut parts = field.split('|');
+                    let path = parts.next().unwrap_or_default().trim();
+                    if path.is_empty() {
+                        bail!("missing field name in template field {field:?}");
+                    }
+
+                    tokens.push(Token::Field {
+                        path: path.split('.').map(ToOwned::to_owned).collect(),
+                        filters: parts.map(str::parse).collect::<Result<_>>()?,
+                    });
+                }
+                c => text.push(c),
+            }
+        }
+
+        if !text.is_empty() {
+            tokens.push(Token::Text(text));
+        }
+
+        Ok(Self {
+            raw: raw.to_owned(),
+            tokens,
+            date_fmts: Vec::new(),
+        })
+    }
+}
+
+impl TryFrom<String> for Template {
+    type Error = Error;
+
+    fn try_from(raw: String) -> Result<Self> {
+        raw.parse()
+    }
+}
+
+impl From<Template> for String {
+    fn from(template: Template) -> Self {
+        template.raw
+    }
+}
+
+impl fmt::Display for Template {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.raw)
+    }
+}
+
+/// Wrapper rendering a listing using a template instead of a table.
+///
+/// The wrapper serializes as the wrapped listing, so that it does not
+/// alter non-plain output formats.
+#[derive(Debug, Serialize)]
+#[serde(transparent)]
+pub struct TemplatedTable<T: Serialize> {
+    #[serde(skip)]
+    template: Template,
+    data: T,
+}
+
+impl<T: Serialize> TemplatedTable<T> {
+    pub fn new(template: Template, data: T) -> Self {
+        Self { template, data }
+    }
+}
+
+impl<T: Serialize> PrintTable for TemplatedTable<T> {
+    fn print_table(&self, writer: &mut dyn WriteColor, _opts: PrintTableOpts) -> Result<()> {
+        let value = serde_json::to_value(&self.data)?;
+
+        for line in self.template.render_items(&value)? {
+            writeln!(writer, "{line}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Print the given listing using the given template, or as a table
+/// if no template is given.
+pub fn print_table_or_template<T>(
+    printer: &mut impl Printer,
+    data: T,
+    template: Option<Template>,
+    opts: PrintTableOpts,
+) -> Result<()>
+where
+    T: fmt::Debug + Serialize + PrintTable,
+{
+    match template {
+        Some(template) => printer.print_table(Box::new(TemplatedTable::new(template, data)), opts),
+        None => printer.print_table(Box::new(data), opts),
+    }
+}
+
+fn to_text(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(string) => string.clone(),
+        Value::Array(values) => values.iter().map(to_text).collect::<Vec<_>>().join(", "),
+        value => value.to_string(),
+    }
+}
+
+fn text_width(text: &str) -> usize {
+    text.chars().filter_map(UnicodeWidthChar::width).sum()
+}
+
+fn truncate(text: String, width: usize) -> String {
+    if text_width(&text) <= width {
+        return text;
+    }
+
+    let mut truncated = String::new();
+    let mut truncated_width = 0;
+
+    for c in text.chars() {
+        let c_width = UnicodeWidthChar::width(c).unwrap_or_default();
+        // keep room for the ellipsis
+        if truncated_width + c_width >= width {
+            break;
+        }
+        truncated.push(c);
+        truncated_width += c_width;
+    }
+
+    if width > 0 {
+        truncated.push('…');
+    }
+
+    truncated
+}
+
+fn format_date(date: &str, fmt: &str, date_fmts: &[String]) -> Result<String> {
+    let parsed = parse_date(date, date_fmts)
+        .ok_or_else(|| anyhow!("cannot parse date {date:?} for template filter date"))?;
+
+    // formatting fails when the format uses fields the date does not
+    // have, like the time of a naive date
+    let mut formatted = String::new();
+    match parsed {
+        ParsedDate::DateTime(date) => write!(formatted, "{}", date.format(fmt)),
+        ParsedDate::NaiveDateTime(date) => write!(formatted, "{}", date.format(fmt)),
+        ParsedDate::NaiveDate(date) => write!(formatted, "{}", date.format(fmt)),
+    }
+    .map_err(|_| anyhow!("cannot format date {date:?} using format {fmt:?}"))?;
+
+    Ok(formatted)
+}
+
+/// Represents a date parsed by the date filter.
+enum ParsedDate {
+    DateTime(DateTime<FixedOffset>),
+    NaiveDateTime(NaiveDateTime),
+    NaiveDate(NaiveDate),
+}
+
+fn parse_date(date: &str, date_fmts: &[String]) -> Option<ParsedDate> {
+    let date_fmts = || {
+        date_fmts
+            .iter()
+            .map(String::as_str)
+            .chain(DATE_FORMATS.iter().copied())
+    };
+
+    if let Ok(date) = DateTime::parse_from_rfc3339(date) {
+        return Some(ParsedDate::DateTime(date));
+    }
+
+    if let Ok(date) = DateTime::parse_from_rfc2822(date) {
+        return Some(ParsedDate::DateTime(date));
+    }
+
+    if let Some(date) = date_fmts().find_map(|fmt| DateTime::parse_from_str(date, fmt).ok()) {
+        return Some(ParsedDate::DateTime(date));
+    }
+
+    if let Some(date) = date_fmts()
+        .chain(["%F %R", "%F %T"])
+        .find_map(|fmt| NaiveDateTime::parse_from_str(date, fmt).ok())
+    {
+        return Some(ParsedDate::NaiveDateTime(date));
+    }
+
+    date_fmts()
+        .chain(["%F"])
+        .find_map(|fmt| NaiveDate::parse_from_str(date, fmt).ok())
+        .map(ParsedDate::NaiveDate)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::Template;
+
+    fn render(template: &str, value: serde_json::Value) -> String {
+        template
+            .parse::<Template>()
+            .unwrap()
+            .render(&value)
+            .unwrap()
+    }
+
+    #[test]
+    fn fields() {
+        let value = json!({
+            "id": "42",
+            "from": {"name": null, "addr": "alice@localhost"},
+            "flags": ["Seen", "Flagged"],
+            "cc": [{"addr": "bob@localhost"}],
+        });
+
+        assert_eq!(
+            render(r"{id}\t{from.addr}\t{from.name}", value.clone()),
+            "42\talice@localhost\t"
+        );
+        assert_eq!(
+            render("{flags} {cc.0.addr}", value.clone()),
+            "Seen, Flagged bob@localhost"
+        );
+        assert_eq!(render("{{{id}}} {unknown}", value), "{42} ");
+    }
+
+    #[test]
+    fn filters() {
+        let value = json!({"id": "42", "subject": "Hello, world!"});
+
+        assert_eq!(render("[{id|pad:4}]", value.clone()), "[42  ]");
+        assert_eq!(render("[{id|lpad:4}]", value.clone()), "[  42]");
+        assert_eq!(render("{subject|truncate:6}", value.clone()), "Hello…");
+        assert_eq!(render("{subject|truncate:6|pad:8}.", value), "Hello…  .");
+    }
+
+    #[test]
+    fn dates() {
+        let value = json!({"date": "2024-03-01 14:30+01:00", "naive": "2024-03-01"});
+
+        assert_eq!(
+            render("{date|date:%d/%m %H:%M}", value.clone()),
+            "01/03 14:30"
+        );
+        assert_eq!(render("{naive|date:%Y}", value.clone()), "2024");
+        assert_eq!(render("{id|date:%Y}", value.clone()), "");
+
+        let template: Template = "{naive|date:%H}".parse().unwrap();
+        assert!(template.render(&value).is_err());
+
+        let value = json!({"date": "01/03/2024 14:30"});
+        let template: Template = "{date|date:%F}".parse().unwrap();
+        assert!(template.render(&value).is_err());
+
+        let template = template.with_date_fmt("%d/%m/%Y %H:%M");
+        assert_eq!(template.render(&value).unwrap(), "2024-03-01");
+    }
+
+    #[test]
+    fn invalid_templates() {
+        assert!("{id".parse::<Template>().is_err());
+        assert!("id}".parse::<Template>().is_err());
+        assert!("{}".parse::<Template>().is_err());
+        assert!("{id|pad}".parse::<Template>().is_err());
+        assert!("{id|unknown}".parse::<Template>().is_err());
+        assert!("{date|date:%Q}".parse::<Template>().is_err());
+    }
+}